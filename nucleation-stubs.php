@@ -30,6 +30,19 @@ function nucleation_version(): array {}
  */
 function nucleation_detect_format(string $data): string {}
 
+/**
+ * Read format, version, metadata and dimensions without loading the blocks
+ *
+ * Keys always present: 'format', 'version', 'width', 'height', 'length', 'regions'.
+ * Keys present when the file records them: 'data_version', 'name', 'author',
+ * 'description', 'total_blocks', and 'preview_image' (a JSON array of ARGB pixels).
+ *
+ * @param string $data Binary schematic data
+ * @return array<string, string> Schematic information
+ * @throws Exception If the data cannot be read
+ */
+function nucleation_peek_info(string $data): array {}
+
 /**
  * Convert between schematic formats
  *
@@ -84,28 +97,31 @@ namespace Nucleation {
          * Load from binary data (auto-detect format)
          *
          * @param string $data Binary schematic data
+         * @param bool|null $untrusted Cap sizes, palettes, entities and decompressed bytes, for uploads
          * @return bool Success status
          * @throws Exception On parse failure
          */
-        public function loadFromData(string $data): bool {}
+        public function loadFromData(string $data, ?bool $untrusted = null): bool {}
 
         /**
          * Load from litematic data
          *
          * @param string $data Litematic binary data
+         * @param bool|null $untrusted Cap sizes, palettes, entities and decompressed bytes, for uploads
          * @return bool Success status
          * @throws Exception On parse failure
          */
-        public function fromLitematic(string $data): bool {}
+        public function fromLitematic(string $data, ?bool $untrusted = null): bool {}
 
         /**
          * Load from schematic data
          *
          * @param string $data Schematic binary data
+         * @param bool|null $untrusted Cap sizes, palettes, entities and decompressed bytes, for uploads
          * @return bool Success status
          * @throws Exception On parse failure
          */
-        public function fromSchematic(string $data): bool {}
+        public function fromSchematic(string $data, ?bool $untrusted = null): bool {}
 
         /**
          * Export to litematic format
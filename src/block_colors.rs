@@ -0,0 +1,478 @@
+//! Average texture colors for vanilla blocks, used wherever a block has to become a single color
+//! (voxel export, previews, meshes) or a color has to become a block (voxel import, pixel art).
+
+use std::collections::HashMap;
+use crate::BlockState;
+
+/// Full, opaque cubes. These make up the default palette for color-to-block matching.
+const FULL_BLOCK_COLORS: &[(&str, [u8; 3])] = &[
+    ("stone", [125, 125, 125]),
+    ("granite", [149, 103, 85]),
+    ("polished_granite", [154, 106, 89]),
+    ("diorite", [188, 188, 188]),
+    ("polished_diorite", [192, 193, 194]),
+    ("andesite", [136, 136, 136]),
+    ("polished_andesite", [132, 134, 133]),
+    ("deepslate", [80, 80, 82]),
+    ("cobbled_deepslate", [77, 77, 80]),
+    ("polished_deepslate", [72, 72, 73]),
+    ("deepslate_bricks", [70, 70, 71]),
+    ("deepslate_tiles", [54, 54, 55]),
+    ("calcite", [223, 224, 220]),
+    ("tuff", [108, 109, 102]),
+    ("dripstone_block", [134, 107, 92]),
+    ("grass_block", [95, 159, 53]),
+    ("dirt", [134, 96, 67]),
+    ("coarse_dirt", [119, 85, 59]),
+    ("rooted_dirt", [144, 103, 76]),
+    ("podzol", [91, 63, 24]),
+    ("mycelium", [111, 99, 105]),
+    ("mud", [60, 57, 60]),
+    ("clay", [160, 166, 179]),
+    ("gravel", [131, 127, 126]),
+    ("sand", [219, 207, 163]),
+    ("red_sand", [190, 102, 33]),
+    ("sandstone", [216, 203, 155]),
+    ("smooth_sandstone", [223, 214, 170]),
+    ("cut_sandstone", [217, 206, 159]),
+    ("chiseled_sandstone", [216, 202, 155]),
+    ("red_sandstone", [186, 99, 29]),
+    ("smooth_red_sandstone", [181, 98, 31]),
+    ("cut_red_sandstone", [189, 101, 31]),
+    ("cobblestone", [127, 127, 127]),
+    ("mossy_cobblestone", [110, 118, 94]),
+    ("stone_bricks", [122, 121, 122]),
+    ("mossy_stone_bricks", [115, 121, 105]),
+    ("cracked_stone_bricks", [118, 117, 118]),
+    ("chiseled_stone_bricks", [119, 118, 119]),
+    ("smooth_stone", [158, 158, 158]),
+    ("bricks", [150, 97, 83]),
+    ("mud_bricks", [137, 103, 79]),
+    ("packed_mud", [142, 106, 79]),
+    ("oak_planks", [162, 130, 78]),
+    ("spruce_planks", [114, 84, 48]),
+    ("birch_planks", [192, 175, 121]),
+    ("jungle_planks", [160, 115, 80]),
+    ("acacia_planks", [168, 90, 50]),
+    ("dark_oak_planks", [66, 43, 20]),
+    ("mangrove_planks", [117, 54, 48]),
+    ("cherry_planks", [226, 178, 172]),
+    ("bamboo_planks", [193, 173, 80]),
+    ("crimson_planks", [101, 48, 70]),
+    ("warped_planks", [43, 104, 99]),
+    ("oak_log", [109, 85, 50]),
+    ("spruce_log", [58, 37, 16]),
+    ("birch_log", [216, 215, 210]),
+    ("jungle_log", [85, 67, 25]),
+    ("acacia_log", [103, 96, 86]),
+    ("dark_oak_log", [60, 46, 26]),
+    ("mangrove_log", [84, 66, 36]),
+    ("cherry_log", [54, 33, 44]),
+    ("crimson_stem", [92, 25, 29]),
+    ("warped_stem", [58, 58, 77]),
+    ("white_wool", [234, 236, 237]),
+    ("orange_wool", [241, 118, 20]),
+    ("magenta_wool", [189, 68, 179]),
+    ("light_blue_wool", [58, 175, 217]),
+    ("yellow_wool", [248, 198, 40]),
+    ("lime_wool", [112, 185, 26]),
+    ("pink_wool", [238, 141, 172]),
+    ("gray_wool", [63, 68, 72]),
+    ("light_gray_wool", [142, 142, 135]),
+    ("cyan_wool", [21, 138, 145]),
+    ("purple_wool", [122, 42, 173]),
+    ("blue_wool", [53, 57, 157]),
+    ("brown_wool", [114, 72, 41]),
+    ("green_wool", [85, 110, 28]),
+    ("red_wool", [161, 39, 35]),
+    ("black_wool", [21, 21, 26]),
+    ("white_concrete", [207, 213, 214]),
+    ("orange_concrete", [224, 97, 1]),
+    ("magenta_concrete", [169, 48, 159]),
+    ("light_blue_concrete", [36, 137, 199]),
+    ("yellow_concrete", [241, 175, 21]),
+    ("lime_concrete", [94, 169, 24]),
+    ("pink_concrete", [214, 101, 143]),
+    ("gray_concrete", [55, 58, 62]),
+    ("light_gray_concrete", [125, 125, 115]),
+    ("cyan_concrete", [21, 119, 136]),
+    ("purple_concrete", [100, 32, 156]),
+    ("blue_concrete", [45, 47, 143]),
+    ("brown_concrete", [96, 60, 32]),
+    ("green_concrete", [73, 91, 36]),
+    ("red_concrete", [142, 33, 33]),
+    ("black_concrete", [8, 10, 15]),
+    ("white_concrete_powder", [226, 227, 228]),
+    ("orange_concrete_powder", [227, 132, 32]),
+    ("magenta_concrete_powder", [193, 84, 185]),
+    ("light_blue_concrete_powder", [74, 181, 213]),
+    ("yellow_concrete_powder", [233, 199, 55]),
+    ("lime_concrete_powder", [125, 189, 42]),
+    ("pink_concrete_powder", [229, 153, 181]),
+    ("gray_concrete_powder", [77, 81, 85]),
+    ("light_gray_concrete_powder", [155, 155, 148]),
+    ("cyan_concrete_powder", [37, 148, 157]),
+    ("purple_concrete_powder", [132, 56, 178]),
+    ("blue_concrete_powder", [70, 73, 167]),
+    ("brown_concrete_powder", [126, 85, 54]),
+    ("green_concrete_powder", [97, 119, 45]),
+    ("red_concrete_powder", [168, 54, 51]),
+    ("black_concrete_powder", [25, 27, 32]),
+    ("terracotta", [152, 94, 68]),
+    ("white_terracotta", [210, 178, 161]),
+    ("orange_terracotta", [162, 84, 38]),
+    ("magenta_terracotta", [150, 88, 109]),
+    ("light_blue_terracotta", [113, 109, 138]),
+    ("yellow_terracotta", [186, 133, 35]),
+    ("lime_terracotta", [104, 118, 53]),
+    ("pink_terracotta", [162, 78, 79]),
+    ("gray_terracotta", [58, 42, 36]),
+    ("light_gray_terracotta", [135, 107, 98]),
+    ("cyan_terracotta", [87, 91, 91]),
+    ("purple_terracotta", [118, 70, 86]),
+    ("blue_terracotta", [74, 60, 91]),
+    ("brown_terracotta", [77, 51, 36]),
+    ("green_terracotta", [76, 83, 42]),
+    ("red_terracotta", [143, 61, 47]),
+    ("black_terracotta", [37, 23, 16]),
+    ("iron_block", [220, 220, 220]),
+    ("gold_block", [246, 208, 62]),
+    ("diamond_block", [98, 237, 228]),
+    ("emerald_block", [42, 203, 88]),
+    ("lapis_block", [31, 67, 140]),
+    ("redstone_block", [175, 24, 5]),
+    ("coal_block", [16, 16, 16]),
+    ("netherite_block", [66, 61, 63]),
+    ("copper_block", [192, 107, 79]),
+    ("exposed_copper", [161, 125, 103]),
+    ("weathered_copper", [108, 153, 110]),
+    ("oxidized_copper", [82, 162, 132]),
+    ("amethyst_block", [133, 97, 191]),
+    ("quartz_block", [236, 230, 223]),
+    ("smooth_quartz", [236, 230, 223]),
+    ("chiseled_quartz_block", [231, 226, 218]),
+    ("quartz_pillar", [235, 230, 224]),
+    ("quartz_bricks", [234, 229, 221]),
+    ("purpur_block", [169, 125, 169]),
+    ("purpur_pillar", [171, 129, 171]),
+    ("end_stone", [219, 222, 158]),
+    ("end_stone_bricks", [218, 224, 162]),
+    ("obsidian", [15, 10, 24]),
+    ("crying_obsidian", [32, 10, 60]),
+    ("netherrack", [97, 38, 38]),
+    ("nether_bricks", [44, 21, 26]),
+    ("red_nether_bricks", [69, 7, 9]),
+    ("soul_sand", [81, 62, 50]),
+    ("soul_soil", [75, 57, 46]),
+    ("basalt", [80, 81, 86]),
+    ("polished_basalt", [99, 98, 100]),
+    ("smooth_basalt", [72, 72, 78]),
+    ("blackstone", [42, 36, 41]),
+    ("polished_blackstone", [53, 48, 56]),
+    ("polished_blackstone_bricks", [48, 42, 49]),
+    ("glowstone", [171, 131, 84]),
+    ("shroomlight", [240, 146, 70]),
+    ("magma_block", [142, 63, 31]),
+    ("nether_wart_block", [114, 2, 2]),
+    ("warped_wart_block", [22, 119, 121]),
+    ("crimson_nylium", [130, 31, 31]),
+    ("warped_nylium", [43, 114, 101]),
+    ("prismarine", [99, 156, 151]),
+    ("prismarine_bricks", [99, 171, 158]),
+    ("dark_prismarine", [51, 91, 75]),
+    ("sea_lantern", [172, 199, 190]),
+    ("sponge", [195, 192, 74]),
+    ("wet_sponge", [171, 181, 70]),
+    ("hay_block", [166, 136, 38]),
+    ("melon", [111, 145, 30]),
+    ("pumpkin", [198, 118, 24]),
+    ("carved_pumpkin", [150, 84, 17]),
+    ("jack_o_lantern", [214, 152, 52]),
+    ("bookshelf", [117, 94, 59]),
+    ("crafting_table", [119, 90, 55]),
+    ("snow_block", [249, 254, 254]),
+    ("packed_ice", [141, 180, 250]),
+    ("blue_ice", [116, 167, 253]),
+    ("honeycomb_block", [229, 148, 29]),
+    ("bone_block", [229, 225, 207]),
+    ("dried_kelp_block", [50, 58, 38]),
+    ("moss_block", [89, 109, 45]),
+    ("target", [226, 170, 157]),
+    ("note_block", [88, 58, 40]),
+    ("redstone_lamp", [95, 54, 30]),
+    ("brown_mushroom_block", [149, 111, 81]),
+    ("red_mushroom_block", [200, 46, 45]),
+    ("mushroom_stem", [203, 196, 185]),
+    ("coal_ore", [105, 105, 105]),
+    ("iron_ore", [136, 129, 122]),
+    ("gold_ore", [145, 133, 106]),
+    ("diamond_ore", [121, 141, 140]),
+    ("redstone_ore", [140, 109, 109]),
+    ("lapis_ore", [107, 117, 141]),
+    ("emerald_ore", [108, 136, 115]),
+];
+
+/// Blocks that have a color but are not plain opaque cubes.
+const OTHER_BLOCK_COLORS: &[(&str, [u8; 3])] = &[
+    ("water", [63, 118, 228]),
+    ("lava", [207, 92, 20]),
+    ("glass", [175, 213, 219]),
+    ("glass_pane", [175, 213, 219]),
+    ("ice", [145, 183, 253]),
+    ("slime_block", [111, 192, 91]),
+    ("honey_block", [251, 185, 52]),
+    ("oak_leaves", [77, 106, 40]),
+    ("spruce_leaves", [61, 99, 61]),
+    ("birch_leaves", [107, 141, 64]),
+    ("jungle_leaves", [48, 113, 20]),
+    ("acacia_leaves", [75, 104, 40]),
+    ("dark_oak_leaves", [61, 90, 30]),
+    ("mangrove_leaves", [69, 109, 33]),
+    ("cherry_leaves", [229, 173, 194]),
+    ("azalea_leaves", [90, 114, 44]),
+    ("short_grass", [94, 145, 58]),
+    ("tall_grass", [94, 145, 58]),
+    ("fern", [88, 135, 52]),
+    ("snow", [249, 254, 254]),
+    ("tnt", [142, 62, 53]),
+    ("observer", [98, 98, 98]),
+    ("piston", [110, 104, 96]),
+    ("sticky_piston", [110, 104, 96]),
+    ("dispenser", [122, 121, 122]),
+    ("dropper", [122, 121, 122]),
+    ("furnace", [110, 110, 110]),
+    ("chest", [162, 118, 48]),
+    ("barrel", [134, 100, 58]),
+    ("hopper", [66, 66, 68]),
+    ("redstone_wire", [175, 24, 5]),
+    ("repeater", [160, 157, 156]),
+    ("comparator", [166, 162, 160]),
+    ("redstone_torch", [185, 50, 30]),
+    ("lever", [110, 92, 62]),
+    ("torch", [237, 186, 78]),
+    ("lantern", [106, 91, 83]),
+    ("rail", [125, 108, 77]),
+    ("powered_rail", [154, 109, 73]),
+    ("ladder", [124, 96, 54]),
+    ("cactus", [85, 127, 43]),
+    ("sugar_cane", [148, 192, 101]),
+    ("bamboo", [93, 144, 19]),
+    ("vine", [68, 103, 34]),
+    ("lily_pad", [32, 128, 48]),
+    ("dandelion", [148, 158, 26]),
+    ("poppy", [129, 64, 37]),
+    ("farmland", [143, 102, 70]),
+    ("dirt_path", [148, 121, 65]),
+    ("cobweb", [228, 233, 234]),
+    ("iron_bars", [136, 139, 135]),
+    ("bell", [253, 235, 110]),
+    ("anvil", [68, 68, 68]),
+    ("enchanting_table", [128, 75, 85]),
+    ("beacon", [118, 221, 215]),
+    ("scaffolding", [174, 134, 80]),
+    ("cake", [248, 222, 214]),
+];
+
+/// Non-full shapes that take the color of the block they are made from, e.g. `oak_stairs` → `oak_planks`.
+const SHAPE_SUFFIXES: &[&str] = &[
+    "_stairs", "_slab", "_wall", "_fence_gate", "_fence", "_pressure_plate", "_button",
+    "_trapdoor", "_door", "_wall_hanging_sign", "_hanging_sign", "_wall_sign", "_sign",
+];
+
+/// Dyed blocks that take the color of the matching wool (or terracotta for glazed terracotta).
+const DYED_SUFFIXES: &[(&str, &str)] = &[
+    ("_carpet", "_wool"),
+    ("_stained_glass_pane", "_wool"),
+    ("_stained_glass", "_wool"),
+    ("_shulker_box", "_wool"),
+    ("_bed", "_wool"),
+    ("_wall_banner", "_wool"),
+    ("_banner", "_wool"),
+    ("_candle", "_wool"),
+    ("_glazed_terracotta", "_terracotta"),
+];
+
+/// The three kinds of air, with or without namespace.
+pub(crate) fn is_air(name: &str) -> bool {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    matches!(name, "air" | "cave_air" | "void_air")
+}
+
+/// Blocks that never show up in a render: air and the technical blocks players can't see.
+pub(crate) fn is_invisible(name: &str) -> bool {
+    is_air(name) || matches!(name.strip_prefix("minecraft:").unwrap_or(name), "structure_void" | "barrier" | "light")
+}
+
+fn lookup(name: &str) -> Option<[u8; 3]> {
+    FULL_BLOCK_COLORS.iter()
+        .chain(OTHER_BLOCK_COLORS.iter())
+        .find(|(block, _)| *block == name)
+        .map(|(_, color)| *color)
+}
+
+/// Average color of a block by name (with or without namespace), or `None` for air and blocks
+/// the table does not know.
+pub fn get_block_color(name: &str) -> Option<[u8; 3]> {
+    if is_invisible(name) {
+        return None;
+    }
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    if let Some(color) = lookup(name) {
+        return Some(color);
+    }
+
+    let name = name.strip_prefix("waxed_").unwrap_or(name);
+    let name = name.strip_prefix("infested_").unwrap_or(name);
+    let name = name.strip_prefix("deepslate_").filter(|n| n.ends_with("_ore")).unwrap_or(name);
+    if let Some(color) = lookup(name) {
+        return Some(color);
+    }
+
+    for (suffix, replacement) in DYED_SUFFIXES {
+        if let Some(color) = name.strip_suffix(suffix) {
+            return lookup(&format!("{}{}", color, replacement));
+        }
+    }
+
+    // Stripped logs show the inside of the wood, which is what planks look like
+    if let Some(wood) = name.strip_prefix("stripped_") {
+        for suffix in ["_log", "_wood", "_stem", "_hyphae"] {
+            if let Some(base) = wood.strip_suffix(suffix) {
+                return lookup(&format!("{}_planks", base));
+            }
+        }
+    }
+    for (suffix, log_suffix) in [("_wood", "_log"), ("_hyphae", "_stem")] {
+        if let Some(base) = name.strip_suffix(suffix) {
+            if let Some(color) = lookup(&format!("{}{}", base, log_suffix)) {
+                return Some(color);
+            }
+        }
+    }
+
+    for suffix in SHAPE_SUFFIXES {
+        if let Some(base) = name.strip_suffix(suffix) {
+            return [
+                base.to_string(),
+                format!("{}s", base),
+                format!("{}_planks", base),
+                format!("{}_block", base),
+            ].iter().find_map(|candidate| lookup(candidate));
+        }
+    }
+    None
+}
+
+/// Squared color distance weighted for human perception ("redmean").
+pub fn color_distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let mean_r = (a[0] as f64 + b[0] as f64) / 2.0;
+    let dr = a[0] as f64 - b[0] as f64;
+    let dg = a[1] as f64 - b[1] as f64;
+    let db = a[2] as f64 - b[2] as f64;
+    (2.0 + mean_r / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - mean_r) / 256.0) * db * db
+}
+
+/// A set of blocks to choose from when turning colors into blocks.
+#[derive(Debug, Clone)]
+pub struct BlockColorPalette {
+    entries: Vec<(BlockState, [u8; 3])>,
+    cache: HashMap<[u8; 3], usize>,
+}
+
+impl Default for BlockColorPalette {
+    fn default() -> Self {
+        Self::full_blocks()
+    }
+}
+
+impl BlockColorPalette {
+    /// Every full, opaque block in the bundled table.
+    pub fn full_blocks() -> Self {
+        Self::from_colors(FULL_BLOCK_COLORS.iter()
+            .map(|(name, color)| (BlockState::new(format!("minecraft:{}", name)), *color))
+            .collect())
+    }
+
+    /// The given blocks, colored from the bundled table. Blocks without a known color are skipped.
+    pub fn from_blocks(blocks: &[BlockState]) -> Self {
+        Self::from_colors(blocks.iter()
+            .filter_map(|block| get_block_color(&block.name).map(|color| (block.clone(), color)))
+            .collect())
+    }
+
+    /// Blocks with explicitly chosen colors.
+    pub fn from_colors(entries: Vec<(BlockState, [u8; 3])>) -> Self {
+        BlockColorPalette { entries, cache: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(BlockState, [u8; 3])] {
+        &self.entries
+    }
+
+    /// Keeps only the blocks for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&BlockState) -> bool) {
+        self.entries.retain(|(block, _)| keep(block));
+        self.cache.clear();
+    }
+
+    /// Index of the entry closest to `color`, or `None` if the palette is empty.
+    pub fn nearest_index(&mut self, color: [u8; 3]) -> Option<usize> {
+        if let Some(&index) = self.cache.get(&color) {
+            return Some(index);
+        }
+        let index = self.entries.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| color_distance(a.1, color).total_cmp(&color_distance(b.1, color)))
+            .map(|(index, _)| index)?;
+        self.cache.insert(color, index);
+        Some(index)
+    }
+
+    /// The block closest to `color`, or `None` if the palette is empty.
+    pub fn nearest(&mut self, color: [u8; 3]) -> Option<&BlockState> {
+        let index = self.nearest_index(color)?;
+        Some(&self.entries[index].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_color_lookup() {
+        assert_eq!(get_block_color("minecraft:stone"), Some([125, 125, 125]));
+        assert_eq!(get_block_color("minecraft:oak_stairs"), get_block_color("minecraft:oak_planks"));
+        assert_eq!(get_block_color("minecraft:stone_brick_slab"), get_block_color("minecraft:stone_bricks"));
+        assert_eq!(get_block_color("minecraft:red_carpet"), get_block_color("minecraft:red_wool"));
+        assert_eq!(get_block_color("minecraft:waxed_oxidized_copper"), get_block_color("minecraft:oxidized_copper"));
+        assert_eq!(get_block_color("minecraft:deepslate_diamond_ore"), get_block_color("minecraft:diamond_ore"));
+        assert_eq!(get_block_color("minecraft:air"), None);
+        assert_eq!(get_block_color("minecraft:cave_air"), None);
+        assert_eq!(get_block_color("mymod:thing"), None);
+    }
+
+    #[test]
+    fn test_nearest_block() {
+        let mut palette = BlockColorPalette::full_blocks();
+        assert_eq!(palette.nearest([234, 236, 237]).unwrap().name, "minecraft:white_wool");
+        assert_eq!(palette.nearest([8, 10, 15]).unwrap().name, "minecraft:black_concrete");
+
+        let mut wool_only = BlockColorPalette::from_blocks(&[
+            BlockState::new("minecraft:white_wool".to_string()),
+            BlockState::new("minecraft:black_wool".to_string()),
+            BlockState::new("minecraft:unknown_block".to_string()),
+        ]);
+        assert_eq!(wool_only.len(), 2);
+        assert_eq!(wool_only.nearest([200, 200, 200]).unwrap().name, "minecraft:white_wool");
+        assert_eq!(wool_only.nearest([40, 40, 40]).unwrap().name, "minecraft:black_wool");
+    }
+}
@@ -63,17 +63,18 @@ impl BlockEntity {
         chest
     }
 
-    pub fn from_nbt(nbt: &NbtCompound) -> Self {
+    pub fn from_nbt(nbt: &NbtCompound) -> Result<Self, String> {
         let nbt_map = NbtMap::from_quartz_nbt(nbt);
         let id = nbt_map.get("Id")
             .and_then(|v| v.as_string())
             .cloned()
             .unwrap_or_else(|| "unknown".to_string());
-        let position = nbt_map.get("Pos")
-            .and_then(|v| v.as_int_array())
-            .map(|v| (v[0], v[1], v[2]))
-            .unwrap_or_else(|| (0, 0, 0));
-        BlockEntity { nbt: nbt_map, id, position }
+        let position = match nbt_map.get("Pos").and_then(|v| v.as_int_array()).map(Vec::as_slice) {
+            Some(&[x, y, z, ..]) => (x, y, z),
+            Some(pos) => return Err(format!("Block entity Pos has {} elements, expected 3", pos.len())),
+            None => (0, 0, 0),
+        };
+        Ok(BlockEntity { nbt: nbt_map, id, position })
     }
 
     pub fn to_nbt(&self) -> NbtCompound {
@@ -14,8 +14,11 @@ impl fmt::Display for BlockState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)?;
         if !self.properties.is_empty() {
+            // Sorted so the same state always prints the same way
+            let mut properties: Vec<_> = self.properties.iter().collect();
+            properties.sort();
             write!(f, "[")?;
-            for (i, (key, value)) in self.properties.iter().enumerate() {
+            for (i, (key, value)) in properties.into_iter().enumerate() {
                 if i > 0 {
                     write!(f, ",")?;
                 }
@@ -30,10 +33,10 @@ impl fmt::Display for BlockState {
 impl Hash for BlockState {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.name.hash(state);
-        for (k, v) in &self.properties {
-            k.hash(state);
-            v.hash(state);
-        }
+        // Equal states must hash equally whatever order their maps iterate in
+        let mut properties: Vec<_> = self.properties.iter().collect();
+        properties.sort();
+        properties.hash(state);
     }
 }
 
@@ -75,8 +78,11 @@ impl BlockState {
         compound.insert("Name", self.name.clone());
 
         if !self.properties.is_empty() {
+            // Sorted so the same state always writes the same bytes
+            let mut sorted: Vec<_> = self.properties.iter().collect();
+            sorted.sort();
             let mut properties = NbtCompound::new();
-            for (key, value) in &self.properties {
+            for (key, value) in sorted {
                 properties.insert(key, value.clone());
             }
             compound.insert("Properties", properties);
@@ -121,4 +127,26 @@ mod tests {
         assert_eq!(block.name, "minecraft:stone");
         assert_eq!(block.properties.get("variant"), Some(&"granite".to_string()));
     }
+
+    #[test]
+    fn test_equal_states_hash_equally() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let hash = |block: &BlockState| {
+            let mut hasher = DefaultHasher::new();
+            block.hash(&mut hasher);
+            hasher.finish()
+        };
+        // Maps built separately iterate in different orders
+        let properties = [("facing", "east"), ("half", "top"), ("shape", "straight"), ("waterlogged", "false")];
+        let build = || properties.iter().fold(BlockState::new("minecraft:oak_stairs".to_string()), |b, (k, v)| {
+            b.with_property(k.to_string(), v.to_string())
+        });
+        let first = build();
+        for _ in 0..16 {
+            let other = build();
+            assert_eq!(other, first);
+            assert_eq!(hash(&other), hash(&first));
+        }
+    }
 }
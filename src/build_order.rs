@@ -0,0 +1,276 @@
+//! Placement order for printers and build bots.
+//!
+//! The plan walks the schematic chunk by chunk in the order of a [`ChunkLoadingStrategy`].
+//! Within a chunk, plain blocks come first, then gravity blocks, then blocks that hang on
+//! something. Redstone is held back until everything else stands, so no circuit fires
+//! halfway through the build. A block whose support isn't placed yet waits for it, so torches
+//! never come before their wall and sand always goes bottom-up.
+
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use crate::{BlockState, UniversalSchematic};
+use crate::map_art::is_gravity_block;
+use crate::redstone::{self, Direction, Pos};
+use crate::survival::support_offset;
+use crate::universal_schematic::ChunkLoadingStrategy;
+
+/// Redstone-related blocks that `redstone::classify` doesn't cover.
+const OTHER_REDSTONE: &[&str] = &[
+    "powered_rail", "detector_rail", "activator_rail", "target", "dispenser", "dropper", "note_block",
+    "tnt", "tripwire", "tripwire_hook", "daylight_detector", "sculk_sensor", "calibrated_sculk_sensor",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildPhase {
+    Structure,
+    Gravity,
+    Attached,
+    Redstone,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildStep {
+    pub position: Pos,
+    pub block: String,
+    pub phase: BuildPhase,
+    /// Side of the target position to click on, i.e. where the neighbour to place against is.
+    pub against: Option<Direction>,
+    /// Horizontal or vertical direction to look in while placing, for blocks with a `facing`.
+    pub look: Option<Direction>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BuildPlan {
+    pub steps: Vec<BuildStep>,
+}
+
+impl BuildPlan {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildPlanOptions {
+    /// Edge length of the cubic chunks the build is split into.
+    pub chunk_size: i32,
+    pub strategy: ChunkLoadingStrategy,
+    /// Place redstone after everything else instead of chunk by chunk.
+    pub redstone_last: bool,
+}
+
+impl Default for BuildPlanOptions {
+    fn default() -> Self {
+        BuildPlanOptions {
+            chunk_size: 16,
+            strategy: ChunkLoadingStrategy::BottomUp,
+            redstone_last: true,
+        }
+    }
+}
+
+fn short_name(block: &BlockState) -> &str {
+    block.name.strip_prefix("minecraft:").unwrap_or(&block.name)
+}
+
+/// Halves and parts that appear on their own when the rest of the block is placed.
+fn is_placed_automatically(block: &BlockState) -> bool {
+    let name = short_name(block);
+    let property = |key: &str| block.get_property(key).map(|v| v.as_str());
+    matches!(name, "air" | "cave_air" | "void_air" | "structure_void" | "piston_head" | "moving_piston")
+        || (property("half") == Some("upper") && !name.ends_with("_stairs") && !name.ends_with("_trapdoor"))
+        || (name.ends_with("_bed") && property("part") == Some("head"))
+}
+
+fn phase(block: &BlockState) -> BuildPhase {
+    if redstone::classify(block).is_some() || OTHER_REDSTONE.contains(&short_name(block)) {
+        BuildPhase::Redstone
+    } else if support_offset(block).is_some() {
+        BuildPhase::Attached
+    } else if is_gravity_block(&block.name) {
+        BuildPhase::Gravity
+    } else {
+        BuildPhase::Structure
+    }
+}
+
+fn direction_of(offset: Pos) -> Option<Direction> {
+    Direction::ALL.into_iter().find(|d| d.offset((0, 0, 0)) == offset)
+}
+
+/// Where to look when placing, from the block's `facing`. Most blocks face the player; stairs,
+/// doors, beds, gates and observers face away.
+fn look_direction(block: &BlockState) -> Option<Direction> {
+    let facing = Direction::from_name(block.get_property("facing")?)?;
+    let name = short_name(block);
+    let faces_away = name.ends_with("_stairs")
+        || name.ends_with("_door")
+        || name.ends_with("_bed")
+        || name.ends_with("_fence_gate")
+        || name == "observer";
+    Some(if faces_away { facing } else { facing.opposite() })
+}
+
+struct Planner<'a> {
+    blocks: HashMap<Pos, &'a BlockState>,
+    placed: HashSet<Pos>,
+    /// Blocks waiting for the block at the key to be placed.
+    waiting: HashMap<Pos, Vec<Pos>>,
+    steps: Vec<BuildStep>,
+}
+
+impl Planner<'_> {
+    fn dependency(&self, pos: Pos) -> Option<Pos> {
+        let block = self.blocks[&pos];
+        let offset = support_offset(block)
+            .or_else(|| is_gravity_block(&block.name).then_some((0, -1, 0)))?;
+        let dependency = (pos.0 + offset.0, pos.1 + offset.1, pos.2 + offset.2);
+        (self.blocks.contains_key(&dependency) && !self.placed.contains(&dependency)).then_some(dependency)
+    }
+
+    /// The neighbour to click on: the support if the block has one, otherwise any placed
+    /// neighbour, along the block's axis for logs and pillars.
+    fn against(&self, pos: Pos) -> Option<Direction> {
+        let block = self.blocks[&pos];
+        if let Some(offset) = support_offset(block) {
+            return direction_of(offset);
+        }
+        let candidates: &[Direction] = match block.get_property("axis").map(|a| a.as_str()) {
+            Some("x") => &[Direction::West, Direction::East],
+            Some("y") => &[Direction::Down, Direction::Up],
+            Some("z") => &[Direction::North, Direction::South],
+            _ => &[Direction::Down, Direction::North, Direction::South, Direction::West, Direction::East, Direction::Up],
+        };
+        candidates.iter().copied().find(|d| self.placed.contains(&d.offset(pos)))
+    }
+
+    fn place(&mut self, pos: Pos) {
+        let mut ready = vec![pos];
+        while let Some(pos) = ready.pop() {
+            if self.placed.contains(&pos) {
+                continue;
+            }
+            if let Some(dependency) = self.dependency(pos) {
+                self.waiting.entry(dependency).or_default().push(pos);
+                continue;
+            }
+            let block = self.blocks[&pos];
+            self.steps.push(BuildStep {
+                position: pos,
+                block: block.to_string(),
+                phase: phase(block),
+                against: self.against(pos),
+                look: look_direction(block),
+            });
+            self.placed.insert(pos);
+            if let Some(mut waiters) = self.waiting.remove(&pos) {
+                waiters.reverse();
+                ready.extend(waiters);
+            }
+        }
+    }
+}
+
+/// Orders every block of the schematic for placement.
+pub fn plan_build(schematic: &UniversalSchematic, options: &BuildPlanOptions) -> BuildPlan {
+    let blocks: HashMap<Pos, &BlockState> = schematic.iter_blocks()
+        .filter(|(_, block)| !is_placed_automatically(block))
+        .map(|(pos, block)| ((pos.x, pos.y, pos.z), block))
+        .collect();
+    let size = options.chunk_size.max(1);
+    let chunks: Vec<Vec<Pos>> = schematic.iter_chunks(size, size, size, Some(options.strategy))
+        .map(|chunk| chunk.positions.iter().map(|p| (p.x, p.y, p.z)).collect())
+        .collect();
+
+    let mut planner = Planner { blocks, placed: HashSet::new(), waiting: HashMap::new(), steps: Vec::new() };
+    let mut redstone = Vec::new();
+    for mut positions in chunks {
+        positions.retain(|pos| planner.blocks.contains_key(pos));
+        positions.sort_by_key(|&(x, y, z)| (phase(planner.blocks[&(x, y, z)]), y, z, x));
+        for pos in positions {
+            if options.redstone_last && phase(planner.blocks[&pos]) == BuildPhase::Redstone {
+                redstone.push(pos);
+            } else {
+                planner.place(pos);
+            }
+        }
+    }
+    for pos in redstone {
+        planner.place(pos);
+    }
+
+    // Anything still waiting depends on a loop of supports; place it bottom-up regardless
+    let mut stuck: Vec<Pos> = planner.waiting.drain().flat_map(|(_, waiters)| waiters).collect();
+    stuck.sort_by_key(|&(x, y, z)| (y, z, x));
+    for pos in stuck {
+        if planner.placed.insert(pos) {
+            let block = planner.blocks[&pos];
+            let against = planner.against(pos);
+            planner.steps.push(BuildStep { position: pos, block: block.to_string(), phase: phase(block), against, look: look_direction(block) });
+        }
+    }
+    BuildPlan { steps: planner.steps }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::block;
+
+    fn index_of(plan: &BuildPlan, pos: Pos) -> usize {
+        plan.steps.iter().position(|s| s.position == pos).unwrap()
+    }
+
+    #[test]
+    fn test_supports_gravity_and_redstone_order() {
+        let mut schematic = UniversalSchematic::new("Build".to_string());
+        for x in 0..4 {
+            schematic.set_block(x, 0, 0, block("stone", &[]));
+        }
+        schematic.set_block(0, 0, 1, block("wall_torch", &[("facing", "south")]));
+        schematic.set_block(1, 1, 0, block("redstone_wire", &[]));
+        schematic.set_block(2, 1, 0, block("repeater", &[("facing", "west")]));
+        schematic.set_block(3, 1, 0, block("sand", &[]));
+        schematic.set_block(3, 2, 0, block("sand", &[]));
+        schematic.set_block(3, 3, 0, block("gravel", &[]));
+        schematic.set_block(2, 2, 0, block("oak_stairs", &[("facing", "east"), ("half", "bottom")]));
+
+        // Top-down chunks would put the gravel first without the dependency on what's below
+        let options = BuildPlanOptions { chunk_size: 2, strategy: ChunkLoadingStrategy::TopDown, ..BuildPlanOptions::default() };
+        let plan = plan_build(&schematic, &options);
+        assert_eq!(plan.steps.len(), 11);
+        assert!(index_of(&plan, (3, 1, 0)) < index_of(&plan, (3, 2, 0)));
+        assert!(index_of(&plan, (3, 2, 0)) < index_of(&plan, (3, 3, 0)));
+        assert!(index_of(&plan, (0, 0, 0)) < index_of(&plan, (0, 0, 1)));
+        assert!(plan.steps[9..].iter().all(|s| s.phase == BuildPhase::Redstone));
+
+        let torch = &plan.steps[index_of(&plan, (0, 0, 1))];
+        assert_eq!((torch.phase, torch.against), (BuildPhase::Attached, Some(Direction::North)));
+        let repeater = &plan.steps[index_of(&plan, (2, 1, 0))];
+        assert_eq!((repeater.against, repeater.look), (Some(Direction::Down), Some(Direction::East)));
+        let stairs = &plan.steps[index_of(&plan, (2, 2, 0))];
+        assert_eq!(stairs.look, Some(Direction::East));
+    }
+
+    #[test]
+    fn test_upper_halves_skipped_and_json() {
+        let mut schematic = UniversalSchematic::new("Door".to_string());
+        schematic.set_block(0, 0, 0, block("stone", &[]));
+        schematic.set_block(0, 1, 0, block("oak_door", &[("half", "lower"), ("facing", "north")]));
+        schematic.set_block(0, 2, 0, block("oak_door", &[("half", "upper"), ("facing", "north")]));
+        schematic.set_block(1, 0, 0, block("oak_log", &[("axis", "x")]));
+
+        let plan = plan_build(&schematic, &BuildPlanOptions::default());
+        assert_eq!(plan.steps.len(), 3);
+        let log = &plan.steps[index_of(&plan, (1, 0, 0))];
+        assert_eq!(log.against, Some(Direction::West));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        let door = &json["steps"][2];
+        assert_eq!(door["position"], serde_json::json!([0, 1, 0]));
+        assert_eq!(door["phase"], "attached");
+        assert_eq!(door["against"], "down");
+        assert_eq!(door["look"], "north");
+    }
+}
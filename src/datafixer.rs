@@ -0,0 +1,984 @@
+//! Converting schematics between Minecraft data versions.
+//!
+//! A small take on the game's DataFixerUpper: every fix is tied to the data version that
+//! introduced the change and knows how to apply it and, where the old format can hold the
+//! result, undo it. Converting walks the fixes between the schematic's `mc_version` and the
+//! target in order. Palettes, block entities, entities and the items they hold are converted.
+//! Item `tag` entries with a 1.20.5 component (name, lore, colour, damage, enchantments and the
+//! like) become that component; the rest of `tag` moves to `minecraft:custom_data`, as the
+//! game's own upgrade does. Components with no `tag` form are kept as they are on downgrade.
+//!
+//! Blocks that don't exist at all in an older version can't be fixed, only replaced. A
+//! [`SubstitutionRules`] table says what to put in their place, and [`downgrade`] applies it
+//! before converting, reporting every block it swapped and every block it knows is too new but
+//! had no rule for.
+
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::{BlockState, UniversalSchematic};
+use quartz_nbt::NbtTag;
+use crate::block_entity::BlockEntity;
+use crate::entity::{Entity, NbtValue as EntityNbtValue};
+use crate::utils::{NbtMap, NbtValue};
+
+/// Data versions of releases with changes the fixer knows about.
+pub mod data_version {
+    pub const V1_16_5: i32 = 2586;
+    pub const V1_17: i32 = 2724;
+    pub const V1_18_2: i32 = 2975;
+    pub const V1_19_4: i32 = 3337;
+    pub const V1_20: i32 = 3463;
+    pub const V1_20_3: i32 = 3698;
+    pub const V1_20_4: i32 = 3700;
+    pub const V1_20_5: i32 = 3837;
+    pub const V1_21: i32 = 3953;
+    pub const V1_21_4: i32 = 4189;
+    pub const V1_21_5: i32 = 4325;
+    pub const V1_21_6: i32 = 4435;
+    pub const V1_21_9: i32 = 4554;
+}
+
+enum FixKind {
+    /// A block (and its item) renamed from the first to the second name.
+    Rename(&'static str, &'static str),
+    /// Rewrites a block state; the flag is true when upgrading. Returns whether anything changed.
+    Block(fn(&mut BlockState, bool) -> bool),
+    /// Rewrites a block entity.
+    BlockEntity(fn(&mut BlockEntity, bool) -> bool),
+    /// Rewrites an entity's NBT.
+    Entity(fn(&mut HashMap<String, EntityNbtValue>, bool) -> bool),
+}
+
+struct Fix {
+    data_version: i32,
+    description: &'static str,
+    kind: FixKind,
+}
+
+const FIXES: &[Fix] = &[
+    Fix { data_version: data_version::V1_17, description: "grass_path renamed to dirt_path", kind: FixKind::Rename("grass_path", "dirt_path") },
+    Fix { data_version: data_version::V1_17, description: "filled cauldrons split into water_cauldron", kind: FixKind::Block(fix_cauldron) },
+    Fix { data_version: data_version::V1_20, description: "sign text moved to front_text and back_text", kind: FixKind::BlockEntity(fix_sign_text) },
+    Fix { data_version: data_version::V1_20_3, description: "grass renamed to short_grass", kind: FixKind::Rename("grass", "short_grass") },
+    Fix { data_version: data_version::V1_20_5, description: "item Count renamed to count in containers", kind: FixKind::BlockEntity(fix_container_item_counts) },
+    Fix { data_version: data_version::V1_20_5, description: "item Count renamed to count in entities", kind: FixKind::Entity(fix_entity_item_counts) },
+    Fix { data_version: data_version::V1_20_5, description: "item tag moved to components in containers", kind: FixKind::BlockEntity(fix_container_item_components) },
+    Fix { data_version: data_version::V1_20_5, description: "item tag moved to components in entities", kind: FixKind::Entity(fix_entity_item_components) },
+    Fix { data_version: data_version::V1_21_9, description: "chain renamed to iron_chain", kind: FixKind::Rename("chain", "iron_chain") },
+];
+
+/// What a conversion did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FixReport {
+    pub from: i32,
+    pub to: i32,
+    /// Descriptions of the fixes that changed something, in the order they ran.
+    pub applied: Vec<String>,
+}
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn fix_cauldron(block: &mut BlockState, upgrade: bool) -> bool {
+    match strip_namespace(&block.name) {
+        "cauldron" if upgrade => {
+            let level = block.properties.remove("level");
+            match level.as_deref() {
+                Some("0") | None => level.is_some(),
+                Some(level) => {
+                    let level = level.to_string();
+                    block.name = "minecraft:water_cauldron".to_string();
+                    block.properties.insert("level".to_string(), level);
+                    true
+                }
+            }
+        }
+        "water_cauldron" if !upgrade => {
+            block.name = "minecraft:cauldron".to_string();
+            true
+        }
+        // Older cauldrons only held water
+        "lava_cauldron" | "powder_snow_cauldron" if !upgrade => {
+            block.name = "minecraft:cauldron".to_string();
+            block.properties.insert("level".to_string(), "3".to_string());
+            true
+        }
+        "cauldron" => block.properties.insert("level".to_string(), "0".to_string()).is_none(),
+        _ => false,
+    }
+}
+
+fn fix_sign_text(block_entity: &mut BlockEntity, upgrade: bool) -> bool {
+    if strip_namespace(&block_entity.id) != "sign" {
+        return false;
+    }
+    let nbt = &mut block_entity.nbt;
+    let empty_line = || NbtValue::String("\"\"".to_string());
+    if upgrade {
+        if nbt.get("front_text").is_some() {
+            return false;
+        }
+        let messages = (1..=4)
+            .map(|line| nbt.remove(&format!("Text{}", line)).unwrap_or_else(empty_line))
+            .collect();
+        let color = nbt.remove("Color").unwrap_or_else(|| NbtValue::String("black".to_string()));
+        let glowing = nbt.remove("GlowingText").unwrap_or(NbtValue::Byte(0));
+        let mut front = NbtMap::new();
+        front.insert("messages".to_string(), NbtValue::List(messages));
+        front.insert("color".to_string(), color);
+        front.insert("has_glowing_text".to_string(), glowing);
+        let mut back = NbtMap::new();
+        back.insert("messages".to_string(), NbtValue::List((0..4).map(|_| empty_line()).collect()));
+        back.insert("color".to_string(), NbtValue::String("black".to_string()));
+        back.insert("has_glowing_text".to_string(), NbtValue::Byte(0));
+        nbt.insert("front_text".to_string(), NbtValue::Compound(front));
+        nbt.insert("back_text".to_string(), NbtValue::Compound(back));
+        nbt.insert("is_waxed".to_string(), NbtValue::Byte(0));
+    } else {
+        // The back of the sign and waxing don't exist before 1.20
+        let Some(NbtValue::Compound(front)) = nbt.remove("front_text") else { return false };
+        nbt.remove("back_text");
+        nbt.remove("is_waxed");
+        if let Some(NbtValue::List(messages)) = front.get("messages") {
+            for (line, message) in messages.iter().take(4).enumerate() {
+                nbt.insert(format!("Text{}", line + 1), message.clone());
+            }
+        }
+        if let Some(color) = front.get("color") {
+            nbt.insert("Color".to_string(), color.clone());
+        }
+        if let Some(glowing) = front.get("has_glowing_text") {
+            nbt.insert("GlowingText".to_string(), glowing.clone());
+        }
+    }
+    true
+}
+
+/// Calls `f` on every item stack stored directly in a block entity.
+fn for_each_item(nbt: &mut NbtMap, f: &mut impl FnMut(&mut NbtMap) -> bool) -> bool {
+    let mut changed = false;
+    for key in ["Items", "Item", "RecordItem", "Book"] {
+        match nbt.get_mut(key) {
+            Some(NbtValue::List(items)) => {
+                for item in items {
+                    if let NbtValue::Compound(item) = item {
+                        changed |= f(item);
+                    }
+                }
+            }
+            Some(NbtValue::Compound(item)) => changed |= f(item),
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// Calls `f` on every item stack an entity holds or is.
+fn for_each_entity_item(nbt: &mut HashMap<String, EntityNbtValue>, f: &mut impl FnMut(&mut HashMap<String, EntityNbtValue>) -> bool) -> bool {
+    let mut changed = false;
+    for key in ["Items", "Item", "HandItems", "ArmorItems"] {
+        match nbt.get_mut(key) {
+            Some(EntityNbtValue::List(items)) => {
+                for item in items {
+                    if let EntityNbtValue::Compound(item) = item {
+                        changed |= f(item);
+                    }
+                }
+            }
+            Some(EntityNbtValue::Compound(item)) => changed |= f(item),
+            _ => {}
+        }
+    }
+    changed
+}
+
+pub(crate) fn fix_container_item_counts(block_entity: &mut BlockEntity, upgrade: bool) -> bool {
+    for_each_item(&mut block_entity.nbt, &mut |item| {
+        let (from, to) = if upgrade { ("Count", "count") } else { ("count", "Count") };
+        let count = match item.get(from) {
+            Some(NbtValue::Byte(count)) => *count as i32,
+            Some(NbtValue::Int(count)) => *count,
+            _ => return false,
+        };
+        item.remove(from);
+        let count = if upgrade { NbtValue::Int(count) } else { NbtValue::Byte(count.clamp(0, 127) as i8) };
+        item.insert(to.to_string(), count);
+        true
+    })
+}
+
+pub(crate) fn fix_entity_item_counts(nbt: &mut HashMap<String, EntityNbtValue>, upgrade: bool) -> bool {
+    for_each_entity_item(nbt, &mut |item| {
+        let (from, to) = if upgrade { ("Count", "count") } else { ("count", "Count") };
+        let count = match item.get(from) {
+            Some(EntityNbtValue::Byte(count)) => *count as i32,
+            Some(EntityNbtValue::Int(count)) => *count,
+            _ => return false,
+        };
+        item.remove(from);
+        let count = if upgrade { EntityNbtValue::Int(count) } else { EntityNbtValue::Byte(count.clamp(0, 127) as i8) };
+        item.insert(to.to_string(), count);
+        true
+    })
+}
+
+pub(crate) fn fix_container_item_components(block_entity: &mut BlockEntity, upgrade: bool) -> bool {
+    for_each_item(&mut block_entity.nbt, &mut |item| fix_item_components(item, upgrade))
+}
+
+pub(crate) fn fix_entity_item_components(nbt: &mut HashMap<String, EntityNbtValue>, upgrade: bool) -> bool {
+    for_each_entity_item(nbt, &mut |item| {
+        let key = if upgrade { "tag" } else { "components" };
+        if !matches!(item.get(key), Some(EntityNbtValue::Compound(_))) {
+            return false;
+        }
+        let NbtTag::Compound(compound) = Entity::value_to_nbt_tag(&EntityNbtValue::Compound(item.clone())) else { return false };
+        let mut converted = NbtMap::from_quartz_nbt(&compound);
+        if !fix_item_components(&mut converted, upgrade) {
+            return false;
+        }
+        match Entity::nbt_tag_to_value(&NbtTag::Compound(converted.to_quartz_nbt())) {
+            EntityNbtValue::Compound(converted) => *item = converted,
+            _ => return false,
+        }
+        true
+    })
+}
+
+/// `tag` keys that map one-to-one onto a component.
+const ITEM_TAG_COMPONENTS: [(&str, &str); 3] = [
+    ("Damage", "minecraft:damage"),
+    ("RepairCost", "minecraft:repair_cost"),
+    ("CustomModelData", "minecraft:custom_model_data"),
+];
+
+const ENCHANTMENT_COMPONENTS: [(&str, &str); 2] = [
+    ("Enchantments", "minecraft:enchantments"),
+    ("StoredEnchantments", "minecraft:stored_enchantments"),
+];
+
+fn fix_item_components(item: &mut NbtMap, upgrade: bool) -> bool {
+    if upgrade {
+        let Some(tag) = take_compound(item, "tag") else { return false };
+        let components = tag_to_components(tag, take_compound(item, "components").unwrap_or_default());
+        if !components.is_empty() {
+            item.insert("components".to_string(), NbtValue::Compound(components));
+        }
+    } else {
+        let Some(mut components) = take_compound(item, "components") else { return false };
+        let tag = components_to_tag(&mut components);
+        if !components.is_empty() {
+            item.insert("components".to_string(), NbtValue::Compound(components));
+        }
+        if !tag.is_empty() {
+            item.insert("tag".to_string(), NbtValue::Compound(tag));
+        }
+    }
+    true
+}
+
+fn tag_to_components(mut tag: NbtMap, mut components: NbtMap) -> NbtMap {
+    if let Some(mut display) = take_compound(&mut tag, "display") {
+        if let Some(name) = display.remove("Name") {
+            components.insert("minecraft:custom_name".to_string(), name);
+        }
+        if let Some(lore) = display.remove("Lore") {
+            components.insert("minecraft:lore".to_string(), lore);
+        }
+        if let Some(rgb) = display.remove("color") {
+            let mut dyed = NbtMap::new();
+            dyed.insert("rgb".to_string(), rgb);
+            components.insert("minecraft:dyed_color".to_string(), NbtValue::Compound(dyed));
+        }
+        if !display.is_empty() {
+            tag.insert("display".to_string(), NbtValue::Compound(display));
+        }
+    }
+    for (key, component) in ITEM_TAG_COMPONENTS {
+        if let Some(value) = tag.remove(key) {
+            components.insert(component.to_string(), value);
+        }
+    }
+    if let Some(unbreakable) = tag.remove("Unbreakable") {
+        if as_int(&unbreakable).is_some_and(|value| value != 0) {
+            components.insert("minecraft:unbreakable".to_string(), NbtValue::Compound(NbtMap::new()));
+        }
+    }
+    for (key, component) in ENCHANTMENT_COMPONENTS {
+        let Some(NbtValue::List(enchantments)) = tag.remove(key) else { continue };
+        let mut levels = NbtMap::new();
+        for enchantment in enchantments {
+            let NbtValue::Compound(enchantment) = enchantment else { continue };
+            if let (Some(NbtValue::String(id)), Some(level)) = (enchantment.get("id"), enchantment.get("lvl").and_then(as_int)) {
+                levels.insert(id.clone(), NbtValue::Int(level));
+            }
+        }
+        let mut value = NbtMap::new();
+        value.insert("levels".to_string(), NbtValue::Compound(levels));
+        components.insert(component.to_string(), NbtValue::Compound(value));
+    }
+    if !tag.is_empty() {
+        components.insert("minecraft:custom_data".to_string(), NbtValue::Compound(tag));
+    }
+    components
+}
+
+/// Moves every component the old format can express back into a `tag`, leaving the rest.
+fn components_to_tag(components: &mut NbtMap) -> NbtMap {
+    let mut tag = take_compound(components, "minecraft:custom_data").unwrap_or_default();
+    let mut display = take_compound(&mut tag, "display").unwrap_or_default();
+    if let Some(name) = components.remove("minecraft:custom_name") {
+        display.insert("Name".to_string(), name);
+    }
+    if let Some(lore) = components.remove("minecraft:lore") {
+        display.insert("Lore".to_string(), lore);
+    }
+    if let Some(rgb) = take_compound(components, "minecraft:dyed_color").and_then(|mut dyed| dyed.remove("rgb")) {
+        display.insert("color".to_string(), rgb);
+    }
+    if !display.is_empty() {
+        tag.insert("display".to_string(), NbtValue::Compound(display));
+    }
+    for (key, component) in ITEM_TAG_COMPONENTS {
+        if let Some(value) = components.remove(component) {
+            tag.insert(key.to_string(), value);
+        }
+    }
+    if components.remove("minecraft:unbreakable").is_some() {
+        tag.insert("Unbreakable".to_string(), NbtValue::Byte(1));
+    }
+    for (key, component) in ENCHANTMENT_COMPONENTS {
+        let Some(levels) = take_compound(components, component).and_then(|mut value| take_compound(&mut value, "levels")) else { continue };
+        let enchantments = levels.iter()
+            .filter_map(|(id, level)| {
+                let mut enchantment = NbtMap::new();
+                enchantment.insert("id".to_string(), NbtValue::String(id.clone()));
+                enchantment.insert("lvl".to_string(), NbtValue::Short(as_int(level)?.clamp(0, i16::MAX as i32) as i16));
+                Some(NbtValue::Compound(enchantment))
+            })
+            .collect();
+        tag.insert(key.to_string(), NbtValue::List(enchantments));
+    }
+    tag
+}
+
+/// Removes and returns `key` if it holds a compound, leaving any other value in place.
+fn take_compound(nbt: &mut NbtMap, key: &str) -> Option<NbtMap> {
+    match nbt.get(key) {
+        Some(NbtValue::Compound(_)) => match nbt.remove(key) {
+            Some(NbtValue::Compound(compound)) => Some(compound),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn as_int(value: &NbtValue) -> Option<i32> {
+    match value {
+        NbtValue::Byte(value) => Some(i32::from(*value)),
+        NbtValue::Short(value) => Some(i32::from(*value)),
+        NbtValue::Int(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn rename(name: &mut String, from: &str, to: &str) -> bool {
+    if strip_namespace(name) == from {
+        *name = format!("minecraft:{}", to);
+        true
+    } else {
+        false
+    }
+}
+
+fn apply_fix(schematic: &mut UniversalSchematic, fix: &Fix, upgrade: bool) -> bool {
+    let mut changed = false;
+    for region in schematic.regions.values_mut() {
+        match fix.kind {
+            FixKind::Rename(old, new) => {
+                let (from, to) = if upgrade { (old, new) } else { (new, old) };
+                region.remap_palette(|block| {
+                    let mut block = block.clone();
+                    changed |= rename(&mut block.name, from, to);
+                    block
+                });
+                for block_entity in region.block_entities.values_mut() {
+                    changed |= for_each_item(&mut block_entity.nbt, &mut |item| match item.get_mut("id") {
+                        Some(NbtValue::String(id)) => rename(id, from, to),
+                        _ => false,
+                    });
+                }
+                for entity in region.entities.iter_mut() {
+                    changed |= for_each_entity_item(&mut entity.nbt, &mut |item| match item.get_mut("id") {
+                        Some(EntityNbtValue::String(id)) => rename(id, from, to),
+                        _ => false,
+                    });
+                }
+            }
+            FixKind::Block(f) => {
+                region.remap_palette(|block| {
+                    let mut block = block.clone();
+                    changed |= f(&mut block, upgrade);
+                    block
+                });
+            }
+            FixKind::BlockEntity(f) => {
+                for block_entity in region.block_entities.values_mut() {
+                    changed |= f(block_entity, upgrade);
+                }
+            }
+            FixKind::Entity(f) => {
+                for entity in region.entities.iter_mut() {
+                    changed |= f(&mut entity.nbt, upgrade);
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Converts the schematic from its `mc_version` to `target`, upgrading or downgrading, and
+/// sets `mc_version` to `target`.
+pub fn convert(schematic: &mut UniversalSchematic, target: i32) -> Result<FixReport, String> {
+    let from = schematic.metadata.mc_version
+        .ok_or("The schematic has no data version to convert from")?;
+    let mut report = FixReport { from, to: target, applied: Vec::new() };
+    let upgrade = target >= from;
+    let mut fixes: Vec<&Fix> = FIXES.iter()
+        .filter(|fix| fix.data_version > from.min(target) && fix.data_version <= from.max(target))
+        .collect();
+    if !upgrade {
+        fixes.reverse();
+    }
+    for fix in fixes {
+        if apply_fix(schematic, fix, upgrade) {
+            report.applied.push(fix.description.to_string());
+        }
+    }
+    schematic.metadata.mc_version = Some(target);
+    Ok(report)
+}
+
+/// Replaces a block that an older data version doesn't have.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubstitutionRule {
+    /// Block name without the namespace. A `*` at the start or end matches any text, which
+    /// is carried over to a `*` in the replacement.
+    pub pattern: String,
+    /// Data version that added the block; the rule applies to targets before it.
+    pub added_in: i32,
+    /// Block to use instead, optionally with its own `[properties]`.
+    pub replacement: String,
+    /// Keep the original properties and block entity when the replacement sets no properties
+    /// of its own. Meant for swapping between blocks of the same family.
+    #[serde(default = "default_keep_properties")]
+    pub keep_properties: bool,
+}
+
+fn default_keep_properties() -> bool {
+    true
+}
+
+impl SubstitutionRule {
+    /// The text the `*` stands for when the pattern matches `name`, which has no namespace.
+    fn wildcard<'a>(&self, name: &'a str) -> Option<&'a str> {
+        if let Some(suffix) = self.pattern.strip_prefix('*') {
+            name.strip_suffix(suffix)
+        } else if let Some(prefix) = self.pattern.strip_suffix('*') {
+            name.strip_prefix(prefix)
+        } else if self.pattern == name {
+            Some("")
+        } else {
+            None
+        }
+    }
+}
+
+/// `(pattern, added_in, replacement, keep_properties)` rows of the built-in table.
+const VANILLA_SUBSTITUTIONS: &[(&str, i32, &str, bool)] = &[
+    ("*copper_chain", data_version::V1_21_9, "iron_chain", true),
+    ("*copper_bars", data_version::V1_21_9, "iron_bars", true),
+    ("*copper_lantern", data_version::V1_21_9, "lantern", true),
+    ("copper_torch", data_version::V1_21_9, "torch", true),
+    ("copper_wall_torch", data_version::V1_21_9, "wall_torch", true),
+    ("*copper_chest", data_version::V1_21_9, "chest", true),
+    ("*copper_golem_statue", data_version::V1_21_9, "air", false),
+    ("*_lightning_rod", data_version::V1_21_9, "lightning_rod", true),
+    ("*_shelf", data_version::V1_21_9, "*_slab", false),
+    ("dried_ghast", data_version::V1_21_6, "air", false),
+    ("leaf_litter", data_version::V1_21_5, "air", false),
+    ("wildflowers", data_version::V1_21_5, "air", false),
+    ("bush", data_version::V1_21_5, "short_grass", false),
+    ("firefly_bush", data_version::V1_21_5, "fern", false),
+    ("cactus_flower", data_version::V1_21_5, "air", false),
+    ("short_dry_grass", data_version::V1_21_5, "dead_bush", false),
+    ("tall_dry_grass", data_version::V1_21_5, "dead_bush", false),
+    ("stripped_pale_oak_*", data_version::V1_21_4, "stripped_dark_oak_*", true),
+    ("potted_pale_oak_sapling", data_version::V1_21_4, "potted_dark_oak_sapling", true),
+    ("pale_oak_*", data_version::V1_21_4, "dark_oak_*", true),
+    ("pale_moss_block", data_version::V1_21_4, "moss_block", true),
+    ("pale_moss_carpet", data_version::V1_21_4, "moss_carpet", false),
+    ("pale_hanging_moss", data_version::V1_21_4, "air", false),
+    ("creaking_heart", data_version::V1_21_4, "dark_oak_log[axis=y]", false),
+    ("potted_open_eyeblossom", data_version::V1_21_4, "potted_poppy", false),
+    ("potted_closed_eyeblossom", data_version::V1_21_4, "potted_poppy", false),
+    ("*eyeblossom", data_version::V1_21_4, "poppy", false),
+    ("resin_block", data_version::V1_21_4, "orange_terracotta", false),
+    ("resin_bricks", data_version::V1_21_4, "bricks", false),
+    ("chiseled_resin_bricks", data_version::V1_21_4, "bricks", false),
+    ("resin_brick_*", data_version::V1_21_4, "brick_*", true),
+    ("resin_clump", data_version::V1_21_4, "air", false),
+    ("*copper_bulb", data_version::V1_21, "redstone_lamp", false),
+    ("*copper_door", data_version::V1_21, "iron_door", true),
+    ("*copper_trapdoor", data_version::V1_21, "iron_trapdoor", true),
+    ("*copper_grate", data_version::V1_21, "cut_copper", false),
+    ("*chiseled_copper", data_version::V1_21, "cut_copper", false),
+    ("crafter", data_version::V1_21, "dropper[facing=down]", false),
+    ("trial_spawner", data_version::V1_21, "spawner", false),
+    ("vault", data_version::V1_21, "spawner", false),
+    ("heavy_core", data_version::V1_21, "iron_block", false),
+    ("polished_tuff", data_version::V1_21, "polished_andesite", false),
+    ("polished_tuff_wall", data_version::V1_21, "andesite_wall", true),
+    ("polished_tuff_*", data_version::V1_21, "polished_andesite_*", true),
+    ("chiseled_tuff", data_version::V1_21, "chiseled_stone_bricks", false),
+    ("chiseled_tuff_bricks", data_version::V1_21, "chiseled_stone_bricks", false),
+    ("tuff_bricks", data_version::V1_21, "stone_bricks", false),
+    ("tuff_brick_*", data_version::V1_21, "stone_brick_*", true),
+    ("tuff_*", data_version::V1_21, "andesite_*", true),
+    ("*_wall_hanging_sign", data_version::V1_20, "*_wall_sign", false),
+    ("*_hanging_sign", data_version::V1_20, "*_sign", false),
+    ("stripped_cherry_*", data_version::V1_20, "stripped_oak_*", true),
+    ("potted_cherry_sapling", data_version::V1_20, "potted_oak_sapling", true),
+    ("cherry_*", data_version::V1_20, "oak_*", true),
+    ("bamboo_planks", data_version::V1_20, "oak_planks", true),
+    ("bamboo_mosaic", data_version::V1_20, "oak_planks", true),
+    ("bamboo_mosaic_*", data_version::V1_20, "oak_*", true),
+    ("bamboo_stairs", data_version::V1_20, "oak_stairs", true),
+    ("bamboo_slab", data_version::V1_20, "oak_slab", true),
+    ("bamboo_fence", data_version::V1_20, "oak_fence", true),
+    ("bamboo_fence_gate", data_version::V1_20, "oak_fence_gate", true),
+    ("bamboo_door", data_version::V1_20, "oak_door", true),
+    ("bamboo_trapdoor", data_version::V1_20, "oak_trapdoor", true),
+    ("bamboo_pressure_plate", data_version::V1_20, "oak_pressure_plate", true),
+    ("bamboo_button", data_version::V1_20, "oak_button", true),
+    ("bamboo_sign", data_version::V1_20, "oak_sign", true),
+    ("bamboo_wall_sign", data_version::V1_20, "oak_wall_sign", true),
+    ("bamboo_block", data_version::V1_20, "oak_log", true),
+    ("stripped_bamboo_block", data_version::V1_20, "stripped_oak_log", true),
+    ("pink_petals", data_version::V1_20, "air", false),
+    ("torchflower", data_version::V1_20, "dandelion", false),
+    ("potted_torchflower", data_version::V1_20, "potted_dandelion", false),
+    ("torchflower_crop", data_version::V1_20, "air", false),
+    ("pitcher_plant", data_version::V1_20, "large_fern", true),
+    ("pitcher_crop", data_version::V1_20, "air", false),
+    ("sniffer_egg", data_version::V1_20, "air", false),
+    ("chiseled_bookshelf", data_version::V1_20, "bookshelf", false),
+    ("suspicious_sand", data_version::V1_20, "sand", false),
+    ("suspicious_gravel", data_version::V1_20, "gravel", false),
+    ("decorated_pot", data_version::V1_20, "flower_pot", false),
+    ("calibrated_sculk_sensor", data_version::V1_20, "sculk_sensor", false),
+];
+
+/// An ordered table of substitution rules; the first rule that matches a block wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstitutionRules {
+    pub rules: Vec<SubstitutionRule>,
+}
+
+impl Default for SubstitutionRules {
+    fn default() -> Self {
+        Self::vanilla()
+    }
+}
+
+impl SubstitutionRules {
+    /// Rough stand-ins for the blocks added between 1.20 and 1.21.9.
+    pub fn vanilla() -> Self {
+        let rules = VANILLA_SUBSTITUTIONS.iter()
+            .map(|&(pattern, added_in, replacement, keep_properties)| SubstitutionRule {
+                pattern: pattern.to_string(),
+                added_in,
+                replacement: replacement.to_string(),
+                keep_properties,
+            })
+            .collect();
+        SubstitutionRules { rules }
+    }
+
+    /// Reads rules from a JSON array of `{pattern, added_in, replacement, keep_properties}`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let rules = serde_json::from_str(json).map_err(|e| format!("Invalid substitution rules: {}", e))?;
+        Ok(SubstitutionRules { rules })
+    }
+
+    /// Puts `rules` in front of the table, so they take precedence over it.
+    pub fn with_overrides(mut self, rules: Vec<SubstitutionRule>) -> Self {
+        self.rules.splice(0..0, rules);
+        self
+    }
+
+    fn substitute_once(&self, block: &BlockState, target: i32) -> Option<(BlockState, bool)> {
+        let name = block.name.strip_prefix("minecraft:")?;
+        self.rules.iter().filter(|rule| rule.added_in > target).find_map(|rule| {
+            let wildcard = rule.wildcard(name)?;
+            let replacement = format!("minecraft:{}", rule.replacement.replace('*', wildcard));
+            let (mut state, _) = UniversalSchematic::parse_block_string(&replacement).ok()?;
+            let keep = rule.keep_properties && state.properties.is_empty();
+            if keep {
+                state.properties = block.properties.clone();
+            }
+            Some((state, keep))
+        })
+    }
+
+    /// The block to use instead of `block` at `target`, and whether its block entity still
+    /// fits. Replacements are themselves substituted if they are too new.
+    pub fn substitute(&self, block: &BlockState, target: i32) -> Option<(BlockState, bool)> {
+        let (mut block, mut keep_block_entity) = self.substitute_once(block, target)?;
+        for _ in 0..8 {
+            match self.substitute_once(&block, target) {
+                Some((next, keep)) if next != block => {
+                    block = next;
+                    keep_block_entity &= keep;
+                }
+                _ => break,
+            }
+        }
+        Some((block, keep_block_entity))
+    }
+
+    /// Whether a rule lists `block` as added after `target`.
+    pub fn is_newer(&self, block: &BlockState, target: i32) -> bool {
+        let Some(name) = block.name.strip_prefix("minecraft:") else { return false };
+        self.rules.iter().any(|rule| rule.added_in > target && rule.wildcard(name).is_some())
+    }
+}
+
+/// One block swapped for a stand-in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Substitution {
+    pub position: (i32, i32, i32),
+    pub from: String,
+    pub to: String,
+}
+
+/// What a downgrade did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DowngradeReport {
+    /// Every substituted block, ordered by position.
+    pub substitutions: Vec<Substitution>,
+    /// Blocks the built-in table knows are newer than the target but that no rule replaced,
+    /// left as they were.
+    pub unhandled: Vec<String>,
+    pub fixes: FixReport,
+}
+
+impl DowngradeReport {
+    /// How often each block was replaced by each stand-in, most frequent first.
+    pub fn summary(&self) -> Vec<(String, String, usize)> {
+        let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+        for substitution in &self.substitutions {
+            *counts.entry((&substitution.from, &substitution.to)).or_default() += 1;
+        }
+        let mut summary: Vec<_> = counts.into_iter()
+            .map(|((from, to), count)| (from.to_string(), to.to_string(), count))
+            .collect();
+        summary.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+        summary
+    }
+}
+
+/// Replaces blocks `target` doesn't have using `rules`, then converts the schematic to
+/// `target`. Block entities of replaced blocks are dropped unless the rule keeps them.
+pub fn downgrade(schematic: &mut UniversalSchematic, target: i32, rules: &SubstitutionRules) -> Result<DowngradeReport, String> {
+    if schematic.metadata.mc_version.is_none() {
+        return Err("The schematic has no data version to convert from".to_string());
+    }
+    let known = SubstitutionRules::vanilla();
+    let mut substitutions = Vec::new();
+    let mut unhandled = BTreeSet::new();
+    for region in schematic.regions.values_mut() {
+        let replacements: Vec<Option<(BlockState, bool)>> = region.palette.iter()
+            .map(|block| rules.substitute(block, target))
+            .collect();
+        for (block, replacement) in region.palette.iter().zip(&replacements) {
+            let result = replacement.as_ref().map_or(block, |(to, _)| to);
+            if known.is_newer(result, target) {
+                unhandled.insert(result.name.clone());
+            }
+        }
+        if replacements.iter().all(Option::is_none) {
+            continue;
+        }
+        for (index, &palette_index) in region.blocks.iter().enumerate() {
+            let Some((to, keep_block_entity)) = &replacements[palette_index] else { continue };
+            let position = region.index_to_coords(index);
+            substitutions.push(Substitution {
+                position,
+                from: region.palette[palette_index].to_string(),
+                to: to.to_string(),
+            });
+            if !keep_block_entity {
+                region.block_entities.remove(&position);
+            }
+        }
+        let pairs: Vec<(BlockState, BlockState)> = region.palette.iter().cloned()
+            .zip(replacements)
+            .filter_map(|(from, to)| Some((from, to?.0)))
+            .collect();
+        region.remap_palette(|block| pairs.iter()
+            .find(|(from, _)| from == block)
+            .map_or_else(|| block.clone(), |(_, to)| to.clone()));
+    }
+    substitutions.sort_by_key(|s| (s.position.1, s.position.2, s.position.0));
+    let fixes = convert(schematic, target)?;
+    Ok(DowngradeReport { substitutions, unhandled: unhandled.into_iter().collect(), fixes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_position::BlockPosition;
+
+    fn legacy_schematic() -> UniversalSchematic {
+        let mut schematic = UniversalSchematic::new("Old".to_string());
+        schematic.metadata.mc_version = Some(data_version::V1_16_5);
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:grass".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:cauldron".to_string())
+            .with_property("level".to_string(), "2".to_string()));
+        schematic.set_block(2, 0, 0, BlockState::new("minecraft:chain".to_string()));
+        schematic.set_block(3, 0, 0, BlockState::new("minecraft:oak_sign".to_string()));
+
+        let mut sign = BlockEntity::new("minecraft:sign".to_string(), (3, 0, 0));
+        sign.nbt.insert("Text1".to_string(), NbtValue::String("{\"text\":\"Hello\"}".to_string()));
+        sign.nbt.insert("Color".to_string(), NbtValue::String("red".to_string()));
+        schematic.set_block_entity(BlockPosition { x: 3, y: 0, z: 0 }, sign);
+
+        schematic.set_block(0, 1, 0, BlockState::new("minecraft:chest".to_string()));
+        let mut chest = BlockEntity::new("minecraft:chest".to_string(), (0, 1, 0));
+        let mut item = NbtMap::new();
+        item.insert("id".to_string(), NbtValue::String("minecraft:grass".to_string()));
+        item.insert("Count".to_string(), NbtValue::Byte(12));
+        chest.nbt.insert("Items".to_string(), NbtValue::List(vec![NbtValue::Compound(item)]));
+        schematic.set_block_entity(BlockPosition { x: 0, y: 1, z: 0 }, chest);
+        schematic
+    }
+
+    fn chest_item(schematic: &UniversalSchematic) -> NbtMap {
+        let chest = schematic.get_block_entity(BlockPosition { x: 0, y: 1, z: 0 }).unwrap();
+        match chest.nbt.get("Items") {
+            Some(NbtValue::List(items)) => match &items[0] {
+                NbtValue::Compound(item) => item.clone(),
+                _ => panic!("item is not a compound"),
+            },
+            _ => panic!("chest has no items"),
+        }
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut schematic = legacy_schematic();
+        let report = convert(&mut schematic, data_version::V1_21_9).unwrap();
+        assert_eq!(report.applied.len(), 5);
+        assert_eq!(schematic.metadata.mc_version, Some(data_version::V1_21_9));
+
+        assert_eq!(schematic.get_block(0, 0, 0).unwrap().name, "minecraft:short_grass");
+        let cauldron = schematic.get_block(1, 0, 0).unwrap();
+        assert_eq!(cauldron.name, "minecraft:water_cauldron");
+        assert_eq!(cauldron.get_property("level").map(|l| l.as_str()), Some("2"));
+        assert_eq!(schematic.get_block(2, 0, 0).unwrap().name, "minecraft:iron_chain");
+
+        let sign = schematic.get_block_entity(BlockPosition { x: 3, y: 0, z: 0 }).unwrap();
+        let Some(NbtValue::Compound(front)) = sign.nbt.get("front_text") else { panic!("no front_text") };
+        let Some(NbtValue::List(messages)) = front.get("messages") else { panic!("no messages") };
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0], NbtValue::String("{\"text\":\"Hello\"}".to_string()));
+        assert_eq!(front.get("color"), Some(&NbtValue::String("red".to_string())));
+        assert!(sign.nbt.get("Text1").is_none());
+
+        let item = chest_item(&schematic);
+        assert_eq!(item.get("count"), Some(&NbtValue::Int(12)));
+        assert_eq!(item.get("id"), Some(&NbtValue::String("minecraft:short_grass".to_string())));
+    }
+
+    #[test]
+    fn test_downgrade_round_trip() {
+        let mut schematic = legacy_schematic();
+        convert(&mut schematic, data_version::V1_21_9).unwrap();
+        let report = convert(&mut schematic, data_version::V1_16_5).unwrap();
+        assert_eq!(report.applied.len(), 5);
+
+        assert_eq!(schematic.get_block(0, 0, 0).unwrap().name, "minecraft:grass");
+        let cauldron = schematic.get_block(1, 0, 0).unwrap();
+        assert_eq!(cauldron.name, "minecraft:cauldron");
+        assert_eq!(cauldron.get_property("level").map(|l| l.as_str()), Some("2"));
+        assert_eq!(schematic.get_block(2, 0, 0).unwrap().name, "minecraft:chain");
+
+        let sign = schematic.get_block_entity(BlockPosition { x: 3, y: 0, z: 0 }).unwrap();
+        assert_eq!(sign.nbt.get("Text1"), Some(&NbtValue::String("{\"text\":\"Hello\"}".to_string())));
+        assert!(sign.nbt.get("front_text").is_none());
+        assert_eq!(chest_item(&schematic).get("Count"), Some(&NbtValue::Byte(12)));
+
+        schematic.metadata.mc_version = None;
+        assert!(convert(&mut schematic, data_version::V1_21).is_err());
+    }
+
+    #[test]
+    fn test_item_tag_components_round_trip() {
+        let mut display = NbtMap::new();
+        display.insert("Name".to_string(), NbtValue::String("{\"text\":\"Blade\"}".to_string()));
+        let mut sharpness = NbtMap::new();
+        sharpness.insert("id".to_string(), NbtValue::String("minecraft:sharpness".to_string()));
+        sharpness.insert("lvl".to_string(), NbtValue::Short(5));
+        let mut tag = NbtMap::new();
+        tag.insert("display".to_string(), NbtValue::Compound(display));
+        tag.insert("Damage".to_string(), NbtValue::Int(7));
+        tag.insert("Enchantments".to_string(), NbtValue::List(vec![NbtValue::Compound(sharpness)]));
+        tag.insert("Owner".to_string(), NbtValue::String("Steve".to_string()));
+        let mut item = NbtMap::new();
+        item.insert("id".to_string(), NbtValue::String("minecraft:diamond_sword".to_string()));
+        item.insert("tag".to_string(), NbtValue::Compound(tag));
+        let original = item.clone();
+
+        assert!(fix_item_components(&mut item, true));
+        assert!(item.get("tag").is_none());
+        let Some(NbtValue::Compound(components)) = item.get("components") else { panic!("no components") };
+        assert_eq!(components.get("minecraft:damage"), Some(&NbtValue::Int(7)));
+        assert_eq!(components.get("minecraft:custom_name"), Some(&NbtValue::String("{\"text\":\"Blade\"}".to_string())));
+        let Some(NbtValue::Compound(enchantments)) = components.get("minecraft:enchantments") else { panic!("no enchantments") };
+        let Some(NbtValue::Compound(levels)) = enchantments.get("levels") else { panic!("no levels") };
+        assert_eq!(levels.get("minecraft:sharpness"), Some(&NbtValue::Int(5)));
+        let Some(NbtValue::Compound(custom)) = components.get("minecraft:custom_data") else { panic!("no custom_data") };
+        assert_eq!(custom.get("Owner"), Some(&NbtValue::String("Steve".to_string())));
+
+        assert!(fix_item_components(&mut item, false));
+        assert!(item.get("components").is_none());
+        let (Some(NbtValue::Compound(restored)), Some(NbtValue::Compound(expected))) = (item.get("tag"), original.get("tag")) else { panic!("no tag") };
+        for key in ["display", "Damage", "Enchantments", "Owner"] {
+            assert_eq!(restored.get(key), expected.get(key), "{}", key);
+        }
+    }
+
+    fn modern_schematic() -> UniversalSchematic {
+        let block = |s: &str| UniversalSchematic::parse_block_string(s).unwrap().0;
+        let mut schematic = UniversalSchematic::new("New".to_string());
+        schematic.metadata.mc_version = Some(data_version::V1_21_9);
+        schematic.set_block(0, 0, 0, block("minecraft:waxed_copper_bulb[lit=true,powered=false]"));
+        schematic.set_block(1, 0, 0, block("minecraft:cherry_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]"));
+        schematic.set_block(2, 0, 0, block("minecraft:copper_chain[axis=y,waterlogged=false]"));
+        schematic.set_block(3, 0, 0, block("minecraft:decorated_pot[facing=north,cracked=false,waterlogged=false]"));
+        schematic.set_block_entity(BlockPosition { x: 3, y: 0, z: 0 }, BlockEntity::new("minecraft:decorated_pot".to_string(), (3, 0, 0)));
+        schematic.set_block(0, 1, 0, block("minecraft:pale_oak_planks"));
+        schematic.set_block(1, 1, 0, block("minecraft:crafter[crafting=false,orientation=north_up,triggered=false]"));
+        schematic.set_block(2, 1, 0, block("minecraft:stone"));
+        schematic
+    }
+
+    #[test]
+    fn test_downgrade_with_substitutions() {
+        let block = |s: &str| UniversalSchematic::parse_block_string(s).unwrap().0;
+        let mut schematic = modern_schematic();
+        schematic.set_block(0, 2, 0, block("minecraft:creaking_heart[axis=y,creaking_heart_state=uprooted,natural=false]"));
+        schematic.set_block_entity(BlockPosition { x: 0, y: 2, z: 0 }, BlockEntity::new("minecraft:creaking_heart".to_string(), (0, 2, 0)));
+        schematic.set_block(1, 2, 0, block("minecraft:cherry_hanging_sign[attached=false,rotation=4,waterlogged=false]"));
+        schematic.set_block(2, 2, 0, block("minecraft:spruce_wall_hanging_sign[facing=east,waterlogged=false]"));
+        schematic.set_block(3, 2, 0, block("minecraft:bamboo_stairs[facing=west,half=top,shape=straight,waterlogged=false]"));
+        schematic.set_block(4, 2, 0, block("minecraft:bamboo_mosaic_slab[type=top,waterlogged=false]"));
+        schematic.set_block(5, 2, 0, block("minecraft:bamboo_sapling"));
+        let report = downgrade(&mut schematic, data_version::V1_19_4, &SubstitutionRules::vanilla()).unwrap();
+        assert_eq!(report.substitutions.len(), 11);
+        assert_eq!(report.substitutions[0], Substitution {
+            position: (0, 0, 0),
+            from: "minecraft:waxed_copper_bulb[lit=true,powered=false]".to_string(),
+            to: "minecraft:redstone_lamp".to_string(),
+        });
+        assert_eq!(schematic.metadata.mc_version, Some(data_version::V1_19_4));
+
+        assert_eq!(schematic.get_block(0, 0, 0).unwrap().name, "minecraft:redstone_lamp");
+        let stairs = schematic.get_block(1, 0, 0).unwrap();
+        assert_eq!(stairs.name, "minecraft:oak_stairs");
+        assert_eq!(stairs.get_property("facing").map(|f| f.as_str()), Some("east"));
+        // Substituted to iron_chain, then renamed back by the chain fix
+        let chain = schematic.get_block(2, 0, 0).unwrap();
+        assert_eq!(chain.name, "minecraft:chain");
+        assert_eq!(chain.get_property("axis").map(|a| a.as_str()), Some("y"));
+        assert_eq!(report.fixes.applied, vec!["chain renamed to iron_chain".to_string()]);
+
+        let pot = schematic.get_block(3, 0, 0).unwrap();
+        assert_eq!((pot.name.as_str(), pot.properties.len()), ("minecraft:flower_pot", 0));
+        assert!(schematic.get_block_entity(BlockPosition { x: 3, y: 0, z: 0 }).is_none());
+        assert_eq!(schematic.get_block(0, 1, 0).unwrap().name, "minecraft:dark_oak_planks");
+        assert_eq!(schematic.get_block(1, 1, 0).unwrap().to_string(), "minecraft:dropper[facing=down]");
+        assert_eq!(schematic.get_block(2, 1, 0).unwrap().name, "minecraft:stone");
+
+        assert_eq!(schematic.get_block(0, 2, 0).unwrap().to_string(), "minecraft:dark_oak_log[axis=y]");
+        assert!(schematic.get_block_entity(BlockPosition { x: 0, y: 2, z: 0 }).is_none());
+        assert_eq!(schematic.get_block(1, 2, 0).unwrap().to_string(), "minecraft:oak_sign");
+        assert_eq!(schematic.get_block(2, 2, 0).unwrap().to_string(), "minecraft:spruce_wall_sign");
+        let stairs = schematic.get_block(3, 2, 0).unwrap();
+        assert_eq!((stairs.name.as_str(), stairs.get_property("half").map(|h| h.as_str())), ("minecraft:oak_stairs", Some("top")));
+        assert_eq!(schematic.get_block(4, 2, 0).unwrap().name, "minecraft:oak_slab");
+        assert_eq!(schematic.get_block(5, 2, 0).unwrap().name, "minecraft:bamboo_sapling");
+    }
+
+    #[test]
+    fn test_custom_rules_and_summary() {
+        let rules = SubstitutionRules::from_json(
+            r#"[{"pattern": "crafter", "added_in": 3953, "replacement": "dispenser[facing=up]"},
+                {"pattern": "*_bulb", "added_in": 3953, "replacement": "glowstone", "keep_properties": false}]"#,
+        ).unwrap();
+        let rules = SubstitutionRules::vanilla().with_overrides(rules.rules);
+        assert!(SubstitutionRules::from_json("{}").is_err());
+
+        // 1.20.4 already has cherry wood
+        let mut schematic = modern_schematic();
+        schematic.set_block(2, 2, 0, BlockState::new("minecraft:copper_bulb".to_string()));
+        let report = downgrade(&mut schematic, data_version::V1_20_4, &rules).unwrap();
+        assert_eq!(schematic.get_block(1, 0, 0).unwrap().name, "minecraft:cherry_stairs");
+        assert_eq!(schematic.get_block(0, 0, 0).unwrap().name, "minecraft:glowstone");
+        assert_eq!(schematic.get_block(1, 1, 0).unwrap().to_string(), "minecraft:dispenser[facing=up]");
+        assert_eq!(report.summary()[0], ("minecraft:copper_bulb".to_string(), "minecraft:glowstone".to_string(), 1));
+        assert_eq!(report.substitutions.len(), 5);
+
+        let crafter_only = SubstitutionRules::from_json(r#"[{"pattern": "crafter", "added_in": 3953, "replacement": "dropper"}]"#).unwrap();
+        let mut schematic = modern_schematic();
+        let report = downgrade(&mut schematic, data_version::V1_20_4, &crafter_only).unwrap();
+        assert_eq!(report.substitutions.len(), 1);
+        assert!(report.unhandled.contains(&"minecraft:waxed_copper_bulb".to_string()));
+        assert!(!report.unhandled.contains(&"minecraft:crafter".to_string()));
+
+        let mut unversioned = modern_schematic();
+        unversioned.metadata.mc_version = None;
+        assert!(downgrade(&mut unversioned, data_version::V1_20, &rules).is_err());
+        assert_eq!(unversioned.get_block(0, 0, 0).unwrap().name, "minecraft:waxed_copper_bulb");
+    }
+
+    #[test]
+    fn test_every_vanilla_rule_applies() {
+        let rules = SubstitutionRules::vanilla();
+        let oldest = data_version::V1_19_4;
+        for (index, rule) in rules.rules.iter().enumerate() {
+            // A name the pattern matches, with the wildcard filled in
+            let name = match (rule.pattern.strip_prefix('*'), rule.pattern.strip_suffix('*')) {
+                (Some(suffix), _) if suffix.starts_with('_') => format!("oak{}", suffix),
+                (Some(suffix), _) => format!("waxed_{}", suffix),
+                (_, Some(prefix)) => format!("{}stairs", prefix),
+                _ => rule.pattern.clone(),
+            };
+            let block = BlockState::new(format!("minecraft:{}", name));
+            let first = rules.rules.iter().position(|other| other.wildcard(&name).is_some());
+            assert_eq!(first, Some(index), "{} is shadowed by an earlier rule", rule.pattern);
+
+            assert!(rules.substitute(&block, rule.added_in).is_none(), "{} applies at its own version", rule.pattern);
+            let (replaced, _) = rules.substitute(&block, rule.added_in - 1)
+                .unwrap_or_else(|| panic!("{} has an invalid replacement", rule.pattern));
+            assert!(!rules.is_newer(&replaced, rule.added_in - 1), "{} is replaced by too new {}", name, replaced);
+            let (replaced, _) = rules.substitute(&block, oldest).unwrap();
+            assert!(!rules.is_newer(&replaced, oldest), "{} is replaced by too new {}", name, replaced);
+        }
+
+        let potted = BlockState::new("minecraft:potted_open_eyeblossom".to_string());
+        assert_eq!(rules.substitute(&potted, data_version::V1_21).unwrap().0.name, "minecraft:potted_poppy");
+    }
+}
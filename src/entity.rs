@@ -40,7 +40,7 @@ impl Entity {
         self
     }
 
-    fn nbt_tag_to_value(tag: &NbtTag) -> NbtValue {
+    pub(crate) fn nbt_tag_to_value(tag: &NbtTag) -> NbtValue {
         match tag {
             NbtTag::String(s) => NbtValue::String(s.clone()),
             NbtTag::Int(i) => NbtValue::Int(*i),
@@ -68,7 +68,14 @@ impl Entity {
         }
     }
 
-    fn value_to_nbt_tag(value: &NbtValue) -> NbtTag {
+    /// Entries ordered by key, so writers emit the same bytes for the same data.
+    pub(crate) fn sorted_entries(map: &HashMap<String, NbtValue>) -> Vec<(&String, &NbtValue)> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    pub(crate) fn value_to_nbt_tag(value: &NbtValue) -> NbtTag {
         match value {
             NbtValue::String(s) => NbtTag::String(s.clone()),
             NbtValue::Int(i) => NbtTag::Int(*i),
@@ -89,7 +96,7 @@ impl Entity {
             }
             NbtValue::Compound(map) => {
                 let mut compound = NbtCompound::new();
-                for (key, value) in map {
+                for (key, value) in Self::sorted_entries(map) {
                     compound.insert(key, Self::value_to_nbt_tag(value));
                 }
                 NbtTag::Compound(compound)
@@ -119,7 +126,7 @@ impl Entity {
         // Convert HashMap<String, NbtValue> to NbtCompound
         if !self.nbt.is_empty() {
             let mut nbt_compound = NbtCompound::new();
-            for (key, value) in &self.nbt {
+            for (key, value) in Self::sorted_entries(&self.nbt) {
                 nbt_compound.insert(key, Self::value_to_nbt_tag(value));
             }
             compound.insert("NBT", NbtTag::Compound(nbt_compound));
@@ -7,7 +7,7 @@ use std::ptr;
 use crate::{
     UniversalSchematic,
     BlockState,
-    formats::{litematic, schematic},
+    formats::{litematic, schematic, native, peek, limits::LoadLimits},
     print_utils::{format_schematic, format_json_schematic},
     block_position::BlockPosition,
     bounding_box::BoundingBox,
@@ -246,17 +246,38 @@ pub extern "C" fn schematic_from_data(
     data: *const c_uchar,
     data_len: usize,
 ) -> c_int {
+    load_data(schematic, data, data_len, &LoadLimits::default())
+}
+
+/// Like `schematic_from_data`, but caps sizes, palettes, entities and decompressed bytes as
+/// `LoadLimits::untrusted()` does. Use it for uploaded files.
+/// Returns 0 on success, negative on error.
+#[no_mangle]
+pub extern "C" fn schematic_from_data_untrusted(
+    schematic: *mut SchematicWrapper,
+    data: *const c_uchar,
+    data_len: usize,
+) -> c_int {
+    load_data(schematic, data, data_len, &LoadLimits::untrusted())
+}
+
+fn load_data(schematic: *mut SchematicWrapper, data: *const c_uchar, data_len: usize, limits: &LoadLimits) -> c_int {
     if schematic.is_null() || data.is_null() { return -1; }
     let data_slice = unsafe { std::slice::from_raw_parts(data, data_len) };
     let s = unsafe { &mut *(*schematic).0 };
 
-    if litematic::is_litematic(data_slice) {
-        match litematic::from_litematic(data_slice) {
+    if native::is_native(data_slice) {
+        match native::from_native_with_limits(data_slice, limits) {
+            Ok(res) => { *s = res; 0 }
+            Err(_) => -2,
+        }
+    } else if litematic::is_litematic_with_limits(data_slice, limits) {
+        match litematic::from_litematic_with_limits(data_slice, limits) {
             Ok(res) => { *s = res; 0 }
             Err(_) => -2,
         }
-    } else if schematic::is_schematic(data_slice) {
-        match schematic::from_schematic(data_slice) {
+    } else if schematic::is_schematic_with_limits(data_slice, limits) {
+        match schematic::from_schematic_with_limits(data_slice, limits) {
             Ok(res) => { *s = res; 0 }
             Err(_) => -2,
         }
@@ -265,6 +286,19 @@ pub extern "C" fn schematic_from_data(
     }
 }
 
+/// Reads the format, version, metadata and dimensions of a litematic or `.schem` without
+/// decoding its blocks, as a JSON object. Returns null if the data isn't recognised.
+/// The returned C string must be freed with `free_string`.
+#[no_mangle]
+pub extern "C" fn schematic_peek_info(data: *const c_uchar, data_len: usize) -> *mut c_char {
+    if data.is_null() { return ptr::null_mut(); }
+    let data_slice = unsafe { std::slice::from_raw_parts(data, data_len) };
+    match peek::peek_info(data_slice).ok().and_then(|info| serde_json::to_string(&info).ok()) {
+        Some(json) => CString::new(json).unwrap().into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
 /// Populates a schematic from Litematic data.
 /// Returns 0 on success, negative on error.
 #[no_mangle]
@@ -809,4 +843,18 @@ pub extern "C" fn debug_json_schematic(schematic: *const SchematicWrapper) -> *m
                              s.regions.len());
     let info = format!("{}\n{}", debug_info, format_json_schematic(s));
     CString::new(info).unwrap().into_raw()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::gzip_bomb;
+
+    #[test]
+    fn test_untrusted_load_rejects_gzip_bomb() {
+        let bomb = gzip_bomb(LoadLimits::untrusted().max_decompressed_bytes + 1024 * 1024);
+        let schematic = schematic_new();
+        assert!(schematic_from_data_untrusted(schematic, bomb.as_ptr(), bomb.len()) < 0);
+        schematic_free(schematic);
+    }
+}
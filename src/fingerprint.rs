@@ -0,0 +1,336 @@
+//! Content hashes and similarity scores, for spotting duplicate and near-duplicate uploads.
+//!
+//! Both look only at what's built: the non-air blocks and, for the hash, block entity data.
+//! Names, timestamps, how the blocks are split into regions and where they sit in the world
+//! make no difference. With rotation invariance a build also matches its copies turned by a
+//! multiple of 90° around the vertical axis.
+
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use crate::block_colors::is_air;
+use crate::redstone::{Direction, Pos};
+use crate::{BlockState, UniversalSchematic};
+
+#[derive(Debug, Clone)]
+pub struct FingerprintOptions {
+    /// Give the same hash to copies rotated around the Y axis.
+    pub rotation_invariant: bool,
+    /// Include block entity NBT, e.g. chest contents and sign text.
+    pub include_block_entities: bool,
+}
+
+impl Default for FingerprintOptions {
+    fn default() -> Self {
+        FingerprintOptions { rotation_invariant: false, include_block_entities: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Similarity {
+    /// Weighted Jaccard index of the block counts, 0 to 1.
+    pub palette: f64,
+    /// Share of blocks that match in position and state with the builds shifted to overlap as
+    /// much as they can, 0 to 1.
+    pub voxels: f64,
+    /// Mean of the two.
+    pub score: f64,
+}
+
+fn rotate_pos((x, y, z): Pos, turns: u8) -> Pos {
+    (0..turns).fold((x, y, z), |(x, y, z), _| (-z, y, x))
+}
+
+fn rotate_direction(name: &str, turns: u8) -> Option<&'static str> {
+    let direction = Direction::from_name(name).filter(|d| Direction::HORIZONTAL.contains(d))?;
+    Some((0..turns).fold(direction, |d, _| d.rotate_clockwise()).name())
+}
+
+/// Rail shapes such as `north_east` or `ascending_west`.
+fn rotate_shape(shape: &str, turns: u8) -> Option<String> {
+    let (prefix, rest) = match shape.strip_prefix("ascending_") {
+        Some(rest) => ("ascending_", rest),
+        None => ("", shape),
+    };
+    let mut parts = rest.split('_').map(|part| rotate_direction(part, turns)).collect::<Option<Vec<_>>>()?;
+    // Minecraft's names: north_south, east_west, and corners with north or south first
+    parts.sort_by_key(|part| ["north", "south", "east", "west"].iter().position(|d| d == part));
+    Some(format!("{}{}", prefix, parts.join("_")))
+}
+
+/// The block turned clockwise (seen from above) by `turns` quarter turns.
+fn rotate_block(block: &BlockState, turns: u8) -> BlockState {
+    if turns == 0 {
+        return block.clone();
+    }
+    let mut rotated = BlockState::new(block.name.clone());
+    for (key, value) in &block.properties {
+        let rotated_value = match key.as_str() {
+            "facing" => rotate_direction(value, turns).map(String::from),
+            "axis" if turns % 2 == 1 => match value.as_str() {
+                "x" => Some("z".to_string()),
+                "z" => Some("x".to_string()),
+                _ => None,
+            },
+            "rotation" => value.parse::<u8>().ok().map(|r| ((r + 4 * turns) % 16).to_string()),
+            "shape" => rotate_shape(value, turns),
+            _ => None,
+        };
+        // Fences, walls, panes and wire name their connections by side
+        let rotated_key = rotate_direction(key, turns).map(String::from).unwrap_or_else(|| key.clone());
+        rotated.set_property(rotated_key, rotated_value.unwrap_or_else(|| value.clone()));
+    }
+    rotated
+}
+
+/// Non-air blocks as `(position, state)` sorted by position and shifted so their minimum
+/// corner is at 0,0,0, along with the shift.
+fn canonical_blocks(schematic: &UniversalSchematic, turns: u8) -> (Vec<(Pos, String)>, Pos) {
+    let mut blocks: Vec<(Pos, String)> = schematic.iter_blocks()
+        .filter(|(_, block)| !is_air(&block.name))
+        .map(|(pos, block)| (rotate_pos((pos.x, pos.y, pos.z), turns), rotate_block(block, turns).to_string()))
+        .collect();
+    let min = blocks.iter().fold((i32::MAX, i32::MAX, i32::MAX), |min, (pos, _)| {
+        (min.0.min(pos.0), min.1.min(pos.1), min.2.min(pos.2))
+    });
+    for (pos, _) in blocks.iter_mut() {
+        *pos = (pos.0 - min.0, pos.1 - min.1, pos.2 - min.2);
+    }
+    blocks.sort_by_key(|(pos, _)| *pos);
+    (blocks, min)
+}
+
+/// 64-bit FNV-1a, chosen over `DefaultHasher` because its output never changes between
+/// Rust releases, so stored hashes stay comparable.
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn hash_for_turns(schematic: &UniversalSchematic, turns: u8, options: &FingerprintOptions) -> u64 {
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    let (blocks, min) = canonical_blocks(schematic, turns);
+    for ((x, y, z), block) in &blocks {
+        hasher.write(format!("{},{},{}={}\n", x, y, z, block).as_bytes());
+    }
+    if options.include_block_entities && !blocks.is_empty() {
+        // In the same frame as the blocks
+        let mut block_entities: Vec<(Pos, String)> = schematic.regions.values()
+            .flat_map(|region| region.block_entities.values())
+            .map(|block_entity| {
+                let mut nbt = block_entity.nbt.clone();
+                for key in ["x", "y", "z", "id", "Id", "Pos"] {
+                    nbt.remove(key);
+                }
+                let (x, y, z) = rotate_pos(block_entity.position, turns);
+                ((x - min.0, y - min.1, z - min.2), format!("{}{}", block_entity.id, nbt.to_snbt()))
+            })
+            .collect();
+        block_entities.sort();
+        for ((x, y, z), data) in &block_entities {
+            hasher.write(format!("be {},{},{}={}\n", x, y, z, data).as_bytes());
+        }
+    }
+    hasher.0
+}
+
+/// A hex hash of the schematic's content. Equal builds get equal hashes wherever they are
+/// placed and however their regions are laid out.
+pub fn content_hash(schematic: &UniversalSchematic, options: &FingerprintOptions) -> String {
+    let turns: &[u8] = if options.rotation_invariant { &[0, 1, 2, 3] } else { &[0] };
+    let hash = turns.iter().map(|&t| hash_for_turns(schematic, t, options)).min().unwrap_or(0);
+    format!("{:016x}", hash)
+}
+
+fn histogram(blocks: &[(Pos, String)]) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for (_, block) in blocks {
+        *counts.entry(block.as_str()).or_insert(0) += 1;
+    }
+    counts
+}
+
+fn palette_similarity(a: &[(Pos, String)], b: &[(Pos, String)]) -> f64 {
+    let (a, b) = (histogram(a), histogram(b));
+    let keys: HashSet<&str> = a.keys().chain(b.keys()).copied().collect();
+    let (mut shared, mut total) = (0, 0);
+    for key in keys {
+        let (x, y) = (a.get(key).copied().unwrap_or(0), b.get(key).copied().unwrap_or(0));
+        shared += x.min(y);
+        total += x.max(y);
+    }
+    if total == 0 { 1.0 } else { shared as f64 / total as f64 }
+}
+
+/// Block pairs that may vote on an alignment, so large builds stay cheap to compare.
+const MAX_ALIGNMENT_PAIRS: usize = 4096;
+/// Offsets with the most votes that are then checked block by block.
+const ALIGNMENT_CANDIDATES: usize = 16;
+
+fn positions_by_state(blocks: &[(Pos, String)]) -> HashMap<&str, Vec<Pos>> {
+    let mut positions: HashMap<&str, Vec<Pos>> = HashMap::new();
+    for (pos, block) in blocks {
+        positions.entry(block.as_str()).or_default().push(*pos);
+    }
+    positions
+}
+
+/// Shifts of `b` that might line it up with `a`: none at all (both minimum corners at the
+/// origin), and the shifts implied most often by pairing up blocks of the rarest states the
+/// builds share. A stray block moves the minimum corner, but not those pairs.
+fn candidate_offsets(a: &[(Pos, String)], b: &[(Pos, String)]) -> Vec<Pos> {
+    let (in_a, in_b) = (positions_by_state(a), positions_by_state(b));
+    let mut shared: Vec<(&str, &Vec<Pos>, &Vec<Pos>)> = in_a.iter()
+        .filter_map(|(state, from)| Some((*state, from, in_b.get(state)?)))
+        .collect();
+    shared.sort_by_key(|(state, from, to)| (from.len() * to.len(), *state));
+
+    let mut votes: HashMap<Pos, usize> = HashMap::new();
+    let mut budget = MAX_ALIGNMENT_PAIRS;
+    for (_, from, to) in shared {
+        if budget == 0 {
+            break;
+        }
+        // A spread-out sample of `a`'s blocks when pairing all of them would cost too much
+        let samples = (budget / to.len()).clamp(1, from.len());
+        for &(ax, ay, az) in from.iter().step_by(from.len() / samples) {
+            for &(bx, by, bz) in to {
+                *votes.entry((ax - bx, ay - by, az - bz)).or_insert(0) += 1;
+            }
+        }
+        budget = budget.saturating_sub(samples * to.len());
+    }
+    let mut ranked: Vec<(Pos, usize)> = votes.into_iter().collect();
+    ranked.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+    let mut offsets = vec![(0, 0, 0)];
+    offsets.extend(ranked.into_iter().map(|(offset, _)| offset).filter(|&offset| offset != (0, 0, 0)).take(ALIGNMENT_CANDIDATES));
+    offsets
+}
+
+/// The voxel score with `b` moved by `offset`.
+fn voxel_similarity(a: &[(Pos, String)], b: &HashMap<Pos, &str>, offset: Pos) -> f64 {
+    let shared = a.iter()
+        .filter(|((x, y, z), block)| b.get(&(x - offset.0, y - offset.1, z - offset.2)) == Some(&block.as_str()))
+        .count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 { 1.0 } else { shared as f64 / total as f64 }
+}
+
+fn best_voxel_similarity(a: &[(Pos, String)], b: &[(Pos, String)]) -> f64 {
+    let lookup: HashMap<Pos, &str> = b.iter().map(|(pos, block)| (*pos, block.as_str())).collect();
+    candidate_offsets(a, b).into_iter()
+        .map(|offset| voxel_similarity(a, &lookup, offset))
+        .fold(0.0, f64::max)
+}
+
+/// How alike two schematics are. `b` is shifted to best match `a`, and with
+/// `rotation_invariant` the best of its four rotations is used.
+pub fn similarity(a: &UniversalSchematic, b: &UniversalSchematic, rotation_invariant: bool) -> Similarity {
+    let (first, _) = canonical_blocks(a, 0);
+    let turns: &[u8] = if rotation_invariant { &[0, 1, 2, 3] } else { &[0] };
+    turns.iter()
+        .map(|&t| {
+            let (second, _) = canonical_blocks(b, t);
+            let palette = palette_similarity(&first, &second);
+            let voxels = best_voxel_similarity(&first, &second);
+            Similarity { palette, voxels, score: (palette + voxels) / 2.0 }
+        })
+        .max_by(|x, y| x.score.total_cmp(&y.score))
+        .expect("at least one rotation")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_entity::BlockEntity;
+    use crate::region::Region;
+    use crate::test_fixtures::block;
+    use crate::utils::NbtValue;
+
+    /// An L of stone with a chest and a rail corner, placed at `offset`.
+    fn build(offset: Pos, turns: u8) -> UniversalSchematic {
+        let mut schematic = UniversalSchematic::new(format!("Copy {:?}", offset));
+        let place = |schematic: &mut UniversalSchematic, pos: Pos, block: BlockState| {
+            let (x, y, z) = rotate_pos(pos, turns);
+            schematic.set_block(x + offset.0, y + offset.1, z + offset.2, rotate_block(&block, turns));
+        };
+        for x in 0..3 {
+            place(&mut schematic, (x, 0, 0), block("stone", &[]));
+        }
+        place(&mut schematic, (0, 0, 1), block("stone", &[]));
+        place(&mut schematic, (0, 1, 0), block("chest", &[("facing", "north")]));
+        place(&mut schematic, (1, 1, 0), block("rail", &[("shape", "south_east")]));
+        place(&mut schematic, (2, 1, 0), block("oak_fence", &[("north", "true"), ("east", "false"), ("south", "false"), ("west", "false")]));
+        let (x, y, z) = rotate_pos((0, 1, 0), turns);
+        let mut chest = BlockEntity::new("minecraft:chest".to_string(), (x + offset.0, y + offset.1, z + offset.2));
+        chest.nbt.insert("Lock".to_string(), NbtValue::String("key".to_string()));
+        schematic.get_default_region_mut().add_block_entity(chest);
+        schematic
+    }
+
+    #[test]
+    fn test_hash_ignores_placement_and_layout() {
+        let options = FingerprintOptions::default();
+        let original = build((0, 0, 0), 0);
+        let moved = build((100, -20, 7), 0);
+        assert_eq!(content_hash(&original, &options), content_hash(&moved, &options));
+
+        // Same blocks split over two regions
+        let mut split = UniversalSchematic::new("Split".to_string());
+        let mut region = Region::new("Other".to_string(), (0, 0, 1), (1, 1, 1));
+        region.set_block(0, 0, 1, block("stone", &[]));
+        split.add_region(region);
+        for (pos, state) in original.iter_blocks().filter(|(pos, _)| (pos.x, pos.y, pos.z) != (0, 0, 1)) {
+            split.set_block(pos.x, pos.y, pos.z, state.clone());
+        }
+        split.get_default_region_mut().add_block_entity(original.get_block_entity(crate::block_position::BlockPosition { x: 0, y: 1, z: 0 }).unwrap().clone());
+        assert_eq!(content_hash(&original, &options), content_hash(&split, &options));
+
+        let mut changed = build((0, 0, 0), 0);
+        changed.set_block(2, 0, 0, block("dirt", &[]));
+        assert_ne!(content_hash(&original, &options), content_hash(&changed, &options));
+    }
+
+    #[test]
+    fn test_rotation_invariance_and_similarity() {
+        let original = build((0, 0, 0), 0);
+        let rotated = build((5, 0, 5), 1);
+        assert_eq!(rotate_block(&block("rail", &[("shape", "south_east")]), 1).get_property("shape"), Some(&"south_west".to_string()));
+
+        let plain = FingerprintOptions::default();
+        let rotating = FingerprintOptions { rotation_invariant: true, ..FingerprintOptions::default() };
+        assert_ne!(content_hash(&original, &plain), content_hash(&rotated, &plain));
+        assert_eq!(content_hash(&original, &rotating), content_hash(&rotated, &rotating));
+
+        assert_eq!(similarity(&original, &original, false).score, 1.0);
+        assert_eq!(similarity(&original, &rotated, true).voxels, 1.0);
+        assert!(similarity(&original, &rotated, false).voxels < 1.0);
+
+        let mut changed = build((0, 0, 0), 0);
+        changed.set_block(2, 0, 0, block("dirt", &[]));
+        let score = similarity(&original, &changed, false);
+        assert!(score.score > 0.7 && score.score < 1.0, "{:?}", score);
+    }
+
+    #[test]
+    fn test_similarity_survives_a_stray_block() {
+        let mut slab = UniversalSchematic::new("Slab".to_string());
+        for x in 0..10 {
+            for z in 0..10 {
+                slab.set_block(x, 1, z, block("stone", &[]));
+            }
+        }
+        let mut stray = slab.clone();
+        stray.set_block(-3, 0, -2, block("dirt", &[]));
+
+        let score = similarity(&slab, &stray, false);
+        assert_eq!(score.voxels, 100.0 / 101.0);
+        assert!(score.score > 0.98, "{:?}", score);
+        assert_eq!(similarity(&stray, &slab, true).voxels, 100.0 / 101.0);
+    }
+}
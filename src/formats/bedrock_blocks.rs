@@ -0,0 +1,794 @@
+//! Java ↔ Bedrock block state translation.
+//!
+//! Bedrock names most blocks the same way Java does these days, so the table only lists the
+//! blocks that differ. Properties are translated per block family (stairs, doors, slabs, ...);
+//! anything not covered is passed through with its value typed as Bedrock expects.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use quartz_nbt::{NbtCompound, NbtTag};
+use crate::BlockState;
+
+/// Block state version written into `.mcstructure` palettes (1.21.0).
+pub const BEDROCK_BLOCK_VERSION: i32 = 18_153_472;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BedrockStateValue {
+    Bool(bool),
+    Int(i32),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BedrockBlockState {
+    pub name: String,
+    pub states: BTreeMap<String, BedrockStateValue>,
+}
+
+impl BedrockBlockState {
+    pub fn new(name: &str) -> Self {
+        BedrockBlockState {
+            name: name.to_string(),
+            states: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_state(mut self, key: &str, value: BedrockStateValue) -> Self {
+        self.states.insert(key.to_string(), value);
+        self
+    }
+
+    /// The `states` compound as stored in a Bedrock palette.
+    pub fn states_to_nbt(&self) -> NbtCompound {
+        let mut states = NbtCompound::new();
+        for (key, value) in &self.states {
+            let tag = match value {
+                BedrockStateValue::Bool(b) => NbtTag::Byte(*b as i8),
+                BedrockStateValue::Int(i) => NbtTag::Int(*i),
+                BedrockStateValue::String(s) => NbtTag::String(s.clone()),
+            };
+            states.insert(key, tag);
+        }
+        states
+    }
+
+    pub fn from_nbt(name: &str, states: Option<&NbtCompound>) -> Self {
+        let mut block = BedrockBlockState::new(name);
+        if let Some(states) = states {
+            for (key, tag) in states.inner() {
+                let value = match tag {
+                    NbtTag::Byte(b) => BedrockStateValue::Bool(*b != 0),
+                    NbtTag::Short(s) => BedrockStateValue::Int(*s as i32),
+                    NbtTag::Int(i) => BedrockStateValue::Int(*i),
+                    NbtTag::String(s) => BedrockStateValue::String(s.clone()),
+                    _ => continue,
+                };
+                block.states.insert(key.clone(), value);
+            }
+        }
+        block
+    }
+
+    fn take_bool(&mut self, key: &str) -> Option<bool> {
+        match self.states.remove(key)? {
+            BedrockStateValue::Bool(b) => Some(b),
+            BedrockStateValue::Int(i) => Some(i != 0),
+            BedrockStateValue::String(s) => Some(s == "true"),
+        }
+    }
+
+    fn take_int(&mut self, key: &str) -> Option<i32> {
+        match self.states.remove(key)? {
+            BedrockStateValue::Bool(b) => Some(b as i32),
+            BedrockStateValue::Int(i) => Some(i),
+            BedrockStateValue::String(s) => s.parse().ok(),
+        }
+    }
+
+    fn take_string(&mut self, key: &str) -> Option<String> {
+        match self.states.remove(key)? {
+            BedrockStateValue::Bool(b) => Some(b.to_string()),
+            BedrockStateValue::Int(i) => Some(i.to_string()),
+            BedrockStateValue::String(s) => Some(s),
+        }
+    }
+}
+
+/// Blocks whose names differ between editions, as (Java, Bedrock) without the namespace.
+/// Where several Java names map to one Bedrock name the first entry wins on the way back.
+const RENAMES: &[(&str, &str)] = &[
+    ("note_block", "noteblock"),
+    ("cobweb", "web"),
+    ("powered_rail", "golden_rail"),
+    ("dead_bush", "deadbush"),
+    ("snow", "snow_layer"),
+    ("snow_block", "snow"),
+    ("lily_pad", "waterlily"),
+    ("sugar_cane", "reeds"),
+    ("magma_block", "magma"),
+    ("nether_bricks", "nether_brick"),
+    ("red_nether_bricks", "red_nether_brick"),
+    ("melon", "melon_block"),
+    ("terracotta", "hardened_clay"),
+    ("slime_block", "slime"),
+    ("spawner", "mob_spawner"),
+    ("jack_o_lantern", "lit_pumpkin"),
+    ("end_stone_bricks", "end_bricks"),
+    ("nether_quartz_ore", "quartz_ore"),
+    ("dirt_path", "grass_path"),
+    ("bricks", "brick_block"),
+    ("tripwire", "trip_wire"),
+    ("oak_sign", "standing_sign"),
+    ("oak_wall_sign", "wall_sign"),
+    ("oak_door", "wooden_door"),
+    ("oak_trapdoor", "trapdoor"),
+    ("oak_button", "wooden_button"),
+    ("oak_pressure_plate", "wooden_pressure_plate"),
+    ("oak_fence_gate", "fence_gate"),
+    ("moving_piston", "moving_block"),
+    ("piston_head", "piston_arm_collision"),
+    ("nether_portal", "portal"),
+    ("beetroots", "beetroot"),
+    ("shulker_box", "undyed_shulker_box"),
+    ("light", "light_block"),
+];
+
+/// Blocks with a separate `lit_` block on Bedrock instead of a `lit` property.
+const LIT_BLOCKS: &[&str] = &[
+    "redstone_lamp", "furnace", "blast_furnace", "smoker", "redstone_ore", "deepslate_redstone_ore",
+];
+
+/// Blocks using the six-way `facing_direction`; other horizontal blocks use `minecraft:cardinal_direction`.
+const FACING_DIRECTION_BLOCKS: &[&str] = &[
+    "observer", "piston", "sticky_piston", "dispenser", "dropper", "barrel", "end_rod", "hopper",
+    "ladder", "lightning_rod", "command_block", "chain_command_block", "repeating_command_block",
+    "amethyst_cluster", "wall_sign",
+];
+
+const CROPS: &[&str] = &["wheat", "carrots", "potatoes", "beetroot", "melon_stem", "pumpkin_stem"];
+
+/// Blocks that are always waterlogged in Java without saying so.
+const IMPLICITLY_WATERLOGGED: &[&str] = &["seagrass", "tall_seagrass", "kelp", "kelp_plant", "bubble_column"];
+
+const CONNECTION_KEYS: &[&str] = &["north", "east", "south", "west", "up", "down"];
+
+/// Block entity ids, as (Java, Bedrock).
+const BLOCK_ENTITY_IDS: &[(&str, &str)] = &[
+    ("chest", "Chest"),
+    ("trapped_chest", "Chest"),
+    ("ender_chest", "EnderChest"),
+    ("barrel", "Barrel"),
+    ("furnace", "Furnace"),
+    ("blast_furnace", "BlastFurnace"),
+    ("smoker", "Smoker"),
+    ("hopper", "Hopper"),
+    ("dispenser", "Dispenser"),
+    ("dropper", "Dropper"),
+    ("shulker_box", "ShulkerBox"),
+    ("sign", "Sign"),
+    ("hanging_sign", "HangingSign"),
+    ("comparator", "Comparator"),
+    ("note_block", "Music"),
+    ("jukebox", "Jukebox"),
+    ("beacon", "Beacon"),
+    ("mob_spawner", "MobSpawner"),
+    ("command_block", "CommandBlock"),
+    ("banner", "Banner"),
+    ("skull", "Skull"),
+    ("bed", "Bed"),
+    ("brewing_stand", "BrewingStand"),
+    ("enchanting_table", "EnchantTable"),
+    ("lectern", "Lectern"),
+    ("bell", "Bell"),
+    ("campfire", "Campfire"),
+    ("daylight_detector", "DaylightDetector"),
+    ("piston", "PistonArm"),
+    ("flower_pot", "FlowerPot"),
+    ("end_gateway", "EndGateway"),
+    ("structure_block", "StructureBlock"),
+    ("beehive", "Beehive"),
+    ("conduit", "Conduit"),
+    ("chiseled_bookshelf", "ChiseledBookshelf"),
+    ("decorated_pot", "DecoratedPot"),
+];
+
+const RAIL_SHAPES: &[&str] = &[
+    "north_south", "east_west", "ascending_east", "ascending_west", "ascending_north",
+    "ascending_south", "south_east", "south_west", "north_west", "north_east",
+];
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn namespaced(name: &str) -> String {
+    format!("minecraft:{}", name)
+}
+
+// Bedrock packs horizontal directions into small ints, in a different order for each family
+const STAIRS_DIRECTIONS: [&str; 4] = ["east", "west", "south", "north"];
+const DOOR_DIRECTIONS: [&str; 4] = ["east", "south", "west", "north"];
+const LEGACY_DIRECTIONS: [&str; 4] = ["south", "west", "north", "east"];
+const SIX_WAY_DIRECTIONS: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+
+fn direction_index(directions: &[&str], value: &str) -> i32 {
+    directions.iter().position(|d| *d == value).unwrap_or(0) as i32
+}
+
+fn direction_name(directions: &[&str], index: i32) -> String {
+    directions.get(index as usize).unwrap_or(&directions[0]).to_string()
+}
+
+fn opposite(direction: &str) -> &'static str {
+    match direction {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        "west" => "east",
+        "up" => "down",
+        _ => "up",
+    }
+}
+
+/// Translates a Java block state to Bedrock. The flag is true when the block is waterlogged and
+/// needs water in the second block layer.
+pub fn java_to_bedrock(block: &BlockState) -> (BedrockBlockState, bool) {
+    let java_name = strip_namespace(&block.name);
+    let mut props: HashMap<String, String> = block.properties.clone();
+    let waterlogged = props.remove("waterlogged").as_deref() == Some("true")
+        || IMPLICITLY_WATERLOGGED.contains(&java_name);
+
+    // Bedrock has a single air block
+    let java_name = if java_name == "cave_air" || java_name == "void_air" { "air" } else { java_name };
+    let mut name = RENAMES.iter()
+        .find(|(java, _)| *java == java_name)
+        .map(|(_, bedrock)| bedrock.to_string())
+        .unwrap_or_else(|| java_name.to_string());
+    let mut states = BTreeMap::new();
+    let mut take = |key: &str| props.remove(key);
+    let is_true = |value: Option<String>| value.as_deref() == Some("true");
+
+    use BedrockStateValue::{Bool, Int, String as Str};
+
+    if name.ends_with("_stairs") {
+        if let Some(facing) = take("facing") {
+            states.insert("weirdo_direction".into(), Int(direction_index(&STAIRS_DIRECTIONS, &facing)));
+        }
+        if let Some(half) = take("half") {
+            states.insert("upside_down_bit".into(), Bool(half == "top"));
+        }
+        take("shape");
+    } else if name.ends_with("_slab") {
+        match take("type").as_deref() {
+            Some("double") => {
+                name = name.replace("_slab", "_double_slab");
+                states.insert("minecraft:vertical_half".into(), Str("bottom".into()));
+            }
+            Some(half) => {
+                states.insert("minecraft:vertical_half".into(), Str(half.to_string()));
+            }
+            None => {}
+        }
+    } else if name.ends_with("_door") {
+        if let Some(facing) = take("facing") {
+            states.insert("direction".into(), Int(direction_index(&DOOR_DIRECTIONS, &facing)));
+        }
+        if let Some(half) = take("half") {
+            states.insert("upper_block_bit".into(), Bool(half == "upper"));
+        }
+        if let Some(hinge) = take("hinge") {
+            states.insert("door_hinge_bit".into(), Bool(hinge == "right"));
+        }
+        if let Some(open) = take("open") {
+            states.insert("open_bit".into(), Bool(open == "true"));
+        }
+        take("powered");
+    } else if name.ends_with("trapdoor") {
+        if let Some(facing) = take("facing") {
+            states.insert("direction".into(), Int(direction_index(&STAIRS_DIRECTIONS, &facing)));
+        }
+        if let Some(half) = take("half") {
+            states.insert("upside_down_bit".into(), Bool(half == "top"));
+        }
+        if let Some(open) = take("open") {
+            states.insert("open_bit".into(), Bool(open == "true"));
+        }
+        take("powered");
+    } else if name.ends_with("fence_gate") {
+        if let Some(facing) = take("facing") {
+            states.insert("direction".into(), Int(direction_index(&LEGACY_DIRECTIONS, &facing)));
+        }
+        if let Some(open) = take("open") {
+            states.insert("open_bit".into(), Bool(open == "true"));
+        }
+        if let Some(in_wall) = take("in_wall") {
+            states.insert("in_wall_bit".into(), Bool(in_wall == "true"));
+        }
+        take("powered");
+    } else if name.ends_with("_bed") {
+        if let Some(facing) = take("facing") {
+            states.insert("direction".into(), Int(direction_index(&LEGACY_DIRECTIONS, &facing)));
+        }
+        if let Some(part) = take("part") {
+            states.insert("head_piece_bit".into(), Bool(part == "head"));
+        }
+        if let Some(occupied) = take("occupied") {
+            states.insert("occupied_bit".into(), Bool(occupied == "true"));
+        }
+    } else if name == "repeater" || name == "comparator" {
+        let powered = is_true(take("powered"));
+        name = format!("{}_{}", if powered { "powered" } else { "unpowered" }, name);
+        if let Some(facing) = take("facing") {
+            states.insert("direction".into(), Int(direction_index(&LEGACY_DIRECTIONS, &facing)));
+        }
+        if let Some(delay) = take("delay") {
+            states.insert("repeater_delay".into(), Int(delay.parse::<i32>().unwrap_or(1) - 1));
+        }
+        if let Some(mode) = take("mode") {
+            states.insert("output_subtract_bit".into(), Bool(mode == "subtract"));
+        }
+        if name.ends_with("comparator") {
+            states.insert("output_lit_bit".into(), Bool(powered));
+        }
+        take("locked");
+    } else if matches!(name.as_str(), "wall_torch" | "soul_wall_torch" | "redstone_wall_torch" | "torch" | "soul_torch" | "redstone_torch") {
+        let facing = take("facing").map(|f| opposite(&f).to_string()).unwrap_or_else(|| "top".to_string());
+        name = name.replace("wall_torch", "torch");
+        if name == "redstone_torch" && take("lit").as_deref() == Some("false") {
+            name = "unlit_redstone_torch".to_string();
+        }
+        states.insert("torch_facing_direction".into(), Str(facing));
+    } else if name.ends_with("_button") {
+        let face = take("face").unwrap_or_else(|| "wall".to_string());
+        let facing = take("facing").unwrap_or_else(|| "north".to_string());
+        let direction = match face.as_str() {
+            "floor" => 1,
+            "ceiling" => 0,
+            _ => direction_index(&SIX_WAY_DIRECTIONS, &facing),
+        };
+        states.insert("facing_direction".into(), Int(direction));
+        if let Some(powered) = take("powered") {
+            states.insert("button_pressed_bit".into(), Bool(powered == "true"));
+        }
+    } else if name == "lever" {
+        let face = take("face").unwrap_or_else(|| "wall".to_string());
+        let facing = take("facing").unwrap_or_else(|| "north".to_string());
+        let axis = if facing == "north" || facing == "south" { "north_south" } else { "east_west" };
+        let direction = match face.as_str() {
+            "floor" => format!("up_{}", axis),
+            "ceiling" => format!("down_{}", axis),
+            _ => facing,
+        };
+        states.insert("lever_direction".into(), Str(direction));
+        if let Some(powered) = take("powered") {
+            states.insert("open_bit".into(), Bool(powered == "true"));
+        }
+    } else if name.ends_with("rail") {
+        if let Some(shape) = take("shape") {
+            states.insert("rail_direction".into(), Int(direction_index(RAIL_SHAPES, &shape)));
+        }
+        if let Some(powered) = take("powered") {
+            states.insert("rail_data_bit".into(), Bool(powered == "true"));
+        }
+    }
+
+    if LIT_BLOCKS.contains(&name.as_str()) && is_true(take("lit")) {
+        name = format!("lit_{}", name);
+    }
+    if CROPS.contains(&name.as_str()) {
+        if let Some(age) = take("age") {
+            states.insert("growth".into(), Int(age.parse().unwrap_or(0)));
+        }
+    }
+    if name.ends_with("_leaves") {
+        if let Some(persistent) = take("persistent") {
+            states.insert("persistent_bit".into(), Bool(persistent == "true"));
+        }
+        take("distance");
+    }
+    if name == "noteblock" {
+        take("instrument");
+        take("note");
+        take("powered");
+    }
+    if name == "observer" {
+        if let Some(powered) = take("powered") {
+            states.insert("powered_bit".into(), Bool(powered == "true"));
+        }
+    }
+    if name == "hopper" {
+        if let Some(enabled) = take("enabled") {
+            states.insert("toggle_bit".into(), Bool(enabled == "false"));
+        }
+    }
+    if name.ends_with("piston") {
+        take("extended");
+    }
+    if let Some(axis) = take("axis") {
+        let key = if name == "portal" { "portal_axis" } else { "pillar_axis" };
+        states.insert(key.into(), Str(axis));
+    }
+    if let Some(facing) = take("facing") {
+        if FACING_DIRECTION_BLOCKS.contains(&name.as_str())
+            || name.ends_with("shulker_box")
+            || name.ends_with("wall_sign") {
+            states.insert("facing_direction".into(), Int(direction_index(&SIX_WAY_DIRECTIONS, &facing)));
+        } else {
+            states.insert("minecraft:cardinal_direction".into(), Str(facing));
+        }
+    }
+    for (java_key, bedrock_key) in [
+        ("power", "redstone_signal"),
+        ("rotation", "ground_sign_direction"),
+        ("moisture", "moisturized_amount"),
+        ("bites", "bite_counter"),
+        ("level", "liquid_depth"),
+    ] {
+        if let Some(value) = take(java_key) {
+            states.insert(bedrock_key.into(), Int(value.parse().unwrap_or(0)));
+        }
+    }
+    if let Some(layers) = take("layers") {
+        states.insert("height".into(), Int(layers.parse::<i32>().unwrap_or(1) - 1));
+    }
+    if name.ends_with("pressure_plate") && !name.contains("weighted") {
+        if let Some(powered) = take("powered") {
+            states.insert("redstone_signal".into(), Int(if powered == "true" { 15 } else { 0 }));
+        }
+    }
+    if let Some(half) = take("half") {
+        states.insert("upper_block_bit".into(), Bool(half == "upper"));
+    }
+    take("snowy");
+
+    for (key, value) in props {
+        // Fence, pane, wall and wire connections are computed by the game on Bedrock
+        if CONNECTION_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match value.as_str() {
+            "true" => Bool(true),
+            "false" => Bool(false),
+            _ => value.parse::<i32>().map(Int).unwrap_or(Str(value)),
+        };
+        states.insert(key, value);
+    }
+
+    (BedrockBlockState { name: namespaced(&name), states }, waterlogged)
+}
+
+/// Translates a Bedrock block state back to Java. `waterlogged` is whether the second block
+/// layer holds water at this position.
+pub fn bedrock_to_java(block: &BedrockBlockState, waterlogged: bool) -> BlockState {
+    let mut block = block.clone();
+    let bedrock_name = strip_namespace(&block.name).to_string();
+    let mut name = RENAMES.iter()
+        .find(|(_, bedrock)| *bedrock == bedrock_name)
+        .map(|(java, _)| java.to_string())
+        .unwrap_or_else(|| bedrock_name.clone());
+    let mut props: HashMap<String, String> = HashMap::new();
+    let mut set = |key: &str, value: String| {
+        props.insert(key.to_string(), value);
+    };
+
+    if name.ends_with("_stairs") {
+        if let Some(direction) = block.take_int("weirdo_direction") {
+            set("facing", direction_name(&STAIRS_DIRECTIONS, direction));
+        }
+        if let Some(upside_down) = block.take_bool("upside_down_bit") {
+            set("half", if upside_down { "top" } else { "bottom" }.to_string());
+        }
+    } else if name.ends_with("_double_slab") {
+        name = name.replace("_double_slab", "_slab");
+        block.take_string("minecraft:vertical_half");
+        set("type", "double".to_string());
+    } else if name.ends_with("_slab") {
+        if let Some(half) = block.take_string("minecraft:vertical_half") {
+            set("type", half);
+        }
+    } else if name.ends_with("_door") {
+        if let Some(direction) = block.take_int("direction") {
+            set("facing", direction_name(&DOOR_DIRECTIONS, direction));
+        }
+        if let Some(upper) = block.take_bool("upper_block_bit") {
+            set("half", if upper { "upper" } else { "lower" }.to_string());
+        }
+        if let Some(right) = block.take_bool("door_hinge_bit") {
+            set("hinge", if right { "right" } else { "left" }.to_string());
+        }
+        if let Some(open) = block.take_bool("open_bit") {
+            set("open", open.to_string());
+        }
+    } else if name.ends_with("trapdoor") {
+        if let Some(direction) = block.take_int("direction") {
+            set("facing", direction_name(&STAIRS_DIRECTIONS, direction));
+        }
+        if let Some(upside_down) = block.take_bool("upside_down_bit") {
+            set("half", if upside_down { "top" } else { "bottom" }.to_string());
+        }
+        if let Some(open) = block.take_bool("open_bit") {
+            set("open", open.to_string());
+        }
+    } else if name.ends_with("fence_gate") {
+        if let Some(direction) = block.take_int("direction") {
+            set("facing", direction_name(&LEGACY_DIRECTIONS, direction));
+        }
+        if let Some(open) = block.take_bool("open_bit") {
+            set("open", open.to_string());
+        }
+        if let Some(in_wall) = block.take_bool("in_wall_bit") {
+            set("in_wall", in_wall.to_string());
+        }
+    } else if name.ends_with("_bed") {
+        if let Some(direction) = block.take_int("direction") {
+            set("facing", direction_name(&LEGACY_DIRECTIONS, direction));
+        }
+        if let Some(head) = block.take_bool("head_piece_bit") {
+            set("part", if head { "head" } else { "foot" }.to_string());
+        }
+        if let Some(occupied) = block.take_bool("occupied_bit") {
+            set("occupied", occupied.to_string());
+        }
+    } else if let Some(base) = name.strip_prefix("powered_").or_else(|| name.strip_prefix("unpowered_"))
+        .filter(|base| *base == "repeater" || *base == "comparator")
+        .map(str::to_string)
+    {
+        let powered = name.starts_with("powered_");
+        name = base;
+        if let Some(direction) = block.take_int("direction") {
+            set("facing", direction_name(&LEGACY_DIRECTIONS, direction));
+        }
+        if let Some(delay) = block.take_int("repeater_delay") {
+            set("delay", (delay + 1).to_string());
+        }
+        if let Some(subtract) = block.take_bool("output_subtract_bit") {
+            set("mode", if subtract { "subtract" } else { "compare" }.to_string());
+        }
+        let lit = block.take_bool("output_lit_bit").unwrap_or(false);
+        set("powered", (powered || lit).to_string());
+    } else if matches!(name.as_str(), "torch" | "soul_torch" | "redstone_torch" | "unlit_redstone_torch") {
+        if name == "unlit_redstone_torch" {
+            name = "redstone_torch".to_string();
+            set("lit", "false".to_string());
+        } else if name == "redstone_torch" {
+            set("lit", "true".to_string());
+        }
+        let facing = block.take_string("torch_facing_direction").unwrap_or_else(|| "top".to_string());
+        if facing != "top" && facing != "unknown" {
+            name = name.replace("torch", "wall_torch");
+            set("facing", opposite(&facing).to_string());
+        }
+    } else if name.ends_with("_button") {
+        match block.take_int("facing_direction").unwrap_or(2) {
+            0 => {
+                set("face", "ceiling".to_string());
+                set("facing", "north".to_string());
+            }
+            1 => {
+                set("face", "floor".to_string());
+                set("facing", "north".to_string());
+            }
+            direction => {
+                set("face", "wall".to_string());
+                set("facing", direction_name(&SIX_WAY_DIRECTIONS, direction));
+            }
+        }
+        if let Some(pressed) = block.take_bool("button_pressed_bit") {
+            set("powered", pressed.to_string());
+        }
+    } else if name == "lever" {
+        let direction = block.take_string("lever_direction").unwrap_or_else(|| "north".to_string());
+        let (face, facing) = match direction.as_str() {
+            "up_north_south" => ("floor", "north"),
+            "up_east_west" => ("floor", "east"),
+            "down_north_south" => ("ceiling", "north"),
+            "down_east_west" => ("ceiling", "east"),
+            other => ("wall", other),
+        };
+        set("face", face.to_string());
+        set("facing", facing.to_string());
+        if let Some(open) = block.take_bool("open_bit") {
+            set("powered", open.to_string());
+        }
+    } else if name.ends_with("rail") {
+        if let Some(direction) = block.take_int("rail_direction") {
+            set("shape", direction_name(RAIL_SHAPES, direction));
+        }
+        if let Some(powered) = block.take_bool("rail_data_bit") {
+            set("powered", powered.to_string());
+        }
+    }
+
+    if let Some(base) = name.strip_prefix("lit_").filter(|base| LIT_BLOCKS.contains(base)).map(str::to_string) {
+        name = base;
+        set("lit", "true".to_string());
+    } else if LIT_BLOCKS.contains(&name.as_str()) {
+        set("lit", "false".to_string());
+    }
+    if CROPS.contains(&bedrock_name.as_str()) {
+        if let Some(growth) = block.take_int("growth") {
+            set("age", growth.to_string());
+        }
+    }
+    if name.ends_with("_leaves") {
+        if let Some(persistent) = block.take_bool("persistent_bit") {
+            set("persistent", persistent.to_string());
+        }
+        block.take_bool("update_bit");
+    }
+    if name == "observer" {
+        if let Some(powered) = block.take_bool("powered_bit") {
+            set("powered", powered.to_string());
+        }
+    }
+    if name == "hopper" {
+        if let Some(toggle) = block.take_bool("toggle_bit") {
+            set("enabled", (!toggle).to_string());
+        }
+    }
+    if let Some(axis) = block.take_string("pillar_axis").or_else(|| block.take_string("portal_axis")) {
+        set("axis", axis);
+    }
+    if let Some(direction) = block.take_int("facing_direction") {
+        set("facing", direction_name(&SIX_WAY_DIRECTIONS, direction));
+    }
+    if let Some(direction) = block.take_string("minecraft:cardinal_direction") {
+        set("facing", direction);
+    }
+    if name.ends_with("pressure_plate") && !name.contains("weighted") {
+        if let Some(signal) = block.take_int("redstone_signal") {
+            set("powered", (signal > 0).to_string());
+        }
+    }
+    for (bedrock_key, java_key) in [
+        ("redstone_signal", "power"),
+        ("ground_sign_direction", "rotation"),
+        ("moisturized_amount", "moisture"),
+        ("bite_counter", "bites"),
+        ("liquid_depth", "level"),
+    ] {
+        if let Some(value) = block.take_int(bedrock_key) {
+            set(java_key, value.to_string());
+        }
+    }
+    if let Some(height) = block.take_int("height") {
+        set("layers", (height + 1).to_string());
+    }
+    if let Some(upper) = block.take_bool("upper_block_bit") {
+        set("half", if upper { "upper" } else { "lower" }.to_string());
+    }
+
+    for (key, value) in std::mem::take(&mut block.states) {
+        let value = match value {
+            BedrockStateValue::Bool(b) => b.to_string(),
+            BedrockStateValue::Int(i) => i.to_string(),
+            BedrockStateValue::String(s) => s,
+        };
+        set(&key, value);
+    }
+    if waterlogged && !IMPLICITLY_WATERLOGGED.contains(&name.as_str()) && name != "water" {
+        set("waterlogged", "true".to_string());
+    }
+
+    BlockState::new(namespaced(&name)).with_properties(props)
+}
+
+/// Bedrock block entity id (e.g. `Chest`) for a Java block entity id.
+pub fn java_block_entity_id_to_bedrock(id: &str) -> String {
+    let id = strip_namespace(id);
+    let key = if id.ends_with("shulker_box") {
+        "shulker_box"
+    } else if id.ends_with("hanging_sign") {
+        "hanging_sign"
+    } else if id.ends_with("sign") {
+        "sign"
+    } else if id.ends_with("banner") {
+        "banner"
+    } else if id.ends_with("_bed") {
+        "bed"
+    } else if id.ends_with("_head") || id.ends_with("_skull") {
+        "skull"
+    } else if id == "spawner" {
+        "mob_spawner"
+    } else {
+        id
+    };
+    BLOCK_ENTITY_IDS.iter()
+        .find(|(java, _)| *java == key)
+        .map(|(_, bedrock)| bedrock.to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Java block entity id (e.g. `minecraft:chest`) for a Bedrock block entity id.
+pub fn bedrock_block_entity_id_to_java(id: &str) -> String {
+    let java = BLOCK_ENTITY_IDS.iter()
+        .find(|(_, bedrock)| *bedrock == id)
+        .map(|(java, _)| *java)
+        .unwrap_or(id);
+    let java = if java == "mob_spawner" { "spawner" } else { java };
+    if java.contains(':') {
+        java.to_string()
+    } else {
+        namespaced(&java.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn java(name: &str, props: &[(&str, &str)]) -> BlockState {
+        let mut block = BlockState::new(name.to_string());
+        for (key, value) in props {
+            block.set_property(key.to_string(), value.to_string());
+        }
+        block
+    }
+
+    #[test]
+    fn test_renames_and_properties() {
+        let (stairs, waterlogged) = java_to_bedrock(&java("minecraft:oak_stairs", &[
+            ("facing", "north"), ("half", "top"), ("shape", "straight"), ("waterlogged", "true"),
+        ]));
+        assert!(waterlogged);
+        assert_eq!(stairs.name, "minecraft:oak_stairs");
+        assert_eq!(stairs.states.get("weirdo_direction"), Some(&BedrockStateValue::Int(3)));
+        assert_eq!(stairs.states.get("upside_down_bit"), Some(&BedrockStateValue::Bool(true)));
+
+        let (lamp, _) = java_to_bedrock(&java("minecraft:redstone_lamp", &[("lit", "true")]));
+        assert_eq!(lamp, BedrockBlockState::new("minecraft:lit_redstone_lamp"));
+
+        let (slab, _) = java_to_bedrock(&java("minecraft:stone_slab", &[("type", "double")]));
+        assert_eq!(slab.name, "minecraft:stone_double_slab");
+
+        let (grass, _) = java_to_bedrock(&java("minecraft:grass_block", &[("snowy", "false")]));
+        assert_eq!(grass, BedrockBlockState::new("minecraft:grass_block"));
+    }
+
+    #[test]
+    fn test_java_bedrock_roundtrip() {
+        let blocks = vec![
+            java("minecraft:stone", &[]),
+            java("minecraft:snow_block", &[]),
+            java("minecraft:snow", &[("layers", "3")]),
+            java("minecraft:oak_stairs", &[("facing", "east"), ("half", "bottom")]),
+            java("minecraft:oak_log", &[("axis", "x")]),
+            java("minecraft:stone_slab", &[("type", "top")]),
+            java("minecraft:stone_slab", &[("type", "double")]),
+            java("minecraft:oak_door", &[("facing", "west"), ("half", "upper"), ("hinge", "right"), ("open", "false")]),
+            java("minecraft:spruce_trapdoor", &[("facing", "south"), ("half", "top"), ("open", "true")]),
+            java("minecraft:repeater", &[("facing", "north"), ("delay", "3"), ("powered", "true")]),
+            java("minecraft:comparator", &[("facing", "east"), ("mode", "subtract"), ("powered", "false")]),
+            java("minecraft:redstone_wall_torch", &[("facing", "east"), ("lit", "false")]),
+            java("minecraft:torch", &[]),
+            java("minecraft:stone_button", &[("face", "wall"), ("facing", "south"), ("powered", "true")]),
+            java("minecraft:lever", &[("face", "floor"), ("facing", "east"), ("powered", "false")]),
+            java("minecraft:furnace", &[("facing", "west"), ("lit", "true")]),
+            java("minecraft:observer", &[("facing", "up"), ("powered", "false")]),
+            java("minecraft:wheat", &[("age", "7")]),
+            java("minecraft:redstone_wire", &[("power", "15")]),
+            java("minecraft:lantern", &[("hanging", "true")]),
+            java("minecraft:rail", &[("shape", "north_east")]),
+        ];
+        for block in blocks {
+            let (bedrock, waterlogged) = java_to_bedrock(&block);
+            let back = bedrock_to_java(&bedrock, waterlogged);
+            assert_eq!(back, block, "round trip via {:?}", bedrock);
+        }
+    }
+
+    #[test]
+    fn test_waterlogging_and_block_entity_ids() {
+        let (seagrass, waterlogged) = java_to_bedrock(&java("minecraft:seagrass", &[]));
+        assert!(waterlogged);
+        assert!(bedrock_to_java(&seagrass, true).properties.is_empty());
+
+        let fence = bedrock_to_java(&BedrockBlockState::new("minecraft:oak_fence"), true);
+        assert_eq!(fence.get_property("waterlogged"), Some(&"true".to_string()));
+
+        assert_eq!(java_block_entity_id_to_bedrock("minecraft:chest"), "Chest");
+        assert_eq!(java_block_entity_id_to_bedrock("minecraft:red_shulker_box"), "ShulkerBox");
+        assert_eq!(bedrock_block_entity_id_to_java("Chest"), "minecraft:chest");
+        assert_eq!(bedrock_block_entity_id_to_java("MobSpawner"), "minecraft:spawner");
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+use quartz_nbt::NbtTag;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::is_air;
+use crate::block_entity::BlockEntity;
+use crate::entity::Entity;
+use crate::utils::{NbtMap, NbtValue};
+
+/// Vanilla's default `commandModificationBlockLimit`, the largest volume a single `/fill` may touch.
+pub const DEFAULT_MAX_FILL_VOLUME: u32 = 32768;
+
+/// Vanilla's default `maxCommandChainLength`, the number of commands a function may run.
+pub const DEFAULT_MAX_COMMANDS_PER_FUNCTION: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateMode {
+    /// Plain world coordinates (`/setblock 10 64 -3 ...`).
+    Absolute,
+    /// Coordinates relative to the command source (`/setblock ~10 ~64 ~-3 ...`).
+    Relative,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandExportOptions {
+    /// Where the minimum corner of the schematic's bounding box ends up.
+    pub origin: (i32, i32, i32),
+    pub coordinate_mode: CoordinateMode,
+    /// Emit air cuboids as well, clearing whatever is in the way.
+    pub include_air: bool,
+    pub include_entities: bool,
+    pub max_fill_volume: u32,
+    pub max_commands_per_function: usize,
+    pub namespace: String,
+    pub function_name: String,
+    /// `pack_format` written to `pack.mcmeta`; formats below 45 use the old `functions` folder.
+    pub pack_format: i32,
+}
+
+impl Default for CommandExportOptions {
+    fn default() -> Self {
+        CommandExportOptions {
+            origin: (0, 0, 0),
+            coordinate_mode: CoordinateMode::Relative,
+            include_air: false,
+            include_entities: true,
+            max_fill_volume: DEFAULT_MAX_FILL_VOLUME,
+            max_commands_per_function: DEFAULT_MAX_COMMANDS_PER_FUNCTION,
+            namespace: "nucleation".to_string(),
+            function_name: "build".to_string(),
+            pack_format: 48,
+        }
+    }
+}
+
+/// An axis-aligned box of identical block states, in schematic coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cuboid {
+    min: (i32, i32, i32),
+    max: (i32, i32, i32),
+    state: usize,
+}
+
+/// Converts a schematic into `/fill`, `/setblock` and `/summon` commands, merging runs of the
+/// same block state into as few `/fill` commands as the volume limit allows.
+pub fn to_commands(schematic: &UniversalSchematic, options: &CommandExportOptions) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut commands = Vec::new();
+    if schematic.regions.is_empty() {
+        return Ok(commands);
+    }
+
+    let bounding_box = schematic.get_bounding_box();
+    let min = bounding_box.min;
+    let (width, height, length) = bounding_box.get_dimensions();
+
+    let block_entities: HashMap<(i32, i32, i32), BlockEntity> = schematic.get_block_entities_as_list()
+        .into_iter()
+        .map(|be| (be.position, be))
+        .collect();
+
+    // Flatten the schematic into a dense grid of local palette ids, None meaning "no region here"
+    let mut palette: Vec<BlockState> = Vec::new();
+    let mut palette_lookup: HashMap<BlockState, usize> = HashMap::new();
+    // Regions far apart can span more cells than fit in memory
+    let volume = [width, height, length].iter()
+        .try_fold(1usize, |volume, &d| volume.checked_mul(usize::try_from(d).ok()?))
+        .ok_or_else(|| format!("Bounding box of {}x{}x{} blocks is too large to export", width, height, length))?;
+    let mut grid: Vec<Option<usize>> = Vec::new();
+    grid.try_reserve_exact(volume)
+        .map_err(|_| format!("Bounding box of {}x{}x{} blocks is too large to export", width, height, length))?;
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                let id = schematic.get_block(min.0 + x, min.1 + y, min.2 + z).map(|block| {
+                    *palette_lookup.entry(block.clone()).or_insert_with(|| {
+                        palette.push(block.clone());
+                        palette.len() - 1
+                    })
+                });
+                grid.push(id);
+            }
+        }
+    }
+
+    let index = |x: i32, y: i32, z: i32| x as usize + (z as usize + y as usize * length as usize) * width as usize;
+    let is_mergeable = |state: usize, pos: (i32, i32, i32)| {
+        let block = &palette[state];
+        (options.include_air || !is_air(&block.name))
+            && !block_entities.contains_key(&(pos.0 + min.0, pos.1 + min.1, pos.2 + min.2))
+    };
+
+    let mut visited = vec![false; grid.len()];
+    let mut cuboids = Vec::new();
+    let max_volume = options.max_fill_volume.max(1) as i64;
+
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                let start = index(x, y, z);
+                if visited[start] {
+                    continue;
+                }
+                let state = match grid[start] {
+                    Some(state) if is_mergeable(state, (x, y, z)) => state,
+                    _ => continue,
+                };
+                let matches = |cx: i32, cy: i32, cz: i32, visited: &[bool]| {
+                    let i = index(cx, cy, cz);
+                    !visited[i] && grid[i] == Some(state) && is_mergeable(state, (cx, cy, cz))
+                };
+
+                // Grow along x, then z, then y, keeping the box under the fill limit
+                let mut x_end = x;
+                while x_end + 1 < width
+                    && ((x_end - x + 2) as i64) <= max_volume
+                    && matches(x_end + 1, y, z, &visited) {
+                    x_end += 1;
+                }
+                let row = (x_end - x + 1) as i64;
+
+                let mut z_end = z;
+                while z_end + 1 < length
+                    && row * ((z_end - z + 2) as i64) <= max_volume
+                    && (x..=x_end).all(|cx| matches(cx, y, z_end + 1, &visited)) {
+                    z_end += 1;
+                }
+                let layer = row * ((z_end - z + 1) as i64);
+
+                let mut y_end = y;
+                while y_end + 1 < height
+                    && layer * ((y_end - y + 2) as i64) <= max_volume
+                    && (z..=z_end).all(|cz| (x..=x_end).all(|cx| matches(cx, y_end + 1, cz, &visited))) {
+                    y_end += 1;
+                }
+
+                for cy in y..=y_end {
+                    for cz in z..=z_end {
+                        for cx in x..=x_end {
+                            visited[index(cx, cy, cz)] = true;
+                        }
+                    }
+                }
+                cuboids.push(Cuboid {
+                    min: (x + min.0, y + min.1, z + min.2),
+                    max: (x_end + min.0, y_end + min.1, z_end + min.2),
+                    state,
+                });
+            }
+        }
+    }
+
+    for cuboid in &cuboids {
+        let block = palette[cuboid.state].to_string();
+        if cuboid.min == cuboid.max {
+            commands.push(format!("setblock {} {}", format_position(cuboid.min, min, options), block));
+        } else {
+            commands.push(format!(
+                "fill {} {} {}",
+                format_position(cuboid.min, min, options),
+                format_position(cuboid.max, min, options),
+                block
+            ));
+        }
+    }
+
+    // Block entities go last so their NBT isn't wiped by a later fill
+    let mut block_entity_positions: Vec<&(i32, i32, i32)> = block_entities.keys().collect();
+    block_entity_positions.sort_by_key(|&&(x, y, z)| (y, z, x));
+    for position in block_entity_positions {
+        let block = match schematic.get_block(position.0, position.1, position.2) {
+            Some(block) if options.include_air || !is_air(&block.name) => block,
+            _ => continue,
+        };
+        let nbt = block_entity_snbt(&block_entities[position]);
+        commands.push(format!("setblock {} {}{}", format_position(*position, min, options), block, nbt));
+    }
+
+    if options.include_entities {
+        for entity in schematic.get_entities_as_list() {
+            commands.push(summon_command(&entity, min, options));
+        }
+    }
+
+    Ok(commands)
+}
+
+/// The commands as a single newline separated listing.
+pub fn to_command_text(schematic: &UniversalSchematic, options: &CommandExportOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let mut text = to_commands(schematic, options)?.join("\n");
+    text.push('\n');
+    Ok(text)
+}
+
+/// Splits the commands into `.mcfunction` files of at most `max_commands_per_function` lines.
+///
+/// Returns `(file name, contents)` pairs. When more than one part is needed, a file named after
+/// `function_name` calls each `<function_name>_<n>` part in order.
+pub fn to_mcfunctions(schematic: &UniversalSchematic, options: &CommandExportOptions) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let commands = to_commands(schematic, options)?;
+    let per_function = options.max_commands_per_function.max(1);
+
+    if commands.len() <= per_function {
+        let mut text = commands.join("\n");
+        text.push('\n');
+        return Ok(vec![(format!("{}.mcfunction", options.function_name), text)]);
+    }
+
+    let mut files = Vec::new();
+    let mut entry = Vec::new();
+    for (part, chunk) in commands.chunks(per_function).enumerate() {
+        let name = format!("{}_{}", options.function_name, part);
+        entry.push(format!("function {}:{}", options.namespace, name));
+        let mut text = chunk.join("\n");
+        text.push('\n');
+        files.push((format!("{}.mcfunction", name), text));
+    }
+    let mut entry_text = entry.join("\n");
+    entry_text.push('\n');
+    files.insert(0, (format!("{}.mcfunction", options.function_name), entry_text));
+    Ok(files)
+}
+
+/// Packs the `.mcfunction` files into a datapack zip that can be dropped into a world's
+/// `datapacks` folder and run with `/function <namespace>:<function_name>`.
+pub fn to_datapack(schematic: &UniversalSchematic, options: &CommandExportOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let functions_folder = if options.pack_format < 45 { "functions" } else { "function" };
+    let description = format!(
+        "Places {}",
+        schematic.metadata.name.clone().unwrap_or_else(|| "a schematic".to_string())
+    );
+    let pack_mcmeta = serde_json::json!({
+        "pack": {
+            "pack_format": options.pack_format,
+            "description": description,
+        }
+    });
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let file_options = SimpleFileOptions::default();
+
+    zip.start_file("pack.mcmeta", file_options)?;
+    zip.write_all(serde_json::to_string_pretty(&pack_mcmeta)?.as_bytes())?;
+
+    for (name, contents) in to_mcfunctions(schematic, options)? {
+        let path = format!("data/{}/{}/{}", options.namespace, functions_folder, name);
+        zip.start_file(path, file_options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+fn format_position(position: (i32, i32, i32), schematic_min: (i32, i32, i32), options: &CommandExportOptions) -> String {
+    let x = position.0 - schematic_min.0 + options.origin.0;
+    let y = position.1 - schematic_min.1 + options.origin.1;
+    let z = position.2 - schematic_min.2 + options.origin.2;
+    match options.coordinate_mode {
+        CoordinateMode::Absolute => format!("{} {} {}", x, y, z),
+        CoordinateMode::Relative => format!("~{} ~{} ~{}", x, y, z),
+    }
+}
+
+fn format_entity_position(position: (f64, f64, f64), schematic_min: (i32, i32, i32), options: &CommandExportOptions) -> String {
+    let x = position.0 - schematic_min.0 as f64 + options.origin.0 as f64;
+    let y = position.1 - schematic_min.1 as f64 + options.origin.1 as f64;
+    let z = position.2 - schematic_min.2 as f64 + options.origin.2 as f64;
+    match options.coordinate_mode {
+        CoordinateMode::Absolute => format!("{} {} {}", x, y, z),
+        CoordinateMode::Relative => format!("~{} ~{} ~{}", x, y, z),
+    }
+}
+
+fn block_entity_snbt(block_entity: &BlockEntity) -> String {
+    let mut nbt = NbtMap::new();
+    for (key, value) in &block_entity.nbt {
+        // Identity and position come from the command itself
+        if !matches!(key.as_str(), "Id" | "id" | "Pos" | "x" | "y" | "z") {
+            nbt.insert(key.clone(), value.clone());
+        }
+    }
+    if nbt.is_empty() {
+        String::new()
+    } else {
+        nbt.to_snbt()
+    }
+}
+
+fn summon_command(entity: &Entity, schematic_min: (i32, i32, i32), options: &CommandExportOptions) -> String {
+    let position = format_entity_position(entity.position, schematic_min, options);
+
+    let mut nbt = NbtMap::new();
+    if let NbtTag::Compound(compound) = entity.to_nbt() {
+        if let Ok(data) = compound.get::<_, &quartz_nbt::NbtCompound>("NBT") {
+            for (key, value) in data.inner() {
+                // A copied UUID would collide with the original entity
+                if !matches!(key.as_str(), "id" | "Pos" | "UUID") {
+                    nbt.insert(key.clone(), NbtValue::from_quartz_nbt(value));
+                }
+            }
+        }
+    }
+
+    if nbt.is_empty() {
+        format!("summon {} {}", entity.id, position)
+    } else {
+        format!("summon {} {} {}", entity.id, position, nbt.to_snbt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use super::*;
+
+    fn absolute() -> CommandExportOptions {
+        CommandExportOptions {
+            coordinate_mode: CoordinateMode::Absolute,
+            ..CommandExportOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_solid_cube_becomes_single_fill() {
+        let mut schematic = UniversalSchematic::new("Cube".to_string());
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    schematic.set_block(x, y, z, BlockState::new("minecraft:stone".to_string()));
+                }
+            }
+        }
+
+        let commands = to_commands(&schematic, &absolute()).unwrap();
+        assert_eq!(commands, vec!["fill 0 0 0 3 3 3 minecraft:stone".to_string()]);
+    }
+
+    #[test]
+    fn test_fill_volume_limit_and_origin() {
+        let mut schematic = UniversalSchematic::new("Row".to_string());
+        for x in 0..10 {
+            schematic.set_block(x, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        }
+
+        let options = CommandExportOptions {
+            origin: (100, 64, 100),
+            max_fill_volume: 4,
+            ..absolute()
+        };
+        let commands = to_commands(&schematic, &options).unwrap();
+        assert_eq!(commands, vec![
+            "fill 100 64 100 103 64 100 minecraft:stone".to_string(),
+            "fill 104 64 100 107 64 100 minecraft:stone".to_string(),
+            "fill 108 64 100 109 64 100 minecraft:stone".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_properties_air_and_relative_coordinates() {
+        let mut schematic = UniversalSchematic::new("Stairs".to_string());
+        let stairs = BlockState::new("minecraft:oak_stairs".to_string())
+            .with_property("half".to_string(), "bottom".to_string())
+            .with_property("facing".to_string(), "north".to_string());
+        schematic.set_block(0, 0, 0, stairs);
+        schematic.set_block(2, 0, 0, BlockState::new("minecraft:stone".to_string()));
+
+        let commands = to_commands(&schematic, &CommandExportOptions::default()).unwrap();
+        assert_eq!(commands, vec![
+            "setblock ~0 ~0 ~0 minecraft:oak_stairs[facing=north,half=bottom]".to_string(),
+            "setblock ~2 ~0 ~0 minecraft:stone".to_string(),
+        ]);
+
+        let with_air = CommandExportOptions { include_air: true, ..CommandExportOptions::default() };
+        assert!(to_commands(&schematic, &with_air).unwrap().contains(&"setblock ~1 ~0 ~0 minecraft:air".to_string()));
+    }
+
+    #[test]
+    fn test_block_entities_and_entities() {
+        let mut schematic = UniversalSchematic::new("Chest".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:chest".to_string()));
+        let chest = BlockEntity::new("minecraft:chest".to_string(), (1, 0, 0))
+            .with_nbt_data("CustomName".to_string(), NbtValue::String("Loot \"box\"".to_string()))
+            .with_nbt_data("Lock".to_string(), NbtValue::Byte(1));
+        schematic.add_block_entity(chest);
+        schematic.add_entity(Entity::new("minecraft:pig".to_string(), (0.5, 1.0, 0.5)));
+
+        let commands = to_commands(&schematic, &absolute()).unwrap();
+        assert_eq!(commands, vec![
+            "setblock 0 0 0 minecraft:stone".to_string(),
+            r#"setblock 1 0 0 minecraft:chest{CustomName:"Loot \"box\"",Lock:1b}"#.to_string(),
+            "summon minecraft:pig 0.5 1 0.5".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_far_apart_regions_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Sparse".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        let far = 1_000_000_000;
+        schematic.set_block_in_region("Far", far, far, far, BlockState::new("minecraft:stone".to_string()));
+        assert!(to_commands(&schematic, &absolute()).is_err());
+        assert!(to_datapack(&schematic, &absolute()).is_err());
+    }
+
+    #[test]
+    fn test_split_functions_and_datapack() {
+        let mut schematic = UniversalSchematic::new("Checker".to_string());
+        for x in 0..5 {
+            let name = if x % 2 == 0 { "minecraft:stone" } else { "minecraft:dirt" };
+            schematic.set_block(x, 0, 0, BlockState::new(name.to_string()));
+        }
+
+        let options = CommandExportOptions { max_commands_per_function: 2, ..absolute() };
+        let files = to_mcfunctions(&schematic, &options).unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["build.mcfunction", "build_0.mcfunction", "build_1.mcfunction", "build_2.mcfunction"]);
+        assert_eq!(files[0].1, "function nucleation:build_0\nfunction nucleation:build_1\nfunction nucleation:build_2\n");
+
+        let datapack = to_datapack(&schematic, &options).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(datapack)).unwrap();
+        let mut contents = String::new();
+        archive.by_name("data/nucleation/function/build_2.mcfunction").unwrap()
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "setblock 4 0 0 minecraft:stone\n");
+        assert!(archive.by_name("pack.mcmeta").is_ok());
+    }
+}
@@ -0,0 +1,199 @@
+//! Compression of NBT payloads. Writers take an [`NbtCompression`]; readers detect the codec
+//! from the first bytes, so gzip, zlib and uncompressed NBT all load. Zstandard needs the
+//! `zstd` feature; without it, zstd data is detected but reading or writing it is an error.
+
+use std::io::{Read, Write};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use quartz_nbt::io::Flavor;
+use quartz_nbt::NbtCompound;
+use crate::formats::limits::{self, LoadLimits};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbtCompression {
+    None,
+    /// Gzip with a level from 0 (store) to 9 (smallest). What Minecraft and most tools write.
+    Gzip(u32),
+    /// Zlib with a level from 0 to 9.
+    Zlib(u32),
+    /// Zstandard with a level from 1 to 22, or 0 for zstd's default. Requires the `zstd` feature.
+    Zstd(u32),
+}
+
+impl Default for NbtCompression {
+    fn default() -> Self {
+        NbtCompression::Gzip(6)
+    }
+}
+
+impl NbtCompression {
+    /// Parses `"none"`, `"gzip"`, `"zlib"` or `"zstd"`, with the default level of 6 (3 for zstd)
+    /// when `level` is `None`.
+    pub fn parse(codec: &str, level: Option<u32>) -> Result<Self, String> {
+        let codec = codec.to_lowercase();
+        if codec == "zstd" {
+            if !cfg!(feature = "zstd") {
+                return Err("zstd compression needs nucleation built with the zstd feature".to_string());
+            }
+            let level = level.unwrap_or(3);
+            if level > 22 {
+                return Err(format!("zstd level must be between 0 and 22, got {}", level));
+            }
+            return Ok(NbtCompression::Zstd(level));
+        }
+        let level = level.unwrap_or(6);
+        if level > 9 {
+            return Err(format!("Compression level must be between 0 and 9, got {}", level));
+        }
+        match codec.as_str() {
+            "none" => Ok(NbtCompression::None),
+            "gzip" => Ok(NbtCompression::Gzip(level)),
+            "zlib" => Ok(NbtCompression::Zlib(level)),
+            _ => Err(format!("Unknown compression '{}', expected none, gzip, zlib or zstd", codec)),
+        }
+    }
+
+    /// The codec of `data`, judged from its header. The level isn't recoverable and is reported as 6.
+    pub fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1f, 0x8b, ..] => NbtCompression::Gzip(6),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => NbtCompression::Zstd(3),
+            // Deflate method with a header checksum that's a multiple of 31
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => NbtCompression::Zlib(6),
+            _ => NbtCompression::None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            NbtCompression::None => Ok(data.to_vec()),
+            NbtCompression::Gzip(level) => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level.min(9)));
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            NbtCompression::Zlib(level) => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            NbtCompression::Zstd(level) => zstd::stream::encode_all(data, level.min(22) as i32),
+            #[cfg(not(feature = "zstd"))]
+            NbtCompression::Zstd(_) => Err(zstd_unsupported()),
+        }
+    }
+}
+
+/// A streaming zstd decoder over `data`.
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_decoder(data: &[u8]) -> std::io::Result<impl Read + '_> {
+    zstd::stream::read::Decoder::with_buffer(data)
+}
+
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn zstd_decoder(_data: &[u8]) -> std::io::Result<std::io::Empty> {
+    Err(zstd_unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unsupported() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, "zstd compression needs nucleation built with the zstd feature")
+}
+
+/// Decompresses `data` with whichever codec it was written with.
+pub fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    decompress_with_limit(data, u64::MAX)
+}
+
+/// Like [`decompress`], but fails once the output would exceed `max_bytes`, so a small
+/// compressed bomb can't expand into memory.
+pub fn decompress_with_limit(data: &[u8], max_bytes: u64) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    // Read one byte past the limit to tell "exactly at" from "over"
+    let cap = max_bytes.saturating_add(1);
+    match NbtCompression::detect(data) {
+        NbtCompression::None => decompressed.extend_from_slice(&data[..data.len().min(cap as usize)]),
+        NbtCompression::Gzip(_) => GzDecoder::new(data).take(cap).read_to_end(&mut decompressed).map(|_| ())?,
+        NbtCompression::Zlib(_) => ZlibDecoder::new(data).take(cap).read_to_end(&mut decompressed).map(|_| ())?,
+        NbtCompression::Zstd(_) => zstd_decoder(data)?.take(cap).read_to_end(&mut decompressed).map(|_| ())?,
+    };
+    if decompressed.len() as u64 > max_bytes {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Decompressed data exceeds the limit of {} bytes", max_bytes),
+        ));
+    }
+    Ok(decompressed)
+}
+
+pub fn read_nbt(data: &[u8]) -> Result<NbtCompound, Box<dyn std::error::Error>> {
+    read_nbt_with_limits(data, &LoadLimits::default())
+}
+
+/// Decompresses and parses NBT, rejecting payloads over `max_decompressed_bytes` and NBT that's
+/// malformed or nested deeper than `max_nbt_depth` before anything is allocated for it.
+pub fn read_nbt_with_limits(data: &[u8], limits: &LoadLimits) -> Result<NbtCompound, Box<dyn std::error::Error>> {
+    let decompressed = decompress_with_limit(data, limits.max_decompressed_bytes)?;
+    limits::validate_nbt(&decompressed, limits.max_nbt_depth)?;
+    let (root, _) = quartz_nbt::io::read_nbt(&mut std::io::Cursor::new(decompressed), Flavor::Uncompressed)?;
+    Ok(root)
+}
+
+pub fn write_nbt(root: &NbtCompound, compression: NbtCompression) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut raw = Vec::new();
+    quartz_nbt::io::write_nbt(&mut raw, None, root, Flavor::Uncompressed)?;
+    Ok(compression.compress(&raw)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quartz_nbt::NbtTag;
+
+    #[test]
+    fn test_every_codec_is_detected() {
+        let mut root = NbtCompound::new();
+        root.insert("Version", NbtTag::Int(3));
+        for compression in [NbtCompression::None, NbtCompression::Gzip(0), NbtCompression::Gzip(9), NbtCompression::Zlib(1), NbtCompression::Zlib(9)] {
+            let data = write_nbt(&root, compression).unwrap();
+            let detected = NbtCompression::detect(&data);
+            assert_eq!(std::mem::discriminant(&detected), std::mem::discriminant(&compression));
+            assert_eq!(read_nbt(&data).unwrap(), root);
+        }
+        assert_eq!(NbtCompression::parse("ZLIB", Some(9)), Ok(NbtCompression::Zlib(9)));
+        assert!(NbtCompression::parse("brotli", None).is_err());
+        assert!(NbtCompression::parse("gzip", Some(10)).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() {
+        let mut root = NbtCompound::new();
+        root.insert("Version", NbtTag::Int(3));
+        let compression = NbtCompression::parse("zstd", Some(19)).unwrap();
+        let data = write_nbt(&root, compression).unwrap();
+        assert_eq!(NbtCompression::detect(&data), NbtCompression::Zstd(3));
+        assert_eq!(read_nbt(&data).unwrap(), root);
+        assert!(NbtCompression::parse("zstd", Some(23)).is_err());
+
+        let bomb = NbtCompression::Zstd(3).compress(&vec![0u8; 1 << 20]).unwrap();
+        assert!(decompress_with_limit(&bomb, 1024).is_err());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_needs_feature() {
+        assert!(NbtCompression::parse("zstd", None).is_err());
+        assert!(decompress(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_decompressed_size_is_capped() {
+        let bomb = NbtCompression::Gzip(9).compress(&vec![0u8; 1 << 20]).unwrap();
+        assert_eq!(decompress_with_limit(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(decompress_with_limit(&bomb, 1024).is_err());
+        assert!(decompress_with_limit(&[10, 0, 0, 0], 3).is_err());
+    }
+}
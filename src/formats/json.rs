@@ -0,0 +1,396 @@
+//! JSON schematics, for clients that author or inspect schematics without an NBT library.
+//!
+//! Schema version 1:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "metadata": { "name": "House", "author": null, "mc_version": 3953, ... },
+//!   "default_region": "Main",
+//!   "origin": [0, 0, 0],
+//!   "regions": [{
+//!     "name": "Main",
+//!     "position": [0, 0, 0],
+//!     "size": [2, 1, 1],
+//!     "blocks": [
+//!       { "x": 0, "y": 0, "z": 0, "block": "minecraft:stone" },
+//!       { "x": 1, "y": 0, "z": 0, "block": "minecraft:chest[facing=west]" }
+//!     ],
+//!     "block_entities": [{ "id": "minecraft:chest", "position": [1, 0, 0], "nbt": { "Lock": { "String": "key" } } }],
+//!     "entities": [{ "id": "minecraft:pig", "position": [0.5, 1.0, 0.5], "nbt": {} }],
+//!     "pending_block_ticks": [],
+//!     "pending_fluid_ticks": [],
+//!     "extra_nbt": {}
+//!   }]
+//! }
+//! ```
+//!
+//! Block positions are absolute and air is left out. In the compact variant a region has a
+//! `"palette"` of block strings and `"blocks"` is an array of palette indices covering the
+//! whole region in YZX order (x fastest), starting at `position`. NBT values are written as
+//! `{ "<Type>": value }`, e.g. `{ "Int": 5 }` or `{ "Compound": { ... } }`. Everything except
+//! `version`, `regions`, and each region's `name`, `position`, `size` and `blocks` is optional.
+//!
+//! `from_json` also reads the older `UniversalSchematic::get_json_string` layout, where
+//! `regions` is an object keyed by region name.
+//!
+//! A region grows to fit placed blocks outside its `size`. For JSON from untrusted clients,
+//! `from_json_with_limits` caps the grown volume, the palette size and the entity count.
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use crate::block_entity::BlockEntity;
+use crate::entity::Entity;
+use crate::formats::limits::LoadLimits;
+use crate::metadata::Metadata;
+use crate::region::{PendingTick, Region};
+use crate::utils::{parse_block_string, NbtMap};
+use crate::{BlockState, UniversalSchematic};
+
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct JsonSchematic {
+    version: u32,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_region: Option<String>,
+    #[serde(default)]
+    origin: (i32, i32, i32),
+    regions: Vec<JsonRegion>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonRegion {
+    name: String,
+    position: (i32, i32, i32),
+    size: (i32, i32, i32),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    palette: Option<Vec<String>>,
+    blocks: JsonBlocks,
+    #[serde(default)]
+    block_entities: Vec<BlockEntity>,
+    #[serde(default)]
+    entities: Vec<Entity>,
+    #[serde(default)]
+    pending_block_ticks: Vec<PendingTick>,
+    #[serde(default)]
+    pending_fluid_ticks: Vec<PendingTick>,
+    #[serde(default)]
+    extra_nbt: NbtMap,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonBlocks {
+    Indices(Vec<usize>),
+    Placed(Vec<JsonBlock>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonBlock {
+    x: i32,
+    y: i32,
+    z: i32,
+    block: String,
+}
+
+/// The layout written by `get_json_string`.
+#[derive(Deserialize)]
+struct LegacyJsonSchematic {
+    metadata: Metadata,
+    regions: IndexMap<String, Region>,
+    #[serde(default)]
+    default_region_name: Option<String>,
+    #[serde(default)]
+    origin: (i32, i32, i32),
+}
+
+pub fn to_json(schematic: &UniversalSchematic, compact: bool) -> Result<String, String> {
+    let regions = schematic.regions.values()
+        .map(|region| create_region(region, compact))
+        .collect();
+    let json = JsonSchematic {
+        version: JSON_SCHEMA_VERSION,
+        metadata: schematic.metadata.clone(),
+        default_region: Some(schematic.default_region_name.clone()),
+        origin: schematic.origin,
+        regions,
+    };
+    let result = if compact { serde_json::to_string(&json) } else { serde_json::to_string_pretty(&json) };
+    result.map_err(|e| format!("Failed to serialize schematic: {}", e))
+}
+
+pub fn from_json(json: &str) -> Result<UniversalSchematic, String> {
+    from_json_with_limits(json, &LoadLimits::default())
+}
+
+/// Like [`from_json`], failing cleanly on any region, palette or entity list beyond `limits`.
+pub fn from_json_with_limits(json: &str, limits: &LoadLimits) -> Result<UniversalSchematic, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    if value.get("regions").is_some_and(|regions| regions.is_object()) {
+        return from_legacy(value, limits);
+    }
+    let json: JsonSchematic = serde_json::from_value(value).map_err(|e| format!("Invalid schematic JSON: {}", e))?;
+    if json.version > JSON_SCHEMA_VERSION {
+        return Err(format!("Unsupported schematic JSON version {}", json.version));
+    }
+
+    let mut schematic = UniversalSchematic::new(String::new());
+    schematic.metadata = json.metadata;
+    schematic.origin = json.origin;
+    schematic.default_region_name = json.default_region
+        .or_else(|| json.regions.first().map(|region| region.name.clone()))
+        .unwrap_or_else(|| "Main".to_string());
+    let entity_count = json.regions.iter()
+        .map(|region| region.entities.len() + region.block_entities.len())
+        .sum();
+    limits.check_entities(entity_count)?;
+    for region in json.regions {
+        let region = parse_region(region, limits)?;
+        if schematic.regions.contains_key(&region.name) {
+            return Err(format!("Duplicate region '{}'", region.name));
+        }
+        schematic.regions.insert(region.name.clone(), region);
+    }
+    Ok(schematic)
+}
+
+fn from_legacy(value: serde_json::Value, limits: &LoadLimits) -> Result<UniversalSchematic, String> {
+    let json: LegacyJsonSchematic = serde_json::from_value(value).map_err(|e| format!("Invalid schematic JSON: {}", e))?;
+    let entity_count = json.regions.values()
+        .map(|region| region.entities.len() + region.block_entities.len())
+        .sum();
+    limits.check_entities(entity_count)?;
+    for (name, region) in &json.regions {
+        if region.name != *name {
+            return Err(format!("Region '{}' is stored under the key '{}'", region.name, name));
+        }
+        // `get_json_string` always writes the normalized, positive size
+        if region.size.0 < 0 || region.size.1 < 0 || region.size.2 < 0 {
+            return Err(format!("Region '{}' has a negative size {:?}", region.name, region.size));
+        }
+        limits.check_region(region.position, region.size)?;
+        limits.check_palette(region.palette.len())?;
+        if region.blocks.len() != region.volume() {
+            return Err(format!("Region '{}' has {} blocks for a volume of {}", region.name, region.blocks.len(), region.volume()));
+        }
+        if region.blocks.iter().any(|&index| index >= region.palette.len()) {
+            return Err(format!("Region '{}' has a block outside its palette", region.name));
+        }
+    }
+    let mut schematic = UniversalSchematic::new(String::new());
+    schematic.default_region_name = json.default_region_name
+        .or_else(|| json.regions.keys().next().cloned())
+        .unwrap_or_else(|| "Main".to_string());
+    schematic.metadata = json.metadata;
+    schematic.origin = json.origin;
+    schematic.regions = json.regions;
+    Ok(schematic)
+}
+
+fn create_region(region: &Region, compact: bool) -> JsonRegion {
+    let (palette, blocks) = if compact {
+        let palette = region.palette.iter().map(BlockState::to_string).collect();
+        (Some(palette), JsonBlocks::Indices(region.blocks.clone()))
+    } else {
+        let blocks = (0..region.volume())
+            .filter(|&index| !region.palette[region.blocks[index]].name.ends_with(":air"))
+            .map(|index| {
+                let (x, y, z) = region.index_to_coords(index);
+                JsonBlock { x, y, z, block: region.palette[region.blocks[index]].to_string() }
+            })
+            .collect();
+        (None, JsonBlocks::Placed(blocks))
+    };
+    let block_entities = region.sorted_block_entities().into_iter().cloned().collect();
+    JsonRegion {
+        name: region.name.clone(),
+        position: region.position,
+        size: region.size,
+        palette,
+        blocks,
+        block_entities,
+        entities: region.entities.clone(),
+        pending_block_ticks: region.pending_block_ticks.clone(),
+        pending_fluid_ticks: region.pending_fluid_ticks.clone(),
+        extra_nbt: region.extra_nbt.clone(),
+    }
+}
+
+fn parse_block(block: &str) -> Result<(BlockState, Option<NbtMap>), String> {
+    parse_block_string(block).map_err(|e| format!("Invalid block '{}': {}", block, e))
+}
+
+/// An empty region covering the one declared by `json` grown to fit its placed blocks, with the
+/// bounds worked out in `i64` so hostile coordinates can't overflow.
+fn create_empty_region(json: &JsonRegion, limits: &LoadLimits) -> Result<Region, String> {
+    let (position, size) = (json.position, json.size);
+    if size.0 == 0 || size.1 == 0 || size.2 == 0 {
+        return Err(format!("Region '{}' has an empty axis in size {:?}", json.name, size));
+    }
+    let corners = |start: i32, extent: i32| {
+        let end = i64::from(start) + i64::from(extent) - i64::from(extent.signum());
+        (i64::from(start).min(end), i64::from(start).max(end))
+    };
+    let mut bounds = [corners(position.0, size.0), corners(position.1, size.1), corners(position.2, size.2)];
+    if let JsonBlocks::Placed(blocks) = &json.blocks {
+        for block in blocks {
+            for (axis, value) in bounds.iter_mut().zip([block.x, block.y, block.z]) {
+                *axis = (axis.0.min(i64::from(value)), axis.1.max(i64::from(value)));
+            }
+        }
+    }
+    let axis = |(min, max): (i64, i64)| -> Result<(i32, i32), String> {
+        let start = i32::try_from(min).ok();
+        let extent = i32::try_from(max - min + 1).ok();
+        start.zip(extent).ok_or_else(|| format!("Region '{}' is out of range", json.name))
+    };
+    let [x, y, z] = bounds;
+    let ((px, sx), (py, sy), (pz, sz)) = (axis(x)?, axis(y)?, axis(z)?);
+    limits.check_region((px, py, pz), (sx, sy, sz))
+        .map_err(|e| format!("Region '{}': {}", json.name, e))?;
+    Ok(Region::new(json.name.clone(), (px, py, pz), (sx, sy, sz)))
+}
+
+fn parse_region(json: JsonRegion, limits: &LoadLimits) -> Result<Region, String> {
+    let mut region = create_empty_region(&json, limits)?;
+    match (json.palette, json.blocks) {
+        (Some(palette), JsonBlocks::Indices(blocks)) => {
+            if blocks.len() != region.volume() {
+                return Err(format!("Region '{}' has {} blocks for a volume of {}", region.name, blocks.len(), region.volume()));
+            }
+            limits.check_palette(palette.len())?;
+            let palette = palette.iter()
+                .map(|block| parse_block(block).map(|(state, _)| state))
+                .collect::<Result<Vec<BlockState>, String>>()?;
+            if blocks.iter().any(|&index| index >= palette.len()) {
+                return Err(format!("Region '{}' has a block outside its palette", region.name));
+            }
+            region.palette = palette;
+            region.blocks = blocks;
+        }
+        (None, JsonBlocks::Placed(blocks)) => {
+            for block in blocks {
+                let (state, nbt) = parse_block(&block.block)?;
+                if let Some(nbt) = nbt {
+                    let mut block_entity = BlockEntity::new(state.name.clone(), (block.x, block.y, block.z));
+                    block_entity.nbt = nbt;
+                    region.add_block_entity(block_entity);
+                }
+                region.set_block(block.x, block.y, block.z, state);
+            }
+        }
+        // An empty array parses as indices
+        (None, JsonBlocks::Indices(blocks)) if blocks.is_empty() => {}
+        (None, JsonBlocks::Indices(_)) => {
+            return Err(format!("Region '{}' lists palette indices without a palette", region.name));
+        }
+        (Some(_), JsonBlocks::Placed(_)) => {
+            return Err(format!("Region '{}' has a palette, so its blocks must be palette indices", region.name));
+        }
+    }
+    for block_entity in json.block_entities {
+        region.add_block_entity(block_entity);
+    }
+    region.entities = json.entities;
+    region.pending_block_ticks = json.pending_block_ticks;
+    region.pending_fluid_ticks = json.pending_fluid_ticks;
+    region.extra_nbt = json.extra_nbt;
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::sample_schematic;
+
+    fn assert_same(a: &UniversalSchematic, b: &UniversalSchematic) {
+        assert_eq!(a.metadata, b.metadata);
+        assert_eq!(a.origin, b.origin);
+        assert_eq!(a.default_region_name, b.default_region_name);
+        assert_eq!(a.regions.len(), b.regions.len());
+        for (name, region) in &a.regions {
+            let other = &b.regions[name];
+            assert_eq!((other.position, other.size), (region.position, region.size));
+            for index in 0..region.volume() {
+                let (x, y, z) = region.index_to_coords(index);
+                assert_eq!(other.get_block(x, y, z), region.get_block(x, y, z));
+            }
+            assert_eq!(other.block_entities, region.block_entities);
+            assert_eq!(other.entities, region.entities);
+        }
+    }
+
+    #[test]
+    fn test_round_trips_all_layouts() {
+        let schematic = sample_schematic();
+        for compact in [false, true] {
+            let json = to_json(&schematic, compact).unwrap();
+            assert_same(&schematic, &from_json(&json).unwrap());
+        }
+        let legacy = schematic.get_json_string().unwrap();
+        assert_same(&schematic, &from_json(&legacy).unwrap());
+    }
+
+    #[test]
+    fn test_out_of_range_input_is_rejected() {
+        let region = |position: &str, size: &str, blocks: &str| from_json(&format!(
+            r#"{{"version": 1, "regions": [{{"name": "A", "position": {}, "size": {}, "blocks": {}}}]}}"#,
+            position, size, blocks
+        ));
+        assert!(region("[2147483647, 0, 0]", "[2, 1, 1]", "[]").is_err());
+        assert!(region("[0, 0, 0]", "[1, 1, 1]", r#"[{"x": 2147483647, "y": 0, "z": 0, "block": "minecraft:stone"}]"#).is_err());
+        assert!(from_json_with_limits(&format!(
+            r#"{{"version": 1, "regions": [{{"name": "A", "position": [0, 0, 0], "size": [1, 1, 1], "blocks": [{}]}}]}}"#,
+            r#"{"x": 1000, "y": 1000, "z": 1000, "block": "minecraft:stone"}"#
+        ), &LoadLimits::untrusted()).is_err());
+        assert!(region("[0, 0, 0]", "[0, 1, 1]", "[]").is_err());
+        assert!(region("[0, 0, 0]", "[2, 1, 1]", r#"[{"x": 1, "y": 0, "z": 0, "block": "minecraft:stone"}]"#).is_ok());
+        let limits = LoadLimits::untrusted();
+        assert!(from_json_with_limits(r#"{"version": 1, "regions": [{"name": "A", "position": [0, 0, 0], "size": [30000, 30000, 30000], "blocks": []}]}"#, &limits).is_err());
+
+        let mut legacy: serde_json::Value = serde_json::from_str(&sample_schematic().get_json_string().unwrap()).unwrap();
+        let first = legacy["regions"].as_object_mut().unwrap().values_mut().next().unwrap();
+        first["size"] = serde_json::json!([-2, 1, 1]);
+        assert!(from_json(&legacy.to_string()).is_err());
+
+        let mut legacy: serde_json::Value = serde_json::from_str(&sample_schematic().get_json_string().unwrap()).unwrap();
+        legacy["regions"]["Second"]["name"] = serde_json::json!("Other");
+        assert!(from_json(&legacy.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_legacy_default_region_falls_back_to_first_region() {
+        let mut legacy: serde_json::Value = serde_json::from_str(&sample_schematic().get_json_string().unwrap()).unwrap();
+        legacy.as_object_mut().unwrap().remove("default_region_name");
+        let schematic = from_json(&legacy.to_string()).unwrap();
+        let first = legacy["regions"].as_object().unwrap().keys().next().unwrap();
+        assert_eq!(&schematic.default_region_name, first);
+        assert_ne!(schematic.metadata.name.as_ref(), Some(first));
+        assert!(schematic.regions.contains_key(&schematic.default_region_name));
+    }
+
+    #[test]
+    fn test_authored_json() {
+        let json = r#"{
+            "version": 1,
+            "regions": [{
+                "name": "Main", "position": [0, 0, 0], "size": [1, 1, 1],
+                "blocks": [
+                    { "x": 0, "y": 0, "z": 0, "block": "minecraft:oak_log[axis=x]" },
+                    { "x": 0, "y": 1, "z": 0, "block": "minecraft:chest{CustomName:'\"Loot\"'}" }
+                ]
+            }]
+        }"#;
+        let schematic = from_json(json).unwrap();
+        assert_eq!(schematic.default_region_name, "Main");
+        assert_eq!(schematic.get_block(0, 0, 0).unwrap().get_property("axis"), Some(&"x".to_string()));
+        assert!(schematic.get_block_entity(crate::block_position::BlockPosition { x: 0, y: 1, z: 0 }).is_some());
+
+        assert!(from_json(r#"{"version": 1, "regions": [{"name": "A", "position": [0,0,0], "size": [2,1,1], "palette": ["minecraft:air"], "blocks": [0, 1]}]}"#).is_err());
+        assert!(from_json(r#"{"version": 1, "regions": [{"name": "A", "position": [0,0,0], "size": [2,1,1], "palette": ["minecraft:air"], "blocks": [0]}]}"#).is_err());
+        assert!(from_json(r#"{"version": 2, "regions": []}"#).is_err());
+    }
+}
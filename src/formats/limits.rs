@@ -0,0 +1,227 @@
+//! Caps on what the loaders will allocate, for reading files from untrusted sources. Every load
+//! also checks the file's structure (NBT lengths, region sizes, palette indices) so malformed
+//! data produces an error rather than a panic; the limits additionally bound well-formed files.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadLimits {
+    /// Largest NBT payload after decompression, in bytes.
+    pub max_decompressed_bytes: u64,
+    /// Most blocks, air included, in a single region.
+    pub max_volume: u64,
+    /// Most palette entries in a single region.
+    pub max_palette_size: usize,
+    /// Deepest nesting of compounds and lists.
+    pub max_nbt_depth: usize,
+    /// Most entities and block entities, counted together, in the whole file.
+    pub max_entities: usize,
+}
+
+impl Default for LoadLimits {
+    /// No caps beyond the structural checks, and the nesting depth Minecraft itself allows.
+    fn default() -> Self {
+        LoadLimits {
+            max_decompressed_bytes: u64::MAX,
+            max_volume: u64::MAX,
+            max_palette_size: usize::MAX,
+            max_nbt_depth: 512,
+            max_entities: usize::MAX,
+        }
+    }
+}
+
+impl LoadLimits {
+    /// Caps suited to user uploads: 256 MiB of NBT, 256³ blocks per region, 65536 palette
+    /// entries and 65536 entities.
+    pub fn untrusted() -> Self {
+        LoadLimits {
+            max_decompressed_bytes: 256 * 1024 * 1024,
+            max_volume: 256 * 256 * 256,
+            max_palette_size: 65_536,
+            max_nbt_depth: 512,
+            max_entities: 65_536,
+        }
+    }
+
+    /// [`untrusted`](Self::untrusted) when `untrusted` is set, otherwise the defaults. For the
+    /// bindings, whose loaders take a flag rather than limits.
+    pub fn untrusted_if(untrusted: bool) -> Self {
+        if untrusted { Self::untrusted() } else { Self::default() }
+    }
+
+    /// Checks that a region at `position` with signed `size` has no empty axis, fits in `i32`
+    /// coordinates and stays within `max_volume`, returning its volume.
+    pub fn check_region(&self, position: (i32, i32, i32), size: (i32, i32, i32)) -> Result<u64, String> {
+        // `Region::new` widens an empty axis to one block, which the block data wouldn't cover
+        if size.0 == 0 || size.1 == 0 || size.2 == 0 {
+            return Err(format!("Region size {:?} has an empty axis", size));
+        }
+        let fits = position.0.checked_add(size.0).is_some()
+            && position.1.checked_add(size.1).is_some()
+            && position.2.checked_add(size.2).is_some();
+        if !fits {
+            return Err(format!("Region at {:?} with size {:?} is out of range", position, size));
+        }
+        let volume = u64::from(size.0.unsigned_abs())
+            .checked_mul(u64::from(size.1.unsigned_abs()))
+            .and_then(|area| area.checked_mul(u64::from(size.2.unsigned_abs())))
+            .filter(|&volume| volume <= self.max_volume && usize::try_from(volume).is_ok())
+            .ok_or_else(|| format!("Region size {:?} exceeds the volume limit of {}", size, self.max_volume))?;
+        Ok(volume)
+    }
+
+    pub fn check_palette(&self, len: usize) -> Result<(), String> {
+        if len > self.max_palette_size {
+            return Err(format!("Palette of {} entries exceeds the limit of {}", len, self.max_palette_size));
+        }
+        Ok(())
+    }
+
+    pub fn check_entities(&self, count: usize) -> Result<(), String> {
+        if count > self.max_entities {
+            return Err(format!("{} entities exceed the limit of {}", count, self.max_entities));
+        }
+        Ok(())
+    }
+}
+
+/// Walks uncompressed NBT and checks that every length is non-negative and fits in the
+/// remaining data, and that nesting stays within `max_depth`, so parsing it can't over-allocate
+/// or overflow the stack.
+pub fn validate_nbt(data: &[u8], max_depth: usize) -> Result<(), String> {
+    let mut reader = NbtValidator { data, max_depth };
+    if reader.byte()? != 10 {
+        return Err("Root tag is not a compound".to_string());
+    }
+    let name_len = reader.u16()?;
+    reader.skip(u64::from(name_len))?;
+    reader.payload(10, 0)
+}
+
+struct NbtValidator<'a> {
+    data: &'a [u8],
+    max_depth: usize,
+}
+
+impl NbtValidator<'_> {
+    fn payload(&mut self, id: u8, depth: usize) -> Result<(), String> {
+        match id {
+            1 => self.skip(1),
+            2 => self.skip(2),
+            3 | 5 => self.skip(4),
+            4 | 6 => self.skip(8),
+            7 => {
+                let len = self.len()?;
+                self.skip(len)
+            }
+            8 => {
+                let len = self.u16()?;
+                self.skip(u64::from(len))
+            }
+            9 => {
+                self.enter(depth)?;
+                let element = self.byte()?;
+                let len = self.len()?;
+                if element > 12 || (element == 0 && len > 0) {
+                    return Err(format!("Invalid NBT list element type {}", element));
+                }
+                // Every element takes at least a byte, so a length past the end is a lie
+                if len > self.data.len() as u64 {
+                    return Err("NBT list is longer than the data".to_string());
+                }
+                for _ in 0..len {
+                    self.payload(element, depth + 1)?;
+                }
+                Ok(())
+            }
+            10 => {
+                self.enter(depth)?;
+                loop {
+                    let child = self.byte()?;
+                    if child == 0 {
+                        return Ok(());
+                    }
+                    let name_len = self.u16()?;
+                    self.skip(u64::from(name_len))?;
+                    self.payload(child, depth + 1)?;
+                }
+            }
+            11 => {
+                let len = self.len()?;
+                self.skip(len * 4)
+            }
+            12 => {
+                let len = self.len()?;
+                self.skip(len * 8)
+            }
+            _ => Err(format!("Unknown NBT tag type {}", id)),
+        }
+    }
+
+    fn enter(&self, depth: usize) -> Result<(), String> {
+        if depth >= self.max_depth {
+            return Err(format!("NBT is nested deeper than {} levels", self.max_depth));
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), String> {
+        if len > self.data.len() as u64 {
+            return Err("Unexpected end of NBT data".to_string());
+        }
+        self.data = &self.data[len as usize..];
+        Ok(())
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let (&byte, rest) = self.data.split_first().ok_or("Unexpected end of NBT data")?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes([self.byte()?, self.byte()?]))
+    }
+
+    fn len(&mut self) -> Result<u64, String> {
+        let len = i32::from_be_bytes([self.byte()?, self.byte()?, self.byte()?, self.byte()?]);
+        u64::try_from(len).map_err(|_| format!("Negative NBT length {}", len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quartz_nbt::{NbtCompound, NbtList, NbtTag};
+
+    fn raw_nbt(root: &NbtCompound) -> Vec<u8> {
+        let mut raw = Vec::new();
+        quartz_nbt::io::write_nbt(&mut raw, None, root, quartz_nbt::io::Flavor::Uncompressed).unwrap();
+        raw
+    }
+
+    #[test]
+    fn test_validate_nbt() {
+        let mut root = NbtCompound::new();
+        root.insert("Data", NbtTag::ByteArray(vec![1, 2, 3]));
+        root.insert("List", NbtTag::List(NbtList::from(vec![NbtTag::Compound(NbtCompound::new())])));
+        let raw = raw_nbt(&root);
+        assert!(validate_nbt(&raw, 512).is_ok());
+        assert!(validate_nbt(&raw, 1).is_err());
+        assert!(validate_nbt(&raw[..raw.len() - 1], 512).is_err());
+
+        // A byte array claiming two billion entries
+        let mut lying = raw_nbt(&NbtCompound::new());
+        lying.pop();
+        lying.extend_from_slice(&[7, 0, 1, b'a', 0x7f, 0xff, 0xff, 0xff, 0]);
+        assert!(validate_nbt(&lying, 512).is_err());
+    }
+
+    #[test]
+    fn test_check_region() {
+        let limits = LoadLimits::untrusted();
+        assert_eq!(limits.check_region((0, 0, 0), (-4, 2, 3)), Ok(24));
+        assert!(limits.check_region((0, 0, 0), (30_000, 30_000, 30_000)).is_err());
+        assert!(limits.check_region((0, 0, 0), (0, 5, 5)).is_err());
+        assert!(LoadLimits::default().check_region((i32::MAX, 0, 0), (2, 1, 1)).is_err());
+    }
+}
@@ -1,25 +1,25 @@
-use std::io::{Cursor, Read};
 use quartz_nbt::{NbtCompound, NbtTag, NbtList};
 use std::time::{SystemTime, UNIX_EPOCH};
-use flate2::read::GzDecoder;
-use quartz_nbt::io::Flavor;
 use crate::{UniversalSchematic, BlockState};
 use crate::block_entity::BlockEntity;
+use crate::datafixer::{self, data_version};
 use crate::entity::Entity;
-use crate::region::Region;
+use crate::region::{PendingTick, Region};
+use crate::utils::{NbtMap, NbtValue};
+use crate::render::{render_isometric, RenderOptions};
+use crate::formats::compression::{self, NbtCompression};
+use crate::formats::limits::LoadLimits;
 
 
 pub fn is_litematic(data: &[u8]) -> bool {
-    // Decompress the data
-    let mut decoder = GzDecoder::new(data);
-    let mut decompressed = Vec::new();
-    if decoder.read_to_end(&mut decompressed).is_err() {
-        return false;
-    }
+    is_litematic_with_limits(data, &LoadLimits::default())
+}
 
-    // Read the NBT data
-    let (root, _) = match quartz_nbt::io::read_nbt(&mut Cursor::new(decompressed), Flavor::Uncompressed) {
-        Ok(result) => result,
+/// Like [`is_litematic`], but gives up once the data decompresses past `limits`, so sniffing an
+/// upload can't inflate a compressed bomb.
+pub fn is_litematic_with_limits(data: &[u8], limits: &LoadLimits) -> bool {
+    let root = match compression::read_nbt_with_limits(data, limits) {
+        Ok(root) => root,
         Err(_) => return false,
     };
 
@@ -28,49 +28,101 @@ pub fn is_litematic(data: &[u8]) -> bool {
         root.get::<_, &NbtCompound>("Metadata").is_ok() &&
         root.get::<_, &NbtCompound>("Regions").is_ok()
 }
+/// Litematica's format for 1.20.4 and earlier.
+pub const LITEMATIC_V6: i32 = 6;
+/// Litematica's format for 1.20.5 and later, where stored items use `count` and components.
+pub const LITEMATIC_V7: i32 = 7;
+
+#[derive(Debug, Clone, Default)]
+pub struct LitematicWriteOptions {
+    /// Side length in pixels of an isometric preview to embed as `PreviewImageData`.
+    /// No preview is written when `None`.
+    pub preview_size: Option<u32>,
+    /// Format version to write. Picked from `Metadata::mc_version` when `None`.
+    pub version: Option<i32>,
+    pub compression: NbtCompression,
+    /// Milliseconds since the epoch to use instead of the current time for timestamps the
+    /// metadata lacks, so writing the same schematic twice gives the same bytes.
+    pub timestamp: Option<i64>,
+}
+
+/// The format version Litematica uses for a Minecraft data version.
+pub fn litematic_version_for(mc_version: i32) -> i32 {
+    if mc_version >= data_version::V1_20_5 { LITEMATIC_V7 } else { LITEMATIC_V6 }
+}
+
 pub fn to_litematic(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    to_litematic_with_options(schematic, &LitematicWriteOptions::default())
+}
+
+pub fn to_litematic_with_options(schematic: &UniversalSchematic, options: &LitematicWriteOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let mut root = NbtCompound::new();
 
+    // Unknown tags from the source file go first so the known ones below take precedence
+    for (key, value) in schematic.metadata.extra_nbt.iter() {
+        root.insert(key, value.to_quartz_nbt());
+    }
+
     // Add Version and SubVersion
-    root.insert("Version", NbtTag::Int(6));
-    root.insert("SubVersion", NbtTag::Int(1));
+    let version = options.version
+        .unwrap_or_else(|| litematic_version_for(schematic.metadata.mc_version.unwrap_or(3700)));
+    if version != LITEMATIC_V6 && version != LITEMATIC_V7 {
+        return Err(format!("Unsupported litematic version {}", version).into());
+    }
+    root.insert("Version", NbtTag::Int(version));
+    root.insert("SubVersion", NbtTag::Int(schematic.metadata.lm_sub_version.unwrap_or(1)));
 
     // Add MinecraftDataVersion
-    root.insert("MinecraftDataVersion", NbtTag::Int(schematic.metadata.mc_version.unwrap_or(3700)));
+    let default_data_version = if version >= LITEMATIC_V7 { data_version::V1_21 } else { 3700 };
+    root.insert("MinecraftDataVersion", NbtTag::Int(schematic.metadata.mc_version.unwrap_or(default_data_version)));
 
     // Add Metadata
-    let metadata = create_metadata(schematic);
+    let mut metadata = create_metadata(schematic, options.timestamp);
+    if let Some(size) = options.preview_size {
+        // Litematica reads the preview as a square of ARGB pixels
+        let render_options = RenderOptions { resolution: size, ..RenderOptions::default() };
+        let preview = render_isometric(schematic, &render_options).pad_to_square(size, [0, 0, 0, 0]);
+        metadata.insert("PreviewImageData", NbtTag::IntArray(preview.to_argb()));
+    }
     root.insert("Metadata", NbtTag::Compound(metadata));
 
     // Add Regions
-    let regions = create_regions(schematic);
+    let regions = create_regions(schematic, version);
     root.insert("Regions", NbtTag::Compound(regions));
 
-    // Compress and return the NBT data
-    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
-    quartz_nbt::io::write_nbt(&mut encoder, None, &root, quartz_nbt::io::Flavor::Uncompressed)?;
-    Ok(encoder.finish()?)
+    compression::write_nbt(&root, options.compression)
 }
 
 pub fn from_litematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
-    let mut decoder = flate2::read::GzDecoder::new(data);
-    let mut decompressed = Vec::new();
-    std::io::Read::read_to_end(&mut decoder, &mut decompressed)?;
+    from_litematic_with_limits(data, &LoadLimits::default())
+}
 
-    let (root, _) = quartz_nbt::io::read_nbt(&mut std::io::Cursor::new(decompressed), quartz_nbt::io::Flavor::Uncompressed)?;
+/// Loads a litematic, failing cleanly on any region, palette or entity list beyond `limits`.
+pub fn from_litematic_with_limits(data: &[u8], limits: &LoadLimits) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    let root = compression::read_nbt_with_limits(data, limits)?;
 
     let mut schematic = UniversalSchematic::new("Unnamed".to_string());
 
     // Parse Metadata
     parse_metadata(&root, &mut schematic)?;
+    match schematic.metadata.lm_version {
+        Some(version) if version > LITEMATIC_V7 => {
+            return Err(format!("Unsupported litematic version {}", version).into());
+        }
+        // v7 files are always from 1.20.5 or later
+        Some(LITEMATIC_V7) if schematic.metadata.mc_version.is_none() => {
+            schematic.metadata.mc_version = Some(data_version::V1_20_5);
+        }
+        _ => {}
+    }
 
     // Parse Regions
-    parse_regions(&root, &mut schematic)?;
+    parse_regions(&root, &mut schematic, limits)?;
 
     Ok(schematic)
 }
 
-fn create_metadata(schematic: &UniversalSchematic) -> NbtCompound {
+fn create_metadata(schematic: &UniversalSchematic, timestamp: Option<i64>) -> NbtCompound {
     let mut metadata = NbtCompound::new();
 
     metadata.insert("Name", NbtTag::String(schematic.metadata.name.clone().unwrap_or_default()));
@@ -81,6 +133,8 @@ fn create_metadata(schematic: &UniversalSchematic) -> NbtCompound {
     let now = if let Some(time) = schematic.metadata.created {
         // Use existing timestamp if available
         time as i64
+    } else if let Some(time) = timestamp {
+        time
     } else {
         // Generate current timestamp based on platform
         #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
@@ -118,19 +172,23 @@ fn create_metadata(schematic: &UniversalSchematic) -> NbtCompound {
 
     metadata.insert("Software", NbtTag::String("UniversalSchematic".to_string()));
 
+    if let Some(preview) = &schematic.metadata.preview_image {
+        metadata.insert("PreviewImageData", NbtTag::IntArray(preview.clone()));
+    }
+
     metadata
 }
-fn create_regions(schematic: &UniversalSchematic) -> NbtCompound {
+fn create_regions(schematic: &UniversalSchematic, version: i32) -> NbtCompound {
     let mut regions = NbtCompound::new();
 
     for (name, region) in &schematic.regions {
-        let mut region_nbt = NbtCompound::new();
+        let mut region_nbt = region.extra_nbt.to_quartz_nbt();
 
-        // Position
+        // Position, relative to the placement origin
         let mut position = NbtCompound::new();
-        position.insert("x", NbtTag::Int(region.position.0));
-        position.insert("y", NbtTag::Int(region.position.1));
-        position.insert("z", NbtTag::Int(region.position.2));
+        position.insert("x", NbtTag::Int(region.position.0 - schematic.origin.0));
+        position.insert("y", NbtTag::Int(region.position.1 - schematic.origin.1));
+        position.insert("z", NbtTag::Int(region.position.2 - schematic.origin.2));
         region_nbt.insert("Position", NbtTag::Compound(position));
 
         // Size
@@ -214,19 +272,20 @@ fn create_regions(schematic: &UniversalSchematic) -> NbtCompound {
         region_nbt.insert("BlockStates", NbtTag::LongArray(packed_states));
 
         // Entities
-        let entities = NbtList::from(region.entities.iter().map(|entity| entity.to_nbt()).collect::<Vec<NbtTag>>());
+        let entities = NbtList::from(region.entities.iter().map(|entity| {
+            NbtTag::Compound(create_entity(entity, region.position, version))
+        }).collect::<Vec<NbtTag>>());
         region_nbt.insert("Entities", NbtTag::List(entities));
 
         // TileEntities
-        let tile_entities = NbtList::from(region.block_entities.values().map(|block_entity| {
-            NbtTag::Compound(block_entity.to_nbt())
+        let tile_entities = NbtList::from(region.sorted_block_entities().into_iter().map(|block_entity| {
+            NbtTag::Compound(create_tile_entity(block_entity, region.position, version))
         }).collect::<Vec<NbtTag>>());
         region_nbt.insert("TileEntities", NbtTag::List(tile_entities));
 
 
-        // PendingBlockTicks and PendingFluidTicks (not fully supported, using empty lists)
-        region_nbt.insert("PendingBlockTicks", NbtTag::List(NbtList::new()));
-        region_nbt.insert("PendingFluidTicks", NbtTag::List(NbtList::new()));
+        region_nbt.insert("PendingBlockTicks", NbtTag::List(create_pending_ticks(&region.pending_block_ticks, "Block", region.position)));
+        region_nbt.insert("PendingFluidTicks", NbtTag::List(create_pending_ticks(&region.pending_fluid_ticks, "Fluid", region.position)));
 
         regions.insert(name, NbtTag::Compound(region_nbt));
     }
@@ -234,6 +293,154 @@ fn create_regions(schematic: &UniversalSchematic) -> NbtCompound {
     regions
 }
 
+/// Litematica stores the block entity's own NBT with `x`, `y`, `z` relative to the region's
+/// minimum corner. Items are written in the layout of the format version.
+fn create_tile_entity(block_entity: &BlockEntity, origin: (i32, i32, i32), version: i32) -> NbtCompound {
+    let mut block_entity = block_entity.clone();
+    datafixer::fix_container_item_counts(&mut block_entity, version >= LITEMATIC_V7);
+    datafixer::fix_container_item_components(&mut block_entity, version >= LITEMATIC_V7);
+    let mut compound = NbtCompound::new();
+    for (key, value) in block_entity.nbt.iter() {
+        if !matches!(key.as_str(), "Id" | "id" | "Pos" | "x" | "y" | "z") {
+            compound.insert(key, value.to_quartz_nbt());
+        }
+    }
+    compound.insert("id", NbtTag::String(block_entity.id.clone()));
+    compound.insert("x", NbtTag::Int(block_entity.position.0 - origin.0));
+    compound.insert("y", NbtTag::Int(block_entity.position.1 - origin.1));
+    compound.insert("z", NbtTag::Int(block_entity.position.2 - origin.2));
+    compound
+}
+
+/// Block entity ids for blocks whose name differs from their block entity's.
+fn block_entity_id_for(block: &str) -> String {
+    let name = block.strip_prefix("minecraft:").unwrap_or(block);
+    let id = if name.ends_with("_hanging_sign") {
+        "hanging_sign"
+    } else if name.ends_with("_sign") {
+        "sign"
+    } else if name.ends_with("_banner") {
+        "banner"
+    } else if name.ends_with("_bed") {
+        "bed"
+    } else if name.ends_with("shulker_box") {
+        "shulker_box"
+    } else if name.ends_with("_skull") || name.ends_with("_head") {
+        "skull"
+    } else if name == "trapped_chest" {
+        "trapped_chest"
+    } else if name.ends_with("_chest") {
+        "chest"
+    } else {
+        name
+    };
+    format!("minecraft:{}", id)
+}
+
+fn parse_tile_entity(compound: &NbtCompound, region: &Region) -> Result<BlockEntity, String> {
+    let relative = (compound.get::<_, i32>("x"), compound.get::<_, i32>("y"), compound.get::<_, i32>("z"));
+    let position = match relative {
+        (Ok(x), Ok(y), Ok(z)) => (region.position.0.wrapping_add(x), region.position.1.wrapping_add(y), region.position.2.wrapping_add(z)),
+        // Files from older versions of this crate stored an absolute `Pos` instead
+        _ => BlockEntity::from_nbt(compound)?.position,
+    };
+    // Litematica leaves out the id of some block entities
+    let id = compound.get::<_, &str>("id").ok()
+        .or_else(|| compound.get::<_, &str>("Id").ok().filter(|id| *id != "unknown"))
+        .map(String::from)
+        .or_else(|| region.get_block(position.0, position.1, position.2).map(|block| block_entity_id_for(&block.name)))
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut block_entity = BlockEntity::new(id, position);
+    for (key, value) in compound.inner() {
+        if !matches!(key.as_str(), "Id" | "id" | "Pos" | "x" | "y" | "z") {
+            block_entity.nbt.insert(key.clone(), NbtValue::from_quartz_nbt(value));
+        }
+    }
+    Ok(block_entity)
+}
+
+/// Litematica stores the entity's full NBT with `Pos` relative to the region's `Position`
+/// corner.
+fn create_entity(entity: &Entity, origin: (i32, i32, i32), version: i32) -> NbtCompound {
+    let mut nbt = entity.nbt.clone();
+    datafixer::fix_entity_item_counts(&mut nbt, version >= LITEMATIC_V7);
+    datafixer::fix_entity_item_components(&mut nbt, version >= LITEMATIC_V7);
+    let mut compound = NbtCompound::new();
+    for (key, value) in Entity::sorted_entries(&nbt) {
+        compound.insert(key, Entity::value_to_nbt_tag(value));
+    }
+    let id = if entity.id.contains(':') { entity.id.clone() } else { format!("minecraft:{}", entity.id) };
+    compound.insert("id", NbtTag::String(id));
+    compound.insert("Pos", NbtTag::List(NbtList::from(vec![
+        NbtTag::Double(entity.position.0 - origin.0 as f64),
+        NbtTag::Double(entity.position.1 - origin.1 as f64),
+        NbtTag::Double(entity.position.2 - origin.2 as f64),
+    ])));
+    compound
+}
+
+fn parse_entity(compound: &NbtCompound, origin: (i32, i32, i32)) -> Option<Entity> {
+    let mut entity = Entity::from_nbt(compound).ok()?;
+    // Files from older versions of this crate nested the NBT and stored absolute positions
+    if compound.contains_key("NBT") {
+        return Some(entity);
+    }
+    entity.position.0 += origin.0 as f64;
+    entity.position.1 += origin.1 as f64;
+    entity.position.2 += origin.2 as f64;
+    for (key, value) in compound.inner() {
+        if !matches!(key.as_str(), "id" | "Id" | "Pos") {
+            entity.nbt.insert(key.clone(), Entity::nbt_tag_to_value(value));
+        }
+    }
+    Some(entity)
+}
+
+/// Writes scheduled ticks with positions relative to the region's minimum corner; `target_key`
+/// is "Block" or "Fluid".
+fn create_pending_ticks(ticks: &[PendingTick], target_key: &str, origin: (i32, i32, i32)) -> NbtList {
+    NbtList::from(ticks.iter().map(|tick| {
+        let mut compound = NbtCompound::new();
+        compound.insert(target_key, NbtTag::String(tick.target.clone()));
+        compound.insert("Priority", NbtTag::Int(tick.priority));
+        compound.insert("SubTick", NbtTag::Long(tick.sub_tick));
+        compound.insert("Time", NbtTag::Int(tick.time));
+        compound.insert("x", NbtTag::Int(tick.position.0 - origin.0));
+        compound.insert("y", NbtTag::Int(tick.position.1 - origin.1));
+        compound.insert("z", NbtTag::Int(tick.position.2 - origin.2));
+        NbtTag::Compound(compound)
+    }).collect::<Vec<NbtTag>>())
+}
+
+fn parse_pending_ticks(region_nbt: &NbtCompound, key: &str, target_key: &str, origin: (i32, i32, i32)) -> Vec<PendingTick> {
+    let Ok(list) = region_nbt.get::<_, &NbtList>(key) else { return Vec::new() };
+    list.iter().filter_map(|tag| {
+        let NbtTag::Compound(compound) = tag else { return None };
+        Some(PendingTick {
+            position: (
+                origin.0.wrapping_add(compound.get::<_, i32>("x").ok()?),
+                origin.1.wrapping_add(compound.get::<_, i32>("y").ok()?),
+                origin.2.wrapping_add(compound.get::<_, i32>("z").ok()?),
+            ),
+            target: compound.get::<_, &str>(target_key).ok()?.to_string(),
+            priority: compound.get::<_, i32>("Priority").unwrap_or(0),
+            sub_tick: compound.get::<_, i64>("SubTick").unwrap_or(0),
+            time: compound.get::<_, i32>("Time").unwrap_or(0),
+        })
+    }).collect()
+}
+
+/// Copies every tag of `compound` not in `known`.
+fn unknown_tags(compound: &NbtCompound, known: &[&str]) -> NbtMap {
+    let mut extra = NbtMap::new();
+    for (key, value) in compound.inner() {
+        if !known.contains(&key.as_str()) {
+            extra.insert(key.clone(), NbtValue::from_quartz_nbt(value));
+        }
+    }
+    extra
+}
+
 
 fn parse_metadata(root: &NbtCompound, schematic: &mut UniversalSchematic) -> Result<(), Box<dyn std::error::Error>> {
     let metadata = root.get::<_, &NbtCompound>("Metadata")?;
@@ -243,15 +450,22 @@ fn parse_metadata(root: &NbtCompound, schematic: &mut UniversalSchematic) -> Res
     schematic.metadata.author = metadata.get::<_, &str>("Author").ok().map(String::from);
     schematic.metadata.created = metadata.get::<_, i64>("TimeCreated").ok().map(|t| t as u64);
     schematic.metadata.modified = metadata.get::<_, i64>("TimeModified").ok().map(|t| t as u64);
+    schematic.metadata.preview_image = metadata.get::<_, &[i32]>("PreviewImageData").ok().map(<[i32]>::to_vec);
+
+    schematic.metadata.lm_version = root.get::<_, i32>("Version").ok();
+    schematic.metadata.lm_sub_version = root.get::<_, i32>("SubVersion").ok();
+    schematic.metadata.mc_version = root.get::<_, i32>("MinecraftDataVersion").ok();
+    schematic.metadata.extra_nbt = unknown_tags(root, &["Version", "SubVersion", "MinecraftDataVersion", "Metadata", "Regions"]);
 
     // We don't need to parse EnclosingSize, TotalVolume, TotalBlocks as they will be recalculated
 
     Ok(())
 }
 
-fn parse_regions(root: &NbtCompound, schematic: &mut UniversalSchematic) -> Result<(), Box<dyn std::error::Error>> {
+fn parse_regions(root: &NbtCompound, schematic: &mut UniversalSchematic, limits: &LoadLimits) -> Result<(), Box<dyn std::error::Error>> {
     let regions = root.get::<_, &NbtCompound>("Regions")?;
     let mut loop_count = 0;
+    let mut entity_count = 0;
     for (name, region_tag) in regions.inner() {
         //if it's the first region we want to override the default region name
         if loop_count == 0 {
@@ -275,27 +489,46 @@ fn parse_regions(root: &NbtCompound, schematic: &mut UniversalSchematic) -> Resu
                 size.get::<_, i32>("z")?,
             );
 
-            let mut region = Region::new(name.to_string(), position, size);
+            let origin = position;
+            let volume = limits.check_region(position, size)?;
 
             // Parse BlockStatePalette
             let palette = region_nbt.get::<_, &NbtList>("BlockStatePalette")?;
-            region.palette = palette.iter().filter_map(|tag| {
+            limits.check_palette(palette.len())?;
+            let palette: Vec<BlockState> = palette.iter().filter_map(|tag| {
                 if let NbtTag::Compound(compound) = tag {
                     BlockState::from_nbt(compound).ok()
                 } else {
                     None
                 }
             }).collect();
+            if palette.is_empty() {
+                return Err(format!("Region '{}' has an empty palette", name).into());
+            }
 
-            // Parse BlockStates
+            // Parse BlockStates, checking the array covers the region before allocating it
             let block_states = region_nbt.get::<_, &[i64]>("BlockStates")?;
-            // region.unpack_block_states(block_states);
+            let needed = volume.saturating_mul(Region::bits_per_block(palette.len()) as u64).div_ceil(64);
+            if (block_states.len() as u64) < needed {
+                return Err(format!(
+                    "Region '{}' has {} packed block longs, {} needed for its size",
+                    name, block_states.len(), needed
+                ).into());
+            }
+            let mut region = Region::new(name.to_string(), position, size);
+            region.palette = palette;
             region.blocks = region.unpack_block_states(block_states);
+            if let Some(&index) = region.blocks.iter().find(|&&index| index >= region.palette.len()) {
+                return Err(format!("Region '{}' uses palette index {} of {}", name, index, region.palette.len()).into());
+            }
+
             // Parse Entities
             if let Ok(entities_list) = region_nbt.get::<_, &NbtList>("Entities") {
+                entity_count += entities_list.len();
+                limits.check_entities(entity_count)?;
                 region.entities = entities_list.iter().filter_map(|tag| {
                     if let NbtTag::Compound(compound) = tag {
-                        Entity::from_nbt(compound).ok()
+                        parse_entity(compound, origin)
                     } else {
                         None
                     }
@@ -304,15 +537,23 @@ fn parse_regions(root: &NbtCompound, schematic: &mut UniversalSchematic) -> Resu
 
             // Parse TileEntities
             if let Ok(tile_entities_list) = region_nbt.get::<_, &NbtList>("TileEntities") {
+                entity_count += tile_entities_list.len();
+                limits.check_entities(entity_count)?;
                 for tag in tile_entities_list.iter() {
                     if let NbtTag::Compound(compound) = tag {
-                        if let block_entity = BlockEntity::from_nbt(compound) {
-                            region.block_entities.insert(block_entity.position, block_entity);
-                        }
+                        let block_entity = parse_tile_entity(compound, &region)?;
+                        region.block_entities.insert(block_entity.position, block_entity);
                     }
                 }
             }
 
+            region.pending_block_ticks = parse_pending_ticks(region_nbt, "PendingBlockTicks", "Block", region.position);
+            region.pending_fluid_ticks = parse_pending_ticks(region_nbt, "PendingFluidTicks", "Fluid", region.position);
+            region.extra_nbt = unknown_tags(region_nbt, &[
+                "Position", "Size", "BlockStatePalette", "BlockStates", "Entities", "TileEntities",
+                "PendingBlockTicks", "PendingFluidTicks",
+            ]);
+
             schematic.add_region(region);
         }
     }
@@ -336,7 +577,7 @@ mod tests {
         schematic.metadata.created = Some(1000);
         schematic.metadata.modified = Some(2000);
 
-        let metadata = create_metadata(&schematic);
+        let metadata = create_metadata(&schematic, None);
 
         assert_eq!(metadata.get::<_, &str>("Name").unwrap(), "Test Schematic");
         assert_eq!(metadata.get::<_, &str>("Author").unwrap(), "Test Author");
@@ -350,6 +591,25 @@ mod tests {
         assert_eq!(metadata.get::<_, &str>("Software").unwrap(), "UniversalSchematic");
     }
 
+    #[test]
+    fn test_preview_image_embedded() {
+        let mut schematic = UniversalSchematic::new("Preview".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:dirt".to_string()));
+
+        let options = LitematicWriteOptions { preview_size: Some(32), ..LitematicWriteOptions::default() };
+        let data = to_litematic_with_options(&schematic, &options).unwrap();
+        let root = compression::read_nbt(&data).unwrap();
+        let metadata = root.get::<_, &NbtCompound>("Metadata").unwrap();
+        let preview = metadata.get::<_, &[i32]>("PreviewImageData").unwrap();
+        assert_eq!(preview.len(), 32 * 32);
+        assert!(preview.iter().any(|&argb| (argb as u32) >> 24 == 255));
+
+        // Plain to_litematic leaves the preview out
+        assert!(from_litematic(&to_litematic(&schematic).unwrap()).is_ok());
+        assert!(!create_metadata(&schematic, None).contains_key("PreviewImageData"));
+    }
+
     #[test]
     fn test_create_regions() {
         let mut schematic = UniversalSchematic::new("Test Schematic".to_string());
@@ -369,7 +629,7 @@ mod tests {
 
         schematic.add_region(region);
 
-        let regions = create_regions(&schematic);
+        let regions = create_regions(&schematic, LITEMATIC_V6);
 
         assert!(regions.contains_key("TestRegion"));
         let region_nbt = regions.get::<_, &NbtCompound>("TestRegion").unwrap();
@@ -429,8 +689,8 @@ mod tests {
         ]);
         region.insert("BlockStatePalette", NbtTag::List(palette));
 
-        // 2x2x2 region with 2 stone blocks and 6 air blocks
-        region.insert("BlockStates", NbtTag::LongArray(vec![0b10000001]));
+        // 2x2x2 region with 2 stone blocks and 6 air blocks, at 2 bits per block
+        region.insert("BlockStates", NbtTag::LongArray(vec![0b01000001]));
 
         regions.insert("TestRegion", NbtTag::Compound(region));
         root.insert("Regions", NbtTag::Compound(regions));
@@ -438,7 +698,7 @@ mod tests {
         println!("{:?}", root);
 
         let mut schematic = UniversalSchematic::new("Test Schematic".to_string());
-        parse_regions(&root, &mut schematic).unwrap();
+        parse_regions(&root, &mut schematic, &LoadLimits::default()).unwrap();
 
         assert_eq!(schematic.regions.len(), 1);
         assert!(schematic.regions.contains_key("TestRegion"));
@@ -529,4 +789,169 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_roundtrip_keeps_ticks_preview_and_unknown_tags() {
+        let mut schematic = UniversalSchematic::new("Ticks".to_string());
+        schematic.metadata.mc_version = Some(3953);
+        schematic.metadata.lm_sub_version = Some(2);
+        schematic.metadata.preview_image = Some(vec![-1, 0, 0x7f00ff00, 42]);
+        schematic.metadata.extra_nbt.insert("ToolVersion".to_string(), NbtValue::String("1.2.3".to_string()));
+
+        let mut region = Region::new("Clock".to_string(), (10, 5, -3), (2, 1, 1));
+        region.set_block(10, 5, -3, BlockState::new("minecraft:repeater".to_string()));
+        region.set_block(11, 5, -3, BlockState::new("minecraft:water".to_string()));
+        region.pending_block_ticks.push(PendingTick {
+            position: (10, 5, -3), target: "minecraft:repeater".to_string(), priority: -1, sub_tick: 7, time: 2,
+        });
+        region.pending_fluid_ticks.push(PendingTick {
+            position: (11, 5, -3), target: "minecraft:water".to_string(), priority: 0, sub_tick: 8, time: 5,
+        });
+        region.extra_nbt.insert("CustomData".to_string(), NbtValue::Int(99));
+        schematic.add_region(region);
+
+        let data = to_litematic(&schematic).unwrap();
+        let root = compression::read_nbt(&data).unwrap();
+        let region_nbt = root.get::<_, &NbtCompound>("Regions").unwrap().get::<_, &NbtCompound>("Clock").unwrap();
+        let ticks = region_nbt.get::<_, &NbtList>("PendingFluidTicks").unwrap();
+        let NbtTag::Compound(tick) = &ticks[0] else { panic!("tick is not a compound") };
+        assert_eq!((tick.get::<_, i32>("x").unwrap(), tick.get::<_, &str>("Fluid").unwrap()), (1, "minecraft:water"));
+
+        let loaded = from_litematic(&data).unwrap();
+        assert_eq!(loaded.metadata.mc_version, Some(3953));
+        assert_eq!(loaded.metadata.lm_version, Some(LITEMATIC_V7));
+        assert_eq!(loaded.metadata.lm_sub_version, Some(2));
+        assert_eq!(loaded.metadata.preview_image, schematic.metadata.preview_image);
+        assert_eq!(loaded.metadata.extra_nbt, schematic.metadata.extra_nbt);
+        let original = &schematic.regions["Clock"];
+        let region = &loaded.regions["Clock"];
+        assert_eq!(region.pending_block_ticks, original.pending_block_ticks);
+        assert_eq!(region.pending_fluid_ticks, original.pending_fluid_ticks);
+        assert_eq!(region.extra_nbt, original.extra_nbt);
+    }
+
+    #[test]
+    fn test_hostile_files_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Hostile".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(3, 1, 2, BlockState::new("minecraft:glass".to_string()));
+        let litematic = to_litematic(&schematic).unwrap();
+        assert!(from_litematic_with_limits(&litematic, &LoadLimits::untrusted()).is_ok());
+        assert!(from_litematic_with_limits(&litematic, &LoadLimits { max_volume: 8, ..LoadLimits::default() }).is_err());
+        assert!(from_litematic_with_limits(&litematic, &LoadLimits { max_entities: 0, ..LoadLimits::default() }).is_ok());
+
+        let tampered = |size: (i32, i32, i32), block_states: Option<Vec<i64>>| {
+            let mut root = compression::read_nbt(&litematic).unwrap();
+            let regions = root.get_mut::<_, &mut NbtCompound>("Regions").unwrap();
+            let Some(NbtTag::Compound(region)) = regions.inner_mut().values_mut().next() else { panic!("Expected a region") };
+            let mut size_nbt = NbtCompound::new();
+            size_nbt.insert("x", NbtTag::Int(size.0));
+            size_nbt.insert("y", NbtTag::Int(size.1));
+            size_nbt.insert("z", NbtTag::Int(size.2));
+            region.insert("Size", NbtTag::Compound(size_nbt));
+            if let Some(block_states) = block_states {
+                region.insert("BlockStates", NbtTag::LongArray(block_states));
+            }
+            from_litematic_with_limits(&compression::write_nbt(&root, NbtCompression::None).unwrap(), &LoadLimits::untrusted())
+        };
+        assert!(tampered((30_000, 30_000, 30_000), None).is_err());
+        assert!(tampered((0, 0, 0), Some(Vec::new())).is_err());
+        assert!(tampered((0, 5, 5), Some(Vec::new())).is_err());
+        assert!(tampered((8, 8, 8), Some(vec![0])).is_err());
+        assert!(tampered((4, 2, 3), None).is_ok());
+
+        for pos in [vec![], vec![1]] {
+            let mut root = compression::read_nbt(&litematic).unwrap();
+            let regions = root.get_mut::<_, &mut NbtCompound>("Regions").unwrap();
+            let Some(NbtTag::Compound(region)) = regions.inner_mut().values_mut().next() else { panic!("Expected a region") };
+            let mut tile_entity = NbtCompound::new();
+            tile_entity.insert("Pos", NbtTag::IntArray(pos));
+            region.insert("TileEntities", NbtTag::List(NbtList::from(vec![NbtTag::Compound(tile_entity)])));
+            let data = compression::write_nbt(&root, NbtCompression::None).unwrap();
+            assert!(from_litematic_with_limits(&data, &LoadLimits::untrusted()).is_err());
+        }
+
+        let bomb = crate::test_fixtures::gzip_bomb(LoadLimits::untrusted().max_decompressed_bytes + 1);
+        assert!(!is_litematic_with_limits(&bomb, &LoadLimits::untrusted()));
+        let error = from_litematic_with_limits(&bomb, &LoadLimits::untrusted()).unwrap_err();
+        assert!(error.to_string().contains("exceeds the limit"), "{}", error);
+    }
+
+    fn read_root(data: &[u8]) -> NbtCompound {
+        compression::read_nbt(data).unwrap()
+    }
+
+    #[test]
+    fn test_v6_and_v7_layouts() {
+        use crate::entity::NbtValue as EntityNbtValue;
+
+        let mut schematic = UniversalSchematic::new("Versions".to_string());
+        schematic.metadata.mc_version = Some(data_version::V1_21);
+        let mut region = Region::new("Main".to_string(), (4, 2, -6), (2, 1, 1));
+        region.set_block(5, 2, -6, BlockState::new("minecraft:chest".to_string()));
+        let mut chest = BlockEntity::new("minecraft:chest".to_string(), (5, 2, -6));
+        let mut item = crate::utils::NbtMap::new();
+        item.insert("id".to_string(), NbtValue::String("minecraft:stone".to_string()));
+        item.insert("Count".to_string(), NbtValue::Byte(3));
+        let mut tag = crate::utils::NbtMap::new();
+        tag.insert("Damage".to_string(), NbtValue::Int(4));
+        item.insert("tag".to_string(), NbtValue::Compound(tag));
+        chest.nbt.insert("Items".to_string(), NbtValue::List(vec![NbtValue::Compound(item)]));
+        region.add_block_entity(chest);
+        let mut frame = Entity::new("minecraft:item_frame".to_string(), (4.5, 2.5, -5.5));
+        frame.nbt.insert("Facing".to_string(), EntityNbtValue::Byte(1));
+        region.add_entity(frame);
+        schematic.add_region(region);
+
+        let data = to_litematic(&schematic).unwrap();
+        let root = read_root(&data);
+        assert_eq!(root.get::<_, i32>("Version").unwrap(), LITEMATIC_V7);
+        let region_nbt = root.get::<_, &NbtCompound>("Regions").unwrap().get::<_, &NbtCompound>("Main").unwrap();
+        let NbtTag::Compound(tile) = &region_nbt.get::<_, &NbtList>("TileEntities").unwrap()[0] else { panic!() };
+        assert_eq!((tile.get::<_, i32>("x").unwrap(), tile.get::<_, i32>("z").unwrap()), (1, 0));
+        assert_eq!(tile.get::<_, &str>("id").unwrap(), "minecraft:chest");
+        let NbtTag::Compound(item) = &tile.get::<_, &NbtList>("Items").unwrap()[0] else { panic!() };
+        assert_eq!(item.get::<_, i32>("count").unwrap(), 3);
+        assert!(!item.contains_key("tag"));
+        assert_eq!(item.get::<_, &NbtCompound>("components").unwrap().get::<_, i32>("minecraft:damage").unwrap(), 4);
+        let NbtTag::Compound(entity) = &region_nbt.get::<_, &NbtList>("Entities").unwrap()[0] else { panic!() };
+        assert_eq!(entity.get::<_, &NbtList>("Pos").unwrap().get::<f64>(0).unwrap(), 0.5);
+        assert_eq!(entity.get::<_, i8>("Facing").unwrap(), 1);
+
+        let loaded = from_litematic(&data).unwrap();
+        let region = &loaded.regions["Main"];
+        let chest = &region.block_entities[&(5, 2, -6)];
+        assert_eq!(chest.id, "minecraft:chest");
+        assert_eq!(region.entities[0].position, (4.5, 2.5, -5.5));
+        assert_eq!(region.entities[0].nbt.get("Facing"), Some(&EntityNbtValue::Byte(1)));
+
+        let options = LitematicWriteOptions { version: Some(LITEMATIC_V6), ..LitematicWriteOptions::default() };
+        let root = read_root(&to_litematic_with_options(&loaded, &options).unwrap());
+        assert_eq!(root.get::<_, i32>("Version").unwrap(), LITEMATIC_V6);
+        let region_nbt = root.get::<_, &NbtCompound>("Regions").unwrap().get::<_, &NbtCompound>("Main").unwrap();
+        let NbtTag::Compound(tile) = &region_nbt.get::<_, &NbtList>("TileEntities").unwrap()[0] else { panic!() };
+        let NbtTag::Compound(item) = &tile.get::<_, &NbtList>("Items").unwrap()[0] else { panic!() };
+        assert_eq!(item.get::<_, i8>("Count").unwrap(), 3);
+        assert_eq!(item.get::<_, &NbtCompound>("tag").unwrap().get::<_, i32>("Damage").unwrap(), 4);
+
+        let options = LitematicWriteOptions { version: Some(8), ..LitematicWriteOptions::default() };
+        assert!(to_litematic_with_options(&loaded, &options).is_err());
+    }
+
+    #[test]
+    fn test_sample_tile_entities() {
+        // Litematica leaves the id out of sign tile entities
+        let schematic = from_litematic(&std::fs::read("tests/samples/trencher.litematic").unwrap()).unwrap();
+        let signs: Vec<_> = schematic.regions.values()
+            .flat_map(|region| region.block_entities.values())
+            .filter(|block_entity| block_entity.nbt.get("Text1").is_some())
+            .collect();
+        assert!(!signs.is_empty());
+        assert!(signs.iter().all(|sign| sign.id == "minecraft:sign"));
+        for region in schematic.regions.values() {
+            for (position, block_entity) in &region.block_entities {
+                assert_eq!(position, &block_entity.position);
+                assert!(region.get_block(position.0, position.1, position.2).is_some());
+            }
+        }
+    }
+}
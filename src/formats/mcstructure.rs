@@ -0,0 +1,443 @@
+//! Bedrock Edition `.mcstructure` files, as written by structure blocks.
+//!
+//! These are uncompressed little-endian NBT with two block layers: layer 0 holds the blocks and
+//! layer 1 holds water for waterlogged blocks. Block states and block entities are translated
+//! with [`bedrock_blocks`](super::bedrock_blocks).
+
+use std::collections::HashMap;
+use quartz_nbt::{NbtCompound, NbtList, NbtTag};
+use crate::{BlockState, UniversalSchematic};
+use crate::block_entity::BlockEntity;
+use crate::entity::Entity;
+use crate::formats::limits::LoadLimits;
+use crate::region::Region;
+use crate::utils::{read_le_nbt, write_le_nbt, NbtMap};
+use super::bedrock_blocks::{
+    bedrock_block_entity_id_to_java, bedrock_to_java, java_block_entity_id_to_bedrock, java_to_bedrock,
+    BedrockBlockState, BedrockStateValue, BEDROCK_BLOCK_VERSION,
+};
+
+/// Index used by `block_indices` for "no block here".
+const NO_BLOCK: i32 = -1;
+
+pub fn is_mcstructure(data: &[u8]) -> bool {
+    match read_le_nbt(data) {
+        Ok((root, _)) => {
+            root.get::<_, i32>("format_version").is_ok()
+                && root.get::<_, &NbtList>("size").is_ok()
+                && root.get::<_, &NbtCompound>("structure").is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn to_mcstructure(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if schematic.regions.is_empty() {
+        return Err("Cannot export a schematic without regions".into());
+    }
+
+    let bounding_box = schematic.get_bounding_box();
+    let min = bounding_box.min;
+    let (width, height, length) = bounding_box.get_dimensions();
+    // Regions far apart can span more cells than fit in memory
+    let too_large = || format!("Bounding box of {}x{}x{} blocks is too large to export", width, height, length);
+    let volume = [width, height, length].iter()
+        .try_fold(1usize, |volume, &d| volume.checked_mul(usize::try_from(d).ok()?))
+        .ok_or_else(too_large)?;
+
+    let mut palette: Vec<BedrockBlockState> = Vec::new();
+    let mut palette_lookup: HashMap<BedrockBlockState, i32> = HashMap::new();
+    let mut add_to_palette = |block: BedrockBlockState| -> i32 {
+        *palette_lookup.entry(block.clone()).or_insert_with(|| {
+            palette.push(block);
+            palette.len() as i32 - 1
+        })
+    };
+    // Each Java state is translated once
+    let mut translated: HashMap<BlockState, (i32, bool)> = HashMap::new();
+    let mut water_index = None;
+
+    let mut layer0 = Vec::new();
+    let mut layer1 = Vec::new();
+    layer0.try_reserve_exact(volume).map_err(|_| too_large())?;
+    layer1.try_reserve_exact(volume).map_err(|_| too_large())?;
+    layer0.resize(volume, NO_BLOCK);
+    layer1.resize(volume, NO_BLOCK);
+    for x in 0..width {
+        for y in 0..height {
+            for z in 0..length {
+                let block = match schematic.get_block(min.0 + x, min.1 + y, min.2 + z) {
+                    Some(block) if block.name != "minecraft:structure_void" => block,
+                    _ => continue,
+                };
+                let (id, waterlogged) = *translated.entry(block.clone()).or_insert_with(|| {
+                    let (bedrock, waterlogged) = java_to_bedrock(block);
+                    (add_to_palette(bedrock), waterlogged)
+                });
+                let index = mcstructure_index(x, y, z, height, length);
+                layer0[index] = id;
+                if waterlogged {
+                    layer1[index] = *water_index.get_or_insert_with(|| {
+                        add_to_palette(BedrockBlockState::new("minecraft:water")
+                            .with_state("liquid_depth", BedrockStateValue::Int(0)))
+                    });
+                }
+            }
+        }
+    }
+
+    let block_palette: Vec<NbtTag> = palette.iter().map(|block| {
+        let mut entry = NbtCompound::new();
+        entry.insert("name", block.name.clone());
+        entry.insert("states", block.states_to_nbt());
+        entry.insert("version", NbtTag::Int(BEDROCK_BLOCK_VERSION));
+        NbtTag::Compound(entry)
+    }).collect();
+
+    let mut block_position_data = NbtCompound::new();
+    for block_entity in schematic.get_block_entities_as_list() {
+        let (x, y, z) = block_entity.position;
+        if !bounding_box.contains((x, y, z)) {
+            continue;
+        }
+        let index = mcstructure_index(x - min.0, y - min.1, z - min.2, height, length);
+        let mut data = NbtCompound::new();
+        data.insert("block_entity_data", block_entity_to_bedrock(&block_entity));
+        block_position_data.insert(index.to_string(), data);
+    }
+
+    let entities: Vec<NbtTag> = schematic.get_entities_as_list().iter()
+        .map(entity_to_bedrock)
+        .collect();
+
+    let mut default_palette = NbtCompound::new();
+    default_palette.insert("block_palette", NbtList::from(block_palette));
+    default_palette.insert("block_position_data", block_position_data);
+    let mut palettes = NbtCompound::new();
+    palettes.insert("default", default_palette);
+
+    let mut structure = NbtCompound::new();
+    structure.insert("block_indices", NbtList::from(vec![int_list(&layer0), int_list(&layer1)]));
+    structure.insert("entities", NbtList::from(entities));
+    structure.insert("palette", palettes);
+
+    let mut root = NbtCompound::new();
+    root.insert("format_version", NbtTag::Int(1));
+    root.insert("size", int_list(&[width, height, length]));
+    root.insert("structure", structure);
+    root.insert("structure_world_origin", int_list(&[min.0, min.1, min.2]));
+
+    Ok(write_le_nbt(&root, ""))
+}
+
+pub fn from_mcstructure(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    let (root, _) = read_le_nbt(data)?;
+
+    let size = read_int_list(root.get::<_, &NbtList>("size")?);
+    if size.len() != 3 || size.iter().any(|&s| s < 0) {
+        return Err(format!("Invalid structure size {:?}", size).into());
+    }
+    let (width, height, length) = (size[0], size[1], size[2]);
+    let origin = root.get::<_, &NbtList>("structure_world_origin")
+        .map(read_int_list)
+        .unwrap_or_default();
+    let origin = if origin.len() == 3 { (origin[0], origin[1], origin[2]) } else { (0, 0, 0) };
+
+    let structure = root.get::<_, &NbtCompound>("structure")?;
+    let layers: Vec<Vec<i32>> = structure.get::<_, &NbtList>("block_indices")?
+        .iter()
+        .map(|layer| match layer {
+            NbtTag::List(list) => read_int_list(list),
+            _ => Vec::new(),
+        })
+        .collect();
+    let volume = LoadLimits::default().check_region((0, 0, 0), (width, height, length))? as usize;
+    let layer0 = layers.first().ok_or("Structure has no block layers")?;
+    if layer0.len() != volume {
+        return Err(format!("Block layer has {} entries, expected {}", layer0.len(), volume).into());
+    }
+    let layer1 = layers.get(1).filter(|layer| layer.len() == volume);
+
+    let default_palette = structure.get::<_, &NbtCompound>("palette")?
+        .get::<_, &NbtCompound>("default")?;
+    let bedrock_palette: Vec<BedrockBlockState> = default_palette.get::<_, &NbtList>("block_palette")?
+        .iter()
+        .map(|entry| match entry {
+            NbtTag::Compound(entry) => {
+                let name = entry.get::<_, &str>("name").unwrap_or("minecraft:air");
+                BedrockBlockState::from_nbt(name, entry.get::<_, &NbtCompound>("states").ok())
+            }
+            _ => BedrockBlockState::new("minecraft:air"),
+        })
+        .collect();
+    let is_water = |index: i32| {
+        bedrock_palette.get(index as usize)
+            .map(|block| block.name == "minecraft:water" || block.name == "minecraft:flowing_water")
+            .unwrap_or(false)
+    };
+
+    let mut region = Region::new("Main".to_string(), (0, 0, 0), (width, height, length));
+    // Region palette ids for each (bedrock palette index, waterlogged) pair
+    let mut translated: HashMap<(i32, bool), usize> = HashMap::new();
+    for x in 0..width {
+        for y in 0..height {
+            for z in 0..length {
+                let index = mcstructure_index(x, y, z, height, length);
+                let id = layer0[index];
+                if id < 0 || id as usize >= bedrock_palette.len() {
+                    continue;
+                }
+                let waterlogged = layer1.map(|layer| is_water(layer[index])).unwrap_or(false);
+                let palette_id = *translated.entry((id, waterlogged)).or_insert_with(|| {
+                    let block = bedrock_to_java(&bedrock_palette[id as usize], waterlogged);
+                    match region.palette.iter().position(|existing| *existing == block) {
+                        Some(existing) => existing,
+                        None => {
+                            region.palette.push(block);
+                            region.palette.len() - 1
+                        }
+                    }
+                });
+                let region_index = (x + z * width + y * width * length) as usize;
+                region.blocks[region_index] = palette_id;
+            }
+        }
+    }
+
+    if let Ok(position_data) = default_palette.get::<_, &NbtCompound>("block_position_data") {
+        for (key, value) in position_data.inner() {
+            let (index, data) = match (key.parse::<usize>(), value) {
+                (Ok(index), NbtTag::Compound(data)) if index < volume => (index, data),
+                _ => continue,
+            };
+            if let Ok(block_entity_data) = data.get::<_, &NbtCompound>("block_entity_data") {
+                let position = mcstructure_coords(index, height, length);
+                region.add_block_entity(block_entity_from_bedrock(block_entity_data, position));
+            }
+        }
+    }
+
+    if let Ok(entities) = structure.get::<_, &NbtList>("entities") {
+        for entity in entities.iter() {
+            if let NbtTag::Compound(entity) = entity {
+                if let Some(entity) = entity_from_bedrock(entity, origin) {
+                    region.add_entity(entity);
+                }
+            }
+        }
+    }
+
+    let mut schematic = UniversalSchematic::new("Unnamed".to_string());
+    schematic.add_region(region);
+    Ok(schematic)
+}
+
+// `.mcstructure` orders blocks with z varying fastest, then y, then x
+/// Index of a local position in the z-fastest block layers. Coordinates are non-negative.
+fn mcstructure_index(x: i32, y: i32, z: i32, height: i32, length: i32) -> usize {
+    (x as usize * height as usize + y as usize) * length as usize + z as usize
+}
+
+fn mcstructure_coords(index: usize, height: i32, length: i32) -> (i32, i32, i32) {
+    let (height, length) = (height as usize, length as usize);
+    ((index / (height * length)) as i32, ((index / length) % height) as i32, (index % length) as i32)
+}
+
+fn int_list(values: &[i32]) -> NbtList {
+    NbtList::from(values.iter().map(|&v| NbtTag::Int(v)).collect::<Vec<_>>())
+}
+
+fn read_int_list(list: &NbtList) -> Vec<i32> {
+    list.iter()
+        .filter_map(|tag| match tag {
+            NbtTag::Int(v) => Some(*v),
+            NbtTag::Short(v) => Some(*v as i32),
+            NbtTag::Byte(v) => Some(*v as i32),
+            _ => None,
+        })
+        .collect()
+}
+
+fn block_entity_to_bedrock(block_entity: &BlockEntity) -> NbtCompound {
+    let mut nbt = block_entity.nbt.to_quartz_nbt();
+    for key in ["Id", "id", "Pos", "x", "y", "z"] {
+        nbt.inner_mut().shift_remove(key);
+    }
+    convert_items(&mut nbt, "id", "Name");
+    if let Ok(NbtTag::List(items)) = nbt.get::<_, &NbtTag>("Items").cloned() {
+        let items: Vec<NbtTag> = items.iter().cloned().map(|item| match item {
+            NbtTag::Compound(mut item) => {
+                if !item.contains_key("Damage") {
+                    item.insert("Damage", NbtTag::Short(0));
+                }
+                NbtTag::Compound(item)
+            }
+            other => other,
+        }).collect();
+        nbt.insert("Items", NbtList::from(items));
+    }
+    let (x, y, z) = block_entity.position;
+    nbt.insert("id", java_block_entity_id_to_bedrock(&block_entity.id));
+    nbt.insert("x", NbtTag::Int(x));
+    nbt.insert("y", NbtTag::Int(y));
+    nbt.insert("z", NbtTag::Int(z));
+    nbt.insert("isMovable", NbtTag::Byte(1));
+    nbt
+}
+
+fn block_entity_from_bedrock(data: &NbtCompound, position: (i32, i32, i32)) -> BlockEntity {
+    let mut nbt = data.clone();
+    let id = nbt.get::<_, &str>("id").map(bedrock_block_entity_id_to_java).unwrap_or_else(|_| "unknown".to_string());
+    for key in ["id", "x", "y", "z", "isMovable"] {
+        nbt.inner_mut().shift_remove(key);
+    }
+    convert_items(&mut nbt, "Name", "id");
+    let mut block_entity = BlockEntity::new(id, position);
+    block_entity.nbt = NbtMap::from_quartz_nbt(&nbt);
+    block_entity
+}
+
+/// Renames the item id key of every entry in an `Items` list.
+fn convert_items(nbt: &mut NbtCompound, from: &str, to: &str) {
+    if let Ok(items) = nbt.get_mut::<_, &mut NbtList>("Items") {
+        for item in items.iter_mut() {
+            if let NbtTag::Compound(item) = item {
+                if let Some(id) = item.inner_mut().shift_remove(from) {
+                    item.insert(to, id);
+                }
+            }
+        }
+    }
+}
+
+fn entity_to_bedrock(entity: &Entity) -> NbtTag {
+    let mut nbt = NbtCompound::new();
+    for (key, value) in &entity.nbt {
+        nbt.insert(key, Entity::value_to_nbt_tag(value));
+    }
+    nbt.insert("identifier", entity.id.clone());
+    nbt.insert("Pos", NbtList::from(vec![
+        NbtTag::Float(entity.position.0 as f32),
+        NbtTag::Float(entity.position.1 as f32),
+        NbtTag::Float(entity.position.2 as f32),
+    ]));
+    NbtTag::Compound(nbt)
+}
+
+fn entity_from_bedrock(nbt: &NbtCompound, origin: (i32, i32, i32)) -> Option<Entity> {
+    let id = nbt.get::<_, &str>("identifier").ok()?;
+    let pos = nbt.get::<_, &NbtList>("Pos").ok()?;
+    let coordinate = |i: usize| match pos.as_ref().get(i) {
+        Some(NbtTag::Float(v)) => Some(*v as f64),
+        Some(NbtTag::Double(v)) => Some(*v),
+        _ => None,
+    };
+    let position = (
+        coordinate(0)? - origin.0 as f64,
+        coordinate(1)? - origin.1 as f64,
+        coordinate(2)? - origin.2 as f64,
+    );
+    let mut entity = Entity::new(id.to_string(), position);
+    for (key, value) in nbt.inner() {
+        // UniqueID would clash with the original entity if the structure is placed twice
+        if !matches!(key.as_str(), "identifier" | "Pos" | "UniqueID") {
+            entity.nbt.insert(key.clone(), Entity::nbt_tag_to_value(value));
+        }
+    }
+    Some(entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemStack;
+
+    #[test]
+    fn test_mcstructure_roundtrip() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:snow_block".to_string()));
+        schematic.set_block(0, 1, 2, BlockState::new("minecraft:oak_stairs".to_string())
+            .with_property("facing".to_string(), "north".to_string())
+            .with_property("half".to_string(), "bottom".to_string())
+            .with_property("waterlogged".to_string(), "true".to_string()));
+        schematic.set_block(1, 1, 1, BlockState::new("minecraft:chest".to_string())
+            .with_property("facing".to_string(), "west".to_string()));
+        schematic.set_block_entity(
+            crate::block_position::BlockPosition { x: 1, y: 1, z: 1 },
+            BlockEntity::create_chest((1, 1, 1), vec![ItemStack::new("minecraft:diamond", 5).with_slot(0)]),
+        );
+        schematic.add_entity(Entity::new("minecraft:pig".to_string(), (0.5, 1.0, 0.5)));
+
+        let bytes = to_mcstructure(&schematic).unwrap();
+        assert!(is_mcstructure(&bytes));
+        let loaded = from_mcstructure(&bytes).unwrap();
+
+        assert_eq!(loaded.get_dimensions(), schematic.get_dimensions());
+        for (position, block) in schematic.iter_blocks() {
+            let loaded_block = loaded.get_block(position.x, position.y, position.z).unwrap();
+            assert_eq!(loaded_block, block, "block at {:?}", position);
+        }
+
+        let chest = loaded.get_block_entity(crate::block_position::BlockPosition { x: 1, y: 1, z: 1 }).unwrap();
+        assert_eq!(chest.id, "minecraft:chest");
+        let items = match chest.nbt.get("Items") {
+            Some(crate::utils::NbtValue::List(items)) => items,
+            other => panic!("unexpected items {:?}", other),
+        };
+        let item = items[0].as_compound().unwrap();
+        assert_eq!(item.get("id").and_then(|v| v.as_string()).map(|s| s.as_str()), Some("minecraft:diamond"));
+
+        let entities = loaded.get_entities_as_list();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].id, "minecraft:pig");
+        assert_eq!(entities[0].position, (0.5, 1.0, 0.5));
+    }
+
+    #[test]
+    fn test_mcstructure_layout() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:air".to_string()));
+        schematic.set_block(0, 0, 1, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:oak_fence".to_string())
+            .with_property("waterlogged".to_string(), "true".to_string()));
+
+        let bytes = to_mcstructure(&schematic).unwrap();
+        let (root, _) = read_le_nbt(&bytes).unwrap();
+        assert_eq!(read_int_list(root.get::<_, &NbtList>("size").unwrap()), vec![2, 1, 2]);
+
+        let structure = root.get::<_, &NbtCompound>("structure").unwrap();
+        let layers: Vec<Vec<i32>> = structure.get::<_, &NbtList>("block_indices").unwrap().iter()
+            .map(|layer| match layer {
+                NbtTag::List(list) => read_int_list(list),
+                _ => panic!("layer is not a list"),
+            })
+            .collect();
+        // z varies fastest: (0,0,0) air, (0,0,1) stone, (1,0,0) fence, (1,0,1) air
+        assert_eq!(layers[0][0], layers[0][3]);
+        assert_ne!(layers[0][1], layers[0][2]);
+        assert_eq!(layers[1][..2], [NO_BLOCK, NO_BLOCK]);
+        assert_ne!(layers[1][2], NO_BLOCK);
+        assert_eq!(layers[1][3], NO_BLOCK);
+    }
+
+    #[test]
+    fn test_hostile_sizes_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        let (mut root, _) = read_le_nbt(&to_mcstructure(&schematic).unwrap()).unwrap();
+        for size in [[i32::MAX, i32::MAX, i32::MAX], [0, 1, 1], [65_536, 65_536, 2]] {
+            root.insert("size", int_list(&size));
+            assert!(from_mcstructure(&write_le_nbt(&root, "")).is_err(), "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn test_far_apart_regions_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Sparse".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        let far = 1_000_000_000;
+        schematic.set_block_in_region("Far", far, far, far, BlockState::new("minecraft:stone".to_string()));
+        assert!(to_mcstructure(&schematic).is_err());
+    }
+}
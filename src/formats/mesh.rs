@@ -0,0 +1,610 @@
+//! Triangle mesh export (OBJ + MTL, binary glTF, STL).
+//!
+//! Full blocks are greedy meshed: faces hidden by an opaque neighbour are dropped and the
+//! remaining coplanar faces of the same block type are merged into larger quads. Slabs, stairs,
+//! carpets and snow layers are approximated by their collision boxes; every other block is
+//! treated as a full cube. One unit is one block and the mesh starts at the origin.
+
+use std::collections::HashMap;
+use serde_json::json;
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::{get_block_color, is_invisible};
+
+/// Color used for blocks missing from the color table.
+const UNKNOWN_BLOCK_COLOR: [u8; 3] = [128, 128, 128];
+
+#[derive(Debug, Clone)]
+pub struct MeshOptions {
+    /// Merge adjacent faces of the same block type into larger quads.
+    pub greedy: bool,
+    /// Drop faces that are covered by a neighbouring opaque block.
+    pub cull_hidden_faces: bool,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        MeshOptions {
+            greedy: true,
+            cull_hidden_faces: true,
+        }
+    }
+}
+
+/// The triangles of one block type.
+#[derive(Debug, Clone, Default)]
+pub struct MeshPrimitive {
+    pub material: String,
+    pub color: [u8; 3],
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub primitives: Vec<MeshPrimitive>,
+}
+
+type Cuboid = ([f32; 3], [f32; 3]);
+
+#[derive(Debug, Clone)]
+enum Shape {
+    Empty,
+    Full,
+    Boxes(Vec<Cuboid>),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cell {
+    /// Index into the primitives, plus one; zero means empty.
+    material: u32,
+    full: bool,
+    opaque: bool,
+    /// Bit `axis * 2 + positive` is set when the block covers that whole side of its cell.
+    solid_faces: u8,
+}
+
+fn solid_faces(boxes: &[Cuboid]) -> u8 {
+    let mut faces = 0;
+    for (min, max) in boxes {
+        for axis in 0..3 {
+            let (ua, va) = ((axis + 1) % 3, (axis + 2) % 3);
+            let spans = min[ua] <= 0.0 && max[ua] >= 1.0 && min[va] <= 0.0 && max[va] >= 1.0;
+            if spans && min[axis] <= 0.0 {
+                faces |= 1 << (axis * 2);
+            }
+            if spans && max[axis] >= 1.0 {
+                faces |= 1 << (axis * 2 + 1);
+            }
+        }
+    }
+    faces
+}
+
+fn classify(block: &BlockState) -> Shape {
+    if is_invisible(&block.name) {
+        return Shape::Empty;
+    }
+    let name = block.name.strip_prefix("minecraft:").unwrap_or(&block.name);
+    let property = |key: &str| block.get_property(key).map(|v| v.as_str());
+
+    if name.ends_with("_slab") {
+        return match property("type") {
+            Some("double") => Shape::Full,
+            Some("top") => Shape::Boxes(vec![([0.0, 0.5, 0.0], [1.0, 1.0, 1.0])]),
+            _ => Shape::Boxes(vec![([0.0, 0.0, 0.0], [1.0, 0.5, 1.0])]),
+        };
+    }
+    if name.ends_with("_stairs") {
+        let (base_y, step_y) = if property("half") == Some("top") { (0.5, 0.0) } else { (0.0, 0.5) };
+        let base = ([0.0, base_y, 0.0], [1.0, base_y + 0.5, 1.0]);
+        let (step_min_xz, step_max_xz) = match property("facing").unwrap_or("north") {
+            "east" => ([0.5, 0.0], [1.0, 1.0]),
+            "west" => ([0.0, 0.0], [0.5, 1.0]),
+            "south" => ([0.0, 0.5], [1.0, 1.0]),
+            _ => ([0.0, 0.0], [1.0, 0.5]),
+        };
+        let step = (
+            [step_min_xz[0], step_y, step_min_xz[1]],
+            [step_max_xz[0], step_y + 0.5, step_max_xz[1]],
+        );
+        return Shape::Boxes(vec![base, step]);
+    }
+    if name.ends_with("carpet") {
+        return Shape::Boxes(vec![([0.0, 0.0, 0.0], [1.0, 1.0 / 16.0, 1.0])]);
+    }
+    if name == "snow" {
+        let layers = property("layers").and_then(|l| l.parse::<u8>().ok()).unwrap_or(1).clamp(1, 8);
+        if layers == 8 {
+            return Shape::Full;
+        }
+        return Shape::Boxes(vec![([0.0, 0.0, 0.0], [1.0, layers as f32 / 8.0, 1.0])]);
+    }
+    Shape::Full
+}
+
+/// See-through blocks don't hide their neighbours' faces.
+pub(crate) fn is_transparent(name: &str) -> bool {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    name.contains("glass")
+        || name.ends_with("_leaves")
+        || matches!(name, "ice" | "water" | "slime_block" | "honey_block" | "spawner" | "beacon")
+}
+
+fn material_name(block_name: &str) -> String {
+    block_name.strip_prefix("minecraft:").unwrap_or(block_name).replace([':', ' ', '/'], "_")
+}
+
+impl MeshPrimitive {
+    /// Appends a quad lying in the plane `axis = plane`, spanning `u` and `v` on the two other
+    /// axes (in x → y → z cyclic order), facing along +axis or -axis.
+    fn push_quad(&mut self, axis: usize, positive: bool, plane: f32, u: (f32, f32), v: (f32, f32)) {
+        let (ua, va) = ((axis + 1) % 3, (axis + 2) % 3);
+        let corner = |cu: f32, cv: f32| {
+            let mut p = [0.0f32; 3];
+            p[axis] = plane;
+            p[ua] = cu;
+            p[va] = cv;
+            p
+        };
+        let mut normal = [0.0f32; 3];
+        normal[axis] = if positive { 1.0 } else { -1.0 };
+
+        let base = self.positions.len() as u32;
+        self.positions.extend_from_slice(&[corner(u.0, v.0), corner(u.1, v.0), corner(u.1, v.1), corner(u.0, v.1)]);
+        self.normals.extend_from_slice(&[normal; 4]);
+        // Counter-clockwise seen from the side the normal points to
+        if positive {
+            self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        } else {
+            self.indices.extend_from_slice(&[base, base + 2, base + 1, base, base + 3, base + 2]);
+        }
+    }
+}
+
+impl Mesh {
+    pub fn triangle_count(&self) -> usize {
+        self.primitives.iter().map(|p| p.indices.len() / 3).sum()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.primitives.iter().map(|p| p.positions.len()).sum()
+    }
+
+    /// Wavefront OBJ text and the matching MTL text. `mtl_file_name` is referenced from the OBJ.
+    pub fn to_obj(&self, mtl_file_name: &str) -> (String, String) {
+        let mut obj = String::new();
+        obj.push_str(&format!("mtllib {}\n", mtl_file_name));
+        for [x, y, z] in self.primitives.iter().flat_map(|p| p.positions.iter()) {
+            obj.push_str(&format!("v {} {} {}\n", x, y, z));
+        }
+        for [x, y, z] in self.primitives.iter().flat_map(|p| p.normals.iter()) {
+            obj.push_str(&format!("vn {} {} {}\n", x, y, z));
+        }
+        // OBJ indices are one-based and shared across the whole file
+        let mut offset = 1;
+        for primitive in &self.primitives {
+            obj.push_str(&format!("usemtl {}\n", primitive.material));
+            for triangle in primitive.indices.chunks_exact(3) {
+                let [a, b, c] = [triangle[0] + offset, triangle[1] + offset, triangle[2] + offset];
+                obj.push_str(&format!("f {a}//{a} {b}//{b} {c}//{c}\n"));
+            }
+            offset += primitive.positions.len() as u32;
+        }
+
+        let mut mtl = String::new();
+        for primitive in &self.primitives {
+            let [r, g, b] = primitive.color.map(|c| c as f32 / 255.0);
+            mtl.push_str(&format!("newmtl {}\nKa {r:.4} {g:.4} {b:.4}\nKd {r:.4} {g:.4} {b:.4}\nd 1.0\nillum 1\n\n", primitive.material));
+        }
+        (obj, mtl)
+    }
+
+    /// Binary STL. STL has no colors, so materials are dropped.
+    pub fn to_stl(&self) -> Vec<u8> {
+        let mut out = vec![0u8; 80];
+        out[..22].copy_from_slice(b"nucleation schematic\0\0");
+        out.extend_from_slice(&(self.triangle_count() as u32).to_le_bytes());
+        for primitive in &self.primitives {
+            for triangle in primitive.indices.chunks_exact(3) {
+                let normal = primitive.normals[triangle[0] as usize];
+                for value in normal {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+                for &index in triangle {
+                    for value in primitive.positions[index as usize] {
+                        out.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                out.extend_from_slice(&0u16.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Binary glTF 2.0 (`.glb`) with one primitive and one colored material per block type.
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut bin: Vec<u8> = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+
+        let mut add_view = |bin: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+            let offset = bin.len();
+            let length = bytes.len();
+            bin.extend(bytes);
+            buffer_views.push(json!({ "buffer": 0, "byteOffset": offset, "byteLength": length, "target": target }));
+            buffer_views.len() - 1
+        };
+
+        for primitive in self.primitives.iter().filter(|p| !p.indices.is_empty()) {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for position in &primitive.positions {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+
+            let positions: Vec<u8> = primitive.positions.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+            let normals: Vec<u8> = primitive.normals.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+            let indices: Vec<u8> = primitive.indices.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+            let position_view = add_view(&mut bin, positions, 34962);
+            let normal_view = add_view(&mut bin, normals, 34962);
+            let index_view = add_view(&mut bin, indices, 34963);
+
+            let count = primitive.positions.len();
+            accessors.push(json!({ "bufferView": position_view, "componentType": 5126, "count": count, "type": "VEC3", "min": min, "max": max }));
+            accessors.push(json!({ "bufferView": normal_view, "componentType": 5126, "count": count, "type": "VEC3" }));
+            accessors.push(json!({ "bufferView": index_view, "componentType": 5125, "count": primitive.indices.len(), "type": "SCALAR" }));
+
+            let [r, g, b] = primitive.color.map(|c| c as f32 / 255.0);
+            materials.push(json!({
+                "name": primitive.material,
+                "pbrMetallicRoughness": { "baseColorFactor": [r, g, b, 1.0], "metallicFactor": 0.0, "roughnessFactor": 1.0 }
+            }));
+            let first = accessors.len() - 3;
+            primitives.push(json!({
+                "attributes": { "POSITION": first, "NORMAL": first + 1 },
+                "indices": first + 2,
+                "material": materials.len() - 1
+            }));
+        }
+
+        let document = if primitives.is_empty() {
+            // glTF forbids empty arrays and zero-length buffers, so an empty mesh is just an empty scene
+            json!({
+                "asset": { "version": "2.0", "generator": "nucleation" },
+                "scene": 0,
+                "scenes": [{}]
+            })
+        } else {
+            json!({
+                "asset": { "version": "2.0", "generator": "nucleation" },
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [{ "mesh": 0, "name": "schematic" }],
+                "meshes": [{ "primitives": primitives }],
+                "materials": materials,
+                "accessors": accessors,
+                "bufferViews": buffer_views,
+                "buffers": [{ "byteLength": bin.len() }]
+            })
+        };
+
+        let mut json_bytes = serde_json::to_vec(&document).expect("glTF JSON is always serializable");
+        while !json_bytes.len().is_multiple_of(4) {
+            json_bytes.push(b' ');
+        }
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+
+        let bin_chunk = if bin.is_empty() { 0 } else { 8 + bin.len() };
+        let total = 12 + 8 + json_bytes.len() + bin_chunk;
+        let mut out = Vec::with_capacity(total);
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend(json_bytes);
+        if !bin.is_empty() {
+            out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            out.extend_from_slice(b"BIN\0");
+            out.extend(bin);
+        }
+        out
+    }
+}
+
+/// Builds the mesh for every block in the schematic. Fails when the regions are spread so far
+/// apart that the block grid wouldn't fit in memory.
+pub fn build_mesh(schematic: &UniversalSchematic, options: &MeshOptions) -> Result<Mesh, Box<dyn std::error::Error>> {
+    let mut mesh = Mesh::default();
+    if schematic.regions.is_empty() {
+        return Ok(mesh);
+    }
+
+    let bounding_box = schematic.get_bounding_box();
+    let min = bounding_box.min;
+    let (width, height, length) = bounding_box.get_dimensions();
+    let dims = [width, height, length];
+    let too_large = || format!("Bounding box of {}x{}x{} blocks is too large to mesh", width, height, length);
+    let volume = dims.iter()
+        .try_fold(1usize, |volume, &d| volume.checked_mul(usize::try_from(d).ok()?))
+        .ok_or_else(too_large)?;
+    let (row, layer) = (width as usize, width as usize * length as usize);
+    let index = |p: [i32; 3]| p[0] as usize + p[2] as usize * row + p[1] as usize * layer;
+
+    let mut material_lookup: HashMap<String, u32> = HashMap::new();
+    let mut cells = Vec::new();
+    cells.try_reserve_exact(volume).map_err(|_| too_large())?;
+    cells.resize(volume, Cell::default());
+    let mut partial: Vec<([i32; 3], u32, Vec<Cuboid>)> = Vec::new();
+
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                let block = match schematic.get_block(min.0 + x, min.1 + y, min.2 + z) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                let shape = classify(block);
+                if let Shape::Empty = shape {
+                    continue;
+                }
+                let material = *material_lookup.entry(block.name.clone()).or_insert_with(|| {
+                    mesh.primitives.push(MeshPrimitive {
+                        material: material_name(&block.name),
+                        color: get_block_color(&block.name).unwrap_or(UNKNOWN_BLOCK_COLOR),
+                        ..MeshPrimitive::default()
+                    });
+                    mesh.primitives.len() as u32
+                });
+                let cell = &mut cells[index([x, y, z])];
+                cell.material = material;
+                cell.opaque = !is_transparent(&block.name);
+                match shape {
+                    Shape::Full => {
+                        cell.full = true;
+                        cell.solid_faces = 0b11_1111;
+                    }
+                    Shape::Boxes(boxes) => {
+                        cell.solid_faces = solid_faces(&boxes);
+                        partial.push(([x, y, z], material, boxes));
+                    }
+                    Shape::Empty => {}
+                }
+            }
+        }
+    }
+
+    let neighbour = |p: [i32; 3], axis: usize, positive: bool| -> Option<Cell> {
+        let mut n = p;
+        n[axis] += if positive { 1 } else { -1 };
+        if n[axis] < 0 || n[axis] >= dims[axis] {
+            None
+        } else {
+            Some(cells[index(n)])
+        }
+    };
+    // Whether the neighbour on the `axis`/`positive` side hides that face of `cell`
+    let hides = |cell: Cell, other: Option<Cell>, axis: usize, positive: bool| match other {
+        Some(other) if options.cull_hidden_faces => {
+            let facing_side = 1 << (axis * 2 + !positive as usize);
+            other.solid_faces & facing_side != 0 && (other.opaque || other.material == cell.material)
+        }
+        _ => false,
+    };
+
+    for axis in 0..3 {
+        let (ua, va) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (du, dv) = (dims[ua] as usize, dims[va] as usize);
+        for positive in [false, true] {
+            let mut mask = vec![0u32; du * dv];
+            for slice in 0..dims[axis] {
+                for j in 0..dv {
+                    for i in 0..du {
+                        let mut p = [0; 3];
+                        p[axis] = slice;
+                        p[ua] = i as i32;
+                        p[va] = j as i32;
+                        let cell = cells[index(p)];
+                        let visible = cell.full && !hides(cell, neighbour(p, axis, positive), axis, positive);
+                        mask[i + j * du] = if visible { cell.material } else { 0 };
+                    }
+                }
+
+                let plane = (slice + positive as i32) as f32;
+                for j in 0..dv {
+                    let mut i = 0;
+                    while i < du {
+                        let material = mask[i + j * du];
+                        if material == 0 {
+                            i += 1;
+                            continue;
+                        }
+                        let mut w = 1;
+                        let mut h = 1;
+                        if options.greedy {
+                            while i + w < du && mask[i + w + j * du] == material {
+                                w += 1;
+                            }
+                            'grow: while j + h < dv {
+                                for k in 0..w {
+                                    if mask[i + k + (j + h) * du] != material {
+                                        break 'grow;
+                                    }
+                                }
+                                h += 1;
+                            }
+                        }
+                        for row in j..j + h {
+                            for k in i..i + w {
+                                mask[k + row * du] = 0;
+                            }
+                        }
+                        mesh.primitives[material as usize - 1].push_quad(
+                            axis,
+                            positive,
+                            plane,
+                            (i as f32, (i + w) as f32),
+                            (j as f32, (j + h) as f32),
+                        );
+                        i += w;
+                    }
+                }
+            }
+        }
+    }
+
+    for (p, material, boxes) in partial {
+        let cell = cells[index(p)];
+        for (box_min, box_max) in boxes {
+            for axis in 0..3 {
+                let (ua, va) = ((axis + 1) % 3, (axis + 2) % 3);
+                for positive in [false, true] {
+                    // Only faces on the block boundary can be covered by a neighbour
+                    let on_boundary = if positive { box_max[axis] >= 1.0 } else { box_min[axis] <= 0.0 };
+                    if on_boundary && hides(cell, neighbour(p, axis, positive), axis, positive) {
+                        continue;
+                    }
+                    let plane = p[axis] as f32 + if positive { box_max[axis] } else { box_min[axis] };
+                    mesh.primitives[material as usize - 1].push_quad(
+                        axis,
+                        positive,
+                        plane,
+                        (p[ua] as f32 + box_min[ua], p[ua] as f32 + box_max[ua]),
+                        (p[va] as f32 + box_min[va], p[va] as f32 + box_max[va]),
+                    );
+                }
+            }
+        }
+    }
+
+    mesh.primitives.retain(|p| !p.indices.is_empty());
+    Ok(mesh)
+}
+
+/// OBJ and MTL text for the schematic, see [`Mesh::to_obj`].
+pub fn to_obj(schematic: &UniversalSchematic, mtl_file_name: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    Ok(build_mesh(schematic, &MeshOptions::default())?.to_obj(mtl_file_name))
+}
+
+pub fn to_stl(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(build_mesh(schematic, &MeshOptions::default())?.to_stl())
+}
+
+pub fn to_glb(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(build_mesh(schematic, &MeshOptions::default())?.to_glb())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(size: i32, name: &str) -> UniversalSchematic {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    schematic.set_block(x, y, z, BlockState::new(name.to_string()));
+                }
+            }
+        }
+        schematic
+    }
+
+    #[test]
+    fn test_greedy_cube_is_twelve_triangles() {
+        let schematic = cube(4, "minecraft:stone");
+        let mesh = build_mesh(&schematic, &MeshOptions::default()).unwrap();
+        assert_eq!(mesh.primitives.len(), 1);
+        assert_eq!(mesh.triangle_count(), 12);
+
+        let naive = build_mesh(&schematic, &MeshOptions { greedy: false, cull_hidden_faces: true }).unwrap();
+        assert_eq!(naive.triangle_count(), 6 * 16 * 2);
+
+        let unculled = build_mesh(&schematic, &MeshOptions { greedy: false, cull_hidden_faces: false }).unwrap();
+        assert_eq!(unculled.triangle_count(), 64 * 12);
+    }
+
+    #[test]
+    fn test_transparent_neighbours_and_slabs() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(1, 0, 0, BlockState::new("minecraft:glass".to_string()));
+        schematic.set_block(0, 1, 0, BlockState::new("minecraft:oak_slab".to_string())
+            .with_property("type".to_string(), "bottom".to_string()));
+        let mesh = build_mesh(&schematic, &MeshOptions::default()).unwrap();
+
+        let triangles = |name: &str| mesh.primitives.iter()
+            .find(|p| p.material == name)
+            .map(|p| p.indices.len() / 3)
+            .unwrap_or(0);
+        // Stone keeps the face behind the glass but loses the one under the slab
+        assert_eq!(triangles("stone"), 10);
+        // Glass loses the face against the stone
+        assert_eq!(triangles("glass"), 10);
+        // The slab's bottom sits on the stone
+        assert_eq!(triangles("oak_slab"), 10);
+
+        let positions: Vec<[f32; 3]> = mesh.primitives.iter()
+            .find(|p| p.material == "oak_slab").unwrap()
+            .positions.clone();
+        assert!(positions.iter().all(|p| p[1] >= 1.0 && p[1] <= 1.5));
+    }
+
+    #[test]
+    fn test_exporters() {
+        let mut schematic = cube(2, "minecraft:red_wool");
+        schematic.set_block(2, 0, 0, BlockState::new("minecraft:oak_stairs".to_string())
+            .with_property("facing".to_string(), "east".to_string())
+            .with_property("half".to_string(), "bottom".to_string()));
+        let mesh = build_mesh(&schematic, &MeshOptions::default()).unwrap();
+
+        let (obj, mtl) = mesh.to_obj("test.mtl");
+        assert!(obj.starts_with("mtllib test.mtl\n"));
+        assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), mesh.triangle_count());
+        assert!(mtl.contains("newmtl red_wool"));
+        assert!(mtl.contains("newmtl oak_stairs"));
+
+        let stl = mesh.to_stl();
+        assert_eq!(stl.len(), 84 + 50 * mesh.triangle_count());
+        assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize, mesh.triangle_count());
+
+        let glb = mesh.to_glb();
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+        let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let document: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        assert_eq!(document["meshes"][0]["primitives"].as_array().unwrap().len(), 2);
+        assert_eq!(document["materials"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_empty_glb_has_no_buffers() {
+        let glb = build_mesh(&UniversalSchematic::new("Empty".to_string()), &MeshOptions::default()).unwrap().to_glb();
+        assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+        let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        assert_eq!(glb.len(), 20 + json_len);
+        let document: serde_json::Value = serde_json::from_slice(&glb[20..]).unwrap();
+        assert!(document.get("buffers").is_none());
+        assert!(document.get("bufferViews").is_none());
+        assert!(document.get("meshes").is_none());
+    }
+
+    #[test]
+    fn test_far_apart_regions_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Sparse".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        let far = 5_000_000;
+        schematic.set_block_in_region("Far", far, far, far, BlockState::new("minecraft:stone".to_string()));
+        assert!(build_mesh(&schematic, &MeshOptions::default()).is_err());
+        assert!(to_glb(&schematic).is_err());
+    }
+}
@@ -1,2 +1,12 @@
 pub mod litematic;
-pub mod schematic;
\ No newline at end of file
+pub mod schematic;
+pub mod commands;
+pub mod mcstructure;
+pub mod bedrock_blocks;
+pub mod vox;
+pub mod mesh;
+pub mod native;
+pub mod json;
+pub mod compression;
+pub mod limits;
+pub mod peek;
\ No newline at end of file
@@ -0,0 +1,377 @@
+//! Nucleation's own binary format, meant as a fast cache rather than for exchange.
+//!
+//! A file is the magic `NUCL`, a format version byte, a flags byte and an NBT root, compressed
+//! when flag bit 0 is set. Each region stores its palette once and its blocks as run-length
+//! encoded varint pairs `(palette index, run length)` in YZX order, which decodes much faster
+//! than bit-packed litematic states and stays small for the large uniform areas typical of
+//! builds. Every field of the schematic is kept, including NBT the other formats drop.
+
+use std::collections::HashMap;
+use quartz_nbt::{NbtCompound, NbtList, NbtTag};
+use crate::block_entity::BlockEntity;
+use crate::entity::Entity;
+use crate::formats::compression::{self, NbtCompression};
+use crate::formats::limits::LoadLimits;
+use crate::metadata::Metadata;
+use crate::region::{PendingTick, Region};
+use crate::utils::NbtMap;
+use crate::{BlockState, UniversalSchematic};
+
+const MAGIC: &[u8; 4] = b"NUCL";
+/// Version written by this build; files from newer versions are rejected.
+pub const NATIVE_FORMAT_VERSION: u8 = 1;
+const FLAG_COMPRESSED: u8 = 1;
+
+#[derive(Debug, Clone)]
+pub struct NativeWriteOptions {
+    /// `NbtCompression::None` stores the NBT as is for the fastest loads.
+    pub compression: NbtCompression,
+}
+
+impl Default for NativeWriteOptions {
+    fn default() -> Self {
+        NativeWriteOptions { compression: NbtCompression::Gzip(1) }
+    }
+}
+
+pub fn is_native(data: &[u8]) -> bool {
+    data.len() >= 6 && &data[..4] == MAGIC
+}
+
+pub fn to_native(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    to_native_with_options(schematic, &NativeWriteOptions::default())
+}
+
+pub fn to_native_with_options(schematic: &UniversalSchematic, options: &NativeWriteOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut root = NbtCompound::new();
+    root.insert("Metadata", NbtTag::Compound(create_metadata(&schematic.metadata)));
+    root.insert("DefaultRegion", NbtTag::String(schematic.default_region_name.clone()));
+    root.insert("Origin", int_array(schematic.origin));
+    let regions = schematic.regions.values().map(|region| NbtTag::Compound(create_region(region)));
+    root.insert("Regions", NbtTag::List(NbtList::from(regions.collect::<Vec<NbtTag>>())));
+
+    let mut data = MAGIC.to_vec();
+    data.push(NATIVE_FORMAT_VERSION);
+    data.push(if options.compression == NbtCompression::None { 0 } else { FLAG_COMPRESSED });
+    data.extend(compression::write_nbt(&root, options.compression)?);
+    Ok(data)
+}
+
+pub fn from_native(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    from_native_with_limits(data, &LoadLimits::default())
+}
+
+/// Loads a native file, failing cleanly on a size, palette or entity count beyond `limits`.
+pub fn from_native_with_limits(data: &[u8], limits: &LoadLimits) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    if !is_native(data) {
+        return Err("Not a native schematic".into());
+    }
+    let version = data[4];
+    if version > NATIVE_FORMAT_VERSION {
+        return Err(format!("Unsupported native format version {}", version).into());
+    }
+    // The codec is detected from the payload, the flag only records that there is one
+    let root = compression::read_nbt_with_limits(&data[6..], limits)?;
+
+    let mut schematic = UniversalSchematic::new(String::new());
+    schematic.metadata = parse_metadata(root.get::<_, &NbtCompound>("Metadata")?);
+    schematic.default_region_name = root.get::<_, &str>("DefaultRegion")?.to_string();
+    schematic.origin = read_int_triple(&root, "Origin")?;
+    let mut entity_count = 0;
+    for tag in root.get::<_, &NbtList>("Regions")?.iter() {
+        if let NbtTag::Compound(region_nbt) = tag {
+            entity_count += region_nbt.get::<_, &NbtList>("Entities")?.len() + region_nbt.get::<_, &NbtList>("BlockEntities")?.len();
+            limits.check_entities(entity_count)?;
+            let region = parse_region(region_nbt, limits)?;
+            schematic.regions.insert(region.name.clone(), region);
+        }
+    }
+    Ok(schematic)
+}
+
+fn int_array(value: (i32, i32, i32)) -> NbtTag {
+    NbtTag::IntArray(vec![value.0, value.1, value.2])
+}
+
+fn read_int_triple(compound: &NbtCompound, key: &str) -> Result<(i32, i32, i32), Box<dyn std::error::Error>> {
+    match compound.get::<_, &[i32]>(key)? {
+        &[x, y, z] => Ok((x, y, z)),
+        _ => Err(format!("{} must have three components", key).into()),
+    }
+}
+
+fn create_metadata(metadata: &Metadata) -> NbtCompound {
+    let mut compound = NbtCompound::new();
+    let strings = [("Name", &metadata.name), ("Author", &metadata.author), ("Description", &metadata.description)];
+    for (key, value) in strings {
+        if let Some(value) = value {
+            compound.insert(key, NbtTag::String(value.clone()));
+        }
+    }
+    for (key, value) in [("Created", metadata.created), ("Modified", metadata.modified)] {
+        if let Some(value) = value {
+            compound.insert(key, NbtTag::Long(value as i64));
+        }
+    }
+    let versions = [
+        ("LitematicVersion", metadata.lm_version),
+        ("LitematicSubVersion", metadata.lm_sub_version),
+        ("DataVersion", metadata.mc_version),
+        ("WorldEditVersion", metadata.we_version),
+    ];
+    for (key, value) in versions {
+        if let Some(value) = value {
+            compound.insert(key, NbtTag::Int(value));
+        }
+    }
+    if let Some(preview) = &metadata.preview_image {
+        compound.insert("PreviewImage", NbtTag::IntArray(preview.clone()));
+    }
+    compound.insert("Extra", NbtTag::Compound(metadata.extra_nbt.to_quartz_nbt()));
+    compound
+}
+
+fn parse_metadata(compound: &NbtCompound) -> Metadata {
+    let string = |key: &str| compound.get::<_, &str>(key).ok().map(String::from);
+    let int = |key: &str| compound.get::<_, i32>(key).ok();
+    Metadata {
+        name: string("Name"),
+        author: string("Author"),
+        description: string("Description"),
+        created: compound.get::<_, i64>("Created").ok().map(|t| t as u64),
+        modified: compound.get::<_, i64>("Modified").ok().map(|t| t as u64),
+        lm_version: int("LitematicVersion"),
+        lm_sub_version: int("LitematicSubVersion"),
+        mc_version: int("DataVersion"),
+        we_version: int("WorldEditVersion"),
+        preview_image: compound.get::<_, &[i32]>("PreviewImage").ok().map(<[i32]>::to_vec),
+        extra_nbt: compound.get::<_, &NbtCompound>("Extra").map(NbtMap::from_quartz_nbt).unwrap_or_default(),
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// `None` when the data ends mid-varint or the value doesn't fit in a `usize`, so a hostile
+/// varint can't overflow the shift on 32-bit targets.
+fn read_varint(data: &[u8], cursor: &mut usize) -> Option<usize> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*cursor)?;
+        *cursor += 1;
+        let bits = u64::from(byte & 0x7f);
+        if (bits << shift) >> shift != bits {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return usize::try_from(value).ok();
+        }
+        shift += 7;
+        if shift >= usize::BITS {
+            return None;
+        }
+    }
+}
+
+fn encode_runs(blocks: &[usize]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut index = 0;
+    while index < blocks.len() {
+        let value = blocks[index];
+        let run = blocks[index..].iter().take_while(|&&b| b == value).count();
+        push_varint(&mut out, value);
+        push_varint(&mut out, run);
+        index += run;
+    }
+    out
+}
+
+fn decode_runs(data: &[u8], volume: usize, palette_len: usize) -> Result<Vec<usize>, String> {
+    let mut blocks = Vec::with_capacity(volume);
+    let mut cursor = 0;
+    while cursor < data.len() {
+        let (value, run) = read_varint(data, &mut cursor)
+            .zip(read_varint(data, &mut cursor))
+            .ok_or("Truncated block data")?;
+        if value >= palette_len {
+            return Err(format!("Palette index {} out of range", value));
+        }
+        if run > volume - blocks.len() {
+            return Err("Block data is longer than the region".to_string());
+        }
+        blocks.resize(blocks.len() + run, value);
+    }
+    if blocks.len() != volume {
+        return Err(format!("Expected {} blocks, found {}", volume, blocks.len()));
+    }
+    Ok(blocks)
+}
+
+fn create_region(region: &Region) -> NbtCompound {
+    let mut compound = NbtCompound::new();
+    compound.insert("Name", NbtTag::String(region.name.clone()));
+    compound.insert("Position", int_array(region.position));
+    compound.insert("Size", int_array(region.size));
+    let palette = region.palette.iter().map(|block| block.to_nbt()).collect::<Vec<NbtTag>>();
+    compound.insert("Palette", NbtTag::List(NbtList::from(palette)));
+    compound.insert("Blocks", NbtTag::ByteArray(encode_runs(&region.blocks).into_iter().map(|b| b as i8).collect()));
+
+    let entities = region.entities.iter().map(|entity| {
+        let mut data = NbtCompound::new();
+        for (key, value) in Entity::sorted_entries(&entity.nbt) {
+            data.insert(key, Entity::value_to_nbt_tag(value));
+        }
+        let mut entity_nbt = NbtCompound::new();
+        entity_nbt.insert("Id", NbtTag::String(entity.id.clone()));
+        let (x, y, z) = entity.position;
+        entity_nbt.insert("Pos", NbtTag::List(NbtList::from(vec![NbtTag::Double(x), NbtTag::Double(y), NbtTag::Double(z)])));
+        entity_nbt.insert("Data", NbtTag::Compound(data));
+        NbtTag::Compound(entity_nbt)
+    });
+    compound.insert("Entities", NbtTag::List(NbtList::from(entities.collect::<Vec<NbtTag>>())));
+
+    let block_entities = region.sorted_block_entities().into_iter().map(|block_entity| {
+        let mut block_entity_nbt = NbtCompound::new();
+        block_entity_nbt.insert("Id", NbtTag::String(block_entity.id.clone()));
+        block_entity_nbt.insert("Pos", int_array(block_entity.position));
+        block_entity_nbt.insert("Data", NbtTag::Compound(block_entity.nbt.to_quartz_nbt()));
+        NbtTag::Compound(block_entity_nbt)
+    });
+    compound.insert("BlockEntities", NbtTag::List(NbtList::from(block_entities.collect::<Vec<NbtTag>>())));
+
+    compound.insert("PendingBlockTicks", create_ticks(&region.pending_block_ticks));
+    compound.insert("PendingFluidTicks", create_ticks(&region.pending_fluid_ticks));
+    compound.insert("Extra", NbtTag::Compound(region.extra_nbt.to_quartz_nbt()));
+    compound
+}
+
+fn create_ticks(ticks: &[PendingTick]) -> NbtTag {
+    NbtTag::List(NbtList::from(ticks.iter().map(|tick| {
+        let mut compound = NbtCompound::new();
+        compound.insert("Pos", int_array(tick.position));
+        compound.insert("Target", NbtTag::String(tick.target.clone()));
+        compound.insert("Priority", NbtTag::Int(tick.priority));
+        compound.insert("SubTick", NbtTag::Long(tick.sub_tick));
+        compound.insert("Time", NbtTag::Int(tick.time));
+        NbtTag::Compound(compound)
+    }).collect::<Vec<NbtTag>>()))
+}
+
+fn parse_ticks(region_nbt: &NbtCompound, key: &str) -> Result<Vec<PendingTick>, Box<dyn std::error::Error>> {
+    let mut ticks = Vec::new();
+    for tag in region_nbt.get::<_, &NbtList>(key)?.iter() {
+        if let NbtTag::Compound(compound) = tag {
+            ticks.push(PendingTick {
+                position: read_int_triple(compound, "Pos")?,
+                target: compound.get::<_, &str>("Target")?.to_string(),
+                priority: compound.get::<_, i32>("Priority")?,
+                sub_tick: compound.get::<_, i64>("SubTick")?,
+                time: compound.get::<_, i32>("Time")?,
+            });
+        }
+    }
+    Ok(ticks)
+}
+
+fn parse_region(region_nbt: &NbtCompound, limits: &LoadLimits) -> Result<Region, Box<dyn std::error::Error>> {
+    let name = region_nbt.get::<_, &str>("Name")?.to_string();
+    let position = read_int_triple(region_nbt, "Position")?;
+    let size = read_int_triple(region_nbt, "Size")?;
+    limits.check_region(position, size)?;
+    let palette = region_nbt.get::<_, &NbtList>("Palette")?;
+    limits.check_palette(palette.len())?;
+    let mut region = Region::new(name, position, size);
+
+    region.palette = palette.iter()
+        .map(|tag| match tag {
+            NbtTag::Compound(compound) => BlockState::from_nbt(compound),
+            _ => Err("Palette entries must be compounds".to_string()),
+        })
+        .collect::<Result<Vec<BlockState>, String>>()?;
+    let runs: &[u8] = bytemuck::cast_slice(region_nbt.get::<_, &[i8]>("Blocks")?);
+    region.blocks = decode_runs(runs, region.volume(), region.palette.len())?;
+
+    for tag in region_nbt.get::<_, &NbtList>("Entities")?.iter() {
+        if let NbtTag::Compound(compound) = tag {
+            let pos = compound.get::<_, &NbtList>("Pos")?;
+            let mut entity = Entity::new(
+                compound.get::<_, &str>("Id")?.to_string(),
+                (pos.get::<f64>(0)?, pos.get::<f64>(1)?, pos.get::<f64>(2)?),
+            );
+            entity.nbt = compound.get::<_, &NbtCompound>("Data")?.inner().iter()
+                .map(|(key, value)| (key.clone(), Entity::nbt_tag_to_value(value)))
+                .collect::<HashMap<_, _>>();
+            region.entities.push(entity);
+        }
+    }
+    for tag in region_nbt.get::<_, &NbtList>("BlockEntities")?.iter() {
+        if let NbtTag::Compound(compound) = tag {
+            let mut block_entity = BlockEntity::new(compound.get::<_, &str>("Id")?.to_string(), read_int_triple(compound, "Pos")?);
+            block_entity.nbt = NbtMap::from_quartz_nbt(compound.get::<_, &NbtCompound>("Data")?);
+            region.block_entities.insert(block_entity.position, block_entity);
+        }
+    }
+
+    region.pending_block_ticks = parse_ticks(region_nbt, "PendingBlockTicks")?;
+    region.pending_fluid_ticks = parse_ticks(region_nbt, "PendingFluidTicks")?;
+    region.extra_nbt = NbtMap::from_quartz_nbt(region_nbt.get::<_, &NbtCompound>("Extra")?);
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::sample_schematic;
+
+    #[test]
+    fn test_round_trip_keeps_every_field() {
+        let schematic = sample_schematic();
+        for compression in [NbtCompression::Gzip(1), NbtCompression::Zlib(9), NbtCompression::None] {
+            let options = NativeWriteOptions { compression };
+            let data = to_native_with_options(&schematic, &options).unwrap();
+            assert!(is_native(&data));
+            let loaded = from_native(&data).unwrap();
+            assert_eq!(loaded.metadata, schematic.metadata);
+            assert_eq!(loaded.origin, schematic.origin);
+            assert_eq!(loaded.default_region_name, schematic.default_region_name);
+            assert_eq!(loaded.regions.len(), 2);
+            for (name, region) in &schematic.regions {
+                let other = &loaded.regions[name];
+                assert_eq!((other.position, other.size), (region.position, region.size));
+                assert_eq!(other.palette, region.palette);
+                assert_eq!(other.blocks, region.blocks);
+                assert_eq!(other.entities, region.entities);
+                assert_eq!(other.block_entities, region.block_entities);
+                assert_eq!(other.pending_fluid_ticks, region.pending_fluid_ticks);
+                assert_eq!(other.extra_nbt, region.extra_nbt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_data() {
+        assert!(from_native(b"NUCL").is_err());
+        let mut data = to_native(&sample_schematic()).unwrap();
+        data[4] = NATIVE_FORMAT_VERSION + 1;
+        assert!(from_native(&data).is_err());
+
+        let data = to_native(&sample_schematic()).unwrap();
+        assert!(from_native_with_limits(&data, &LoadLimits::untrusted()).is_ok());
+        let tight = LoadLimits { max_volume: 10, ..LoadLimits::untrusted() };
+        assert!(from_native_with_limits(&data, &tight).is_err());
+
+        assert_eq!(decode_runs(&encode_runs(&[0, 0, 1, 1, 1, 0]), 6, 2).unwrap(), vec![0, 0, 1, 1, 1, 0]);
+        assert!(decode_runs(&encode_runs(&[0, 0, 2]), 3, 2).is_err());
+        assert!(decode_runs(&encode_runs(&[0; 4]), 3, 1).is_err());
+        assert!(decode_runs(&[0x80], 1, 1).is_err());
+        assert!(decode_runs(&[0xff; 12], 1, 1).is_err());
+        assert!(decode_runs(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f, 0x01], 1, 1).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Header-only reading of `.litematic` and Sponge `.schem` files, for listing folders of
+//! schematics without loading them. The NBT is streamed from the decompressor and arrays and
+//! lists (block data, entities, ticks) are skipped instead of being read; the only array kept is
+//! the litematic preview image.
+
+use std::io::{self, BufReader, Read};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use quartz_nbt::{NbtCompound, NbtTag};
+use serde::Serialize;
+use crate::formats::compression::{self, NbtCompression};
+use crate::formats::limits::LoadLimits;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchematicInfo {
+    /// `"litematic"` or `"schematic"`.
+    pub format: String,
+    /// Litematica format version, or the Sponge schematic version.
+    pub version: i32,
+    pub data_version: Option<i32>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub length: u32,
+    pub region_count: usize,
+    /// Non-air blocks as recorded by Litematica. Sponge files don't store it.
+    pub block_count: Option<u64>,
+    /// ARGB preview pixels, when the file embeds one.
+    pub preview_image: Option<Vec<i32>>,
+}
+
+/// Reads the format, version, metadata and dimensions of a litematic or Sponge schematic
+/// without decoding its blocks.
+pub fn peek_info(data: &[u8]) -> Result<SchematicInfo, Box<dyn std::error::Error>> {
+    peek_info_with_limits(data, &LoadLimits::default())
+}
+
+/// Like [`peek_info`], but stops reading once the decompressed NBT exceeds
+/// `max_decompressed_bytes` and rejects nesting deeper than `max_nbt_depth`.
+pub fn peek_info_with_limits(data: &[u8], limits: &LoadLimits) -> Result<SchematicInfo, Box<dyn std::error::Error>> {
+    let root = match NbtCompression::detect(data) {
+        NbtCompression::None => skim_nbt(data, limits)?,
+        NbtCompression::Gzip(_) => skim_nbt(BufReader::new(GzDecoder::new(data)), limits)?,
+        NbtCompression::Zlib(_) => skim_nbt(BufReader::new(ZlibDecoder::new(data)), limits)?,
+        NbtCompression::Zstd(_) => skim_nbt(compression::zstd_decoder(data)?, limits)?,
+    };
+
+    if let (Ok(metadata), Ok(regions)) = (root.get::<_, &NbtCompound>("Metadata"), root.get::<_, &NbtCompound>("Regions")) {
+        return Ok(litematic_info(&root, metadata, regions));
+    }
+    let schem = root.get::<_, &NbtCompound>("Schematic").unwrap_or(&root);
+    if schem.contains_key("Width") && schem.contains_key("Height") && schem.contains_key("Length") {
+        return Ok(sponge_info(schem));
+    }
+    Err("Unknown format: expected a litematic or a Sponge schematic".into())
+}
+
+fn litematic_info(root: &NbtCompound, metadata: &NbtCompound, regions: &NbtCompound) -> SchematicInfo {
+    let size = metadata.get::<_, &NbtCompound>("EnclosingSize").ok();
+    let axis = |key: &str| size.and_then(|size| size.get::<_, i32>(key).ok()).map_or(0, i32::unsigned_abs);
+    SchematicInfo {
+        format: "litematic".to_string(),
+        version: root.get::<_, i32>("Version").unwrap_or(0),
+        data_version: root.get::<_, i32>("MinecraftDataVersion").ok(),
+        name: metadata.get::<_, &str>("Name").ok().map(String::from),
+        author: metadata.get::<_, &str>("Author").ok().map(String::from),
+        description: metadata.get::<_, &str>("Description").ok().map(String::from),
+        width: axis("x"),
+        height: axis("y"),
+        length: axis("z"),
+        region_count: regions.len(),
+        block_count: metadata.get::<_, i32>("TotalBlocks").ok().map(|count| count.max(0) as u64),
+        preview_image: metadata.get::<_, &[i32]>("PreviewImageData").ok().map(<[i32]>::to_vec),
+    }
+}
+
+fn sponge_info(schem: &NbtCompound) -> SchematicInfo {
+    let metadata = schem.get::<_, &NbtCompound>("Metadata").ok();
+    let text = |key: &str| metadata.and_then(|metadata| metadata.get::<_, &str>(key).ok()).map(String::from);
+    // Sponge sizes are unsigned shorts
+    let axis = |key: &str| schem.get::<_, i16>(key).map_or(0, |value| u32::from(value as u16));
+    SchematicInfo {
+        format: "schematic".to_string(),
+        version: schem.get::<_, i32>("Version").unwrap_or(1),
+        data_version: schem.get::<_, i32>("DataVersion").ok(),
+        name: text("Name"),
+        author: text("Author"),
+        description: text("Description"),
+        width: axis("Width"),
+        height: axis("Height"),
+        length: axis("Length"),
+        region_count: 1,
+        block_count: None,
+        preview_image: None,
+    }
+}
+
+/// Reads an uncompressed NBT document, keeping compounds, scalars, strings and the preview image.
+fn skim_nbt<R: Read>(reader: R, limits: &LoadLimits) -> io::Result<NbtCompound> {
+    // Read one byte past the limit to tell "exactly at" from "over"
+    let mut reader = reader.take(limits.max_decompressed_bytes.saturating_add(1));
+    let root = skim_root(&mut reader, limits.max_nbt_depth);
+    if reader.limit() == 0 {
+        return Err(invalid(&format!("Decompressed data exceeds the limit of {} bytes", limits.max_decompressed_bytes)));
+    }
+    root
+}
+
+fn skim_root<R: Read>(reader: &mut R, max_depth: usize) -> io::Result<NbtCompound> {
+    if read_u8(reader)? != 10 {
+        return Err(invalid("Root tag is not a compound"));
+    }
+    read_string(reader)?;
+    skim_compound(reader, 0, max_depth, false)
+}
+
+/// `is_metadata` is set for the root's `Metadata` compound, the only place an int array is kept.
+fn skim_compound<R: Read>(reader: &mut R, depth: usize, max_depth: usize, is_metadata: bool) -> io::Result<NbtCompound> {
+    if depth > max_depth {
+        return Err(invalid("NBT nested too deeply"));
+    }
+    let mut compound = NbtCompound::new();
+    loop {
+        let id = read_u8(reader)?;
+        if id == 0 {
+            return Ok(compound);
+        }
+        let name = read_string(reader)?;
+        let keep = match id {
+            10 => depth == 0 && name == "Metadata",
+            11 => is_metadata && name == "PreviewImageData",
+            _ => false,
+        };
+        if let Some(tag) = skim_payload(reader, id, depth, max_depth, keep)? {
+            compound.insert(name, tag);
+        }
+    }
+}
+
+/// Reads the payload of a tag with type `id`, or skips it and returns `None` for the bulky types.
+/// An int array is only read when `keep` is set, which for a compound marks it as `Metadata`.
+fn skim_payload<R: Read>(reader: &mut R, id: u8, depth: usize, max_depth: usize, keep: bool) -> io::Result<Option<NbtTag>> {
+    let tag = match id {
+        1 => NbtTag::Byte(read_u8(reader)? as i8),
+        2 => NbtTag::Short(i16::from_be_bytes(read_array(reader)?)),
+        3 => NbtTag::Int(i32::from_be_bytes(read_array(reader)?)),
+        4 => NbtTag::Long(i64::from_be_bytes(read_array(reader)?)),
+        5 => NbtTag::Float(f32::from_be_bytes(read_array(reader)?)),
+        6 => NbtTag::Double(f64::from_be_bytes(read_array(reader)?)),
+        7 => {
+            let len = read_len(reader)?;
+            skip(reader, len)?;
+            return Ok(None);
+        }
+        8 => NbtTag::String(read_string(reader)?),
+        9 => {
+            skip_list(reader, depth + 1, max_depth)?;
+            return Ok(None);
+        }
+        10 => NbtTag::Compound(skim_compound(reader, depth + 1, max_depth, keep)?),
+        11 if !keep => {
+            let len = read_len(reader)?;
+            skip(reader, len * 4)?;
+            return Ok(None);
+        }
+        11 => {
+            let len = read_len(reader)?;
+            let mut values = Vec::new();
+            for _ in 0..len {
+                values.push(i32::from_be_bytes(read_array(reader)?));
+            }
+            NbtTag::IntArray(values)
+        }
+        12 => {
+            let len = read_len(reader)?;
+            skip(reader, len * 8)?;
+            return Ok(None);
+        }
+        _ => return Err(invalid(&format!("Unknown NBT tag type {}", id))),
+    };
+    Ok(Some(tag))
+}
+
+fn skip_list<R: Read>(reader: &mut R, depth: usize, max_depth: usize) -> io::Result<()> {
+    if depth > max_depth {
+        return Err(invalid("NBT nested too deeply"));
+    }
+    let id = read_u8(reader)?;
+    let len = read_len(reader)?;
+    match id {
+        0 => Ok(()),
+        1 => skip(reader, len),
+        2 => skip(reader, len * 2),
+        3 | 5 => skip(reader, len * 4),
+        4 | 6 => skip(reader, len * 8),
+        _ => {
+            for _ in 0..len {
+                skim_payload(reader, id, depth, max_depth, false)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    Ok(read_array::<R, 1>(reader)?[0])
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_len<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let len = i32::from_be_bytes(read_array(reader)?);
+    u64::try_from(len).map_err(|_| invalid("Negative NBT length"))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = u16::from_be_bytes(read_array(reader)?);
+    let mut buf = vec![0u8; usize::from(len)];
+    reader.read_exact(&mut buf)?;
+    // Java's modified UTF-8 only differs for NUL and supplementary characters
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.by_ref().take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{litematic, schematic};
+    use crate::test_fixtures::{gzip_bomb_after, sample_schematic};
+    use crate::UniversalSchematic;
+
+    #[test]
+    fn test_peek_matches_full_load() {
+        let schematic = sample_schematic();
+        let dimensions = |loaded: &UniversalSchematic| {
+            let (width, height, length) = loaded.get_dimensions();
+            (width as u32, height as u32, length as u32)
+        };
+
+        let data = litematic::to_litematic(&schematic).unwrap();
+        let (info, loaded) = (peek_info(&data).unwrap(), litematic::from_litematic(&data).unwrap());
+        assert_eq!(info.format, "litematic");
+        assert_eq!(info.name.as_deref(), Some("Sample"));
+        assert_eq!(info.author.as_deref(), Some("Tester"));
+        assert_eq!(info.data_version, Some(3953));
+        assert_eq!((info.width, info.height, info.length), dimensions(&loaded));
+        assert_eq!(info.region_count, 2);
+        assert_eq!(info.block_count, Some(22));
+        assert_eq!(info.preview_image, Some(vec![1, 2, 3]));
+
+        let data = schematic::to_schematic(&schematic).unwrap();
+        let (info, loaded) = (peek_info(&data).unwrap(), schematic::from_schematic(&data).unwrap());
+        assert_eq!(info.format, "schematic");
+        assert_eq!(info.version, 3);
+        assert_eq!(info.name.as_deref(), Some("Sample"));
+        assert_eq!((info.width, info.height, info.length), dimensions(&loaded));
+        assert_eq!(info.block_count, None);
+    }
+
+    #[test]
+    fn test_peek_rejects_other_data() {
+        assert!(peek_info(b"not nbt at all").is_err());
+        let truncated = &schematic::to_schematic(&sample_schematic()).unwrap()[..20];
+        assert!(peek_info(truncated).is_err());
+    }
+
+    #[test]
+    fn test_peek_stops_at_decompressed_limit() {
+        // A root compound holding a byte array that claims 2 GiB of zeros
+        let mut header = vec![10, 0, 0, 7, 0, 6];
+        header.extend_from_slice(b"Blocks");
+        header.extend_from_slice(&i32::MAX.to_be_bytes());
+        let limits = LoadLimits::untrusted();
+        let bomb = gzip_bomb_after(&header, limits.max_decompressed_bytes);
+        let error = peek_info_with_limits(&bomb, &limits).unwrap_err();
+        assert!(error.to_string().contains("exceeds the limit"), "{}", error);
+
+        let within = gzip_bomb_after(&header, 1024);
+        let error = peek_info_with_limits(&within, &limits).unwrap_err();
+        assert!(!error.to_string().contains("exceeds the limit"), "{}", error);
+    }
+}
@@ -1,14 +1,12 @@
-use std::io::{BufReader, Cursor, Read};
+use std::io::{Cursor, Read};
 
-use flate2::Compression;
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
 use quartz_nbt::{NbtCompound, NbtList, NbtTag};
-use quartz_nbt::io::{read_nbt, Flavor};
 use crate::{BlockState, UniversalSchematic};
 use crate::block_entity::BlockEntity;
 use crate::entity::Entity;
 use crate::region::Region;
+use crate::formats::compression::{self, NbtCompression};
+use crate::formats::limits::LoadLimits;
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::JsValue;
@@ -18,10 +16,13 @@ use web_sys::console;
 
 
 pub fn is_schematic(data: &[u8]) -> bool {
-    // Decompress the data
-    let reader = BufReader::with_capacity(1 << 20, data); // 1 MiB buf
-    let mut gz = GzDecoder::new(reader);
-    let (root, _) = match read_nbt(&mut gz, Flavor::Uncompressed) {
+    is_schematic_with_limits(data, &LoadLimits::default())
+}
+
+/// Like [`is_schematic`], but gives up once the data decompresses past `limits`, so sniffing an
+/// upload can't inflate a compressed bomb.
+pub fn is_schematic_with_limits(data: &[u8], limits: &LoadLimits) -> bool {
+    let root = match compression::read_nbt_with_limits(data, limits) {
         Ok(result) => result,
         Err(_) => {
             #[cfg(feature = "wasm")]
@@ -58,13 +59,44 @@ pub fn is_schematic(data: &[u8]) -> bool {
     root.get::<_, &Vec<i8>>("BlockData").is_ok()
 }
 
+#[derive(Debug, Clone)]
+pub struct SchematicWriteOptions {
+    /// Sponge format version, 2 or 3.
+    pub version: i32,
+    pub compression: NbtCompression,
+}
+
+impl Default for SchematicWriteOptions {
+    fn default() -> Self {
+        SchematicWriteOptions { version: 3, compression: NbtCompression::default() }
+    }
+}
+
 // Default function uses v3 format
 pub fn to_schematic(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     to_schematic_v3(schematic)
 }
 
+pub fn to_schematic_with_options(schematic: &UniversalSchematic, options: &SchematicWriteOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let root = match options.version {
+        2 => create_v2_root(schematic),
+        3 => create_v3_root(schematic),
+        version => return Err(format!("Unsupported schematic version {}", version).into()),
+    };
+    compression::write_nbt(&root, options.compression)
+}
+
 // Version 3 format (recommended)
 pub fn to_schematic_v3(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    to_schematic_with_options(schematic, &SchematicWriteOptions::default())
+}
+
+// Version 2 format (legacy compatibility)
+pub fn to_schematic_v2(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    to_schematic_with_options(schematic, &SchematicWriteOptions { version: 2, ..SchematicWriteOptions::default() })
+}
+
+fn create_v3_root(schematic: &UniversalSchematic) -> NbtCompound {
     let mut schematic_data = NbtCompound::new();
 
     // Version 3 format
@@ -78,8 +110,9 @@ pub fn to_schematic_v3(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dy
     schematic_data.insert("Height", NbtTag::Short((height as i16).abs()));
     schematic_data.insert("Length", NbtTag::Short((length as i16).abs()));
 
-    let offset = vec![0, 0, 0];
-    schematic_data.insert("Offset", NbtTag::IntArray(offset));
+    // v3 stores where the minimum corner sits relative to the paste origin
+    let offset = origin_offset(schematic);
+    schematic_data.insert("Offset", NbtTag::IntArray(vec![offset.0, offset.1, offset.2]));
 
     let merged_region = schematic.get_merged_region();
 
@@ -170,15 +203,10 @@ pub fn to_schematic_v3(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dy
     // Create the proper root structure with "Schematic" tag
     let mut root = NbtCompound::new();
     root.insert("Schematic", NbtTag::Compound(schematic_data));
-
-    // Write NBT with proper compression
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-    quartz_nbt::io::write_nbt(&mut encoder, None, &root, quartz_nbt::io::Flavor::Uncompressed)?;
-    Ok(encoder.finish()?)
+    root
 }
 
-// Version 2 format (legacy compatibility)
-pub fn to_schematic_v2(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+fn create_v2_root(schematic: &UniversalSchematic) -> NbtCompound {
     let mut schematic_data = NbtCompound::new();
 
     schematic_data.insert("Version", NbtTag::Int(2)); // Schematic format version 2
@@ -193,6 +221,7 @@ pub fn to_schematic_v2(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dy
 
     schematic_data.insert("Size", NbtTag::IntArray(vec![width as i32, height as i32, length as i32]));
 
+    // v2 keeps the world position in `Offset` and the paste origin in WorldEdit's metadata
     let offset = vec![0, 0, 0];
     schematic_data.insert("Offset", NbtTag::IntArray(offset));
 
@@ -219,15 +248,26 @@ pub fn to_schematic_v2(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dy
     }
     schematic_data.insert("Entities", NbtTag::List(entities));
 
-    schematic_data.insert("Metadata", schematic.metadata.to_nbt());
+    let mut metadata = match schematic.metadata.to_nbt() {
+        NbtTag::Compound(metadata) => metadata,
+        _ => NbtCompound::new(),
+    };
+    let (offset_x, offset_y, offset_z) = origin_offset(schematic);
+    metadata.insert("WEOffsetX", NbtTag::Int(offset_x));
+    metadata.insert("WEOffsetY", NbtTag::Int(offset_y));
+    metadata.insert("WEOffsetZ", NbtTag::Int(offset_z));
+    schematic_data.insert("Metadata", NbtTag::Compound(metadata));
 
     // Create the proper root structure with "Schematic" tag
     let mut root = NbtCompound::new();
     root.insert("Schematic", NbtTag::Compound(schematic_data));
+    root
+}
 
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-    quartz_nbt::io::write_nbt(&mut encoder, None, &root, quartz_nbt::io::Flavor::Uncompressed)?;
-    Ok(encoder.finish()?)
+/// Position of the schematic's minimum corner relative to its origin, as WorldEdit stores it.
+fn origin_offset(schematic: &UniversalSchematic) -> (i32, i32, i32) {
+    let min = schematic.get_bounding_box().min;
+    (min.0 - schematic.origin.0, min.1 - schematic.origin.1, min.2 - schematic.origin.2)
 }
 
 // Palette conversion for v3 (creates clean sequential indices)
@@ -259,15 +299,8 @@ fn convert_palette_with_mapping(palette: &Vec<BlockState>) -> (NbtCompound, Vec<
             continue;
         }
 
-        let key = if block_state.properties.is_empty() {
-            block_state.name.clone()
-        } else {
-            format!("{}[{}]", block_state.name,
-                    block_state.properties.iter()
-                        .map(|(k, v)| format!("{}={}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(","))
-        };
+        // Display sorts the properties, so equal states always get the same key
+        let key = block_state.to_string();
 
         // Check if this block state already exists in the palette
         let mut found_id = None;
@@ -310,15 +343,8 @@ fn convert_palette_v2(palette: &Vec<BlockState>) -> (NbtCompound, i32) {
             continue; // Skip air blocks as we already added it at index 0
         }
 
-        let key = if block_state.properties.is_empty() {
-            block_state.name.clone()
-        } else {
-            format!("{}[{}]", block_state.name,
-                    block_state.properties.iter()
-                        .map(|(k, v)| format!("{}={}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(","))
-        };
+        // Display sorts the properties, so equal states always get the same key
+        let key = block_state.to_string();
 
         nbt_palette.insert(&key, NbtTag::Int(next_id));
         max_id = max_id.max(next_id);
@@ -328,9 +354,12 @@ fn convert_palette_v2(palette: &Vec<BlockState>) -> (NbtCompound, i32) {
     (nbt_palette, max_id as i32)
 }
 pub fn from_schematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
-    let reader   = BufReader::with_capacity(1 << 20, data);   // 1 MiB buf
-    let mut gz   = GzDecoder::new(reader);
-    let (root, _) = read_nbt(&mut gz, Flavor::Uncompressed)?;
+    from_schematic_with_limits(data, &LoadLimits::default())
+}
+
+/// Loads a Sponge schematic, failing cleanly on a size, palette or entity list beyond `limits`.
+pub fn from_schematic_with_limits(data: &[u8], limits: &LoadLimits) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    let root = compression::read_nbt_with_limits(data, limits)?;
 
     let schem = root.get::<_, &NbtCompound>("Schematic").unwrap_or(&root);
     let schem_version = schem.get::<_, i32>("Version")?;
@@ -346,9 +375,11 @@ pub fn from_schematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::er
     let mut schematic = UniversalSchematic::new(name);
     schematic.metadata.mc_version = mc_version;
 
-    let width = schem.get::<_, i16>("Width")? as u32;
-    let height = schem.get::<_, i16>("Height")? as u32;
-    let length = schem.get::<_, i16>("Length")? as u32;
+    // Sponge sizes are unsigned shorts
+    let width = u32::from(schem.get::<_, i16>("Width")? as u16);
+    let height = u32::from(schem.get::<_, i16>("Height")? as u16);
+    let length = u32::from(schem.get::<_, i16>("Length")? as u16);
+    limits.check_region((0, 0, 0), (width as i32, height as i32, length as i32))?;
 
     let block_container=
     if schem_version == 2 {
@@ -357,9 +388,15 @@ pub fn from_schematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::er
         schem.get::<_, &NbtCompound>("Blocks")?
     };
 
-    let block_palette = parse_block_palette(&block_container)?;
+    let block_palette = parse_block_palette(&block_container, limits)?;
 
     let block_data = parse_block_data(&block_container, width, height, length)?;
+    if let Some(&id) = block_data.iter().find(|&&id| id as usize >= block_palette.len()) {
+        return Err(format!("Block data uses palette id {} of {}", id, block_palette.len()).into());
+    }
+
+    let list_len = |compound: &NbtCompound, key: &str| compound.get::<_, &NbtList>(key).map_or(0, |list| list.len());
+    limits.check_entities(list_len(block_container, "BlockEntities") + list_len(schem, "Entities"))?;
 
 
     let mut region = Region::new("Main".to_string(), (0, 0, 0), (width as i32, height as i32, length as i32));
@@ -378,6 +415,25 @@ pub fn from_schematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::er
     }
 
     schematic.add_region(region);
+
+    // The region starts at (0, 0, 0), so the origin is the negated offset
+    let offset = if schem_version == 2 {
+        schem.get::<_, &NbtCompound>("Metadata").ok().and_then(|metadata| {
+            Some((
+                metadata.get::<_, i32>("WEOffsetX").ok()?,
+                metadata.get::<_, i32>("WEOffsetY").ok()?,
+                metadata.get::<_, i32>("WEOffsetZ").ok()?,
+            ))
+        })
+    } else {
+        match schem.get::<_, &[i32]>("Offset") {
+            Ok(&[x, y, z]) => Some((x, y, z)),
+            _ => None,
+        }
+    };
+    if let Some((x, y, z)) = offset {
+        schematic.origin = (-x, -y, -z);
+    }
     Ok(schematic)
 }
 
@@ -386,7 +442,7 @@ pub fn from_schematic(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::er
 fn convert_block_entities(region: &Region) -> NbtList {
     let mut block_entities = NbtList::new();
 
-    for (_, block_entity) in &region.block_entities {
+    for block_entity in region.sorted_block_entities() {
         block_entities.push(block_entity.to_nbt());
     }
 
@@ -403,16 +459,23 @@ fn convert_entities(region: &Region) -> NbtList {
     entities
 }
 
-fn parse_block_palette(region_tag: &NbtCompound) -> Result<Vec<BlockState>, Box<dyn std::error::Error>> {
+fn parse_block_palette(region_tag: &NbtCompound, limits: &LoadLimits) -> Result<Vec<BlockState>, Box<dyn std::error::Error>> {
     let palette_compound = region_tag.get::<_, &NbtCompound>("Palette")?;
     let palette_max = region_tag.get::<_, i32>("PaletteMax") // V2
-        .unwrap_or(palette_compound.len() as i32) as usize; // V3
+        .unwrap_or(palette_compound.len() as i32); // V3
+    // Every id has to be backed by an entry, so a larger maximum can only be bogus
+    let palette_max = usize::try_from(palette_max).ok()
+        .filter(|&max| max <= palette_compound.len())
+        .ok_or_else(|| format!("Invalid PaletteMax {} for {} entries", palette_max, palette_compound.len()))?;
+    limits.check_palette(palette_compound.len())?;
     let mut palette = vec![BlockState::new("minecraft:air".to_string()); palette_max + 1];
 
     for (block_state_str, value) in palette_compound.inner() {
         if let NbtTag::Int(id) = value {
-            let block_state = parse_block_state(block_state_str);
-            palette[*id as usize] = block_state;
+            let slot = usize::try_from(*id).ok()
+                .and_then(|id| palette.get_mut(id))
+                .ok_or_else(|| format!("Palette id {} for '{}' is out of range", id, block_state_str))?;
+            *slot = parse_block_state(block_state_str);
         }
     }
 
@@ -490,6 +553,7 @@ fn parse_block_data(
     };
 
     // ---------- fast var-int decode ----------
+    /// `None` when the data ends mid-varint or the varint runs past 32 bits.
     #[inline]
     fn read_varint(slice: &mut &[u8]) -> Option<u32> {
         let mut out = 0u32;
@@ -502,14 +566,26 @@ fn parse_block_data(
                 return Some(out);
             }
             shift += 7;
+            if shift > 28 {
+                return None;
+            }
         }
         None
     }
 
-    let expected_length = (width * height * length) as usize;
+    let expected_length = width as usize * height as usize * length as usize;
+    // Each id takes at least one byte, so too short an array can be rejected before allocating
+    if block_data_u8.len() < expected_length {
+        return Err(format!(
+            "Block data length mismatch: expected {}, got {} bytes",
+            expected_length,
+            block_data_u8.len()
+        ).into());
+    }
     let mut block_data: Vec<u32> = Vec::with_capacity(expected_length);
 
-    while let Some(id) = read_varint(&mut block_data_u8) {
+    while !block_data_u8.is_empty() {
+        let id = read_varint(&mut block_data_u8).ok_or("Truncated or overlong varint in block data")?;
         block_data.push(id);
     }
 
@@ -532,7 +608,7 @@ fn parse_block_entities(region_tag: &NbtCompound) -> Result<Vec<BlockEntity>, Bo
 
     for tag in block_entities_list.iter() {
         if let NbtTag::Compound(compound) = tag {
-            let block_entity = BlockEntity::from_nbt(compound);
+            let block_entity = BlockEntity::from_nbt(compound)?;
             block_entities.push(block_entity);
         }
     }
@@ -774,4 +850,77 @@ mod tests {
         let mut schematic_output_file = File::create(output_schematic_name).expect("Failed to create schematic file");
         schematic_output_file.write_all(&schematic_output_data).expect("Failed to write schematic file");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_origin_round_trip() {
+        // WorldEdit stored the paste origin one block east and two south of the minimum corner
+        let schematic = from_schematic(&fs::read("tests/samples/and.schem").unwrap()).unwrap();
+        assert_eq!(schematic.origin, (1, 0, 2));
+
+        for data in [to_schematic_v2(&schematic).unwrap(), to_schematic_v3(&schematic).unwrap()] {
+            assert_eq!(from_schematic(&data).unwrap().origin, (1, 0, 2));
+        }
+
+        // Litematic region positions are relative to the origin
+        let litematic = from_litematic(&to_litematic(&schematic).unwrap()).unwrap();
+        assert_eq!(litematic.origin, (0, 0, 0));
+        assert_eq!(litematic.get_bounding_box().min, (-1, 0, -2));
+        assert_eq!(litematic.get_block(0, 1, 0), schematic.get_block(1, 1, 2));
+    }
+
+    #[test]
+    fn test_compression_options_and_detection() {
+        use crate::litematic::{is_litematic, to_litematic_with_options, LitematicWriteOptions};
+        let mut schematic = UniversalSchematic::new("Packed".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(2, 1, 0, BlockState::new("minecraft:glass".to_string()));
+
+        for compression in [NbtCompression::None, NbtCompression::Gzip(0), NbtCompression::Gzip(9), NbtCompression::Zlib(6)] {
+            for version in [2, 3] {
+                let data = to_schematic_with_options(&schematic, &SchematicWriteOptions { version, compression }).unwrap();
+                assert!(is_schematic(&data));
+                assert_eq!(from_schematic(&data).unwrap().get_block(2, 1, 0), schematic.get_block(2, 1, 0));
+            }
+            let options = LitematicWriteOptions { compression, ..LitematicWriteOptions::default() };
+            let data = to_litematic_with_options(&schematic, &options).unwrap();
+            assert!(is_litematic(&data));
+            assert_eq!(from_litematic(&data).unwrap().get_block(2, 1, 0), schematic.get_block(2, 1, 0));
+        }
+        assert!(to_schematic_with_options(&schematic, &SchematicWriteOptions { version: 4, ..SchematicWriteOptions::default() }).is_err());
+    }
+
+    #[test]
+    fn test_hostile_files_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Hostile".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block(3, 1, 2, BlockState::new("minecraft:glass".to_string()));
+        let schem = to_schematic(&schematic).unwrap();
+        assert!(from_schematic_with_limits(&schem, &LoadLimits::untrusted()).is_ok());
+        assert!(from_schematic_with_limits(&schem, &LoadLimits { max_volume: 8, ..LoadLimits::default() }).is_err());
+
+        let tampered = |edit: &dyn Fn(&mut NbtCompound)| {
+            let mut root = compression::read_nbt(&schem).unwrap();
+            edit(root.get_mut::<_, &mut NbtCompound>("Schematic").unwrap());
+            from_schematic(&compression::write_nbt(&root, NbtCompression::None).unwrap())
+        };
+        assert!(tampered(&|schem| {
+            schem.insert("Width", NbtTag::Short(30_000));
+            schem.insert("Height", NbtTag::Short(30_000));
+        }).is_err());
+        assert!(tampered(&|schem| schem.insert("Width", NbtTag::Short(0))).is_err());
+        // Every byte has its continuation bit set, so no varint ever ends
+        assert!(tampered(&|schem| {
+            let blocks = schem.get_mut::<_, &mut NbtCompound>("Blocks").unwrap();
+            blocks.insert("Data", NbtTag::ByteArray(vec![-1; 40]));
+        }).is_err());
+        for pos in [vec![], vec![1]] {
+            assert!(tampered(&|schem| {
+                let mut block_entity = NbtCompound::new();
+                block_entity.insert("Id", NbtTag::String("minecraft:chest".to_string()));
+                block_entity.insert("Pos", NbtTag::IntArray(pos.clone()));
+                let blocks = schem.get_mut::<_, &mut NbtCompound>("Blocks").unwrap();
+                blocks.insert("BlockEntities", NbtTag::List(NbtList::from(vec![NbtTag::Compound(block_entity)])));
+            }).is_err());
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! MagicaVoxel `.vox` files.
+//!
+//! MagicaVoxel is Z-up, so a voxel at (x, y, z) becomes the block at (x, z, -1 - y). Each model
+//! in the scene becomes its own region, placed using the scene graph translations. Model
+//! rotations are not applied.
+
+use std::collections::HashMap;
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::{get_block_color, is_invisible, BlockColorPalette};
+use crate::formats::limits::LoadLimits;
+use crate::region::Region;
+
+/// MagicaVoxel models are limited to 256 voxels along each axis.
+const MAX_MODEL_SIZE: i32 = 256;
+
+/// Color used on export for blocks missing from the color table.
+const UNKNOWN_BLOCK_COLOR: [u8; 3] = [128, 128, 128];
+
+#[derive(Debug, Clone, Default)]
+pub struct VoxImportOptions {
+    /// Blocks that voxel colors are matched against.
+    pub palette: BlockColorPalette,
+}
+
+struct VoxModel {
+    size: (i32, i32, i32),
+    voxels: Vec<[u8; 4]>,
+}
+
+/// A model instance from the scene graph, positioned by the translation of its center.
+struct Placement {
+    model: usize,
+    name: Option<String>,
+    center: (i32, i32, i32),
+}
+
+enum SceneNode {
+    Transform { name: Option<String>, child: i32, translation: (i32, i32, i32) },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+struct VoxReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> VoxReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.pos < len {
+            return Err("Unexpected end of .vox data".to_string());
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        usize::try_from(self.i32()?).map_err(|_| "Negative length in .vox data".to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn dict(&mut self) -> Result<HashMap<String, String>, String> {
+        let count = self.len()?;
+        let mut dict = HashMap::new();
+        for _ in 0..count {
+            let key = self.string()?;
+            let value = self.string()?;
+            dict.insert(key, value);
+        }
+        Ok(dict)
+    }
+}
+
+pub fn is_vox(data: &[u8]) -> bool {
+    data.len() >= 8 && &data[0..4] == b"VOX "
+}
+
+pub fn from_vox(data: &[u8]) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    from_vox_with_options(data, &VoxImportOptions::default())
+}
+
+pub fn from_vox_with_options(data: &[u8], options: &VoxImportOptions) -> Result<UniversalSchematic, Box<dyn std::error::Error>> {
+    if !is_vox(data) {
+        return Err("Not a MagicaVoxel file".into());
+    }
+    let mut cursor = VoxReader { data, pos: 8 };
+    if cursor.take(4)? != b"MAIN" {
+        return Err("Missing MAIN chunk".into());
+    }
+    let main_content = cursor.len()?;
+    cursor.take(main_content)?;
+    let main_children = cursor.len()?;
+    let end = cursor.pos.checked_add(main_children).filter(|&end| end <= data.len()).unwrap_or(data.len());
+
+    let mut models: Vec<VoxModel> = Vec::new();
+    let mut pending_size = None;
+    let mut palette = default_palette();
+    let mut nodes: HashMap<i32, SceneNode> = HashMap::new();
+
+    while cursor.pos + 12 <= end {
+        let id: [u8; 4] = cursor.take(4)?.try_into().unwrap();
+        let content_len = cursor.len()?;
+        let children_len = cursor.len()?;
+        let mut chunk = VoxReader { data: cursor.take(content_len)?, pos: 0 };
+        cursor.take(children_len)?;
+
+        match &id {
+            b"SIZE" => pending_size = Some((chunk.i32()?, chunk.i32()?, chunk.i32()?)),
+            b"XYZI" => {
+                let size = pending_size.take().ok_or("XYZI chunk without a SIZE chunk")?;
+                let count = chunk.len()?;
+                let bytes = chunk.take(count.checked_mul(4).ok_or("Too many voxels")?)?;
+                let voxels = bytes.chunks_exact(4).map(|v| [v[0], v[1], v[2], v[3]]).collect();
+                models.push(VoxModel { size, voxels });
+            }
+            b"RGBA" => {
+                let bytes = chunk.take(256 * 4)?;
+                // Color index i is stored at position i - 1
+                for i in 0..255 {
+                    palette[i + 1] = [bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2]];
+                }
+            }
+            b"nTRN" => {
+                let node_id = chunk.i32()?;
+                let attributes = chunk.dict()?;
+                let child = chunk.i32()?;
+                let _reserved = chunk.i32()?;
+                let _layer = chunk.i32()?;
+                let frames = chunk.len()?;
+                let mut translation = (0, 0, 0);
+                for frame in 0..frames {
+                    let frame_attributes = chunk.dict()?;
+                    if frame == 0 {
+                        if let Some(t) = frame_attributes.get("_t") {
+                            translation = parse_translation(t);
+                        }
+                    }
+                }
+                let name = attributes.get("_name").cloned();
+                nodes.insert(node_id, SceneNode::Transform { name, child, translation });
+            }
+            b"nGRP" => {
+                let node_id = chunk.i32()?;
+                chunk.dict()?;
+                let count = chunk.len()?;
+                let children = (0..count).map(|_| chunk.i32()).collect::<Result<Vec<_>, _>>()?;
+                nodes.insert(node_id, SceneNode::Group { children });
+            }
+            b"nSHP" => {
+                let node_id = chunk.i32()?;
+                chunk.dict()?;
+                let count = chunk.len()?;
+                let mut shape_models = Vec::new();
+                for _ in 0..count {
+                    shape_models.push(chunk.i32()?);
+                    chunk.dict()?;
+                }
+                nodes.insert(node_id, SceneNode::Shape { models: shape_models });
+            }
+            _ => {}
+        }
+    }
+
+    if models.is_empty() {
+        return Err("The .vox file contains no models".into());
+    }
+
+    let mut placements: Vec<Placement> = Vec::new();
+    if nodes.contains_key(&0) {
+        collect_placements(&nodes, 0, (0, 0, 0), None, &mut placements, 0)?;
+    }
+    if placements.is_empty() {
+        // No scene graph: models sit on the origin
+        placements = (0..models.len())
+            .map(|i| {
+                let (sx, sy, sz) = models[i].size;
+                Placement { model: i, name: None, center: (sx / 2, sy / 2, sz / 2) }
+            })
+            .collect();
+    }
+
+    let mut block_palette = options.palette.clone();
+    let mut blocks_by_color: HashMap<u8, BlockState> = HashMap::new();
+    let mut schematic = UniversalSchematic::new("Unnamed".to_string());
+
+    for (placement_index, Placement { model, name, center }) in placements.into_iter().enumerate() {
+        let model = match models.get(model) {
+            Some(model) => model,
+            None => continue,
+        };
+        let (sx, sy, sz) = model.size;
+        let valid_size = |s: i32| s > 0 && s <= MAX_MODEL_SIZE;
+        if !valid_size(sx) || !valid_size(sy) || !valid_size(sz) {
+            continue;
+        }
+        // Widened, as translations can sit anywhere in the i32 range
+        let min = (i64::from(center.0) - i64::from(sx / 2), i64::from(center.1) - i64::from(sy / 2), i64::from(center.2) - i64::from(sz / 2));
+        let position = match (i32::try_from(min.0), i32::try_from(min.2), i32::try_from(-min.1 - i64::from(sy))) {
+            (Ok(x), Ok(y), Ok(z)) => (x, y, z),
+            _ => return Err(format!("Placement {} is out of range", placement_index).into()),
+        };
+        LoadLimits::default().check_region(position, (sx, sz, sy))?;
+
+        // Models split from one large region share a name
+        let base_name = name.unwrap_or_else(|| format!("model_{}", placement_index));
+        let mut region_name = base_name.clone();
+        let mut suffix = 1;
+        while schematic.regions.contains_key(&region_name) {
+            region_name = format!("{}_{}", base_name, suffix);
+            suffix += 1;
+        }
+
+        let mut region = Region::new(region_name, position, (sx, sz, sy));
+        let mut region_ids: HashMap<u8, usize> = HashMap::new();
+        for &[x, y, z, color] in &model.voxels {
+            let (x, y, z) = (x as i32, y as i32, z as i32);
+            if color == 0 || x >= sx || y >= sy || z >= sz {
+                continue;
+            }
+            let block = match blocks_by_color.get(&color) {
+                Some(block) => block.clone(),
+                None => {
+                    let block = block_palette.nearest(palette[color as usize])
+                        .cloned()
+                        .ok_or("The import block palette is empty")?;
+                    blocks_by_color.insert(color, block.clone());
+                    block
+                }
+            };
+            let id = *region_ids.entry(color).or_insert_with(|| {
+                region.palette.push(block);
+                region.palette.len() - 1
+            });
+            // Local block coordinates: x stays, z becomes height, y is flipped into z
+            let (bx, by, bz) = (x, z, sy - 1 - y);
+            let index = (bx + bz * sx + by * sx * sy) as usize;
+            region.blocks[index] = id;
+        }
+        schematic.add_region(region);
+    }
+
+    Ok(schematic)
+}
+
+fn collect_placements(
+    nodes: &HashMap<i32, SceneNode>,
+    node_id: i32,
+    translation: (i32, i32, i32),
+    name: Option<String>,
+    placements: &mut Vec<Placement>,
+    depth: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Guards against cycles in malformed files
+    if depth > 64 {
+        return Ok(());
+    }
+    match nodes.get(&node_id) {
+        Some(SceneNode::Transform { name: node_name, child, translation: t }) => {
+            let translation = match (translation.0.checked_add(t.0), translation.1.checked_add(t.1), translation.2.checked_add(t.2)) {
+                (Some(x), Some(y), Some(z)) => (x, y, z),
+                _ => return Err(format!("Translation of node {} is out of range", node_id).into()),
+            };
+            let name = node_name.clone().or(name);
+            collect_placements(nodes, *child, translation, name, placements, depth + 1)?;
+        }
+        Some(SceneNode::Group { children }) => {
+            for child in children {
+                collect_placements(nodes, *child, translation, name.clone(), placements, depth + 1)?;
+            }
+        }
+        Some(SceneNode::Shape { models }) => {
+            for &model in models {
+                if model >= 0 {
+                    placements.push(Placement { model: model as usize, name: name.clone(), center: translation });
+                }
+            }
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+fn parse_translation(value: &str) -> (i32, i32, i32) {
+    let mut parts = value.split_whitespace().map(|p| p.parse::<i32>().unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+/// MagicaVoxel's built-in palette, used when a file has no RGBA chunk. Index 0 is unused.
+fn default_palette() -> [[u8; 3]; 256] {
+    let mut palette = [[0u8; 3]; 256];
+    let levels = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let mut i = 1;
+    for &r in &levels {
+        for &g in &levels {
+            for &b in &levels {
+                if i < 216 {
+                    palette[i] = [r, g, b];
+                    i += 1;
+                }
+            }
+        }
+    }
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    for (channel, masks) in [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]].iter().enumerate() {
+        for (step, &value) in ramp.iter().enumerate() {
+            palette[216 + channel * 10 + step] = [value * masks[0], value * masks[1], value * masks[2]];
+        }
+    }
+    palette
+}
+
+pub fn to_vox(schematic: &UniversalSchematic) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    // Sorted so the output does not depend on map order
+    let mut regions: Vec<&Region> = schematic.regions.values().collect();
+    regions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // Palette: the 255 most common colors, anything else snaps to the closest of those
+    let mut color_counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for region in &regions {
+        let mut counts = vec![0usize; region.palette.len()];
+        for &id in &region.blocks {
+            counts[id] += 1;
+        }
+        for (block, count) in region.palette.iter().zip(counts) {
+            if count > 0 && !is_invisible(&block.name) {
+                let color = get_block_color(&block.name).unwrap_or(UNKNOWN_BLOCK_COLOR);
+                *color_counts.entry(color).or_insert(0) += count;
+            }
+        }
+    }
+    let mut colors: Vec<([u8; 3], usize)> = color_counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let kept: Vec<[u8; 3]> = colors.iter().take(255).map(|(color, _)| *color).collect();
+    let mut kept_palette = BlockColorPalette::from_colors(
+        kept.iter().map(|&color| (BlockState::new(String::new()), color)).collect()
+    );
+    let mut color_index: HashMap<[u8; 3], u8> = HashMap::new();
+    for (color, _) in &colors {
+        let index = kept_palette.nearest_index(*color).unwrap_or(0);
+        color_index.insert(*color, index as u8 + 1);
+    }
+
+    let mut model_chunks = Vec::new();
+    let mut scene_chunks = Vec::new();
+    let mut model_count = 0;
+    for region in &regions {
+        let bbox = region.get_bounding_box();
+        let (width, height, length) = region.get_dimensions();
+        let block_ids: Vec<u8> = region.palette.iter()
+            .map(|block| if is_invisible(&block.name) {
+                0
+            } else {
+                // Palette entries no block uses were not counted and never get looked up
+                color_index.get(&get_block_color(&block.name).unwrap_or(UNKNOWN_BLOCK_COLOR)).copied().unwrap_or(0)
+            })
+            .collect();
+
+        for x0 in (0..width).step_by(MAX_MODEL_SIZE as usize) {
+            for y0 in (0..height).step_by(MAX_MODEL_SIZE as usize) {
+                for z0 in (0..length).step_by(MAX_MODEL_SIZE as usize) {
+                    let pw = (width - x0).min(MAX_MODEL_SIZE);
+                    let ph = (height - y0).min(MAX_MODEL_SIZE);
+                    let pl = (length - z0).min(MAX_MODEL_SIZE);
+
+                    let mut voxels = Vec::new();
+                    for y in 0..ph {
+                        for z in 0..pl {
+                            for x in 0..pw {
+                                let index = ((x0 + x) + (z0 + z) * width + (y0 + y) * width * length) as usize;
+                                let color = block_ids[region.blocks[index]];
+                                if color != 0 {
+                                    voxels.extend_from_slice(&[x as u8, (pl - 1 - z) as u8, y as u8, color]);
+                                }
+                            }
+                        }
+                    }
+
+                    let mut size = Vec::new();
+                    for v in [pw, pl, ph] {
+                        size.extend_from_slice(&v.to_le_bytes());
+                    }
+                    model_chunks.extend(chunk(b"SIZE", &size, &[]));
+                    let mut xyzi = ((voxels.len() / 4) as i32).to_le_bytes().to_vec();
+                    xyzi.extend(voxels);
+                    model_chunks.extend(chunk(b"XYZI", &xyzi, &[]));
+
+                    // Translation of the model center in MagicaVoxel space
+                    let min = (bbox.min.0 + x0, bbox.min.1 + y0, bbox.min.2 + z0);
+                    let vox_min = (min.0, -min.2 - pl, min.1);
+                    let center = (vox_min.0 + pw / 2, vox_min.1 + pl / 2, vox_min.2 + ph / 2);
+                    let transform_id = 2 + model_count * 2;
+                    scene_chunks.extend(transform_chunk(
+                        transform_id,
+                        Some(&region.name),
+                        transform_id + 1,
+                        0,
+                        center,
+                    ));
+                    let mut shape = Vec::new();
+                    write_i32(&mut shape, transform_id + 1);
+                    write_dict(&mut shape, &[]);
+                    write_i32(&mut shape, 1);
+                    write_i32(&mut shape, model_count);
+                    write_dict(&mut shape, &[]);
+                    scene_chunks.extend(chunk(b"nSHP", &shape, &[]));
+                    model_count += 1;
+                }
+            }
+        }
+    }
+
+    let mut children = model_chunks;
+    children.extend(transform_chunk(0, None, 1, -1, (0, 0, 0)));
+    let mut group = Vec::new();
+    write_i32(&mut group, 1);
+    write_dict(&mut group, &[]);
+    write_i32(&mut group, model_count);
+    for i in 0..model_count {
+        write_i32(&mut group, 2 + i * 2);
+    }
+    children.extend(chunk(b"nGRP", &group, &[]));
+    children.extend(scene_chunks);
+
+    let mut rgba = Vec::with_capacity(256 * 4);
+    for i in 0..256 {
+        let color = kept.get(i).copied().unwrap_or([0, 0, 0]);
+        rgba.extend_from_slice(&[color[0], color[1], color[2], 255]);
+    }
+    children.extend(chunk(b"RGBA", &rgba, &[]));
+
+    let mut out = b"VOX ".to_vec();
+    write_i32(&mut out, 150);
+    out.extend(chunk(b"MAIN", &[], &children));
+    Ok(out)
+}
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(12 + content.len() + children.len());
+    out.extend_from_slice(id);
+    write_i32(&mut out, content.len() as i32);
+    write_i32(&mut out, children.len() as i32);
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+    out
+}
+
+fn transform_chunk(node_id: i32, name: Option<&str>, child: i32, layer: i32, translation: (i32, i32, i32)) -> Vec<u8> {
+    let mut content = Vec::new();
+    write_i32(&mut content, node_id);
+    match name {
+        Some(name) => write_dict(&mut content, &[("_name", name)]),
+        None => write_dict(&mut content, &[]),
+    }
+    write_i32(&mut content, child);
+    write_i32(&mut content, -1);
+    write_i32(&mut content, layer);
+    write_i32(&mut content, 1);
+    let t = format!("{} {} {}", translation.0, translation.1, translation.2);
+    write_dict(&mut content, &[("_t", &t)]);
+    chunk(b"nTRN", &content, &[])
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dict(out: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    write_i32(out, entries.len() as i32);
+    for (key, value) in entries {
+        write_i32(out, key.len() as i32);
+        out.extend_from_slice(key.as_bytes());
+        write_i32(out, value.len() as i32);
+        out.extend_from_slice(value.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vox_roundtrip() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:white_wool".to_string()));
+        schematic.set_block(2, 1, 3, BlockState::new("minecraft:black_concrete".to_string()));
+        schematic.set_block(1, 2, 1, BlockState::new("minecraft:red_wool".to_string()));
+        schematic.set_block(-4, 0, 5, BlockState::new("minecraft:gold_block".to_string()));
+
+        let bytes = to_vox(&schematic).unwrap();
+        assert!(is_vox(&bytes));
+        let loaded = from_vox(&bytes).unwrap();
+
+        assert_eq!(loaded.regions.len(), 1);
+        assert!(loaded.regions.contains_key("Main"));
+        for (position, block) in schematic.iter_blocks() {
+            let loaded_block = loaded.get_block(position.x, position.y, position.z);
+            if block.name == "minecraft:air" {
+                assert_eq!(loaded_block.map(|b| b.name.as_str()), Some("minecraft:air"));
+            } else {
+                assert_eq!(loaded_block, Some(block), "block at {:?}", position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_vox_multiple_models_and_block_set() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block_in_region("first", 0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block_in_region("second", 10, 5, -3, BlockState::new("minecraft:white_concrete".to_string()));
+
+        let bytes = to_vox(&schematic).unwrap();
+        let options = VoxImportOptions {
+            palette: BlockColorPalette::from_blocks(&[
+                BlockState::new("minecraft:black_wool".to_string()),
+                BlockState::new("minecraft:white_wool".to_string()),
+            ]),
+        };
+        let loaded = from_vox_with_options(&bytes, &options).unwrap();
+
+        assert_eq!(loaded.regions.len(), 2);
+        assert_eq!(loaded.get_block_from_region("first", 0, 0, 0).unwrap().name, "minecraft:black_wool");
+        assert_eq!(loaded.get_block_from_region("second", 10, 5, -3).unwrap().name, "minecraft:white_wool");
+    }
+
+    #[test]
+    fn test_vox_rejects_out_of_range_translations() {
+        let scene = |outer: (i32, i32, i32), inner: (i32, i32, i32)| {
+            let mut children = chunk(b"SIZE", &[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0], &[]);
+            children.extend(chunk(b"XYZI", &[1, 0, 0, 0, 0, 0, 0, 1], &[]));
+            children.extend(transform_chunk(0, None, 1, -1, outer));
+            children.extend(transform_chunk(1, None, 2, 0, inner));
+            let mut shape = Vec::new();
+            write_i32(&mut shape, 2);
+            write_dict(&mut shape, &[]);
+            write_i32(&mut shape, 1);
+            write_i32(&mut shape, 0);
+            write_dict(&mut shape, &[]);
+            children.extend(chunk(b"nSHP", &shape, &[]));
+            let mut out = b"VOX ".to_vec();
+            write_i32(&mut out, 150);
+            out.extend(chunk(b"MAIN", &[], &children));
+            from_vox(&out)
+        };
+        assert!(scene((1, 2, 3), (4, 5, 6)).is_ok());
+        assert!(scene((1, 0, 0), (i32::MAX, 0, 0)).is_err());
+        assert!(scene((0, 0, i32::MIN), (0, 0, -1)).is_err());
+        assert!(scene((0, 0, i32::MAX), (0, 0, 0)).is_err());
+        assert!(scene((0, i32::MIN, 0), (0, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = default_palette();
+        assert_eq!(palette[1], [0xff, 0xff, 0xff]);
+        assert_eq!(palette[2], [0xff, 0xff, 0xcc]);
+        assert_eq!(palette[216], [0xee, 0, 0]);
+        assert_eq!(palette[255], [0x11, 0x11, 0x11]);
+    }
+}
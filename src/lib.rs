@@ -14,6 +14,19 @@ mod block_position;
 pub mod utils;
 mod item;
 mod chunk;
+pub mod block_colors;
+pub mod render;
+pub mod map_art;
+pub mod terrain;
+pub mod text;
+pub mod redstone;
+pub mod lighting;
+pub mod survival;
+pub mod build_order;
+pub mod datafixer;
+pub mod fingerprint;
+#[cfg(test)]
+mod test_fixtures;
 
 // Feature-specific modules
 #[cfg(feature = "wasm")]
@@ -28,8 +41,8 @@ mod php;
 // Public re-exports
 pub use universal_schematic::UniversalSchematic;
 pub use block_state::BlockState;
-pub use region::Region;
-pub use formats::{litematic, schematic};
+pub use region::{Region, PendingTick};
+pub use formats::{litematic, schematic, commands, mcstructure, bedrock_blocks, vox, mesh, native, json, compression, peek, limits};
 pub use print_utils::{format_schematic, format_json_schematic};
 
 // Re-export WASM types when building with WASM feature
@@ -0,0 +1,364 @@
+//! Block light and sky light.
+//!
+//! Light is computed on a grid covering the schematic plus one block of air on every side, so
+//! light can reach the build from the outside. Block light spreads from emitters like torches
+//! and glowstone; sky light comes straight down at full strength through air and glass and
+//! then spreads sideways. Both lose one level per block travelled, and more through leaves and
+//! water. Slabs, stairs and other partial blocks are treated as letting all light through.
+
+use std::collections::VecDeque;
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::is_invisible;
+use crate::formats::mesh::is_transparent;
+use crate::redstone::is_conductor;
+
+const MAX_LIGHT: u8 = 15;
+
+fn short_name(block: &BlockState) -> &str {
+    block.name.strip_prefix("minecraft:").unwrap_or(&block.name)
+}
+
+fn property_is(block: &BlockState, key: &str, value: &str) -> bool {
+    block.get_property(key).map(|v| v.as_str()) == Some(value)
+}
+
+fn property_number(block: &BlockState, key: &str) -> Option<u8> {
+    block.get_property(key).and_then(|v| v.parse().ok())
+}
+
+/// The light level a block gives off, using vanilla values.
+pub fn light_emission(block: &BlockState) -> u8 {
+    let name = short_name(block);
+    let lit = property_is(block, "lit", "true");
+    match name {
+        "glowstone" | "sea_lantern" | "jack_o_lantern" | "beacon" | "shroomlight" | "lantern" | "conduit"
+        | "end_portal" | "end_gateway" | "lava" | "fire" | "ochre_froglight" | "verdant_froglight"
+        | "pearlescent_froglight" => 15,
+        "torch" | "wall_torch" | "end_rod" => 14,
+        "furnace" | "smoker" | "blast_furnace" if lit => 13,
+        "nether_portal" => 11,
+        "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "crying_obsidian" => 10,
+        "redstone_torch" | "redstone_wall_torch" if lit || block.get_property("lit").is_none() => 7,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "small_amethyst_bud" | "brewing_stand" | "brown_mushroom" | "dragon_egg" | "end_portal_frame"
+        | "sculk_sensor" | "calibrated_sculk_sensor" => 1,
+        "redstone_lamp" if lit => 15,
+        "campfire" if lit || block.get_property("lit").is_none() => 15,
+        "soul_campfire" if lit || block.get_property("lit").is_none() => 10,
+        "cave_vines" | "cave_vines_plant" if property_is(block, "berries", "true") => 14,
+        "respawn_anchor" => property_number(block, "charges").map_or(0, |c| match c {
+            0 => 0,
+            c => (c * 4 - 1).min(MAX_LIGHT),
+        }),
+        "light" => property_number(block, "level").unwrap_or(MAX_LIGHT).min(MAX_LIGHT),
+        "sea_pickle" if property_is(block, "waterlogged", "true") => {
+            3 * (property_number(block, "pickles").unwrap_or(1) + 1)
+        }
+        _ if name.ends_with("candle") && lit => 3 * property_number(block, "candles").unwrap_or(1).min(4),
+        _ if name.ends_with("copper_bulb") && lit => {
+            if name.contains("weathered") { 8 } else if name.contains("oxidized") { 4 } else if name.contains("exposed") { 12 } else { 15 }
+        }
+        _ => 0,
+    }
+}
+
+/// How much light a block takes away when light passes into it: 0 for air, glass and partial
+/// blocks, 1 for leaves, water and ice, and 15 for full opaque blocks.
+pub fn light_opacity(block: &BlockState) -> u8 {
+    let name = short_name(block);
+    if is_invisible(&block.name) {
+        return 0;
+    }
+    if name.ends_with("_leaves") || matches!(name, "water" | "ice" | "frosted_ice" | "cobweb" | "slime_block" | "honey_block") {
+        return 1;
+    }
+    if is_transparent(&block.name) {
+        return 0;
+    }
+    let opaque = is_conductor(block)
+        || matches!(name, "glowstone" | "sea_lantern" | "redstone_block" | "observer" | "soul_sand" | "mud")
+        || (matches!(name, "piston" | "sticky_piston") && !property_is(block, "extended", "true"));
+    if opaque { MAX_LIGHT } else { 0 }
+}
+
+/// Light levels for every block in a box, stored x first, then z, then y.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightGrid {
+    pub min: (i32, i32, i32),
+    pub size: (i32, i32, i32),
+    pub block_light: Vec<u8>,
+    pub sky_light: Vec<u8>,
+    /// Sky light outside the grid: 15 under open sky, 0 otherwise.
+    outside_sky_light: u8,
+}
+
+impl LightGrid {
+    pub fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        // Widened, as the grid can sit anywhere in the i32 range
+        let offset = |v: i32, min: i32, size: i32| {
+            usize::try_from(i64::from(v) - i64::from(min)).ok().filter(|&d| d < size as usize)
+        };
+        let dx = offset(x, self.min.0, self.size.0)?;
+        let dy = offset(y, self.min.1, self.size.1)?;
+        let dz = offset(z, self.min.2, self.size.2)?;
+        let (width, length) = (self.size.0 as usize, self.size.2 as usize);
+        Some(dx + (dz + dy * length) * width)
+    }
+
+    pub fn get_block_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.index(x, y, z).map_or(0, |i| self.block_light[i])
+    }
+
+    pub fn get_sky_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.index(x, y, z).map_or(self.outside_sky_light, |i| self.sky_light[i])
+    }
+
+    /// The brighter of block light and sky light, i.e. the light level at full daylight.
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.get_block_light(x, y, z).max(self.get_sky_light(x, y, z))
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (width, height, length) = (self.size.0 as usize, self.size.1 as usize, self.size.2 as usize);
+        let x = index % width;
+        let z = (index / width) % length;
+        let y = index / (width * length);
+        let layer = width * length;
+        [
+            (x > 0).then(|| index - 1),
+            (x + 1 < width).then(|| index + 1),
+            (z > 0).then(|| index - width),
+            (z + 1 < length).then(|| index + width),
+            (y > 0).then(|| index - layer),
+            (y + 1 < height).then(|| index + layer),
+        ].into_iter().flatten()
+    }
+}
+
+/// Spreads light from the cells in `queue`, dimming it by at least one level per step.
+fn propagate(grid: &LightGrid, light: &mut [u8], opacity: &[u8], mut queue: VecDeque<usize>) {
+    while let Some(index) = queue.pop_front() {
+        let level = light[index];
+        for neighbour in grid.neighbours(index) {
+            let reached = level.saturating_sub(opacity[neighbour].max(1));
+            if reached > light[neighbour] {
+                light[neighbour] = reached;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Computes block light and, if the build stands under open sky, sky light. Fails when the
+/// regions are spread so far apart that the grid wouldn't fit in memory.
+pub fn compute_light(schematic: &UniversalSchematic, open_sky: bool) -> Result<LightGrid, String> {
+    let (min, size) = if schematic.regions.is_empty() {
+        ((0, 0, 0), (0, 0, 0))
+    } else {
+        let bounding_box = schematic.get_bounding_box();
+        let (width, height, length) = bounding_box.get_dimensions();
+        let min = bounding_box.min;
+        // One block of padding on every side
+        let grown = |v: i32, by: i32| v.checked_add(by).ok_or_else(|| format!("Bounding box at {:?} is out of range", min));
+        let padded_min = (grown(min.0, -1)?, grown(min.1, -1)?, grown(min.2, -1)?);
+        (padded_min, (grown(width, 2)?, grown(height, 2)?, grown(length, 2)?))
+    };
+    let volume = [size.0, size.1, size.2].iter()
+        .try_fold(1usize, |volume, &d| volume.checked_mul(usize::try_from(d).ok()?))
+        .ok_or_else(|| format!("Light grid of {:?} blocks is too large", size))?;
+    let mut block_light = Vec::new();
+    block_light.try_reserve_exact(volume).map_err(|_| format!("Light grid of {:?} blocks is too large", size))?;
+    block_light.resize(volume, 0);
+    let mut grid = LightGrid {
+        min,
+        size,
+        block_light,
+        sky_light: vec![0; volume],
+        outside_sky_light: if open_sky { MAX_LIGHT } else { 0 },
+    };
+    let mut opacity = vec![0u8; volume];
+    let mut emitters = VecDeque::new();
+    for (pos, block) in schematic.iter_blocks() {
+        let Some(index) = grid.index(pos.x, pos.y, pos.z) else { continue };
+        opacity[index] = light_opacity(block);
+        let emission = light_emission(block);
+        if emission > 0 {
+            grid.block_light[index] = emission;
+            emitters.push_back(index);
+        }
+    }
+    let mut block_light = std::mem::take(&mut grid.block_light);
+    propagate(&grid, &mut block_light, &opacity, emitters);
+    grid.block_light = block_light;
+
+    if open_sky {
+        let mut sky_light = std::mem::take(&mut grid.sky_light);
+        let mut lit = VecDeque::new();
+        let layer = size.0 as usize * size.2 as usize;
+        for column in 0..layer {
+            let mut level = MAX_LIGHT;
+            for y in (0..size.1 as usize).rev() {
+                let index = y * layer + column;
+                level = level.saturating_sub(opacity[index]);
+                if level == 0 {
+                    break;
+                }
+                sky_light[index] = level;
+                lit.push_back(index);
+            }
+        }
+        propagate(&grid, &mut sky_light, &opacity, lit);
+        grid.sky_light = sky_light;
+    }
+    Ok(grid)
+}
+
+/// Whether a mob can stand in this block: empty, or something without collision.
+fn is_spawn_space(block: Option<&BlockState>) -> bool {
+    let Some(block) = block else { return true };
+    let name = short_name(block);
+    (is_invisible(&block.name) && name != "barrier")
+        || name.ends_with("_rail")
+        || name.ends_with("_button")
+        || name.ends_with("_sapling")
+        || matches!(name,
+            "short_grass" | "grass" | "fern" | "dead_bush" | "dandelion" | "poppy" | "rail" | "lever"
+            | "redstone_wire" | "tripwire" | "vine" | "glow_lichen")
+}
+
+/// Whether hostile mobs can spawn on top of this block.
+fn is_spawn_surface(block: &BlockState) -> bool {
+    let name = short_name(block);
+    if matches!(name, "barrier" | "magma_block") {
+        return false;
+    }
+    if name.ends_with("_slab") {
+        return matches!(block.get_property("type").map(|t| t.as_str()), Some("top" | "double"));
+    }
+    if name.ends_with("_stairs") {
+        return property_is(block, "half", "top");
+    }
+    light_opacity(block) == MAX_LIGHT
+}
+
+/// Spots where hostile mobs could spawn: a two block tall space above a spawnable block with
+/// no block light. With `include_sky_light`, the sky light has to be 0 as well, which leaves
+/// the spots that are dark even during the day.
+pub fn find_dark_spots(schematic: &UniversalSchematic, grid: &LightGrid, include_sky_light: bool) -> Vec<(i32, i32, i32)> {
+    let mut spots = Vec::new();
+    if schematic.regions.is_empty() {
+        return spots;
+    }
+    let bounding_box = schematic.get_bounding_box();
+    let (min, max) = (bounding_box.min, bounding_box.max);
+    for y in min.1 + 1..=max.1 + 1 {
+        for z in min.2..=max.2 {
+            for x in min.0..=max.0 {
+                let dark = grid.get_block_light(x, y, z) == 0
+                    && (!include_sky_light || grid.get_sky_light(x, y, z) == 0);
+                if dark
+                    && schematic.get_block(x, y - 1, z).is_some_and(is_spawn_surface)
+                    && is_spawn_space(schematic.get_block(x, y, z))
+                    && is_spawn_space(schematic.get_block(x, y + 1, z)) {
+                    spots.push((x, y, z));
+                }
+            }
+        }
+    }
+    spots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(name: &str) -> BlockState {
+        BlockState::new(format!("minecraft:{}", name))
+    }
+
+    /// A hollow stone box from (0, 0, 0) to (size - 1, size - 1, size - 1).
+    fn stone_box(size: i32) -> UniversalSchematic {
+        let mut schematic = UniversalSchematic::new("Box".to_string());
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let edge = [x, y, z].iter().any(|&c| c == 0 || c == size - 1);
+                    schematic.set_block(x, y, z, block(if edge { "stone" } else { "air" }));
+                }
+            }
+        }
+        schematic
+    }
+
+    #[test]
+    fn test_block_light_spreads_and_stops_at_walls() {
+        let mut schematic = stone_box(9);
+        schematic.set_block(1, 1, 1, block("torch"));
+        let grid = compute_light(&schematic, true).unwrap();
+        assert_eq!(grid.get_block_light(1, 1, 1), 14);
+        assert_eq!(grid.get_block_light(2, 1, 1), 13);
+        assert_eq!(grid.get_block_light(3, 2, 4), 14 - 2 - 1 - 3);
+        assert_eq!(grid.get_block_light(0, 1, 1), 0);
+        assert_eq!(grid.get_block_light(-1, 1, 1), 0);
+        // Sealed in, so no sky light gets inside
+        assert_eq!(grid.get_sky_light(4, 4, 4), 0);
+        assert_eq!(grid.get_sky_light(4, 9, 4), 15);
+    }
+
+    #[test]
+    fn test_sky_light_under_roof_and_leaves() {
+        let mut schematic = UniversalSchematic::new("Roof".to_string());
+        for x in 0..5 {
+            for z in 0..5 {
+                schematic.set_block(x, 0, z, block("stone"));
+                schematic.set_block(x, 3, z, block("oak_planks"));
+            }
+        }
+        schematic.set_block(2, 3, 2, block("oak_leaves"));
+        schematic.set_block(0, 3, 0, block("glass"));
+        let grid = compute_light(&schematic, true).unwrap();
+        assert_eq!(grid.get_sky_light(0, 2, 0), 15);
+        assert_eq!(grid.get_sky_light(0, 1, 0), 15);
+        assert_eq!(grid.get_sky_light(2, 2, 2), 14);
+        assert_eq!(grid.get_sky_light(2, 1, 2), 14);
+        // Open sides let sky light in from around the roof
+        assert_eq!(grid.get_sky_light(4, 1, 3), 14);
+        assert_eq!(grid.get_sky_light(2, 0, 2), 0);
+
+        let night = compute_light(&schematic, false).unwrap();
+        assert_eq!(night.get_sky_light(0, 2, 0), 0);
+    }
+
+    #[test]
+    fn test_dark_spots() {
+        let mut schematic = stone_box(12);
+        schematic.set_block(2, 1, 2, block("torch"));
+        let grid = compute_light(&schematic, true).unwrap();
+        let spots = find_dark_spots(&schematic, &grid, true);
+        assert!(spots.contains(&(10, 1, 10)));
+        assert!(!spots.contains(&(3, 1, 2)));
+        assert!(!spots.contains(&(2, 1, 2)));
+        // The roof is outside under the sky, and only counts as dark at night
+        assert!(!spots.contains(&(5, 12, 5)));
+        assert!(find_dark_spots(&schematic, &grid, false).contains(&(5, 12, 5)));
+        assert!(spots.iter().all(|&(x, y, z)| grid.get_block_light(x, y, z) == 0));
+    }
+
+    #[test]
+    fn test_far_apart_regions_fail_cleanly() {
+        let mut schematic = UniversalSchematic::new("Sparse".to_string());
+        schematic.set_block(0, 0, 0, block("torch"));
+        let far = 1_000_000_000;
+        schematic.set_block_in_region("Far", far, far, far, block("stone"));
+        assert!(compute_light(&schematic, true).is_err());
+
+        let grid = compute_light(&stone_box(4), true).unwrap();
+        assert_eq!(grid.index(i32::MAX, 0, 0), None);
+        assert_eq!(grid.index(i32::MIN, 0, 0), None);
+    }
+}
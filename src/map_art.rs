@@ -0,0 +1,422 @@
+//! Turns images into pixel art and map art.
+//!
+//! Pixel art matches every pixel to the closest block in a [`BlockColorPalette`] and lays the
+//! result out as a wall or a floor. Map art matches pixels against the colors blocks show on
+//! in-game maps instead. With staircasing, each block is raised or lowered relative to the
+//! block north of it, which unlocks the darker and brighter shade of every map color.
+
+use std::collections::HashMap;
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::{color_distance, BlockColorPalette};
+use crate::render::Image;
+
+/// Pixels less opaque than this are left empty.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Map shade multipliers for a block lower than, level with and higher than its north neighbour.
+const MAP_SHADES: [u32; 3] = [180, 220, 255];
+
+/// Vanilla map colors and blocks that show them. The first block of each entry is used by
+/// default; the others are alternatives for restricted block sets. Water is left out because
+/// its shade depends on depth rather than height.
+const MAP_COLORS: &[([u8; 3], &[&str])] = &[
+    ([127, 178, 56], &["grass_block", "slime_block"]),
+    ([247, 233, 163], &["sandstone", "birch_planks", "end_stone", "sand"]),
+    ([199, 199, 199], &["mushroom_stem", "cobweb"]),
+    ([255, 0, 0], &["redstone_block", "tnt"]),
+    ([160, 160, 255], &["packed_ice", "ice", "blue_ice"]),
+    ([167, 167, 167], &["iron_block"]),
+    ([0, 124, 0], &["oak_leaves[persistent=true]", "birch_leaves[persistent=true]"]),
+    ([255, 255, 255], &["white_wool", "snow_block", "white_concrete"]),
+    ([164, 168, 184], &["clay"]),
+    ([151, 109, 77], &["dirt", "granite", "jungle_planks", "coarse_dirt"]),
+    ([112, 112, 112], &["cobblestone", "stone", "andesite"]),
+    ([143, 119, 72], &["oak_planks"]),
+    ([255, 252, 245], &["quartz_block", "diorite", "sea_lantern"]),
+    ([216, 127, 51], &["orange_wool", "acacia_planks", "orange_concrete", "pumpkin"]),
+    ([178, 76, 216], &["magenta_wool", "purpur_block", "magenta_concrete"]),
+    ([102, 153, 216], &["light_blue_wool", "light_blue_concrete"]),
+    ([229, 229, 51], &["yellow_wool", "hay_block", "yellow_concrete"]),
+    ([127, 204, 25], &["lime_wool", "melon", "lime_concrete"]),
+    ([242, 127, 165], &["pink_wool", "pink_concrete"]),
+    ([76, 76, 76], &["gray_wool", "gray_concrete"]),
+    ([153, 153, 153], &["light_gray_wool", "light_gray_concrete"]),
+    ([76, 127, 153], &["cyan_wool", "prismarine", "cyan_concrete"]),
+    ([127, 63, 178], &["purple_wool", "purple_concrete"]),
+    ([51, 76, 178], &["blue_wool", "blue_concrete"]),
+    ([102, 76, 51], &["brown_wool", "dark_oak_planks", "brown_concrete", "soul_sand"]),
+    ([102, 127, 51], &["green_wool", "green_concrete"]),
+    ([153, 51, 51], &["red_wool", "bricks", "nether_wart_block", "red_concrete"]),
+    ([25, 25, 25], &["black_wool", "obsidian", "coal_block", "black_concrete"]),
+    ([250, 238, 77], &["gold_block"]),
+    ([92, 219, 213], &["diamond_block", "prismarine_bricks"]),
+    ([74, 128, 255], &["lapis_block"]),
+    ([0, 217, 58], &["emerald_block"]),
+    ([129, 86, 49], &["spruce_planks", "podzol"]),
+    ([112, 2, 0], &["netherrack", "nether_bricks"]),
+    ([209, 177, 161], &["white_terracotta"]),
+    ([159, 82, 36], &["orange_terracotta"]),
+    ([149, 87, 108], &["magenta_terracotta"]),
+    ([112, 108, 138], &["light_blue_terracotta"]),
+    ([186, 133, 36], &["yellow_terracotta"]),
+    ([103, 117, 53], &["lime_terracotta"]),
+    ([160, 77, 78], &["pink_terracotta"]),
+    ([57, 41, 35], &["gray_terracotta"]),
+    ([135, 107, 98], &["light_gray_terracotta"]),
+    ([87, 92, 92], &["cyan_terracotta"]),
+    ([122, 73, 88], &["purple_terracotta"]),
+    ([76, 62, 92], &["blue_terracotta"]),
+    ([76, 50, 35], &["brown_terracotta"]),
+    ([76, 82, 42], &["green_terracotta"]),
+    ([142, 60, 46], &["red_terracotta"]),
+    ([37, 22, 16], &["black_terracotta"]),
+    ([189, 48, 49], &["crimson_nylium"]),
+    ([148, 63, 97], &["crimson_planks"]),
+    ([92, 25, 29], &["crimson_hyphae"]),
+    ([22, 126, 134], &["warped_nylium"]),
+    ([58, 142, 140], &["warped_planks"]),
+    ([86, 44, 62], &["warped_hyphae"]),
+    ([20, 180, 133], &["warped_wart_block"]),
+    ([100, 100, 100], &["cobbled_deepslate", "deepslate"]),
+    ([216, 175, 147], &["raw_iron_block"]),
+    ([127, 167, 150], &["verdant_froglight"]),
+];
+
+/// Blocks that can't be obtained in survival, even with silk touch.
+const CREATIVE_ONLY_BLOCKS: &[&str] = &[
+    "bedrock", "barrier", "light", "command_block", "chain_command_block", "repeating_command_block",
+    "structure_block", "structure_void", "jigsaw", "spawner", "trial_spawner", "vault",
+    "budding_amethyst", "reinforced_deepslate", "end_portal_frame", "petrified_oak_slab",
+    "farmland", "dirt_path", "frosted_ice", "chorus_plant", "end_portal", "nether_portal",
+    "end_gateway", "moving_piston", "piston_head", "fire", "soul_fire", "bubble_column",
+    "tall_seagrass", "test_block", "test_instance_block",
+];
+
+/// Whether a survival player can get the block as an item and place it.
+pub fn is_survival_obtainable(name: &str) -> bool {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    !name.starts_with("infested_") && !CREATIVE_ONLY_BLOCKS.contains(&name)
+}
+
+/// Blocks that fall when there is nothing below them.
+pub fn is_gravity_block(name: &str) -> bool {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    name.ends_with("concrete_powder")
+        || name.ends_with("anvil")
+        || matches!(name, "sand" | "red_sand" | "gravel" | "suspicious_sand" | "suspicious_gravel" | "dragon_egg" | "scaffolding")
+}
+
+fn parse_block(block: &str) -> BlockState {
+    UniversalSchematic::parse_block_string(&format!("minecraft:{}", block))
+        .map(|(state, _)| state)
+        .unwrap_or_else(|_| BlockState::new(format!("minecraft:{}", block)))
+}
+
+/// Blocks to use for map art, each with the base map color it shows.
+#[derive(Debug, Clone)]
+pub struct MapArtPalette {
+    entries: Vec<(BlockState, [u8; 3])>,
+}
+
+impl Default for MapArtPalette {
+    fn default() -> Self {
+        Self::vanilla()
+    }
+}
+
+impl MapArtPalette {
+    /// One block for every map color.
+    pub fn vanilla() -> Self {
+        MapArtPalette {
+            entries: MAP_COLORS.iter().map(|(color, blocks)| (parse_block(blocks[0]), *color)).collect(),
+        }
+    }
+
+    /// The given blocks that have a known map color. Only the first block per color is kept.
+    pub fn from_blocks(blocks: &[BlockState]) -> Self {
+        let mut entries: Vec<(BlockState, [u8; 3])> = Vec::new();
+        for block in blocks {
+            let name = block.name.strip_prefix("minecraft:").unwrap_or(&block.name);
+            let color = MAP_COLORS.iter()
+                .find(|(_, blocks)| blocks.iter().any(|b| b.split('[').next() == Some(name)))
+                .map(|(color, _)| *color);
+            if let Some(color) = color {
+                if !entries.iter().any(|(_, c)| *c == color) {
+                    entries.push((block.clone(), color));
+                }
+            }
+        }
+        MapArtPalette { entries }
+    }
+
+    pub fn entries(&self) -> &[(BlockState, [u8; 3])] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Keeps only the blocks for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&BlockState) -> bool) {
+        self.entries.retain(|(block, _)| keep(block));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtOrientation {
+    /// Standing in the XY plane, facing south. The top of the image is up.
+    Wall,
+    /// Lying in the XZ plane. The top of the image is north.
+    Floor,
+}
+
+#[derive(Debug, Clone)]
+pub struct PixelArtOptions {
+    pub palette: BlockColorPalette,
+    pub orientation: ArtOrientation,
+    pub dithering: bool,
+    /// Placed under gravity blocks that would otherwise float. `None` disables support blocks.
+    pub support_block: Option<BlockState>,
+}
+
+impl Default for PixelArtOptions {
+    fn default() -> Self {
+        PixelArtOptions {
+            palette: BlockColorPalette::full_blocks(),
+            orientation: ArtOrientation::Wall,
+            dithering: false,
+            support_block: Some(BlockState::new("minecraft:cobblestone".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapArtOptions {
+    pub palette: MapArtPalette,
+    /// Vary block heights to use all three shades of each map color. Flat map art only uses the
+    /// middle shade.
+    pub staircasing: bool,
+    pub dithering: bool,
+    /// Used for the reference row north of the image and under floating gravity blocks.
+    pub support_block: BlockState,
+}
+
+impl Default for MapArtOptions {
+    fn default() -> Self {
+        MapArtOptions {
+            palette: MapArtPalette::vanilla(),
+            staircasing: true,
+            dithering: true,
+            support_block: BlockState::new("minecraft:cobblestone".to_string()),
+        }
+    }
+}
+
+/// Picks a color for every pixel, spreading the error to the neighbours with Floyd–Steinberg
+/// dithering if asked to. `nearest` returns the chosen index and its color.
+fn quantize(image: &Image, dithering: bool, mut nearest: impl FnMut([u8; 3]) -> (usize, [u8; 3])) -> Vec<Option<usize>> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let mut error = vec![[0.0f32; 3]; width * height];
+    let mut result = vec![None; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b, a] = image.get_pixel(x as u32, y as u32);
+            if a < ALPHA_THRESHOLD {
+                continue;
+            }
+            let i = y * width + x;
+            let wanted = [r, g, b].map(|c| c as f32);
+            let adjusted = [0, 1, 2].map(|c| (wanted[c] + error[i][c]).clamp(0.0, 255.0));
+            let (index, chosen) = nearest(adjusted.map(|c| c.round() as u8));
+            result[i] = Some(index);
+            if !dithering {
+                continue;
+            }
+            let diff = [0, 1, 2].map(|c| adjusted[c] - chosen[c] as f32);
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    let n = (y + dy) * width + nx as usize;
+                    for c in 0..3 {
+                        error[n][c] += diff[c] * weight;
+                    }
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+    result
+}
+
+/// Puts `support` under every gravity block that has nothing below it.
+fn add_support_blocks(schematic: &mut UniversalSchematic, support: &BlockState) {
+    let floating: Vec<_> = schematic.iter_blocks()
+        .filter(|(_, block)| is_gravity_block(&block.name))
+        .map(|(pos, _)| pos)
+        .filter(|pos| {
+            schematic.get_block(pos.x, pos.y - 1, pos.z)
+                .is_none_or(|below| below.name == "minecraft:air")
+        })
+        .collect();
+    for pos in floating {
+        schematic.set_block(pos.x, pos.y - 1, pos.z, support.clone());
+    }
+}
+
+/// Builds a block-per-pixel picture of `image`. Transparent pixels are left empty.
+pub fn generate_pixel_art(image: &Image, options: &PixelArtOptions) -> Result<UniversalSchematic, String> {
+    if options.palette.is_empty() {
+        return Err("The block palette is empty".to_string());
+    }
+    let mut palette = options.palette.clone();
+    let pixels = quantize(image, options.dithering, |color| {
+        let index = palette.nearest_index(color).unwrap();
+        (index, palette.entries()[index].1)
+    });
+
+    let mut schematic = UniversalSchematic::new("Pixel Art".to_string());
+    let height = image.height as i32;
+    for (i, index) in pixels.iter().enumerate() {
+        let Some(index) = index else { continue };
+        let (px, py) = ((i % image.width as usize) as i32, (i / image.width as usize) as i32);
+        let block = palette.entries()[*index].0.clone();
+        match options.orientation {
+            ArtOrientation::Wall => schematic.set_block(px, height - 1 - py, 0, block),
+            ArtOrientation::Floor => schematic.set_block(px, 0, py, block),
+        };
+    }
+    if let Some(support) = &options.support_block {
+        add_support_blocks(&mut schematic, support);
+    }
+    Ok(schematic)
+}
+
+/// Builds map art of `image`, one block per map pixel. Image row `r` ends up at z = `r + 1`;
+/// z = 0 holds a row of support blocks that the first row is shaded against.
+pub fn generate_map_art(image: &Image, options: &MapArtOptions) -> Result<UniversalSchematic, String> {
+    if options.palette.is_empty() {
+        return Err("The block palette is empty".to_string());
+    }
+    // Every usable (block, shade) pair; the shade is the step relative to the north neighbour
+    let steps: &[i32] = if options.staircasing { &[-1, 0, 1] } else { &[0] };
+    let mut candidates = Vec::new();
+    for (block, base) in options.palette.entries() {
+        for &step in steps {
+            let shade = MAP_SHADES[(step + 1) as usize];
+            candidates.push((block, step, base.map(|c| (c as u32 * shade / 255) as u8)));
+        }
+    }
+
+    let mut cache: HashMap<[u8; 3], usize> = HashMap::new();
+    let pixels = quantize(image, options.dithering, |color| {
+        let index = *cache.entry(color).or_insert_with(|| {
+            (0..candidates.len())
+                .min_by(|&a, &b| color_distance(candidates[a].2, color).total_cmp(&color_distance(candidates[b].2, color)))
+                .unwrap()
+        });
+        (index, candidates[index].2)
+    });
+
+    let mut schematic = UniversalSchematic::new("Map Art".to_string());
+    let (width, height) = (image.width as usize, image.height as usize);
+    for x in 0..width {
+        // Walk the column north to south, then drop it so its lowest block sits at y = 0
+        let mut column = vec![(0, None)];
+        let mut y = 0;
+        for z in 0..height {
+            match pixels[z * width + x] {
+                Some(index) => {
+                    y += candidates[index].1;
+                    column.push((y, Some(candidates[index].0)));
+                }
+                // Empty pixels keep the current level for the block after them
+                None => column.push((y, None)),
+            }
+        }
+        let lowest = column.iter().map(|(y, _)| *y).min().unwrap_or(0);
+        for (z, (y, block)) in column.into_iter().enumerate() {
+            let block = match (z, block) {
+                (0, _) => options.support_block.clone(),
+                (_, Some(block)) => block.clone(),
+                (_, None) => continue,
+            };
+            schematic.set_block(x as i32, y - lowest, z as i32, block);
+        }
+    }
+    add_support_blocks(&mut schematic, &options.support_block);
+    Ok(schematic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, colors: &[[u8; 4]]) -> Image {
+        Image { width, height, pixels: colors.concat() }
+    }
+
+    #[test]
+    fn test_pixel_art_wall_and_floor() {
+        let picture = image(2, 2, &[[255, 255, 255, 255], [0, 0, 0, 0], [20, 20, 20, 255], [255, 255, 255, 255]]);
+        let palette = BlockColorPalette::from_blocks(&[
+            BlockState::new("minecraft:white_concrete".to_string()),
+            BlockState::new("minecraft:black_concrete".to_string()),
+        ]);
+        let mut options = PixelArtOptions { palette, ..PixelArtOptions::default() };
+
+        let wall = generate_pixel_art(&picture, &options).unwrap();
+        assert_eq!(wall.get_block(0, 1, 0).unwrap().name, "minecraft:white_concrete");
+        assert_eq!(wall.get_block(0, 0, 0).unwrap().name, "minecraft:black_concrete");
+        assert!(wall.get_block(1, 1, 0).is_none_or(|b| b.name == "minecraft:air"));
+
+        options.orientation = ArtOrientation::Floor;
+        let floor = generate_pixel_art(&picture, &options).unwrap();
+        assert_eq!(floor.get_block(0, 0, 1).unwrap().name, "minecraft:black_concrete");
+        assert_eq!(floor.get_block(1, 0, 1).unwrap().name, "minecraft:white_concrete");
+    }
+
+    #[test]
+    fn test_map_art_staircasing() {
+        // A column going from the normal shade of white to the bright one and the dark one
+        let white = MAP_COLORS[7].0;
+        let shaded = |shade: u32| {
+            let [r, g, b] = white.map(|c| (c as u32 * shade / 255) as u8);
+            [r, g, b, 255]
+        };
+        let picture = image(1, 3, &[shaded(220), shaded(255), shaded(180)]);
+        let options = MapArtOptions { dithering: false, ..MapArtOptions::default() };
+        let art = generate_map_art(&picture, &options).unwrap();
+
+        let height_at = |z: i32| (0..4).find(|&y| art.get_block(0, y, z).is_some_and(|b| b.name != "minecraft:air"));
+        assert_eq!(art.get_block(0, 0, 0).unwrap().name, "minecraft:cobblestone");
+        assert_eq!(art.get_block(0, 0, 1).unwrap().name, "minecraft:white_wool");
+        assert_eq!(height_at(1), Some(0));
+        assert_eq!(height_at(2), Some(1));
+        assert_eq!(height_at(3), Some(0));
+
+        // Without staircasing the dark and bright pixels fall back to flat blocks
+        let flat = generate_map_art(&picture, &MapArtOptions { staircasing: false, ..options }).unwrap();
+        let (_, height, _) = flat.get_dimensions();
+        assert_eq!(height, 1);
+    }
+
+    #[test]
+    fn test_gravity_support_and_survival_filter() {
+        let picture = image(1, 2, &[[0, 0, 0, 0], [219, 211, 160, 255]]);
+        let palette = BlockColorPalette::from_blocks(&[BlockState::new("minecraft:sand".to_string())]);
+        let options = PixelArtOptions { palette, ..PixelArtOptions::default() };
+        let art = generate_pixel_art(&picture, &options).unwrap();
+        assert_eq!(art.get_block(0, 0, 0).unwrap().name, "minecraft:sand");
+        assert_eq!(art.get_block(0, -1, 0).unwrap().name, "minecraft:cobblestone");
+
+        let mut palette = BlockColorPalette::full_blocks();
+        palette.retain(|b| is_survival_obtainable(&b.name));
+        assert!(!palette.is_empty());
+        assert!(palette.entries().iter().all(|(b, _)| b.name != "minecraft:bedrock"));
+        assert!(!is_survival_obtainable("minecraft:infested_stone"));
+    }
+}
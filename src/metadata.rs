@@ -1,7 +1,8 @@
 use quartz_nbt::{NbtCompound, NbtTag};
 use serde::{Deserialize, Serialize};
+use crate::utils::NbtMap;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Metadata {
     pub name: Option<String>,
     pub author: Option<String>,
@@ -11,6 +12,14 @@ pub struct Metadata {
     pub lm_version: Option<i32>,
     pub mc_version: Option<i32>,
     pub we_version: Option<i32>,
+    #[serde(default)]
+    pub lm_sub_version: Option<i32>,
+    /// Preview image as ARGB pixels, row by row, as Litematica stores it.
+    #[serde(default)]
+    pub preview_image: Option<Vec<i32>>,
+    /// Top-level tags from the source file that aren't interpreted, written back unchanged.
+    #[serde(default)]
+    pub extra_nbt: NbtMap,
 }
 impl Default for Metadata {
     fn default() -> Self {
@@ -23,6 +32,9 @@ impl Default for Metadata {
             lm_version: None,
             mc_version: None,
             we_version: None,
+            lm_sub_version: None,
+            preview_image: None,
+            extra_nbt: NbtMap::new(),
         }
     }
 }
@@ -47,6 +59,7 @@ impl Metadata {
             lm_version,
             mc_version,
             we_version,
+            ..Metadata::default()
         }
     }
 
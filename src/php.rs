@@ -9,7 +9,7 @@ use std::collections::HashMap;
 use crate::{
     UniversalSchematic,
     BlockState,
-    formats::{litematic, schematic},
+    formats::{litematic, schematic, peek, limits::LoadLimits},
     print_utils::{format_schematic, format_json_schematic},
 };
 
@@ -44,6 +44,41 @@ pub fn nucleation_detect_format(data: String) -> String {
     }
 }
 
+/// Read format, version, metadata and dimensions without loading the blocks.
+/// `preview_image` holds a JSON array of ARGB pixels when the file has one.
+#[php_function]
+pub fn nucleation_peek_info(data: String) -> PhpResult<HashMap<String, String>> {
+    let info = peek::peek_info(data.as_bytes())
+        .map_err(|e| PhpException::default(format!("Failed to read schematic info: {}", e)))?;
+
+    let mut result = HashMap::new();
+    result.insert("format".to_string(), info.format);
+    result.insert("version".to_string(), info.version.to_string());
+    if let Some(data_version) = info.data_version {
+        result.insert("data_version".to_string(), data_version.to_string());
+    }
+    if let Some(name) = info.name {
+        result.insert("name".to_string(), name);
+    }
+    if let Some(author) = info.author {
+        result.insert("author".to_string(), author);
+    }
+    if let Some(description) = info.description {
+        result.insert("description".to_string(), description);
+    }
+    result.insert("width".to_string(), info.width.to_string());
+    result.insert("height".to_string(), info.height.to_string());
+    result.insert("length".to_string(), info.length.to_string());
+    result.insert("regions".to_string(), info.region_count.to_string());
+    if let Some(block_count) = info.block_count {
+        result.insert("total_blocks".to_string(), block_count.to_string());
+    }
+    if let Some(preview) = info.preview_image {
+        result.insert("preview_image".to_string(), serde_json::to_string(&preview).unwrap_or_default());
+    }
+    Ok(result)
+}
+
 /// Convert between schematic formats
 #[php_function]
 pub fn nucleation_convert_format(input_data: String, output_format: String) -> PhpResult<String> {
@@ -93,21 +128,23 @@ impl NucleationSchematic {
         NucleationSchematic { inner }
     }
 
-    /// Load from binary data (auto-detect format)
+    /// Load from binary data (auto-detect format). Pass `untrusted` for uploads, to cap sizes,
+    /// palettes, entities and decompressed bytes.
     #[php_method]
-    pub fn load_from_data(&mut self, data: String) -> PhpResult<bool> {
+    pub fn load_from_data(&mut self, data: String, untrusted: Option<bool>) -> PhpResult<bool> {
         let bytes = data.as_bytes();
+        let limits = LoadLimits::untrusted_if(untrusted.unwrap_or(false));
 
-        if litematic::is_litematic(bytes) {
-            match litematic::from_litematic(bytes) {
+        if litematic::is_litematic_with_limits(bytes, &limits) {
+            match litematic::from_litematic_with_limits(bytes, &limits) {
                 Ok(schematic) => {
                     self.inner = schematic;
                     Ok(true)
                 }
                 Err(e) => Err(PhpException::default(format!("Failed to load litematic: {}", e)))
             }
-        } else if schematic::is_schematic(bytes) {
-            match schematic::from_schematic(bytes) {
+        } else if schematic::is_schematic_with_limits(bytes, &limits) {
+            match schematic::from_schematic_with_limits(bytes, &limits) {
                 Ok(schematic) => {
                     self.inner = schematic;
                     Ok(true)
@@ -121,9 +158,9 @@ impl NucleationSchematic {
 
     /// Load from litematic data
     #[php_method]
-    pub fn from_litematic(&mut self, data: String) -> PhpResult<bool> {
+    pub fn from_litematic(&mut self, data: String, untrusted: Option<bool>) -> PhpResult<bool> {
         let bytes = data.as_bytes();
-        match litematic::from_litematic(bytes) {
+        match litematic::from_litematic_with_limits(bytes, &LoadLimits::untrusted_if(untrusted.unwrap_or(false))) {
             Ok(schematic) => {
                 self.inner = schematic;
                 Ok(true)
@@ -134,9 +171,9 @@ impl NucleationSchematic {
 
     /// Load from schematic data
     #[php_method]
-    pub fn from_schematic(&mut self, data: String) -> PhpResult<bool> {
+    pub fn from_schematic(&mut self, data: String, untrusted: Option<bool>) -> PhpResult<bool> {
         let bytes = data.as_bytes();
-        match schematic::from_schematic(bytes) {
+        match schematic::from_schematic_with_limits(bytes, &LoadLimits::untrusted_if(untrusted.unwrap_or(false))) {
             Ok(schematic) => {
                 self.inner = schematic;
                 Ok(true)
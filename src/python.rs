@@ -10,9 +10,20 @@ use crate::{
     UniversalSchematic,
     BlockState,
     utils::{NbtValue, NbtMap},
-    formats::{litematic, schematic},
+    formats::{litematic, schematic, mcstructure, vox, native, json, peek, compression::NbtCompression, limits::LoadLimits},
     print_utils::{format_schematic, format_json_schematic},
     bounding_box::BoundingBox,
+    block_colors::BlockColorPalette,
+    render::{self, Image, RenderOptions},
+    map_art,
+    terrain,
+    text,
+    redstone::{Netlist, RedstoneSimulator},
+    lighting,
+    survival,
+    build_order,
+    datafixer,
+    fingerprint,
     block_position::BlockPosition,
     universal_schematic::ChunkLoadingStrategy
 };
@@ -75,12 +86,26 @@ impl PySchematic {
         }
     }
 
-    pub fn from_data(&mut self, data: &[u8]) -> PyResult<()> {
-        if litematic::is_litematic(data) {
-            self.inner = litematic::from_litematic(data)
+    /// Loads any supported format. With `untrusted`, litematic, Sponge, native and JSON loads
+    /// are capped by `LoadLimits::untrusted()`, for data from uploads; mcstructure and vox sizes
+    /// are already bounded by the data they hold.
+    #[pyo3(signature = (data, untrusted=false))]
+    pub fn from_data(&mut self, data: &[u8], untrusted: bool) -> PyResult<()> {
+        let limits = LoadLimits::untrusted_if(untrusted);
+        if native::is_native(data) {
+            self.inner = native::from_native_with_limits(data, &limits)
                 .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-        } else if schematic::is_schematic(data) {
-            self.inner = schematic::from_schematic(data)
+        } else if litematic::is_litematic_with_limits(data, &limits) {
+            self.inner = litematic::from_litematic_with_limits(data, &limits)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        } else if schematic::is_schematic_with_limits(data, &limits) {
+            self.inner = schematic::from_schematic_with_limits(data, &limits)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        } else if mcstructure::is_mcstructure(data) {
+            self.inner = mcstructure::from_mcstructure(data)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        } else if vox::is_vox(data) {
+            self.inner = vox::from_vox(data)
                 .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         } else {
             return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Unknown or unsupported schematic format"));
@@ -88,30 +113,267 @@ impl PySchematic {
         Ok(())
     }
 
-    pub fn from_litematic(&mut self, data: &[u8]) -> PyResult<()> {
-        self.inner = litematic::from_litematic(data)
+    #[pyo3(signature = (data, untrusted=false))]
+    pub fn from_litematic(&mut self, data: &[u8], untrusted: bool) -> PyResult<()> {
+        self.inner = litematic::from_litematic_with_limits(data, &LoadLimits::untrusted_if(untrusted))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Writes a litematic, embedding an isometric preview of `preview_size` pixels if given.
+    /// `version` forces format 6 or 7 instead of picking it from the data version.
+    /// `compression` is "gzip", "zlib" or "none", with `level` from 0 to 9, or "zstd" (level 0 to
+    /// 22) in builds with the `zstd` feature. A fixed `timestamp` in milliseconds replaces the
+    /// current time, making the output byte-stable.
+    #[pyo3(signature = (preview_size=None, version=None, compression="gzip", level=None, timestamp=None))]
+    pub fn to_litematic(&self, py: Python<'_>, preview_size: Option<u32>, version: Option<i32>, compression: &str, level: Option<u32>, timestamp: Option<i64>) -> PyResult<PyObject> {
+        let compression = NbtCompression::parse(compression, level)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let options = litematic::LitematicWriteOptions { preview_size, version, compression, timestamp };
+        let bytes = litematic::to_litematic_with_options(&self.inner, &options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    #[pyo3(signature = (data, untrusted=false))]
+    pub fn from_json(&mut self, data: &str, untrusted: bool) -> PyResult<()> {
+        self.inner = json::from_json_with_limits(data, &LoadLimits::untrusted_if(untrusted))
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(())
+    }
+
+    /// JSON in the schema documented in `formats::json`; `compact` uses palette indices.
+    #[pyo3(signature = (compact=false))]
+    pub fn to_json(&self, compact: bool) -> PyResult<String> {
+        json::to_json(&self.inner, compact)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    #[pyo3(signature = (data, untrusted=false))]
+    pub fn from_native(&mut self, data: &[u8], untrusted: bool) -> PyResult<()> {
+        self.inner = native::from_native_with_limits(data, &LoadLimits::untrusted_if(untrusted))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Writes the lossless cache format. `compression` of "none" gives the fastest loads.
+    #[pyo3(signature = (compression="gzip", level=Some(1)))]
+    pub fn to_native(&self, py: Python<'_>, compression: &str, level: Option<u32>) -> PyResult<PyObject> {
+        let compression = NbtCompression::parse(compression, level)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let options = native::NativeWriteOptions { compression };
+        let bytes = native::to_native_with_options(&self.inner, &options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    #[pyo3(signature = (data, untrusted=false))]
+    pub fn from_schematic(&mut self, data: &[u8], untrusted: bool) -> PyResult<()> {
+        self.inner = schematic::from_schematic_with_limits(data, &LoadLimits::untrusted_if(untrusted))
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
-    pub fn to_litematic(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let bytes = litematic::to_litematic(&self.inner)
+    /// Writes a Sponge schematic of `version` 2 or 3. `compression` is "gzip", "zlib" or
+    /// "none", with `level` from 0 to 9, or "zstd" (level 0 to 22) with the `zstd` feature.
+    #[pyo3(signature = (version=3, compression="gzip", level=None))]
+    pub fn to_schematic(&self, py: Python<'_>, version: i32, compression: &str, level: Option<u32>) -> PyResult<PyObject> {
+        let compression = NbtCompression::parse(compression, level)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let options = schematic::SchematicWriteOptions { version, compression };
+        let bytes = schematic::to_schematic_with_options(&self.inner, &options)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
         Ok(PyBytes::new(py, &bytes).into())
     }
 
-    pub fn from_schematic(&mut self, data: &[u8]) -> PyResult<()> {
-        self.inner = schematic::from_schematic(data)
+    pub fn from_mcstructure(&mut self, data: &[u8]) -> PyResult<()> {
+        self.inner = mcstructure::from_mcstructure(data)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
         Ok(())
     }
 
-    pub fn to_schematic(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let bytes = schematic::to_schematic(&self.inner)
+    pub fn to_mcstructure(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let bytes = mcstructure::to_mcstructure(&self.inner)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
         Ok(PyBytes::new(py, &bytes).into())
     }
 
+    /// Imports a MagicaVoxel file, optionally matching colors only against `block_set`.
+    #[pyo3(signature = (data, block_set=None))]
+    pub fn from_vox(&mut self, data: &[u8], block_set: Option<Vec<String>>) -> PyResult<()> {
+        let mut options = vox::VoxImportOptions::default();
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = BlockColorPalette::from_blocks(&blocks);
+        }
+        self.inner = vox::from_vox_with_options(data, &options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Builds block-per-pixel art from a PNG, as a wall or (with `floor`) lying flat.
+    #[pyo3(signature = (data, block_set=None, floor=false, dithering=false, survival_only=false))]
+    pub fn from_pixel_art(&mut self, data: &[u8], block_set: Option<Vec<String>>, floor: bool, dithering: bool, survival_only: bool) -> PyResult<()> {
+        let image = Image::from_png(data)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let mut options = map_art::PixelArtOptions { dithering, ..map_art::PixelArtOptions::default() };
+        if floor {
+            options.orientation = map_art::ArtOrientation::Floor;
+        }
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = BlockColorPalette::from_blocks(&blocks);
+        }
+        if survival_only {
+            options.palette.retain(|b| map_art::is_survival_obtainable(&b.name));
+        }
+        self.inner = map_art::generate_pixel_art(&image, &options)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(())
+    }
+
+    /// Builds map art from a PNG using the vanilla map colors.
+    #[pyo3(signature = (data, block_set=None, staircasing=true, dithering=true, survival_only=false))]
+    pub fn from_map_art(&mut self, data: &[u8], block_set: Option<Vec<String>>, staircasing: bool, dithering: bool, survival_only: bool) -> PyResult<()> {
+        let image = Image::from_png(data)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let mut options = map_art::MapArtOptions { staircasing, dithering, ..map_art::MapArtOptions::default() };
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = map_art::MapArtPalette::from_blocks(&blocks);
+        }
+        if survival_only {
+            options.palette.retain(|b| map_art::is_survival_obtainable(&b.name));
+        }
+        self.inner = map_art::generate_map_art(&image, &options)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(())
+    }
+
+    /// Builds terrain from a grayscale heightmap PNG; white is `max_height`.
+    #[pyo3(signature = (data, max_height=64.0, water_level=None, surface=None, subsurface=None, fill=None))]
+    pub fn from_heightmap(&mut self, data: &[u8], max_height: f32, water_level: Option<i32>, surface: Option<&str>, subsurface: Option<&str>, fill: Option<&str>) -> PyResult<()> {
+        let heightmap = terrain::Heightmap::from_png(data, max_height)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let options = terrain_options(water_level, surface, subsurface, fill)?;
+        self.inner = terrain::generate_terrain(&heightmap, &options);
+        Ok(())
+    }
+
+    /// Builds terrain from rows of heights, one row per z. Negative heights are left empty.
+    #[pyo3(signature = (heights, water_level=None, surface=None, subsurface=None, fill=None))]
+    pub fn from_height_array(&mut self, heights: Vec<Vec<f32>>, water_level: Option<i32>, surface: Option<&str>, subsurface: Option<&str>, fill: Option<&str>) -> PyResult<()> {
+        let heightmap = terrain::Heightmap::from_rows(&heights)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let options = terrain_options(water_level, surface, subsurface, fill)?;
+        self.inner = terrain::generate_terrain(&heightmap, &options);
+        Ok(())
+    }
+
+    /// Writes `text` in blocks. Keyword options: `foreground` and `background` block strings,
+    /// `scale`, `depth`, `padding`, `placement` ("south", "floor_north", ...) and `bdf_font`
+    /// with the source of a BDF font.
+    #[pyo3(signature = (text, **options))]
+    pub fn from_text(&mut self, text: &str, options: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        let value_error = PyErr::new::<pyo3::exceptions::PyValueError, _>;
+        let parse = |block: String| {
+            UniversalSchematic::parse_block_string(&block).map(|(state, _)| state).map_err(value_error)
+        };
+        let mut text_options = text::TextOptions::default();
+        let mut font = text::BitmapFont::bundled();
+        if let Some(options) = options {
+            for (key, value) in options.iter() {
+                match key.extract::<String>()?.as_str() {
+                    "foreground" => text_options.foreground = text::BlockPattern::Solid(parse(value.extract()?)?),
+                    "background" => text_options.background = Some(text::BlockPattern::Solid(parse(value.extract()?)?)),
+                    "scale" => text_options.scale = value.extract()?,
+                    "depth" => text_options.depth = value.extract()?,
+                    "padding" => text_options.padding = value.extract()?,
+                    "placement" => {
+                        (text_options.orientation, text_options.facing) =
+                            text::parse_placement(&value.extract::<String>()?).map_err(value_error)?;
+                    }
+                    "bdf_font" => font = text::BitmapFont::from_bdf(&value.extract::<String>()?).map_err(value_error)?,
+                    other => return Err(value_error(format!("Unknown text option '{}'", other))),
+                }
+            }
+        }
+        self.inner = text::generate_text(text, &font, &text_options);
+        Ok(())
+    }
+
+    pub fn to_vox(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let bytes = vox::to_vox(&self.inner)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    #[pyo3(signature = (resolution=512))]
+    pub fn render_isometric_png(&self, py: Python<'_>, resolution: u32) -> PyResult<PyObject> {
+        let options = RenderOptions { resolution, ..RenderOptions::default() };
+        let bytes = render::to_isometric_png(&self.inner, &options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    #[pyo3(signature = (resolution=512))]
+    pub fn render_top_down_png(&self, py: Python<'_>, resolution: u32) -> PyResult<PyObject> {
+        let options = RenderOptions { resolution, ..RenderOptions::default() };
+        let bytes = render::to_top_down_png(&self.inner, &options)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    /// The circuit's logic graph as a JSON string with `nodes` and `edges`.
+    pub fn netlist_json(&self) -> PyResult<String> {
+        Netlist::extract(&self.inner).to_json()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// The circuit's logic graph as Graphviz DOT source.
+    pub fn netlist_dot(&self) -> String {
+        Netlist::extract(&self.inner).to_dot()
+    }
+
+    /// Block and sky light for every block, plus one block around the schematic. Returns a dict
+    /// with `min`, `size` and the `block_light` and `sky_light` bytes, x first, then z, then y.
+    #[pyo3(signature = (open_sky=true))]
+    pub fn compute_light<'py>(&self, py: Python<'py>, open_sky: bool) -> PyResult<PyObject> {
+        let grid = lighting::compute_light(&self.inner, open_sky)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let dict = PyDict::new(py);
+        dict.set_item("min", grid.min)?;
+        dict.set_item("size", grid.size)?;
+        dict.set_item("block_light", PyBytes::new(py, &grid.block_light))?;
+        dict.set_item("sky_light", PyBytes::new(py, &grid.sky_light))?;
+        Ok(dict.into())
+    }
+
+    /// Positions where hostile mobs could spawn because there is no block light.
+    #[pyo3(signature = (include_sky_light=false, open_sky=true))]
+    pub fn find_dark_spots(&self, include_sky_light: bool, open_sky: bool) -> PyResult<Vec<(i32, i32, i32)>> {
+        let grid = lighting::compute_light(&self.inner, open_sky)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(lighting::find_dark_spots(&self.inner, &grid, include_sky_light))
+    }
+
+    /// Blocks that would break, fall or can't be obtained when building in survival, as dicts
+    /// with `position`, `block`, `kind` and `reason`.
+    pub fn validate_survival<'py>(&self, py: Python<'py>) -> PyResult<PyObject> {
+        let report = survival::validate_survival(&self.inner);
+        let mut list_items: Vec<PyObject> = Vec::new();
+        for issue in &report.issues {
+            let dict = PyDict::new(py);
+            dict.set_item("position", issue.position)?;
+            dict.set_item("block", &issue.block)?;
+            dict.set_item("kind", issue.kind.name())?;
+            dict.set_item("reason", &issue.reason)?;
+            list_items.push(dict.into());
+        }
+        let list = PyList::new(py, list_items)?;
+        Ok(list.into())
+    }
+
     pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_name: &str) {
         self.inner.set_block(x, y, z, BlockState::new(block_name.to_string()));
     }
@@ -154,6 +416,18 @@ impl PySchematic {
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))
     }
 
+    /// Pastes `from_schematic` so that its origin lands on the given position.
+    #[pyo3(signature = (from_schematic, x, y, z, excluded_blocks=None))]
+    pub fn paste(&mut self, from_schematic: &PySchematic, x: i32, y: i32, z: i32, excluded_blocks: Option<Vec<String>>) -> PyResult<()> {
+        let excluded: Vec<BlockState> = excluded_blocks.unwrap_or_default()
+            .iter()
+            .map(|s| UniversalSchematic::parse_block_string(s).map(|(bs, _)| bs))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.inner.paste(&from_schematic.inner, (x, y, z), &excluded)
+            .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+    }
+
     pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<PyBlockState> {
         self.inner.get_block(x, y, z).cloned().map(|bs| PyBlockState { inner: bs })
     }
@@ -205,6 +479,85 @@ impl PySchematic {
         Ok(list.into())
     }
 
+    /// Upgrades or downgrades blocks, block entities and entities to the `target` data version.
+    /// Returns the fixes that changed something.
+    pub fn convert_data_version(&mut self, target: i32) -> PyResult<Vec<String>> {
+        datafixer::convert(&mut self.inner, target)
+            .map(|report| report.applied)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    /// Exports a copy for an older data version, replacing blocks it doesn't have. `format` is
+    /// "schematic" (Sponge v2), "schematic_v3" or "litematic"; `rules_json` is a list of
+    /// extra substitution rules that take precedence over the built-in ones. Returns the file
+    /// and every substitution made.
+    #[pyo3(signature = (target, format="schematic", rules_json=None))]
+    pub fn export_for_version(&self, py: Python<'_>, target: i32, format: &str, rules_json: Option<&str>) -> PyResult<(PyObject, PyObject)> {
+        let mut rules = datafixer::SubstitutionRules::vanilla();
+        if let Some(json) = rules_json {
+            let overrides = datafixer::SubstitutionRules::from_json(json)
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            rules = rules.with_overrides(overrides.rules);
+        }
+        let mut downgraded = self.inner.clone();
+        let report = datafixer::downgrade(&mut downgraded, target, &rules)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let bytes = match format {
+            "schematic" => schematic::to_schematic_v2(&downgraded),
+            "schematic_v3" => schematic::to_schematic_v3(&downgraded),
+            "litematic" => litematic::to_litematic(&downgraded),
+            _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown format: {}", format))),
+        }.map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+        let mut substitutions = Vec::new();
+        for substitution in &report.substitutions {
+            let dict = PyDict::new(py);
+            dict.set_item("position", substitution.position)?;
+            dict.set_item("from", &substitution.from)?;
+            dict.set_item("to", &substitution.to)?;
+            substitutions.push(dict);
+        }
+        Ok((PyBytes::new(py, &bytes).into(), PyList::new(py, substitutions)?.into()))
+    }
+
+    /// Placement order for printers and bots as JSON, with chunks visited in `strategy` order.
+    #[pyo3(signature = (strategy="bottom_up", chunk_size=16, redstone_last=true))]
+    pub fn build_order_json(&self, strategy: &str, chunk_size: i32, redstone_last: bool) -> PyResult<String> {
+        let strategy = match strategy {
+            "default" => ChunkLoadingStrategy::Default,
+            "top_down" => ChunkLoadingStrategy::TopDown,
+            "bottom_up" => ChunkLoadingStrategy::BottomUp,
+            "center_outward" => ChunkLoadingStrategy::CenterOutward,
+            "random" => ChunkLoadingStrategy::Random,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown strategy '{}'", other)))
+            }
+        };
+        let options = build_order::BuildPlanOptions { chunk_size, strategy, redstone_last };
+        build_order::plan_build(&self.inner, &options).to_json()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    /// Hex hash of the blocks (and block entities) that ignores metadata, region layout and
+    /// position, and with `rotation_invariant` also rotation around the Y axis.
+    #[pyo3(signature = (rotation_invariant=false, include_block_entities=true))]
+    pub fn content_hash(&self, rotation_invariant: bool, include_block_entities: bool) -> String {
+        let options = fingerprint::FingerprintOptions { rotation_invariant, include_block_entities };
+        fingerprint::content_hash(&self.inner, &options)
+    }
+
+    /// Scores from 0 to 1 for how alike this schematic and `other` are: `palette`, `voxels`
+    /// and their mean `score`.
+    #[pyo3(signature = (other, rotation_invariant=false))]
+    pub fn similarity(&self, py: Python<'_>, other: &PySchematic, rotation_invariant: bool) -> PyResult<PyObject> {
+        let result = fingerprint::similarity(&self.inner, &other.inner, rotation_invariant);
+        let dict = PyDict::new(py);
+        dict.set_item("palette", result.palette)?;
+        dict.set_item("voxels", result.voxels)?;
+        dict.set_item("score", result.score)?;
+        Ok(dict.into())
+    }
+
     #[pyo3(signature = (
         chunk_width, chunk_height, chunk_length,
         strategy=None, camera_x=0.0, camera_y=0.0, camera_z=0.0
@@ -260,6 +613,17 @@ impl PySchematic {
         self.inner.get_dimensions()
     }
 
+    /// Paste anchor in schematic coordinates.
+    #[getter]
+    pub fn origin(&self) -> (i32, i32, i32) {
+        self.inner.origin
+    }
+
+    #[setter]
+    pub fn set_origin(&mut self, origin: (i32, i32, i32)) {
+        self.inner.origin = origin;
+    }
+
     #[getter]
     pub fn block_count(&self) -> i32 {
         self.inner.total_blocks()
@@ -332,6 +696,25 @@ fn nbt_value_to_python(py: Python<'_>, value: &NbtValue) -> PyResult<PyObject> {
     }
 }
 
+fn terrain_options(water_level: Option<i32>, surface: Option<&str>, subsurface: Option<&str>, fill: Option<&str>) -> PyResult<terrain::TerrainOptions> {
+    let parse = |block: &str| {
+        UniversalSchematic::parse_block_string(block)
+            .map(|(state, _)| state)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    };
+    let mut options = terrain::TerrainOptions { water_level, ..terrain::TerrainOptions::default() };
+    if let Some(surface) = surface {
+        options.surface = parse(surface)?;
+    }
+    if let Some(subsurface) = subsurface {
+        options.subsurface = parse(subsurface)?;
+    }
+    if let Some(fill) = fill {
+        options.fill = parse(fill)?;
+    }
+    Ok(options)
+}
+
 #[pyfunction]
 fn debug_schematic(schematic: &PySchematic) -> String {
     format!("{}\n{}", schematic.debug_info(), format_schematic(&schematic.inner))
@@ -343,6 +726,26 @@ fn debug_json_schematic(schematic: &PySchematic) -> String {
 }
 
 
+/// Reads format, version, metadata and dimensions of litematic or `.schem` bytes without
+/// decoding the blocks.
+#[pyfunction]
+fn peek_info(py: Python<'_>, data: &[u8]) -> PyResult<PyObject> {
+    let info = peek::peek_info(data)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let dict = PyDict::new(py);
+    dict.set_item("format", info.format)?;
+    dict.set_item("version", info.version)?;
+    dict.set_item("data_version", info.data_version)?;
+    dict.set_item("name", info.name)?;
+    dict.set_item("author", info.author)?;
+    dict.set_item("description", info.description)?;
+    dict.set_item("dimensions", (info.width, info.height, info.length))?;
+    dict.set_item("region_count", info.region_count)?;
+    dict.set_item("block_count", info.block_count)?;
+    dict.set_item("preview_image", info.preview_image)?;
+    Ok(dict.into())
+}
+
 #[pyfunction]
 fn load_schematic(path: &str) -> PyResult<PySchematic> {
     let data = fs::read(path)
@@ -355,7 +758,7 @@ fn load_schematic(path: &str) -> PyResult<PySchematic> {
             .unwrap_or("Unnamed")
             .to_owned(),
     ));
-    sch.from_data(&data)?;
+    sch.from_data(&data, false)?;
     Ok(sch)
 }
 
@@ -365,13 +768,13 @@ fn load_schematic(path: &str) -> PyResult<PySchematic> {
 fn save_schematic(schematic: &PySchematic, path: &str, format: &str) -> PyResult<()> {
     Python::with_gil(|py| {
         let py_bytes = match format {
-            "litematic" => schematic.to_litematic(py)?,
-            "schematic" => schematic.to_schematic(py)?,
+            "litematic" => schematic.to_litematic(py, None, None, "gzip", None, None)?,
+            "schematic" => schematic.to_schematic(py, 3, "gzip", None)?,
             "auto" => {
                 if path.ends_with(".litematic") {
-                    schematic.to_litematic(py)?
+                    schematic.to_litematic(py, None, None, "gzip", None, None)?
                 } else {
-                    schematic.to_schematic(py)?
+                    schematic.to_schematic(py, 3, "gzip", None)?
                 }
             }
             other => {
@@ -393,12 +796,74 @@ fn save_schematic(schematic: &PySchematic, path: &str, format: &str) -> PyResult
     })
 }
 
+#[pyclass(name = "RedstoneSimulator")]
+pub struct PyRedstoneSimulator {
+    inner: RedstoneSimulator,
+}
+
+#[pymethods]
+impl PyRedstoneSimulator {
+    #[new]
+    fn new(schematic: &PySchematic) -> Self {
+        Self { inner: RedstoneSimulator::new(&schematic.inner) }
+    }
+
+    #[pyo3(signature = (ticks=1))]
+    fn step(&mut self, ticks: u32) {
+        self.inner.step(ticks);
+    }
+
+    #[pyo3(signature = (max_ticks=1000))]
+    fn run_until_stable(&mut self, max_ticks: u32) -> bool {
+        self.inner.run_until_stable(max_ticks)
+    }
+
+    fn toggle_lever(&mut self, x: i32, y: i32, z: i32) -> PyResult<bool> {
+        self.inner.toggle_lever(x, y, z)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn set_lever(&mut self, x: i32, y: i32, z: i32, on: bool) -> PyResult<()> {
+        self.inner.set_lever(x, y, z, on)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn press_button(&mut self, x: i32, y: i32, z: i32) -> PyResult<()> {
+        self.inner.press_button(x, y, z)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn get_block(&self, x: i32, y: i32, z: i32) -> Option<PyBlockState> {
+        self.inner.get_block(x, y, z).cloned().map(|bs| PyBlockState { inner: bs })
+    }
+
+    fn get_signal(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.inner.get_signal(x, y, z)
+    }
+
+    fn is_on(&self, x: i32, y: i32, z: i32) -> bool {
+        self.inner.is_on(x, y, z)
+    }
+
+    #[getter]
+    fn tick(&self) -> u64 {
+        self.inner.current_tick()
+    }
+
+    /// Writes the simulated block states back into `schematic`.
+    fn apply_to(&self, schematic: &mut PySchematic) {
+        self.inner.apply_to(&mut schematic.inner);
+    }
+}
+
 #[pymodule]
 fn nucleation(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PySchematic>()?;
     m.add_class::<PyBlockState>()?;
+    m.add_class::<PyRedstoneSimulator>()?;
     m.add_function(wrap_pyfunction!(debug_schematic, m)?)?;
     m.add_function(wrap_pyfunction!(debug_json_schematic, m)?)?;
+    m.add_function(wrap_pyfunction!(peek_info, m)?)?;
     m.add_function(wrap_pyfunction!(load_schematic, m)?)?;
     m.add_function(wrap_pyfunction!(save_schematic, m)?)?;
     Ok(())
@@ -0,0 +1,287 @@
+use serde::Serialize;
+use crate::BlockState;
+use crate::formats::mesh::is_transparent;
+
+pub type Pos = (i32, i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Down,
+    Up,
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Down, Direction::Up, Direction::North, Direction::South, Direction::West, Direction::East,
+    ];
+    pub const HORIZONTAL: [Direction; 4] = [Direction::North, Direction::South, Direction::West, Direction::East];
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "down" => Some(Direction::Down),
+            "up" => Some(Direction::Up),
+            "north" => Some(Direction::North),
+            "south" => Some(Direction::South),
+            "west" => Some(Direction::West),
+            "east" => Some(Direction::East),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Down => "down",
+            Direction::Up => "up",
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::West => "west",
+            Direction::East => "east",
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+
+    /// The horizontal direction 90° clockwise seen from above. Vertical directions are unchanged.
+    pub fn rotate_clockwise(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+            vertical => vertical,
+        }
+    }
+
+    pub fn offset(self, pos: Pos) -> Pos {
+        let (x, y, z) = pos;
+        match self {
+            Direction::Down => (x, y - 1, z),
+            Direction::Up => (x, y + 1, z),
+            Direction::North => (x, y, z - 1),
+            Direction::South => (x, y, z + 1),
+            Direction::West => (x - 1, y, z),
+            Direction::East => (x + 1, y, z),
+        }
+    }
+}
+
+/// A block that takes part in redstone logic, with the parts of its state that don't change
+/// while a circuit runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    Wire,
+    /// `attached` points from the torch to the block it hangs on.
+    Torch { attached: Direction },
+    /// `facing` points from the repeater to its input; `delay` is in redstone ticks.
+    Repeater { facing: Direction, delay: u8 },
+    /// `facing` points from the comparator to its rear input.
+    Comparator { facing: Direction, subtract: bool },
+    Lever { attached: Direction },
+    /// `duration` is how many game ticks a press lasts.
+    Button { attached: Direction, duration: u32 },
+    /// `facing` points to the observed block; the output is on the opposite side.
+    Observer { facing: Direction },
+    Lamp,
+    Piston { facing: Direction, sticky: bool },
+    RedstoneBlock,
+}
+
+impl ComponentKind {
+    /// The side a diode or observer sends its signal out of.
+    pub fn output_side(self) -> Option<Direction> {
+        match self {
+            ComponentKind::Repeater { facing, .. }
+            | ComponentKind::Comparator { facing, .. }
+            | ComponentKind::Observer { facing } => Some(facing.opposite()),
+            _ => None,
+        }
+    }
+
+    /// Whether the component sends power out of its `direction` side when it's on. For wires,
+    /// `wire_sides` is the mask from [`wire_sides`].
+    pub fn emits_towards(self, direction: Direction, wire_sides: u8) -> bool {
+        match self {
+            ComponentKind::Wire => match direction {
+                Direction::Down => true,
+                Direction::Up => false,
+                horizontal => wire_sides & side_bit(horizontal) != 0,
+            },
+            ComponentKind::Torch { attached } => direction != attached,
+            ComponentKind::Lever { .. } | ComponentKind::Button { .. } | ComponentKind::RedstoneBlock => true,
+            kind => kind.output_side() == Some(direction),
+        }
+    }
+
+    /// Whether the component strongly powers the block on its `direction` side, so that the
+    /// block passes the power on to components around it.
+    pub fn strongly_powers(self, direction: Direction) -> bool {
+        match self {
+            ComponentKind::Lever { attached } | ComponentKind::Button { attached, .. } => attached == direction,
+            ComponentKind::Torch { .. } => direction == Direction::Up,
+            ComponentKind::Wire => false,
+            kind => kind.output_side() == Some(direction),
+        }
+    }
+}
+
+pub(crate) fn side_bit(direction: Direction) -> u8 {
+    match direction {
+        Direction::North => 1,
+        Direction::South => 2,
+        Direction::West => 4,
+        Direction::East => 8,
+        _ => 0,
+    }
+}
+
+/// Horizontal sides a wire points to, as a bit per direction. A line with one connection also
+/// points the other way.
+pub fn wire_sides(block: &BlockState) -> u8 {
+    let mut sides = 0;
+    for direction in Direction::HORIZONTAL {
+        if block.get_property(direction.name()).is_some_and(|v| v != "none") {
+            sides |= side_bit(direction);
+        }
+    }
+    for direction in Direction::HORIZONTAL {
+        if sides == side_bit(direction) {
+            sides |= side_bit(direction.opposite());
+        }
+    }
+    sides
+}
+
+fn short_name(block: &BlockState) -> &str {
+    block.name.strip_prefix("minecraft:").unwrap_or(&block.name)
+}
+
+fn facing(block: &BlockState) -> Option<Direction> {
+    block.get_property("facing").and_then(|f| Direction::from_name(f))
+}
+
+/// Where a lever or button is attached, from its `face` and `facing` properties.
+fn attachment(block: &BlockState) -> Direction {
+    match block.get_property("face").map(|f| f.as_str()) {
+        Some("floor") => Direction::Down,
+        Some("ceiling") => Direction::Up,
+        _ => facing(block).map_or(Direction::Down, Direction::opposite),
+    }
+}
+
+pub fn classify(block: &BlockState) -> Option<ComponentKind> {
+    let name = short_name(block);
+    let kind = match name {
+        "redstone_wire" => ComponentKind::Wire,
+        "redstone_torch" => ComponentKind::Torch { attached: Direction::Down },
+        "redstone_wall_torch" => ComponentKind::Torch { attached: facing(block)?.opposite() },
+        "repeater" => ComponentKind::Repeater {
+            facing: facing(block)?,
+            delay: block.get_property("delay").and_then(|d| d.parse().ok()).unwrap_or(1).clamp(1, 4),
+        },
+        "comparator" => ComponentKind::Comparator {
+            facing: facing(block)?,
+            subtract: block.get_property("mode").map(|m| m.as_str()) == Some("subtract"),
+        },
+        "lever" => ComponentKind::Lever { attached: attachment(block) },
+        "observer" => ComponentKind::Observer { facing: facing(block)? },
+        "redstone_lamp" => ComponentKind::Lamp,
+        "piston" | "sticky_piston" => ComponentKind::Piston { facing: facing(block)?, sticky: name == "sticky_piston" },
+        "redstone_block" => ComponentKind::RedstoneBlock,
+        _ if name.ends_with("_button") => ComponentKind::Button {
+            attached: attachment(block),
+            duration: if matches!(name, "stone_button" | "polished_blackstone_button") { 20 } else { 30 },
+        },
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Blocks that let pistons extend into them and break when pushed.
+pub fn is_replaceable(block: &BlockState) -> bool {
+    let name = short_name(block);
+    matches!(name, "air" | "cave_air" | "void_air" | "water" | "lava" | "short_grass" | "grass" | "fern" | "snow" | "fire")
+}
+
+/// Blocks that break instead of moving when a piston pushes them.
+pub fn breaks_when_pushed(block: &BlockState) -> bool {
+    let name = short_name(block);
+    is_replaceable(block)
+        || name.contains("torch")
+        || name.ends_with("_button")
+        || name.ends_with("_carpet")
+        || name.ends_with("_pressure_plate")
+        || matches!(name, "redstone_wire" | "repeater" | "comparator" | "lever" | "cobweb" | "flower_pot")
+}
+
+/// Blocks that pistons can't move at all.
+pub fn is_immovable(block: &BlockState) -> bool {
+    let name = short_name(block);
+    let extended_piston = matches!(name, "piston" | "sticky_piston")
+        && block.get_property("extended").map(|e| e.as_str()) == Some("true");
+    extended_piston
+        || is_container(&block.name)
+        || matches!(name,
+            "obsidian" | "crying_obsidian" | "bedrock" | "barrier" | "piston_head" | "moving_piston"
+            | "end_portal_frame" | "enchanting_table" | "respawn_anchor" | "reinforced_deepslate"
+            | "beacon" | "jukebox" | "ender_chest" | "spawner" | "command_block" | "structure_block")
+}
+
+/// Blocks whose contents comparators can measure.
+pub fn is_container(name: &str) -> bool {
+    container_slots(name).is_some()
+}
+
+pub fn container_slots(name: &str) -> Option<u32> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    match name {
+        "chest" | "trapped_chest" | "barrel" => Some(27),
+        "hopper" | "brewing_stand" => Some(5),
+        "dispenser" | "dropper" | "crafter" => Some(9),
+        "furnace" | "smoker" | "blast_furnace" => Some(3),
+        _ if name.ends_with("shulker_box") => Some(27),
+        _ => None,
+    }
+}
+
+/// Full, opaque blocks: they can be powered and pass the power on to neighbouring components.
+pub fn is_conductor(block: &BlockState) -> bool {
+    let name = short_name(block);
+    if classify(block).is_some_and(|kind| kind != ComponentKind::Lamp) {
+        return false;
+    }
+    if name.ends_with("_slab") {
+        return block.get_property("type").map(|t| t.as_str()) == Some("double");
+    }
+    let partial_suffixes = [
+        "_stairs", "_trapdoor", "_door", "_fence", "_fence_gate", "_wall", "_pane", "_carpet", "_sign",
+        "_banner", "_pressure_plate", "_rail", "rail", "_bars", "torch", "_leaves", "_head", "_skull",
+        "_bed", "_candle", "candle", "_sapling", "_flower", "_tulip", "_coral", "_coral_fan", "_button",
+    ];
+    if partial_suffixes.iter().any(|suffix| name.ends_with(suffix)) {
+        return false;
+    }
+    !is_transparent(&block.name)
+        && !crate::block_colors::is_invisible(&block.name)
+        && !matches!(name,
+            "air" | "cave_air" | "void_air" | "water" | "lava" | "glowstone" | "sea_lantern" | "hopper"
+            | "piston_head" | "moving_piston" | "slime_block" | "honey_block" | "soul_sand" | "mud"
+            | "farmland" | "dirt_path" | "chest" | "trapped_chest" | "ender_chest" | "enchanting_table"
+            | "daylight_detector" | "lectern" | "brewing_stand" | "cauldron" | "composter" | "scaffolding"
+            | "snow" | "cobweb" | "lightning_rod" | "end_rod" | "chain" | "ladder" | "vine" | "lantern"
+            | "soul_lantern" | "flower_pot" | "cactus" | "cake" | "bell" | "grindstone" | "anvil"
+            | "tripwire" | "tripwire_hook" | "string" | "short_grass" | "grass" | "fern" | "fire")
+}
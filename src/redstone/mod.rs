@@ -0,0 +1,13 @@
+//! Redstone circuits: classifying components, simulating them tick by tick and extracting
+//! their logic graph.
+
+mod components;
+mod netlist;
+mod simulator;
+
+pub use components::{
+    classify, is_conductor, is_container, is_immovable, is_replaceable, breaks_when_pushed,
+    container_slots, wire_sides, ComponentKind, Direction, Pos,
+};
+pub use netlist::{EdgeKind, Netlist, NetlistEdge, NetlistNode, NodeKind};
+pub use simulator::RedstoneSimulator;
@@ -0,0 +1,416 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::Serialize;
+use crate::UniversalSchematic;
+use super::components::{classify, is_conductor, wire_sides, ComponentKind, Direction, Pos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// A group of connected redstone wire.
+    Wire,
+    Torch,
+    Repeater,
+    Comparator,
+    Lever,
+    Button,
+    Observer,
+    Lamp,
+    Piston,
+    RedstoneBlock,
+}
+
+impl NodeKind {
+    fn of(kind: ComponentKind) -> NodeKind {
+        match kind {
+            ComponentKind::Wire => NodeKind::Wire,
+            ComponentKind::Torch { .. } => NodeKind::Torch,
+            ComponentKind::Repeater { .. } => NodeKind::Repeater,
+            ComponentKind::Comparator { .. } => NodeKind::Comparator,
+            ComponentKind::Lever { .. } => NodeKind::Lever,
+            ComponentKind::Button { .. } => NodeKind::Button,
+            ComponentKind::Observer { .. } => NodeKind::Observer,
+            ComponentKind::Lamp => NodeKind::Lamp,
+            ComponentKind::Piston { .. } => NodeKind::Piston,
+            ComponentKind::RedstoneBlock => NodeKind::RedstoneBlock,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NodeKind::Wire => "wire",
+            NodeKind::Torch => "torch",
+            NodeKind::Repeater => "repeater",
+            NodeKind::Comparator => "comparator",
+            NodeKind::Lever => "lever",
+            NodeKind::Button => "button",
+            NodeKind::Observer => "observer",
+            NodeKind::Lamp => "lamp",
+            NodeKind::Piston => "piston",
+            NodeKind::RedstoneBlock => "redstone_block",
+        }
+    }
+}
+
+/// How a signal gets from one node to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// The source is right next to the target's input.
+    Direct,
+    /// Through a block the source strongly powers.
+    Strong,
+    /// Through a block a wire powers.
+    Weak,
+    /// Into the side of a comparator.
+    Side,
+    /// Into the side of a repeater, locking it.
+    Lock,
+    /// An observer watching the source change state.
+    Observe,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetlistNode {
+    pub id: usize,
+    pub kind: NodeKind,
+    /// The block state, e.g. `minecraft:repeater[delay=2,facing=north,...]`. Wire nodes use
+    /// the state of their first wire.
+    pub block: String,
+    /// Every block the node covers; several for wires, one for everything else.
+    pub positions: Vec<Pos>,
+    /// Game ticks the node takes to react to its inputs.
+    pub delay: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetlistEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+    /// Game ticks between the source changing and the target reacting.
+    pub delay: u32,
+}
+
+/// The logic graph of a redstone circuit: components as nodes and the paths signals can take
+/// between them as edges. Connected wires are merged into a single node.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Netlist {
+    pub nodes: Vec<NetlistNode>,
+    pub edges: Vec<NetlistEdge>,
+}
+
+fn delay_of(kind: ComponentKind) -> u32 {
+    match kind {
+        ComponentKind::Torch { .. } | ComponentKind::Comparator { .. } | ComponentKind::Observer { .. } | ComponentKind::Piston { .. } => 2,
+        ComponentKind::Repeater { delay, .. } => 2 * delay as u32,
+        _ => 0,
+    }
+}
+
+struct Extractor {
+    components: HashMap<Pos, ComponentKind>,
+    wire_sides: HashMap<Pos, u8>,
+    conductors: HashSet<Pos>,
+    node_of: HashMap<Pos, usize>,
+}
+
+impl Extractor {
+    /// Nodes whose output reaches `pos` from the `direction` side, either straight from the
+    /// neighbour or through the block there.
+    fn sources(&self, pos: Pos, direction: Direction, wires_only_strong: bool) -> Vec<(usize, EdgeKind)> {
+        let neighbour = direction.offset(pos);
+        let towards_us = direction.opposite();
+        if let Some(kind) = self.components.get(&neighbour) {
+            let sides = self.wire_sides.get(&neighbour).copied().unwrap_or(0);
+            return if kind.emits_towards(towards_us, sides) {
+                vec![(self.node_of[&neighbour], EdgeKind::Direct)]
+            } else {
+                Vec::new()
+            };
+        }
+        if !self.conductors.contains(&neighbour) {
+            return Vec::new();
+        }
+        let mut sources = Vec::new();
+        for around in Direction::ALL {
+            let source = around.offset(neighbour);
+            if source == pos {
+                continue;
+            }
+            let Some(kind) = self.components.get(&source) else { continue };
+            let into_block = around.opposite();
+            let sides = self.wire_sides.get(&source).copied().unwrap_or(0);
+            if kind.strongly_powers(into_block) && kind.emits_towards(into_block, sides) {
+                sources.push((self.node_of[&source], EdgeKind::Strong));
+            } else if *kind == ComponentKind::Wire && !wires_only_strong && kind.emits_towards(into_block, sides) {
+                sources.push((self.node_of[&source], EdgeKind::Weak));
+            }
+        }
+        sources
+    }
+
+    /// Comparator and repeater side inputs only come from wires, diodes and redstone blocks.
+    fn side_source(&self, pos: Pos, direction: Direction) -> Option<usize> {
+        let neighbour = direction.offset(pos);
+        let kind = self.components.get(&neighbour)?;
+        let sides = self.wire_sides.get(&neighbour).copied().unwrap_or(0);
+        let counts = match kind {
+            ComponentKind::Repeater { .. } | ComponentKind::Comparator { .. } => kind.emits_towards(direction.opposite(), 0),
+            ComponentKind::Wire => kind.emits_towards(direction.opposite(), sides),
+            ComponentKind::RedstoneBlock => true,
+            _ => false,
+        };
+        counts.then(|| self.node_of[&neighbour])
+    }
+
+    /// Groups connected wires the same way signals spread through them.
+    fn wire_groups(&self) -> Vec<Vec<Pos>> {
+        let mut wires: Vec<Pos> = self.wire_sides.keys().copied().collect();
+        wires.sort();
+        let mut seen = HashSet::new();
+        let mut groups = Vec::new();
+        for &start in &wires {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut group = Vec::new();
+            let mut queue = vec![start];
+            while let Some(wire) = queue.pop() {
+                group.push(wire);
+                let above_cut = self.conductors.contains(&Direction::Up.offset(wire));
+                for direction in Direction::HORIZONTAL {
+                    let side = direction.offset(wire);
+                    let below_side = Direction::Down.offset(side);
+                    let above_side = Direction::Up.offset(side);
+                    let mut targets = vec![side];
+                    if !above_cut {
+                        targets.push(above_side);
+                    }
+                    if !self.conductors.contains(&side) {
+                        targets.push(below_side);
+                    }
+                    for target in targets {
+                        if self.wire_sides.contains_key(&target) && seen.insert(target) {
+                            queue.push(target);
+                        }
+                    }
+                }
+            }
+            group.sort();
+            groups.push(group);
+        }
+        groups
+    }
+}
+
+impl Netlist {
+    pub fn extract(schematic: &UniversalSchematic) -> Self {
+        let mut blocks = BTreeMap::new();
+        let mut extractor = Extractor {
+            components: HashMap::new(),
+            wire_sides: HashMap::new(),
+            conductors: HashSet::new(),
+            node_of: HashMap::new(),
+        };
+        for (pos, block) in schematic.iter_blocks() {
+            let pos = (pos.x, pos.y, pos.z);
+            if is_conductor(block) {
+                extractor.conductors.insert(pos);
+            }
+            if let Some(kind) = classify(block) {
+                if kind == ComponentKind::Wire {
+                    extractor.wire_sides.insert(pos, wire_sides(block));
+                }
+                extractor.components.insert(pos, kind);
+                blocks.insert(pos, block.to_string());
+            }
+        }
+
+        let mut netlist = Netlist::default();
+        for group in extractor.wire_groups() {
+            let id = netlist.nodes.len();
+            for &pos in &group {
+                extractor.node_of.insert(pos, id);
+            }
+            netlist.nodes.push(NetlistNode { id, kind: NodeKind::Wire, block: blocks[&group[0]].clone(), positions: group, delay: 0 });
+        }
+        for (&pos, block) in &blocks {
+            let kind = extractor.components[&pos];
+            if kind == ComponentKind::Wire {
+                continue;
+            }
+            let id = netlist.nodes.len();
+            extractor.node_of.insert(pos, id);
+            netlist.nodes.push(NetlistNode { id, kind: NodeKind::of(kind), block: block.clone(), positions: vec![pos], delay: delay_of(kind) });
+        }
+
+        let mut edges = HashSet::new();
+        for node in &netlist.nodes {
+            let delay = node.delay;
+            let mut add = |from: usize, kind: EdgeKind| {
+                if from != node.id {
+                    edges.insert((from, node.id, kind, delay));
+                }
+            };
+            let pos = node.positions[0];
+            match extractor.components[&pos] {
+                ComponentKind::Wire => {
+                    // Wires only pick up power from blocks that are strongly powered
+                    for &wire in &node.positions {
+                        for direction in Direction::ALL {
+                            for (from, kind) in extractor.sources(wire, direction, true) {
+                                add(from, kind);
+                            }
+                        }
+                    }
+                }
+                ComponentKind::Torch { attached } => {
+                    for (from, kind) in extractor.sources(pos, attached, false) {
+                        add(from, kind);
+                    }
+                }
+                ComponentKind::Repeater { facing, .. } | ComponentKind::Comparator { facing, .. } => {
+                    for (from, kind) in extractor.sources(pos, facing, false) {
+                        add(from, kind);
+                    }
+                    let side_kind = if node.kind == NodeKind::Repeater { EdgeKind::Lock } else { EdgeKind::Side };
+                    let right = facing.rotate_clockwise();
+                    for side in [right, right.opposite()] {
+                        if let Some(from) = extractor.side_source(pos, side) {
+                            add(from, side_kind);
+                        }
+                    }
+                }
+                ComponentKind::Lamp | ComponentKind::Piston { .. } => {
+                    for direction in Direction::ALL {
+                        if matches!(extractor.components[&pos], ComponentKind::Piston { facing, .. } if facing == direction) {
+                            continue;
+                        }
+                        for (from, kind) in extractor.sources(pos, direction, false) {
+                            add(from, kind);
+                        }
+                    }
+                }
+                ComponentKind::Observer { facing } => {
+                    if let Some(&from) = extractor.node_of.get(&facing.offset(pos)) {
+                        add(from, EdgeKind::Observe);
+                    }
+                }
+                ComponentKind::Lever { .. } | ComponentKind::Button { .. } | ComponentKind::RedstoneBlock => {}
+            }
+        }
+        let mut edges: Vec<NetlistEdge> = edges.into_iter()
+            .map(|(from, to, kind, delay)| NetlistEdge { from, to, kind, delay })
+            .collect();
+        edges.sort_by_key(|e| (e.from, e.to, e.kind as u8));
+        netlist.edges = edges;
+        netlist
+    }
+
+    /// Nodes nothing feeds into, like levers and buttons.
+    pub fn inputs(&self) -> Vec<&NetlistNode> {
+        let targets: HashSet<usize> = self.edges.iter().map(|e| e.to).collect();
+        self.nodes.iter().filter(|n| !targets.contains(&n.id)).collect()
+    }
+
+    /// Nodes that feed nothing, like lamps or the last torch of a gate.
+    pub fn outputs(&self) -> Vec<&NetlistNode> {
+        let sources: HashSet<usize> = self.edges.iter().map(|e| e.from).collect();
+        self.nodes.iter().filter(|n| !sources.contains(&n.id)).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Graphviz source with one node per component, labelled with its kind and position.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph netlist {\n    rankdir=LR;\n    node [fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let (x, y, z) = node.positions[0];
+            let shape = match node.kind {
+                NodeKind::Lever | NodeKind::Button => "invhouse",
+                NodeKind::Lamp | NodeKind::Piston => "house",
+                NodeKind::Wire => "ellipse",
+                NodeKind::Torch => "invtriangle",
+                _ => "box",
+            };
+            let mut label = format!("{}\\n{} {} {}", node.kind.name(), x, y, z);
+            if node.positions.len() > 1 {
+                label.push_str(&format!("\\n({} blocks)", node.positions.len()));
+            }
+            dot.push_str(&format!("    n{} [label=\"{}\", shape={}];\n", node.id, label, shape));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Side | EdgeKind::Lock => ", style=dashed",
+                EdgeKind::Observe => ", style=dotted",
+                _ => "",
+            };
+            dot.push_str(&format!("    n{} -> n{} [label=\"{}\"{}];\n", edge.from, edge.to, edge.delay, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_at(netlist: &Netlist, pos: Pos) -> usize {
+        netlist.nodes.iter().find(|n| n.positions.contains(&pos)).unwrap().id
+    }
+
+    #[test]
+    fn test_and_gate_netlist() {
+        let data = std::fs::read("tests/samples/and.schem").unwrap();
+        let schematic = crate::schematic::from_schematic(&data).unwrap();
+        let netlist = Netlist::extract(&schematic);
+        assert_eq!(netlist.nodes.len(), 6);
+
+        let (lever_a, lever_b) = (node_at(&netlist, (0, 0, 0)), node_at(&netlist, (2, 0, 0)));
+        let (torch_a, torch_b) = (node_at(&netlist, (0, 1, 1)), node_at(&netlist, (2, 1, 1)));
+        let wire = node_at(&netlist, (1, 1, 1));
+        let output = node_at(&netlist, (1, 0, 2));
+        let has_edge = |from, to| netlist.edges.iter().any(|e| e.from == from && e.to == to);
+
+        assert!(has_edge(lever_a, torch_a));
+        assert!(has_edge(lever_b, torch_b));
+        assert!(has_edge(torch_a, wire));
+        assert!(has_edge(torch_b, wire));
+        assert!(has_edge(wire, output));
+        assert!(!has_edge(lever_a, output));
+        let edge = netlist.edges.iter().find(|e| e.from == wire && e.to == output).unwrap();
+        assert_eq!((edge.kind, edge.delay), (EdgeKind::Weak, 2));
+
+        let inputs: Vec<usize> = netlist.inputs().iter().map(|n| n.id).collect();
+        assert_eq!(inputs, vec![lever_a.min(lever_b), lever_a.max(lever_b)]);
+        assert_eq!(netlist.outputs().iter().map(|n| n.id).collect::<Vec<_>>(), vec![output]);
+    }
+
+    #[test]
+    fn test_exports() {
+        let mut schematic = UniversalSchematic::new("Line".to_string());
+        let wire = crate::BlockState::new("minecraft:redstone_wire".to_string())
+            .with_property("east".to_string(), "side".to_string())
+            .with_property("west".to_string(), "side".to_string());
+        schematic.set_block(0, 0, 0, crate::BlockState::new("minecraft:redstone_block".to_string()));
+        schematic.set_block(1, 0, 0, wire.clone());
+        schematic.set_block(2, 0, 0, wire);
+        schematic.set_block(3, 0, 0, crate::BlockState::new("minecraft:redstone_lamp".to_string()));
+        let netlist = Netlist::extract(&schematic);
+        assert_eq!(netlist.nodes.len(), 3);
+        assert_eq!(netlist.edges.len(), 2);
+
+        let json: serde_json::Value = serde_json::from_str(&netlist.to_json().unwrap()).unwrap();
+        assert_eq!(json["nodes"][0]["kind"], "wire");
+        assert_eq!(json["nodes"][0]["positions"][1], serde_json::json!([2, 0, 0]));
+        assert_eq!(json["edges"].as_array().unwrap().len(), 2);
+
+        let dot = netlist.to_dot();
+        assert!(dot.starts_with("digraph netlist {"));
+        assert!(dot.contains("n0 -> n2"));
+        assert!(dot.contains("redstone_block\\n0 0 0"));
+    }
+}
@@ -0,0 +1,691 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::{BlockState, UniversalSchematic};
+use crate::utils::NbtValue;
+use super::components::{
+    breaks_when_pushed, classify, container_slots, is_conductor, is_immovable, is_replaceable,
+    wire_sides, ComponentKind, Direction, Pos,
+};
+
+/// Game ticks between a torch's input changing and the torch switching.
+const TORCH_DELAY: u64 = 2;
+const COMPARATOR_DELAY: u64 = 2;
+const OBSERVER_DELAY: u64 = 2;
+const OBSERVER_PULSE: u64 = 2;
+const LAMP_OFF_DELAY: u64 = 4;
+const PISTON_DELAY: u64 = 2;
+const MAX_PUSH: usize = 12;
+/// Limit on immediate update rounds per tick, in case something toggles back and forth.
+const MAX_UPDATE_ROUNDS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Re-evaluate the component and switch it if its input says so.
+    Update,
+    ObserverOn,
+    ObserverOff,
+    ButtonRelease,
+}
+
+/// A tick-based redstone simulator over a copy of a schematic's blocks.
+///
+/// Time is counted in game ticks (a repeater set to 1 waits 2 of them). Wires settle instantly,
+/// torches, comparators and observers switch after 2 game ticks, lamps turn off after 4 and
+/// pistons move after 2. Pistons push up to 12 blocks in a straight line; slime and honey
+/// blocks don't drag their neighbours along.
+#[derive(Debug, Clone)]
+pub struct RedstoneSimulator {
+    blocks: HashMap<Pos, BlockState>,
+    components: HashMap<Pos, ComponentKind>,
+    conductors: HashSet<Pos>,
+    /// Wire power, comparator output, and 15 or 0 for everything that is just on or off.
+    signals: HashMap<Pos, u8>,
+    /// Sides each wire points to, as a bit per horizontal direction.
+    wire_sides: HashMap<Pos, u8>,
+    /// Comparator readings of containers, from their contents when the simulator was created.
+    container_signals: HashMap<Pos, u8>,
+    schedule: BTreeMap<u64, Vec<(Pos, Action)>>,
+    pending: HashSet<Pos>,
+    changed: HashSet<Pos>,
+    dirty: HashSet<Pos>,
+    removed: HashSet<Pos>,
+    time: u64,
+}
+
+fn property_is(block: &BlockState, key: &str, value: &str) -> bool {
+    block.get_property(key).map(|v| v.as_str()) == Some(value)
+}
+
+/// The comparator reading of a container: 0 when empty, otherwise 1 to 15 by fullness.
+fn container_signal(name: &str, items: Option<&NbtValue>) -> u8 {
+    let Some(slots) = container_slots(name) else { return 0 };
+    let Some(NbtValue::List(items)) = items else { return 0 };
+    let mut fullness = 0.0;
+    for item in items {
+        let NbtValue::Compound(item) = item else { continue };
+        let count = match item.get("Count").or_else(|| item.get("count")) {
+            Some(NbtValue::Byte(c)) => *c as f64,
+            Some(NbtValue::Int(c)) => *c as f64,
+            _ => 1.0,
+        };
+        let id = match item.get("id") {
+            Some(NbtValue::String(id)) => id.strip_prefix("minecraft:").unwrap_or(id),
+            _ => "",
+        };
+        let max_stack = if id.ends_with("_bucket") || id.ends_with("_sign") || matches!(id, "ender_pearl" | "snowball" | "egg" | "honey_bottle" | "armor_stand") {
+            16.0
+        } else if id.ends_with("_sword") || id.ends_with("_pickaxe") || id.ends_with("_axe") || id.ends_with("_shovel") || id.ends_with("_hoe") || id.ends_with("_shulker_box") || id == "shulker_box" {
+            1.0
+        } else {
+            64.0
+        };
+        fullness += count / max_stack;
+    }
+    if fullness <= 0.0 {
+        0
+    } else {
+        (1.0 + fullness / slots as f64 * 14.0).floor().min(15.0) as u8
+    }
+}
+
+impl RedstoneSimulator {
+    pub fn new(schematic: &UniversalSchematic) -> Self {
+        let mut simulator = RedstoneSimulator {
+            blocks: HashMap::new(),
+            components: HashMap::new(),
+            conductors: HashSet::new(),
+            signals: HashMap::new(),
+            wire_sides: HashMap::new(),
+            container_signals: HashMap::new(),
+            schedule: BTreeMap::new(),
+            pending: HashSet::new(),
+            changed: HashSet::new(),
+            dirty: HashSet::new(),
+            removed: HashSet::new(),
+            time: 0,
+        };
+        for (pos, block) in schematic.iter_blocks() {
+            if !is_replaceable(block) {
+                simulator.insert_block((pos.x, pos.y, pos.z), block.clone());
+            }
+        }
+        for block_entity in schematic.get_block_entities_as_list() {
+            let pos = block_entity.position;
+            if let Some(block) = simulator.blocks.get(&pos) {
+                let signal = container_signal(&block.name, block_entity.nbt.get("Items"));
+                simulator.container_signals.insert(pos, signal);
+                if matches!(simulator.components.get(&pos), Some(ComponentKind::Comparator { .. })) {
+                    if let Some(NbtValue::Int(output)) = block_entity.nbt.get("OutputSignal") {
+                        simulator.signals.insert(pos, (*output).clamp(0, 15) as u8);
+                    }
+                }
+            }
+        }
+        simulator.update();
+        simulator.dirty.clear();
+        simulator
+    }
+
+    /// Records a block and works out its initial signal from its state.
+    fn insert_block(&mut self, pos: Pos, block: BlockState) {
+        self.components.remove(&pos);
+        self.conductors.remove(&pos);
+        self.signals.remove(&pos);
+        self.wire_sides.remove(&pos);
+        if is_conductor(&block) {
+            self.conductors.insert(pos);
+        }
+        if let Some(kind) = classify(&block) {
+            let on = |key: &str| if property_is(&block, key, "true") { 15 } else { 0 };
+            let signal = match kind {
+                ComponentKind::Wire => {
+                    self.wire_sides.insert(pos, wire_sides(&block));
+                    block.get_property("power").and_then(|p| p.parse().ok()).unwrap_or(0)
+                }
+                ComponentKind::Torch { .. } => if block.get_property("lit").is_some() { on("lit") } else { 15 },
+                ComponentKind::Lamp => on("lit"),
+                ComponentKind::Piston { .. } => on("extended"),
+                ComponentKind::RedstoneBlock => 15,
+                _ => on("powered"),
+            };
+            self.signals.insert(pos, signal);
+            self.components.insert(pos, kind);
+        }
+        self.blocks.insert(pos, block);
+        self.removed.remove(&pos);
+    }
+
+    fn remove_block(&mut self, pos: Pos) -> Option<BlockState> {
+        self.components.remove(&pos);
+        self.conductors.remove(&pos);
+        self.signals.remove(&pos);
+        self.wire_sides.remove(&pos);
+        self.pending.remove(&pos);
+        let block = self.blocks.remove(&pos);
+        if block.is_some() {
+            self.removed.insert(pos);
+            self.dirty.remove(&pos);
+            self.changed.insert(pos);
+        }
+        block
+    }
+
+    fn place_block(&mut self, pos: Pos, block: BlockState) {
+        self.insert_block(pos, block);
+        self.dirty.insert(pos);
+        self.changed.insert(pos);
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.time
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<&BlockState> {
+        self.blocks.get(&(x, y, z))
+    }
+
+    /// The signal a component holds: wire power, comparator output, or 15 for a powered or lit
+    /// component and 0 otherwise.
+    pub fn get_signal(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.signals.get(&(x, y, z)).copied().unwrap_or(0)
+    }
+
+    /// Whether the lamp, torch or other on/off component at the position is on.
+    pub fn is_on(&self, x: i32, y: i32, z: i32) -> bool {
+        self.get_signal(x, y, z) > 0
+    }
+
+    fn signal(&self, pos: Pos) -> u8 {
+        self.signals.get(&pos).copied().unwrap_or(0)
+    }
+
+    /// Sets a component's signal and mirrors it into its block state.
+    fn set_signal(&mut self, pos: Pos, signal: u8) {
+        if self.signal(pos) == signal {
+            return;
+        }
+        self.signals.insert(pos, signal);
+        let Some(kind) = self.components.get(&pos).copied() else { return };
+        let Some(block) = self.blocks.get_mut(&pos) else { return };
+        let flag = (if signal > 0 { "true" } else { "false" }).to_string();
+        match kind {
+            ComponentKind::Wire => block.set_property("power".to_string(), signal.to_string()),
+            ComponentKind::Torch { .. } | ComponentKind::Lamp => block.set_property("lit".to_string(), flag),
+            ComponentKind::Piston { .. } => block.set_property("extended".to_string(), flag),
+            ComponentKind::RedstoneBlock => {}
+            _ => block.set_property("powered".to_string(), flag),
+        }
+        self.dirty.insert(pos);
+        self.changed.insert(pos);
+    }
+
+    fn schedule(&mut self, pos: Pos, delay: u64, action: Action) {
+        if action == Action::Update && !self.pending.insert(pos) {
+            return;
+        }
+        self.schedule.entry(self.time + delay.max(1)).or_default().push((pos, action));
+    }
+
+    /// Signal the component at `from` sends towards `direction`.
+    fn emitted(&self, from: Pos, direction: Direction) -> u8 {
+        let Some(kind) = self.components.get(&from) else { return 0 };
+        let sides = self.wire_sides.get(&from).copied().unwrap_or(0);
+        if kind.emits_towards(direction, sides) { self.signal(from) } else { 0 }
+    }
+
+    /// Power a conductor at `pos` holds. Strong power comes from diodes, observers, torches
+    /// below it and levers or buttons on it; weak power also counts wires on or pointing into it.
+    fn conductor_power(&self, pos: Pos, include_weak: bool) -> u8 {
+        let mut power = 0;
+        for direction in Direction::ALL {
+            let neighbour = direction.offset(pos);
+            let Some(kind) = self.components.get(&neighbour) else { continue };
+            let towards_us = direction.opposite();
+            let strong = kind.strongly_powers(towards_us) || (include_weak && *kind == ComponentKind::Wire);
+            if strong {
+                power = power.max(self.emitted(neighbour, towards_us));
+            }
+        }
+        power
+    }
+
+    /// Power reaching the component at `pos` from the neighbour on side `direction`.
+    fn input_from(&self, pos: Pos, direction: Direction) -> u8 {
+        let neighbour = direction.offset(pos);
+        if self.components.contains_key(&neighbour) {
+            self.emitted(neighbour, direction.opposite())
+        } else if self.conductors.contains(&neighbour) {
+            self.conductor_power(neighbour, true)
+        } else {
+            0
+        }
+    }
+
+    fn received_power(&self, pos: Pos, except: Option<Direction>) -> u8 {
+        Direction::ALL.iter()
+            .filter(|&&d| Some(d) != except)
+            .map(|&d| self.input_from(pos, d))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_locked(&self, pos: Pos, facing: Direction) -> bool {
+        let right = facing.rotate_clockwise();
+        [right, right.opposite()].iter().any(|&side| {
+            let neighbour = side.offset(pos);
+            matches!(self.components.get(&neighbour), Some(ComponentKind::Repeater { .. } | ComponentKind::Comparator { .. }))
+                && self.emitted(neighbour, side.opposite()) > 0
+        })
+    }
+
+    fn comparator_output(&self, pos: Pos, facing: Direction, subtract: bool) -> u8 {
+        let behind = facing.offset(pos);
+        let rear = if self.container_signals.contains_key(&behind) {
+            self.container_signals[&behind]
+        } else if self.conductors.contains(&behind) && self.container_signals.contains_key(&facing.offset(behind)) {
+            self.input_from(pos, facing).max(self.container_signals[&facing.offset(behind)])
+        } else {
+            self.input_from(pos, facing)
+        };
+        let right = facing.rotate_clockwise();
+        let side = [right, right.opposite()].iter()
+            .map(|&s| {
+                let neighbour = s.offset(pos);
+                match self.components.get(&neighbour) {
+                    Some(ComponentKind::Wire) => self.signal(neighbour),
+                    Some(_) => self.emitted(neighbour, s.opposite()),
+                    None => 0,
+                }
+            })
+            .max()
+            .unwrap_or(0);
+        if subtract {
+            rear.saturating_sub(side)
+        } else if side > rear {
+            0
+        } else {
+            rear
+        }
+    }
+
+    fn piston_powered(&self, pos: Pos, facing: Direction) -> bool {
+        // Quasi-connectivity: a piston also reacts to power reaching the space above it
+        self.received_power(pos, Some(facing)) > 0
+            || Direction::ALL.iter()
+                .filter(|&&d| d != Direction::Down)
+                .any(|&d| self.input_from(Direction::Up.offset(pos), d) > 0)
+    }
+
+    /// Recomputes every wire's power from its sources, spreading it along connected wires.
+    fn update_wires(&mut self) {
+        let wires: Vec<Pos> = self.wire_sides.keys().copied().collect();
+        let mut power: HashMap<Pos, u8> = HashMap::with_capacity(wires.len());
+        let mut buckets: Vec<Vec<Pos>> = vec![Vec::new(); 16];
+        for &wire in &wires {
+            let mut source = 0;
+            for direction in Direction::ALL {
+                let neighbour = direction.offset(wire);
+                let incoming = match self.components.get(&neighbour) {
+                    Some(ComponentKind::Wire) => 0,
+                    Some(_) => self.emitted(neighbour, direction.opposite()),
+                    None if self.conductors.contains(&neighbour) => self.conductor_power(neighbour, false),
+                    None => 0,
+                };
+                source = source.max(incoming);
+            }
+            power.insert(wire, source);
+            buckets[source as usize].push(wire);
+        }
+
+        for level in (1..16).rev() {
+            while let Some(wire) = buckets[level].pop() {
+                if power[&wire] as usize != level {
+                    continue;
+                }
+                let above_cut = self.conductors.contains(&Direction::Up.offset(wire));
+                for direction in Direction::HORIZONTAL {
+                    let side = direction.offset(wire);
+                    let mut targets = vec![side];
+                    if !above_cut {
+                        targets.push(Direction::Up.offset(side));
+                    }
+                    if !self.conductors.contains(&side) {
+                        targets.push(Direction::Down.offset(side));
+                    }
+                    for target in targets {
+                        if let Some(current) = power.get_mut(&target) {
+                            if (*current as usize) < level - 1 {
+                                *current = (level - 1) as u8;
+                                buckets[level - 1].push(target);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (wire, level) in power {
+            self.set_signal(wire, level);
+        }
+    }
+
+    /// Settles wires and lamps and schedules every component whose input no longer matches.
+    fn update(&mut self) {
+        for _ in 0..MAX_UPDATE_ROUNDS {
+            self.update_wires();
+            let changed = std::mem::take(&mut self.changed);
+            let components: Vec<(Pos, ComponentKind)> = self.components.iter().map(|(p, k)| (*p, *k)).collect();
+            for (pos, kind) in components {
+                let on = self.signal(pos) > 0;
+                let due = match kind {
+                    ComponentKind::Torch { attached } => {
+                        ((self.input_from(pos, attached) == 0) != on).then_some((TORCH_DELAY, Action::Update))
+                    }
+                    ComponentKind::Repeater { facing, delay } => {
+                        let switching = !self.is_locked(pos, facing) && (self.input_from(pos, facing) > 0) != on;
+                        switching.then_some((2 * delay as u64, Action::Update))
+                    }
+                    ComponentKind::Comparator { facing, subtract } => {
+                        let output = self.comparator_output(pos, facing, subtract);
+                        (output != self.signal(pos)).then_some((COMPARATOR_DELAY, Action::Update))
+                    }
+                    ComponentKind::Lamp => {
+                        // Lamps light up straight away but take a while to go out
+                        let powered = self.received_power(pos, None) > 0;
+                        if powered && !on {
+                            self.set_signal(pos, 15);
+                        }
+                        (!powered && on).then_some((LAMP_OFF_DELAY, Action::Update))
+                    }
+                    ComponentKind::Piston { facing, .. } => {
+                        (self.piston_powered(pos, facing) != on).then_some((PISTON_DELAY, Action::Update))
+                    }
+                    ComponentKind::Observer { facing } => {
+                        let watched_changed = !on && changed.contains(&facing.offset(pos));
+                        watched_changed.then_some((OBSERVER_DELAY, Action::ObserverOn))
+                    }
+                    _ => None,
+                };
+                if let Some((delay, action)) = due {
+                    self.schedule(pos, delay, action);
+                }
+            }
+            if self.changed.is_empty() {
+                break;
+            }
+        }
+        self.changed.clear();
+    }
+
+    fn execute(&mut self, pos: Pos, action: Action) {
+        let Some(kind) = self.components.get(&pos).copied() else { return };
+        match (action, kind) {
+            (Action::ObserverOn, _) => {
+                self.set_signal(pos, 15);
+                self.schedule.entry(self.time + OBSERVER_PULSE).or_default().push((pos, Action::ObserverOff));
+            }
+            (Action::ObserverOff, _) | (Action::ButtonRelease, _) => self.set_signal(pos, 0),
+            (Action::Update, ComponentKind::Torch { attached }) => {
+                let lit = self.input_from(pos, attached) == 0;
+                self.set_signal(pos, if lit { 15 } else { 0 });
+            }
+            (Action::Update, ComponentKind::Repeater { facing, .. }) => {
+                if self.is_locked(pos, facing) {
+                    return;
+                }
+                // A repeater that was switched on always stays on for its full delay
+                let on = self.signal(pos) > 0;
+                if on && self.input_from(pos, facing) == 0 {
+                    self.set_signal(pos, 0);
+                } else if !on {
+                    self.set_signal(pos, 15);
+                }
+            }
+            (Action::Update, ComponentKind::Comparator { facing, subtract }) => {
+                let output = self.comparator_output(pos, facing, subtract);
+                self.set_signal(pos, output);
+            }
+            (Action::Update, ComponentKind::Lamp) if self.received_power(pos, None) == 0 => {
+                self.set_signal(pos, 0);
+            }
+            (Action::Update, ComponentKind::Piston { facing, sticky }) => {
+                let powered = self.piston_powered(pos, facing);
+                let extended = self.signal(pos) > 0;
+                if powered && !extended {
+                    self.extend_piston(pos, facing, sticky);
+                } else if !powered && extended {
+                    self.retract_piston(pos, facing, sticky);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn extend_piston(&mut self, pos: Pos, facing: Direction, sticky: bool) {
+        let mut line = Vec::new();
+        let mut cursor = facing.offset(pos);
+        while let Some(block) = self.blocks.get(&cursor) {
+            if breaks_when_pushed(block) {
+                break;
+            }
+            if is_immovable(block) || line.len() == MAX_PUSH {
+                return;
+            }
+            line.push(cursor);
+            cursor = facing.offset(cursor);
+        }
+        if self.blocks.contains_key(&cursor) {
+            self.remove_block(cursor);
+        }
+        for &from in line.iter().rev() {
+            if let Some(block) = self.remove_block(from) {
+                self.place_block(facing.offset(from), block);
+            }
+        }
+        let head = BlockState::new("minecraft:piston_head".to_string())
+            .with_property("facing".to_string(), facing.name().to_string())
+            .with_property("type".to_string(), (if sticky { "sticky" } else { "normal" }).to_string())
+            .with_property("short".to_string(), "false".to_string());
+        self.place_block(facing.offset(pos), head);
+        self.set_signal(pos, 15);
+    }
+
+    fn retract_piston(&mut self, pos: Pos, facing: Direction, sticky: bool) {
+        let head = facing.offset(pos);
+        if self.blocks.get(&head).is_some_and(|b| b.name == "minecraft:piston_head") {
+            self.remove_block(head);
+        }
+        if sticky {
+            let pulled = facing.offset(head);
+            let movable = self.blocks.get(&pulled).is_some_and(|b| !is_immovable(b) && !breaks_when_pushed(b));
+            if movable && !self.blocks.contains_key(&head) {
+                if let Some(block) = self.remove_block(pulled) {
+                    self.place_block(head, block);
+                }
+            }
+        }
+        self.set_signal(pos, 0);
+    }
+
+    /// Advances the simulation by one game tick.
+    pub fn tick(&mut self) {
+        self.time += 1;
+        if let Some(due) = self.schedule.remove(&self.time) {
+            for (pos, action) in due {
+                if action == Action::Update {
+                    self.pending.remove(&pos);
+                }
+                self.execute(pos, action);
+            }
+        }
+        self.update();
+    }
+
+    /// Advances the simulation by `ticks` game ticks.
+    pub fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// Runs until nothing is scheduled any more, for at most `max_ticks`. Returns whether the
+    /// circuit settled; clocks never do.
+    pub fn run_until_stable(&mut self, max_ticks: u32) -> bool {
+        for _ in 0..max_ticks {
+            if self.schedule.is_empty() {
+                return true;
+            }
+            self.tick();
+        }
+        self.schedule.is_empty()
+    }
+
+    pub fn set_lever(&mut self, x: i32, y: i32, z: i32, on: bool) -> Result<(), String> {
+        let pos = (x, y, z);
+        if !matches!(self.components.get(&pos), Some(ComponentKind::Lever { .. })) {
+            return Err(format!("No lever at {}, {}, {}", x, y, z));
+        }
+        self.set_signal(pos, if on { 15 } else { 0 });
+        self.update();
+        Ok(())
+    }
+
+    /// Flips a lever and returns its new state.
+    pub fn toggle_lever(&mut self, x: i32, y: i32, z: i32) -> Result<bool, String> {
+        let on = !self.is_on(x, y, z);
+        self.set_lever(x, y, z, on)?;
+        Ok(on)
+    }
+
+    /// Presses a button; it releases by itself after 20 (stone) or 30 (wood) game ticks.
+    pub fn press_button(&mut self, x: i32, y: i32, z: i32) -> Result<(), String> {
+        let pos = (x, y, z);
+        let Some(ComponentKind::Button { duration, .. }) = self.components.get(&pos).copied() else {
+            return Err(format!("No button at {}, {}, {}", x, y, z));
+        };
+        if self.signal(pos) == 0 {
+            self.set_signal(pos, 15);
+            self.schedule.entry(self.time + duration as u64).or_default().push((pos, Action::ButtonRelease));
+            self.update();
+        }
+        Ok(())
+    }
+
+    /// Writes every block the simulation changed back into `schematic`.
+    pub fn apply_to(&self, schematic: &mut UniversalSchematic) {
+        for &(x, y, z) in &self.removed {
+            schematic.set_block(x, y, z, BlockState::new("minecraft:air".to_string()));
+        }
+        for &pos in &self.dirty {
+            let Some(block) = self.blocks.get(&pos) else { continue };
+            let (x, y, z) = pos;
+            schematic.set_block(x, y, z, block.clone());
+            if matches!(self.components.get(&pos), Some(ComponentKind::Comparator { .. })) {
+                let position = crate::block_position::BlockPosition { x, y, z };
+                if let Some(mut block_entity) = schematic.get_block_entity(position).cloned() {
+                    block_entity.nbt.insert("OutputSignal".to_string(), NbtValue::Int(self.signal(pos) as i32));
+                    schematic.set_block_entity(position, block_entity);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::block;
+
+    fn load_sample(name: &str) -> UniversalSchematic {
+        let data = std::fs::read(format!("tests/samples/{}", name)).unwrap();
+        crate::schematic::from_schematic(&data).unwrap()
+    }
+
+    #[test]
+    fn test_and_gate_sample() {
+        // Levers at (0,0,0) and (2,0,0), output torch at (1,0,2)
+        let mut schematic = load_sample("and.schem");
+        let mut simulator = RedstoneSimulator::new(&schematic);
+        simulator.run_until_stable(100);
+        assert!(!simulator.is_on(1, 0, 2));
+
+        for (a, b, expected) in [(true, false, false), (true, true, true), (false, true, false), (false, false, false)] {
+            simulator.set_lever(0, 0, 0, a).unwrap();
+            simulator.set_lever(2, 0, 0, b).unwrap();
+            assert!(simulator.run_until_stable(100));
+            assert_eq!(simulator.is_on(1, 0, 2), expected, "inputs {} {}", a, b);
+        }
+
+        simulator.set_lever(0, 0, 0, true).unwrap();
+        simulator.set_lever(2, 0, 0, true).unwrap();
+        simulator.run_until_stable(100);
+        simulator.apply_to(&mut schematic);
+        let output = schematic.get_block(1, 0, 2).unwrap();
+        assert_eq!(output.get_property("lit").map(|s| s.as_str()), Some("true"));
+        let wire = schematic.get_block(1, 1, 1).unwrap();
+        assert_eq!(wire.get_property("power").map(|s| s.as_str()), Some("0"));
+        assert!(simulator.toggle_lever(1, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_wire_decay_repeater_delay_and_lamp() {
+        // Lever -> 3 wires -> repeater (delay 2, facing west so its input is west) -> lamp
+        let mut schematic = UniversalSchematic::new("Line".to_string());
+        schematic.set_block(0, 0, 0, block("lever", &[("face", "floor"), ("facing", "east"), ("powered", "false")]));
+        for x in 1..4 {
+            schematic.set_block(x, 0, 0, block("redstone_wire", &[("east", "side"), ("west", "side"), ("power", "0")]));
+        }
+        schematic.set_block(4, 0, 0, block("repeater", &[("facing", "west"), ("delay", "2"), ("powered", "false")]));
+        schematic.set_block(5, 0, 0, block("redstone_lamp", &[("lit", "false")]));
+        for x in 0..6 {
+            schematic.set_block(x, -1, 0, block("stone", &[]));
+        }
+
+        let mut simulator = RedstoneSimulator::new(&schematic);
+        simulator.set_lever(0, 0, 0, true).unwrap();
+        assert_eq!(simulator.get_signal(1, 0, 0), 15);
+        assert_eq!(simulator.get_signal(3, 0, 0), 13);
+        simulator.step(3);
+        assert!(!simulator.is_on(4, 0, 0));
+        simulator.step(1);
+        assert!(simulator.is_on(4, 0, 0));
+        assert!(simulator.is_on(5, 0, 0));
+
+        // Lamps take 4 game ticks to turn off after the repeater does
+        simulator.set_lever(0, 0, 0, false).unwrap();
+        simulator.step(4);
+        assert!(!simulator.is_on(4, 0, 0));
+        assert!(simulator.is_on(5, 0, 0));
+        simulator.step(4);
+        assert!(!simulator.is_on(5, 0, 0));
+    }
+
+    #[test]
+    fn test_piston_and_observer() {
+        // A piston pushing a block when its lever is on
+        let mut schematic = UniversalSchematic::new("Piston".to_string());
+        schematic.set_block(0, 0, 0, block("lever", &[("face", "floor"), ("facing", "north"), ("powered", "false")]));
+        schematic.set_block(1, 0, 0, block("sticky_piston", &[("facing", "east"), ("extended", "false")]));
+        schematic.set_block(2, 0, 0, block("oak_planks", &[]));
+        // An observer watching the planks' final spot, lighting a lamp behind it
+        schematic.set_block(3, 1, 0, block("observer", &[("facing", "down"), ("powered", "false")]));
+        schematic.set_block(3, 2, 0, block("redstone_lamp", &[("lit", "false")]));
+
+        let mut simulator = RedstoneSimulator::new(&schematic);
+        simulator.set_lever(0, 0, 0, true).unwrap();
+        simulator.run_until_stable(20);
+        assert_eq!(simulator.get_block(2, 0, 0).unwrap().name, "minecraft:piston_head");
+        assert_eq!(simulator.get_block(3, 0, 0).unwrap().name, "minecraft:oak_planks");
+        assert!(!simulator.is_on(3, 1, 0));
+
+        let mut pulse_seen = false;
+        simulator.set_lever(0, 0, 0, false).unwrap();
+        for _ in 0..20 {
+            simulator.tick();
+            pulse_seen |= simulator.is_on(3, 1, 0) && simulator.is_on(3, 2, 0);
+        }
+        assert!(pulse_seen);
+        assert_eq!(simulator.get_block(2, 0, 0).unwrap().name, "minecraft:oak_planks");
+        assert!(simulator.get_block(3, 0, 0).is_none());
+        assert!(!simulator.is_on(3, 1, 0));
+    }
+}
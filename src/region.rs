@@ -6,6 +6,8 @@ use crate::block_entity::BlockEntity;
 use crate::block_position::BlockPosition;
 use crate::bounding_box::BoundingBox;
 use crate::entity::Entity;
+use crate::utils::NbtMap;
+use crate::formats::limits::LoadLimits;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Region {
@@ -17,6 +19,25 @@ pub struct Region {
     pub entities: Vec<Entity>,
     #[serde(serialize_with = "serialize_block_entities", deserialize_with = "deserialize_block_entities")]
     pub block_entities: HashMap<(i32, i32, i32), BlockEntity>,
+    #[serde(default)]
+    pub pending_block_ticks: Vec<PendingTick>,
+    #[serde(default)]
+    pub pending_fluid_ticks: Vec<PendingTick>,
+    /// Region tags from the source file that aren't interpreted, written back unchanged.
+    #[serde(default)]
+    pub extra_nbt: NbtMap,
+}
+
+/// A block or fluid update scheduled for a later game tick.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingTick {
+    pub position: (i32, i32, i32),
+    /// Id of the block or fluid to tick.
+    pub target: String,
+    pub priority: i32,
+    pub sub_tick: i64,
+    /// Game ticks until the update runs.
+    pub time: i32,
 }
 
 fn serialize_block_entities<S>(
@@ -26,7 +47,8 @@ fn serialize_block_entities<S>(
 where
     S: Serializer,
 {
-    let block_entities_vec: Vec<&BlockEntity> = block_entities.values().collect();
+    let mut block_entities_vec: Vec<&BlockEntity> = block_entities.values().collect();
+    block_entities_vec.sort_by_key(|block_entity| block_entity.position);
     block_entities_vec.serialize(serializer)
 }
 
@@ -45,6 +67,19 @@ where
         })
         .collect())
 }
+
+/// Parses the `"x,y,z"` keys `to_nbt` writes for blocks and block entities.
+fn parse_coordinate_key(key: &str) -> Result<(i32, i32, i32), String> {
+    let coords: Vec<i32> = key.split(',')
+        .map(|s| s.parse::<i32>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid position key '{}': {}", key, e))?;
+    match coords[..] {
+        [x, y, z] => Ok((x, y, z)),
+        _ => Err(format!("Invalid position key '{}'", key)),
+    }
+}
+
 impl Region {
     pub fn new(name: String, position: (i32, i32, i32), size: (i32, i32, i32)) -> Self {
         let bounding_box = BoundingBox::from_position_and_size(position, size);
@@ -60,6 +95,9 @@ impl Region {
             palette,
             entities: Vec::new(),
             block_entities: HashMap::new(),
+            pending_block_ticks: Vec::new(),
+            pending_fluid_ticks: Vec::new(),
+            extra_nbt: NbtMap::new(),
         }
     }
 
@@ -143,6 +181,27 @@ impl Region {
         }
     }
 
+    /// Replaces every palette entry with `f(entry)`, merging entries that become identical.
+    pub(crate) fn remap_palette(&mut self, mut f: impl FnMut(&BlockState) -> BlockState) {
+        let mut new_palette: Vec<BlockState> = Vec::with_capacity(self.palette.len());
+        let mapping: Vec<usize> = self.palette.iter()
+            .map(|block| {
+                let mapped = f(block);
+                match new_palette.iter().position(|b| b == &mapped) {
+                    Some(index) => index,
+                    None => {
+                        new_palette.push(mapped);
+                        new_palette.len() - 1
+                    }
+                }
+            })
+            .collect();
+        for block in self.blocks.iter_mut() {
+            *block = mapping[*block];
+        }
+        self.palette = new_palette;
+    }
+
     pub fn volume(&self) -> usize {
         self.size.0 as usize * self.size.1 as usize * self.size.2 as usize
     }
@@ -176,9 +235,12 @@ impl Region {
 
 
     fn calculate_bits_per_block(&self) -> usize {
-        let palette_size = self.palette.len();
-        let bits_per_block = std::cmp::max((palette_size as f64).log2().ceil() as usize, 2);
-        bits_per_block
+        Self::bits_per_block(self.palette.len())
+    }
+
+    /// Bits per entry in Litematica's packed `BlockStates` for a palette of `palette_size`.
+    pub(crate) fn bits_per_block(palette_size: usize) -> usize {
+        std::cmp::max((palette_size as f64).log2().ceil() as usize, 2)
     }
 
 
@@ -239,9 +301,11 @@ impl Region {
         self.palette = new_palette;
 
 
-        // Merge entities and block entities
+        // Merge entities, block entities and scheduled ticks
         self.merge_entities(other);
         self.merge_block_entities(other);
+        self.pending_block_ticks.extend(other.pending_block_ticks.iter().cloned());
+        self.pending_fluid_ticks.extend(other.pending_fluid_ticks.iter().cloned());
     }
 
     fn calculate_new_index(&self, x: i32, y: i32, z: i32, new_position: &(i32, i32, i32), new_size: &(i32, i32, i32)) -> usize {
@@ -271,6 +335,13 @@ impl Region {
         self.block_entities.insert(block_entity.position, block_entity);
     }
 
+    /// Block entities ordered by position (x, then y, then z), for stable output.
+    pub fn sorted_block_entities(&self) -> Vec<&BlockEntity> {
+        let mut block_entities: Vec<&BlockEntity> = self.block_entities.values().collect();
+        block_entities.sort_by_key(|block_entity| block_entity.position);
+        block_entities
+    }
+
     pub fn remove_block_entity(&mut self, position: (i32, i32, i32)) -> Option<BlockEntity> {
         self.block_entities.remove(&position)
     }
@@ -334,16 +405,28 @@ impl Region {
 
         let blocks_tag = nbt.get::<_, &NbtCompound>("Blocks")
             .map_err(|e| format!("Failed to get Blocks: {}", e))?;
-        let mut blocks = vec![0; (size.0 * size.1 * size.2) as usize];
+        if size.0 < 0 || size.1 < 0 || size.2 < 0 {
+            return Err(format!("Invalid Size {:?}", size));
+        }
+        let volume = LoadLimits::default().check_region(position, size)?;
+        // Every block is listed, so the entries bound how much a lying Size can allocate
+        if volume > blocks_tag.len() as u64 {
+            return Err(format!("Blocks has {} entries for a region of {} blocks", blocks_tag.len(), volume));
+        }
+        let mut blocks = vec![0; volume as usize];
         for (key, value) in blocks_tag.inner() {
             if let NbtTag::Int(index) = value {
-                let coords: Vec<i32> = key.split(',')
-                    .map(|s| s.parse::<i32>().unwrap())
-                    .collect();
-                if coords.len() == 3 {
-                    let block_index = (coords[1] * size.0 * size.2 + coords[2] * size.0 + coords[0]) as usize;
-                    blocks[block_index] = *index as usize;
-                }
+                // Keys are world coordinates, as `to_nbt` writes them
+                let (x, y, z) = parse_coordinate_key(key)?;
+                let local = (x.checked_sub(position.0), y.checked_sub(position.1), z.checked_sub(position.2));
+                let (x, y, z) = match local {
+                    (Some(x), Some(y), Some(z))
+                        if (0..size.0).contains(&x) && (0..size.1).contains(&y) && (0..size.2).contains(&z)
+                            && *index >= 0 && (*index as usize) < palette.len() => (x, y, z),
+                    _ => return Err(format!("Invalid block {} at {}", index, key)),
+                };
+                let block_index = (y as usize * size.2 as usize + z as usize) * size.0 as usize + x as usize;
+                blocks[block_index] = *index as usize;
             }
         }
 
@@ -364,14 +447,8 @@ impl Region {
         let mut block_entities = HashMap::new();
         for (key, value) in block_entities_tag.inner() {
             if let NbtTag::Compound(be_compound) = value {
-                let coords: Vec<i32> = key.split(',')
-                    .map(|s| s.parse::<i32>().unwrap())
-                    .collect();
-                if coords.len() == 3 {
-                    if let block_entity = BlockEntity::from_nbt(be_compound) {
-                        block_entities.insert((coords[0], coords[1], coords[2]), block_entity);
-                    }
-                }
+                let position = parse_coordinate_key(key)?;
+                block_entities.insert(position, BlockEntity::from_nbt(be_compound)?);
             }
         }
 
@@ -383,6 +460,9 @@ impl Region {
             palette,
             entities,
             block_entities,
+            pending_block_ticks: Vec::new(),
+            pending_fluid_ticks: Vec::new(),
+            extra_nbt: NbtMap::new(),
         })
     }
 
@@ -529,6 +609,9 @@ mod tests {
             palette,
             entities: Vec::new(),
             block_entities: HashMap::new(),
+            pending_block_ticks: Vec::new(),
+            pending_fluid_ticks: Vec::new(),
+            extra_nbt: NbtMap::new(),
         };
         let packed_states = region.create_packed_block_states();
         assert_eq!(packed_states.len(), 2);
@@ -619,6 +702,25 @@ mod tests {
         assert_eq!(region.get_block(0, 0, 0), deserialized_region.get_block(0, 0, 0));
     }
 
+    #[test]
+    fn test_from_nbt_rejects_bad_blocks() {
+        let region = Region::new("Test".to_string(), (0, 0, 0), (2, 2, 2));
+        let NbtTag::Compound(mut compound) = region.to_nbt() else { panic!("Expected NbtTag::Compound") };
+        let mut blocks = NbtCompound::new();
+        blocks.insert("0,x,0", NbtTag::Int(0));
+        compound.insert("Blocks", NbtTag::Compound(blocks.clone()));
+        assert!(Region::from_nbt(&compound).is_err());
+
+        blocks = NbtCompound::new();
+        blocks.insert("5,0,0", NbtTag::Int(0));
+        compound.insert("Blocks", NbtTag::Compound(blocks));
+        assert!(Region::from_nbt(&compound).is_err());
+
+        let NbtTag::Compound(mut compound) = region.to_nbt() else { panic!("Expected NbtTag::Compound") };
+        compound.insert("Size", NbtTag::IntArray(vec![30_000, 30_000, 30_000]));
+        assert!(Region::from_nbt(&compound).is_err());
+    }
+
     #[test]
     fn test_to_litematic_nbt() {
         let mut region = Region::new("Test".to_string(), (0, 0, 0), (2, 2, 2));
@@ -0,0 +1,490 @@
+//! Software-rendered previews: an isometric view and a top-down map.
+//!
+//! Blocks are drawn as flat-colored cubes using the bundled color table from
+//! [`crate::block_colors`]. The isometric view shades the top, south and east faces differently.
+//! The top-down view shades each column by its height relative to the column north of it, like
+//! in-game maps.
+
+use std::collections::HashMap;
+use crate::UniversalSchematic;
+use crate::block_colors::{get_block_color, is_invisible};
+use crate::formats::mesh::is_transparent;
+
+/// Color used for blocks missing from the color table.
+const UNKNOWN_BLOCK_COLOR: [u8; 3] = [128, 128, 128];
+/// Opacity of glass, water, ice and the like.
+const TRANSLUCENT_ALPHA: u8 = 150;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Upper bound for the width and height of the image, in pixels.
+    pub resolution: u32,
+    /// RGBA color behind the blocks. Transparent by default.
+    pub background: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            resolution: 512,
+            background: [0, 0, 0, 0],
+        }
+    }
+}
+
+/// An RGBA8 image, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, background: [u8; 4]) -> Self {
+        Image {
+            width,
+            height,
+            pixels: background.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// Draws `color` over the pixel at (`x`, `y`) with the given opacity.
+    fn blend(&mut self, x: u32, y: u32, color: [u8; 3], alpha: u8) {
+        let i = ((y * self.width + x) * 4) as usize;
+        if alpha == 255 {
+            self.pixels[i..i + 3].copy_from_slice(&color);
+            self.pixels[i + 3] = 255;
+            return;
+        }
+        let a = alpha as f32 / 255.0;
+        let below = self.pixels[i + 3] as f32 / 255.0;
+        let out = a + below * (1.0 - a);
+        for (c, &value) in color.iter().enumerate() {
+            let mixed = value as f32 * a + self.pixels[i + c] as f32 * below * (1.0 - a);
+            self.pixels[i + c] = (mixed / out).round() as u8;
+        }
+        self.pixels[i + 3] = (out * 255.0).round() as u8;
+    }
+
+    /// Shrinks the image by an integer factor until both sides fit in `max_size`, averaging
+    /// each square of pixels.
+    fn downscale_to_fit(self, max_size: u32) -> Image {
+        let factor = self.width.max(self.height).div_ceil(max_size.max(1));
+        if factor <= 1 {
+            return self;
+        }
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut out = Image::new(width, height, [0, 0, 0, 0]);
+        for y in 0..height {
+            for x in 0..width {
+                // Premultiply so transparent pixels don't darken the edges
+                let mut sum = [0.0f32; 4];
+                let mut count = 0.0;
+                for sy in y * factor..((y + 1) * factor).min(self.height) {
+                    for sx in x * factor..((x + 1) * factor).min(self.width) {
+                        let [r, g, b, a] = self.get_pixel(sx, sy);
+                        let a = a as f32;
+                        sum[0] += r as f32 * a;
+                        sum[1] += g as f32 * a;
+                        sum[2] += b as f32 * a;
+                        sum[3] += a;
+                        count += 1.0;
+                    }
+                }
+                let i = ((y * width + x) * 4) as usize;
+                if sum[3] > 0.0 {
+                    for c in 0..3 {
+                        out.pixels[i + c] = (sum[c] / sum[3]).round() as u8;
+                    }
+                }
+                out.pixels[i + 3] = (sum[3] / count).round() as u8;
+            }
+        }
+        out
+    }
+
+    /// Centers the image on a square canvas filled with `background`. The canvas is `size`
+    /// pixels wide, or larger if the image doesn't fit.
+    pub fn pad_to_square(&self, size: u32, background: [u8; 4]) -> Image {
+        let size = size.max(self.width).max(self.height);
+        let mut out = Image::new(size, size, background);
+        let (ox, oy) = ((size - self.width) / 2, (size - self.height) / 2);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [r, g, b, a] = self.get_pixel(x, y);
+                if a > 0 {
+                    out.blend(ox + x, oy + y, [r, g, b], a);
+                }
+            }
+        }
+        out
+    }
+
+    /// Pixels packed as ARGB integers, the layout litematic preview images use.
+    pub fn to_argb(&self) -> Vec<i32> {
+        self.pixels
+            .chunks_exact(4)
+            .map(|p| i32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+            .collect()
+    }
+
+    /// Decodes a PNG of any color type into RGBA8.
+    pub fn from_png(data: &[u8]) -> Result<Image, Box<dyn std::error::Error>> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+            png::ColorType::Indexed => return Err("Indexed PNG was not expanded".into()),
+        };
+        Ok(Image { width: info.width, height: info.height, pixels })
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(data)
+    }
+}
+
+/// The schematic flattened into a dense grid of colors over its bounding box. Each cell covers
+/// `step` blocks along every axis, so large builds are sampled down to the output size instead
+/// of being drawn in full and shrunk afterwards.
+struct Voxels {
+    dims: [i32; 3],
+    colors: Vec<([u8; 3], u8)>,
+    /// Index into `colors` plus one; zero means empty.
+    cells: Vec<u16>,
+}
+
+impl Voxels {
+    /// `step_for` picks the cell size from the bounding box dimensions. A cell takes the
+    /// topmost visible block it covers, the northernmost then westernmost one on ties.
+    ///
+    /// Only the blocks the regions actually store are visited, so regions far apart don't cost
+    /// more than regions next to each other.
+    fn new(schematic: &UniversalSchematic, step_for: impl Fn([i64; 3]) -> i64) -> Self {
+        let empty = Voxels { dims: [0, 0, 0], colors: Vec::new(), cells: Vec::new() };
+        if schematic.regions.is_empty() {
+            return empty;
+        }
+        let bounding_box = schematic.get_bounding_box();
+        let min = bounding_box.min;
+        let (width, height, length) = bounding_box.get_dimensions();
+        let step = step_for([width, height, length].map(i64::from)).clamp(1, i64::from(i32::MAX)) as i32;
+        let dims = [width, height, length].map(|d| div_ceil(i64::from(d), i64::from(step)) as i32);
+        let Some(volume) = dims.iter().try_fold(1usize, |volume, &d| volume.checked_mul(usize::try_from(d).ok()?)) else {
+            return empty;
+        };
+
+        // Best block so far for every cell that has one, as its position and name
+        let mut picked: HashMap<usize, ((i32, i32, i32), &str)> = HashMap::new();
+        let mut voxels = Voxels { dims, colors: Vec::new(), cells: vec![0u16; volume] };
+        let regions: Vec<_> = schematic.regions.values().collect();
+        for (i, region) in regions.iter().enumerate() {
+            let region_box = region.get_bounding_box();
+            for (index, &palette_index) in region.blocks.iter().enumerate() {
+                let Some(block) = region.palette.get(palette_index) else { continue };
+                if is_invisible(&block.name) {
+                    continue;
+                }
+                let (x, y, z) = region_box.index_to_coords(index);
+                // Where regions overlap the first one wins, like in `get_block`
+                if regions[..i].iter().any(|earlier| earlier.get_bounding_box().contains((x, y, z))) {
+                    continue;
+                }
+                let cell = [x - min.0, y - min.1, z - min.2].map(|offset| (i64::from(offset) / i64::from(step)) as i32);
+                let cell = voxels.index(cell[0], cell[1], cell[2]);
+                let better = |(px, py, pz): (i32, i32, i32)| (-y, z, x) < (-py, pz, px);
+                match picked.get(&cell) {
+                    Some(&(position, _)) if !better(position) => {}
+                    _ => {
+                        picked.insert(cell, ((x, y, z), block.name.as_str()));
+                    }
+                }
+            }
+        }
+
+        let mut lookup: HashMap<&str, u16> = HashMap::new();
+        for (cell, (_, name)) in picked {
+            voxels.cells[cell] = *lookup.entry(name).or_insert_with(|| {
+                let alpha = if is_transparent(name) { TRANSLUCENT_ALPHA } else { 255 };
+                voxels.colors.push((get_block_color(name).unwrap_or(UNKNOWN_BLOCK_COLOR), alpha));
+                voxels.colors.len() as u16
+            });
+        }
+        voxels
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dims.iter().any(|&d| d <= 0)
+    }
+
+    fn index(&self, x: i32, y: i32, z: i32) -> usize {
+        let [width, _, length] = self.dims.map(|d| d as usize);
+        x as usize + (z as usize + y as usize * length) * width
+    }
+
+    fn get(&self, x: i32, y: i32, z: i32) -> Option<([u8; 3], u8)> {
+        let [width, height, length] = self.dims;
+        if x < 0 || y < 0 || z < 0 || x >= width || y >= height || z >= length {
+            return None;
+        }
+        match self.cells[self.index(x, y, z)] {
+            0 => None,
+            color => Some(self.colors[color as usize - 1]),
+        }
+    }
+
+    fn is_opaque(&self, x: i32, y: i32, z: i32) -> bool {
+        matches!(self.get(x, y, z), Some((_, 255)))
+    }
+}
+
+/// `a / b` rounded up, for positive `b`.
+fn div_ceil(a: i64, b: i64) -> i64 {
+    (a + b - 1) / b
+}
+
+fn shade(color: [u8; 3], factor: f32) -> [u8; 3] {
+    color.map(|c| (c as f32 * factor).round() as u8)
+}
+
+/// Renders the schematic seen from above the south-east corner.
+pub fn render_isometric(schematic: &UniversalSchematic, options: &RenderOptions) -> Image {
+    // At the smallest tile of 2px the canvas is 2(w + l) wide and w + l + 2h tall
+    let resolution = i64::from(options.resolution.max(1));
+    let voxels = Voxels::new(schematic, |[w, h, l]| div_ceil((2 * (w + l)).max(w + l + 2 * h), resolution));
+    if voxels.is_empty() {
+        return Image::new(options.resolution, options.resolution, options.background);
+    }
+    let [width, height, length] = voxels.dims;
+
+    // Each block is a hexagon 2s wide and 2s tall; s is kept even so the top face lines up
+    let footprint = (width + length) as f32;
+    let fit = (options.resolution as f32 / footprint).min(options.resolution as f32 / (footprint / 2.0 + height as f32));
+    let s = ((fit as i32) & !1).max(2);
+    let image_width = ((width + length) * s) as u32;
+    let image_height = ((width + length) * s / 2 + height * s) as u32;
+    let mut image = Image::new(image_width, image_height, options.background);
+
+    // Which face every pixel of the hexagon belongs to: 0 outside, 1 top, 2 south, 3 east
+    let tile = (2 * s) as usize;
+    let mut sprite = vec![0u8; tile * tile];
+    for py in 0..tile {
+        for px in 0..tile {
+            let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+            let dx = cx - s as f32;
+            let half = dx.abs() / 2.0;
+            sprite[py * tile + px] = if cy < half || cy > 2.0 * s as f32 - half {
+                0
+            } else if cy < s as f32 - half {
+                1
+            } else if dx < 0.0 {
+                2
+            } else {
+                3
+            };
+        }
+    }
+
+    // Painter's algorithm: cubes on the same x + y + z plane never overlap, so drawing the
+    // planes back to front is enough
+    let mut visible = Vec::new();
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                if voxels.get(x, y, z).is_none() {
+                    continue;
+                }
+                if voxels.is_opaque(x + 1, y, z) && voxels.is_opaque(x, y + 1, z) && voxels.is_opaque(x, y, z + 1) {
+                    continue;
+                }
+                visible.push((x + y + z, [x, y, z]));
+            }
+        }
+    }
+    visible.sort_by_key(|(depth, _)| *depth);
+
+    for (_, [x, y, z]) in visible {
+        let (color, alpha) = voxels.get(x, y, z).unwrap();
+        let faces = [shade(color, 1.0), shade(color, 0.8), shade(color, 0.64)];
+        let ox = ((x - z + length - 1) * s) as u32;
+        let oy = ((x + z) * s / 2 + (height - 1 - y) * s) as u32;
+        for py in 0..tile {
+            for px in 0..tile {
+                let face = sprite[py * tile + px];
+                if face != 0 {
+                    image.blend(ox + px as u32, oy + py as u32, faces[face as usize - 1], alpha);
+                }
+            }
+        }
+    }
+    image.downscale_to_fit(options.resolution)
+}
+
+/// Renders the schematic seen from straight above, one square per column.
+pub fn render_top_down(schematic: &UniversalSchematic, options: &RenderOptions) -> Image {
+    let resolution = i64::from(options.resolution.max(1));
+    let voxels = Voxels::new(schematic, |[w, _, l]| div_ceil(w.max(l), resolution));
+    if voxels.is_empty() {
+        return Image::new(options.resolution, options.resolution, options.background);
+    }
+    let [width, height, length] = voxels.dims;
+
+    // Height of the topmost opaque block in each column, used for the map-style shading
+    let surface = |x: i32, z: i32| (0..height).rev().find(|&y| voxels.is_opaque(x, y, z));
+
+    let scale = (options.resolution / width.max(length) as u32).max(1);
+    let mut image = Image::new(width as u32 * scale, length as u32 * scale, options.background);
+    for z in 0..length {
+        for x in 0..width {
+            let top = surface(x, z);
+            let factor = match (top, if z > 0 { surface(x, z - 1) } else { top }) {
+                (Some(y), Some(north)) if y > north => 1.0,
+                (Some(y), Some(north)) if y < north => 0.71,
+                _ => 0.86,
+            };
+            // Composite everything down to the surface, translucent blocks included
+            let lowest = top.unwrap_or(0);
+            let mut layers = Vec::new();
+            for y in (lowest..height).rev() {
+                if let Some((color, alpha)) = voxels.get(x, y, z) {
+                    layers.push((shade(color, factor), alpha));
+                }
+            }
+            for py in 0..scale {
+                for px in 0..scale {
+                    for &(color, alpha) in layers.iter().rev() {
+                        image.blend(x as u32 * scale + px, z as u32 * scale + py, color, alpha);
+                    }
+                }
+            }
+        }
+    }
+    image.downscale_to_fit(options.resolution)
+}
+
+pub fn to_isometric_png(schematic: &UniversalSchematic, options: &RenderOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    render_isometric(schematic, options).to_png()
+}
+
+pub fn to_top_down_png(schematic: &UniversalSchematic, options: &RenderOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    render_top_down(schematic, options).to_png()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockState;
+
+    fn options(resolution: u32) -> RenderOptions {
+        RenderOptions { resolution, ..RenderOptions::default() }
+    }
+
+    #[test]
+    fn test_isometric_single_block_faces() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+
+        let image = render_isometric(&schematic, &options(64));
+        assert_eq!((image.width, image.height), (64, 64));
+        let stone = get_block_color("minecraft:stone").unwrap();
+
+        // Top face above the center, south face lower left, east face lower right
+        assert_eq!(image.get_pixel(32, 16), [stone[0], stone[1], stone[2], 255]);
+        let [r, ..] = image.get_pixel(16, 40);
+        assert_eq!(r, shade(stone, 0.8)[0]);
+        let [r, ..] = image.get_pixel(48, 40);
+        assert_eq!(r, shade(stone, 0.64)[0]);
+        // Corners stay background
+        assert_eq!(image.get_pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(63, 63), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_top_down_height_shading_and_resolution() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        for z in 0..3 {
+            schematic.set_block(0, 0, z, BlockState::new("minecraft:stone".to_string()));
+        }
+        // Raised block in the middle: brighter than its northern neighbour, and makes the
+        // block south of it darker
+        schematic.set_block(0, 1, 1, BlockState::new("minecraft:stone".to_string()));
+
+        let image = render_top_down(&schematic, &options(30));
+        assert_eq!((image.width, image.height), (10, 30));
+        let stone = get_block_color("minecraft:stone").unwrap();
+        assert_eq!(image.get_pixel(5, 5)[0], shade(stone, 0.86)[0]);
+        assert_eq!(image.get_pixel(5, 15)[0], stone[0]);
+        assert_eq!(image.get_pixel(5, 25)[0], shade(stone, 0.71)[0]);
+
+        let png = to_top_down_png(&schematic, &options(30)).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(Image::from_png(&png).unwrap(), image);
+    }
+
+    #[test]
+    fn test_large_schematic_fits_resolution() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        for x in 0..100 {
+            schematic.set_block(x, 0, 0, BlockState::new("minecraft:glass".to_string()));
+        }
+        let image = render_isometric(&schematic, &options(64));
+        assert!(image.width <= 64 && image.height <= 64);
+        let image = render_top_down(&schematic, &options(64));
+        assert!(image.width <= 64 && image.height <= 64);
+    }
+
+    #[test]
+    fn test_huge_schematic_is_sampled_before_drawing() {
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        let mut far = crate::Region::new("Far".to_string(), (600, 0, 600), (1, 1, 1));
+        far.set_block(600, 0, 600, BlockState::new("minecraft:stone".to_string()));
+        schematic.add_region(far);
+        let voxels = Voxels::new(&schematic, |[w, h, l]| div_ceil((2 * (w + l)).max(w + l + 2 * h), 64));
+        assert!(voxels.cells.len() <= 32 * 32);
+        assert!(voxels.get(0, 0, 0).is_some());
+
+        let image = render_isometric(&schematic, &options(64));
+        assert!(image.width <= 64 && image.height <= 64);
+        assert!(image.pixels.chunks(4).any(|pixel| pixel[3] > 0));
+    }
+
+    #[test]
+    fn test_far_apart_regions_render_quickly() {
+        let far = 1_000_000_000;
+        let mut schematic = UniversalSchematic::new("Test".to_string());
+        schematic.set_block(0, 0, 0, BlockState::new("minecraft:stone".to_string()));
+        schematic.set_block_in_region("Far", far, far, far, BlockState::new("minecraft:stone".to_string()));
+
+        let started = std::time::Instant::now();
+        let image = render_isometric(&schematic, &options(64));
+        assert!(image.width <= 64 && image.height <= 64);
+        assert!(image.pixels.chunks(4).any(|pixel| pixel[3] > 0));
+        let image = render_top_down(&schematic, &options(64));
+        assert!(image.width <= 64 && image.height <= 64);
+        assert!(image.pixels.chunks(4).any(|pixel| pixel[3] > 0));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    }
+}
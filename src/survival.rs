@@ -0,0 +1,350 @@
+//! Checks whether a build can be placed block by block in survival.
+//!
+//! The validator looks for blocks that would break or fall as soon as they're placed (torches
+//! on nothing, sand over air, half a door) and blocks survival players can't get at all.
+
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::{BlockState, UniversalSchematic};
+use crate::block_colors::is_air;
+use crate::formats::mesh::is_transparent;
+use crate::lighting::light_opacity;
+use crate::map_art::{is_gravity_block, is_survival_obtainable};
+
+/// Blocks without an item that players still make in survival, e.g. farmland with a hoe.
+const MADE_IN_WORLD: &[&str] = &[
+    "farmland", "dirt_path", "fire", "soul_fire", "nether_portal", "frosted_ice", "bubble_column",
+    "piston_head", "moving_piston", "tall_seagrass",
+];
+
+/// Blocks plants can be planted on.
+const SOIL: &[&str] = &[
+    "grass_block", "dirt", "coarse_dirt", "podzol", "rooted_dirt", "mycelium", "moss_block", "mud",
+    "muddy_mangrove_roots", "farmland", "pale_moss_block",
+];
+
+const DOUBLE_PLANTS: &[&str] = &[
+    "tall_grass", "large_fern", "sunflower", "lilac", "rose_bush", "peony", "pitcher_plant",
+    "tall_seagrass", "small_dripleaf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The block needs something to stand on or hang from and it isn't there.
+    Unsupported,
+    /// A gravity block over air.
+    Falls,
+    /// One half of a door, bed or tall plant without the other.
+    MissingHalf,
+    /// The block can't be obtained in survival.
+    Unobtainable,
+}
+
+impl IssueKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::Unsupported => "unsupported",
+            IssueKind::Falls => "falls",
+            IssueKind::MissingHalf => "missing_half",
+            IssueKind::Unobtainable => "unobtainable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildIssue {
+    pub position: (i32, i32, i32),
+    pub block: String,
+    pub kind: IssueKind,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SurvivalReport {
+    pub issues: Vec<BuildIssue>,
+}
+
+impl SurvivalReport {
+    pub fn is_buildable(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn issues_of(&self, kind: IssueKind) -> impl Iterator<Item = &BuildIssue> {
+        self.issues.iter().filter(move |issue| issue.kind == kind)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn short_name(block: &BlockState) -> &str {
+    block.name.strip_prefix("minecraft:").unwrap_or(&block.name)
+}
+
+fn property<'a>(block: &'a BlockState, key: &str) -> Option<&'a str> {
+    block.get_property(key).map(|v| v.as_str())
+}
+
+/// Air and other blocks that hold nothing up.
+fn is_empty(block: Option<&BlockState>) -> bool {
+    block.is_none_or(|block| matches!(short_name(block),
+        "air" | "cave_air" | "void_air" | "structure_void" | "light" | "water" | "lava" | "bubble_column"
+        | "short_grass" | "grass" | "fern" | "fire" | "soul_fire"))
+}
+
+/// Full blocks, whose every face can hold a torch, button or ladder.
+fn is_full_block(block: &BlockState) -> bool {
+    let name = short_name(block);
+    light_opacity(block) == 15 || (is_transparent(&block.name) && !name.contains("pane") && name != "water")
+}
+
+/// Whether the top of the block can hold things like rails, redstone and snow.
+fn has_sturdy_top(block: &BlockState) -> bool {
+    let name = short_name(block);
+    if name.ends_with("_slab") {
+        return matches!(property(block, "type"), Some("top" | "double"));
+    }
+    if name.ends_with("_stairs") {
+        return property(block, "half") == Some("top");
+    }
+    is_full_block(block) || matches!(name, "hopper" | "scaffolding" | "dirt_path" | "farmland" | "soul_sand" | "mud")
+}
+
+/// Torches and pressure plates can also stand on fences, walls and panes.
+fn has_post_top(block: &BlockState) -> bool {
+    let name = short_name(block);
+    has_sturdy_top(block)
+        || name.ends_with("_fence")
+        || name.ends_with("_wall")
+        || name.ends_with("_pane")
+        || matches!(name, "glass_pane" | "iron_bars" | "chain")
+}
+
+/// Where a block has to be attached. Floor attachments also say what the block below must be.
+enum Support {
+    Below(fn(&BlockState) -> bool, &'static str),
+    Above,
+    /// A full block on the side the offset points to.
+    Side((i32, i32, i32)),
+}
+
+fn horizontal_offset(facing: Option<&str>) -> Option<(i32, i32, i32)> {
+    match facing? {
+        "north" => Some((0, 0, -1)),
+        "south" => Some((0, 0, 1)),
+        "west" => Some((-1, 0, 0)),
+        "east" => Some((1, 0, 0)),
+        _ => None,
+    }
+}
+
+/// Offset to the block behind something facing `facing`, i.e. the block it's mounted on.
+fn behind(block: &BlockState) -> Option<(i32, i32, i32)> {
+    horizontal_offset(property(block, "facing")).map(|(x, y, z)| (-x, -y, -z))
+}
+
+fn required_support(block: &BlockState) -> Option<Support> {
+    let name = short_name(block);
+    let is_plant = name.ends_with("_sapling")
+        || name.ends_with("_tulip")
+        || matches!(name,
+            "dandelion" | "poppy" | "blue_orchid" | "allium" | "azure_bluet" | "oxeye_daisy" | "cornflower"
+            | "lily_of_the_valley" | "wither_rose" | "torchflower" | "pink_petals" | "sweet_berry_bush"
+            | "short_grass" | "fern" | "bush" | "firefly_bush")
+        || (DOUBLE_PLANTS.contains(&name) && property(block, "half") == Some("lower") && name != "tall_seagrass");
+    let support = if name.ends_with("_wall_torch") || name == "wall_torch" || name.ends_with("_wall_sign")
+        || name.ends_with("_wall_banner") || name.ends_with("_wall_hanging_sign") || matches!(name, "ladder" | "tripwire_hook") {
+        Support::Side(behind(block)?)
+    } else if name == "lever" || name.ends_with("_button") {
+        match property(block, "face") {
+            Some("floor") => Support::Below(is_full_block, "a full block"),
+            Some("ceiling") => Support::Above,
+            _ => Support::Side(behind(block)?),
+        }
+    } else if name.ends_with("torch") || name.ends_with("_pressure_plate") {
+        Support::Below(has_post_top, "a block, fence or wall")
+    } else if name.ends_with("_hanging_sign") || (name.ends_with("lantern") && property(block, "hanging") == Some("true")) {
+        Support::Above
+    } else if name.ends_with("_carpet") || name.ends_with("_sign") || name.ends_with("_banner") || name.ends_with("candle") {
+        Support::Below(|below| !is_empty(Some(below)), "a block")
+    } else if name.ends_with("rail") || (name.ends_with("_door") && property(block, "half") == Some("lower"))
+        || matches!(name, "redstone_wire" | "repeater" | "comparator" | "snow" | "flower_pot") {
+        Support::Below(has_sturdy_top, "a block with a flat top")
+    } else if is_plant {
+        Support::Below(|below| SOIL.contains(&short_name(below)), "dirt or grass")
+    } else if name.ends_with("_mushroom") {
+        Support::Below(is_full_block, "a full block")
+    } else {
+        return None;
+    };
+    Some(support)
+}
+
+/// Offset from a block to the block it's attached to, if it needs one.
+pub(crate) fn support_offset(block: &BlockState) -> Option<(i32, i32, i32)> {
+    match required_support(block)? {
+        Support::Below(..) => Some((0, -1, 0)),
+        Support::Above => Some((0, 1, 0)),
+        Support::Side(offset) => Some(offset),
+    }
+}
+
+/// The other half of a two-block block, and where it should be.
+fn other_half(block: &BlockState) -> Option<((i32, i32, i32), &'static str, &'static str)> {
+    let name = short_name(block);
+    if name.ends_with("_door") || DOUBLE_PLANTS.contains(&name) {
+        return match property(block, "half")? {
+            "lower" => Some(((0, 1, 0), "half", "upper")),
+            "upper" => Some(((0, -1, 0), "half", "lower")),
+            _ => None,
+        };
+    }
+    if name.ends_with("_bed") {
+        let forward = horizontal_offset(property(block, "facing"))?;
+        return match property(block, "part")? {
+            "foot" => Some((forward, "part", "head")),
+            "head" => Some(((-forward.0, 0, -forward.2), "part", "foot")),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Finds every block that would break, fall or can't be obtained when building in survival.
+pub fn validate_survival(schematic: &UniversalSchematic) -> SurvivalReport {
+    let blocks: HashMap<(i32, i32, i32), &BlockState> = schematic.iter_blocks()
+        .map(|(pos, block)| ((pos.x, pos.y, pos.z), block))
+        .filter(|(_, block)| !is_air(&block.name))
+        .collect();
+    let at = |pos: (i32, i32, i32), offset: (i32, i32, i32)| {
+        blocks.get(&(pos.0 + offset.0, pos.1 + offset.1, pos.2 + offset.2)).copied()
+    };
+
+    let mut positions: Vec<&(i32, i32, i32)> = blocks.keys().collect();
+    positions.sort_by_key(|&&(x, y, z)| (y, z, x));
+
+    let mut report = SurvivalReport::default();
+    for &pos in positions {
+        let block = blocks[&pos];
+        let name = short_name(block);
+        let mut issue = |kind: IssueKind, reason: String| {
+            report.issues.push(BuildIssue { position: pos, block: block.to_string(), kind, reason });
+        };
+
+        if !is_survival_obtainable(&block.name) && !MADE_IN_WORLD.contains(&name) {
+            issue(IssueKind::Unobtainable, format!("{} can't be obtained in survival", name));
+        }
+        // Liquids, light and the like are still placed, but nothing rests on or hangs off them
+        if is_empty(Some(block)) {
+            continue;
+        }
+
+        if is_gravity_block(&block.name) && name != "scaffolding" {
+            let mut below = (pos.0, pos.1 - 1, pos.2);
+            while blocks.get(&below).is_some_and(|b| is_gravity_block(&b.name)) {
+                below.1 -= 1;
+            }
+            if is_empty(blocks.get(&below).copied()) {
+                issue(IssueKind::Falls, format!("{} has nothing below it and will fall", name));
+            }
+        }
+
+        match required_support(block) {
+            Some(Support::Below(supports, what)) if !at(pos, (0, -1, 0)).is_some_and(supports) => {
+                issue(IssueKind::Unsupported, format!("{} needs {} below it", name, what));
+            }
+            Some(Support::Above) if is_empty(at(pos, (0, 1, 0))) => {
+                issue(IssueKind::Unsupported, format!("{} needs a block above it to hang from", name));
+            }
+            Some(Support::Side(offset)) if !at(pos, offset).is_some_and(is_full_block) => {
+                issue(IssueKind::Unsupported, format!("{} needs a full block behind it", name));
+            }
+            _ => {}
+        }
+
+        if let Some((offset, key, value)) = other_half(block) {
+            let matches = at(pos, offset).is_some_and(|other| other.name == block.name && property(other, key) == Some(value));
+            if !matches {
+                issue(IssueKind::MissingHalf, format!("{} is missing its {} {}", name, value, key));
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::block;
+
+    fn kinds_at(report: &SurvivalReport, pos: (i32, i32, i32)) -> Vec<IssueKind> {
+        report.issues.iter().filter(|i| i.position == pos).map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn test_support_and_gravity() {
+        let mut schematic = UniversalSchematic::new("Build".to_string());
+        for x in 0..6 {
+            schematic.set_block(x, 0, 0, block("stone", &[]));
+        }
+        schematic.set_block(0, 1, 0, block("torch", &[]));
+        schematic.set_block(1, 2, 0, block("torch", &[]));
+        schematic.set_block(2, 0, 1, block("wall_torch", &[("facing", "south")]));
+        schematic.set_block(3, 0, 1, block("wall_torch", &[("facing", "north")]));
+        schematic.set_block(4, 3, 0, block("sand", &[]));
+        schematic.set_block(4, 4, 0, block("gravel", &[]));
+        schematic.set_block(5, 1, 0, block("sand", &[]));
+        schematic.set_block(5, 2, 0, block("white_carpet", &[]));
+        schematic.set_block(0, 3, 2, block("oak_button", &[("face", "wall"), ("facing", "east")]));
+
+        let report = validate_survival(&schematic);
+        assert!(kinds_at(&report, (0, 1, 0)).is_empty());
+        assert_eq!(kinds_at(&report, (1, 2, 0)), vec![IssueKind::Unsupported]);
+        assert!(kinds_at(&report, (2, 0, 1)).is_empty());
+        assert_eq!(kinds_at(&report, (3, 0, 1)), vec![IssueKind::Unsupported]);
+        assert_eq!(kinds_at(&report, (4, 3, 0)), vec![IssueKind::Falls]);
+        assert_eq!(kinds_at(&report, (4, 4, 0)), vec![IssueKind::Falls]);
+        assert!(kinds_at(&report, (5, 1, 0)).is_empty());
+        assert!(kinds_at(&report, (5, 2, 0)).is_empty());
+        assert_eq!(kinds_at(&report, (0, 3, 2)), vec![IssueKind::Unsupported]);
+        assert_eq!(report.issues.len(), 5);
+        assert!(report.issues[0].reason.contains("torch"));
+    }
+
+    #[test]
+    fn test_halves_and_unobtainable_blocks() {
+        let mut schematic = UniversalSchematic::new("Build".to_string());
+        for x in 0..4 {
+            schematic.set_block(x, 0, 0, block("grass_block", &[]));
+        }
+        schematic.set_block(0, 1, 0, block("rose_bush", &[("half", "lower")]));
+        schematic.set_block(0, 2, 0, block("rose_bush", &[("half", "upper")]));
+        schematic.set_block(1, 1, 0, block("sunflower", &[("half", "lower")]));
+        schematic.set_block(2, 1, 0, block("bedrock", &[]));
+        schematic.set_block(3, 1, 0, block("command_block", &[("facing", "up")]));
+        schematic.set_block(3, 0, 1, block("farmland", &[]));
+        schematic.set_block(0, 0, 2, block("red_bed", &[("part", "foot"), ("facing", "east")]));
+        schematic.set_block(1, 0, 2, block("red_bed", &[("part", "head"), ("facing", "east")]));
+        schematic.set_block(0, 0, 3, block("red_bed", &[("part", "foot"), ("facing", "north")]));
+        schematic.set_block(2, 2, 0, block("light", &[("level", "15")]));
+        schematic.set_block(2, 3, 0, block("structure_void", &[]));
+
+        let report = validate_survival(&schematic);
+        assert!(kinds_at(&report, (0, 1, 0)).is_empty());
+        assert!(kinds_at(&report, (0, 2, 0)).is_empty());
+        assert_eq!(kinds_at(&report, (1, 1, 0)), vec![IssueKind::MissingHalf]);
+        assert_eq!(kinds_at(&report, (2, 1, 0)), vec![IssueKind::Unobtainable]);
+        assert_eq!(kinds_at(&report, (3, 1, 0)), vec![IssueKind::Unobtainable]);
+        assert!(kinds_at(&report, (3, 0, 1)).is_empty());
+        assert!(kinds_at(&report, (0, 0, 2)).is_empty());
+        assert_eq!(kinds_at(&report, (0, 0, 3)), vec![IssueKind::MissingHalf]);
+        assert_eq!(kinds_at(&report, (2, 2, 0)), vec![IssueKind::Unobtainable]);
+        assert_eq!(kinds_at(&report, (2, 3, 0)), vec![IssueKind::Unobtainable]);
+        assert_eq!(report.issues_of(IssueKind::Unobtainable).count(), 4);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["issues"][0]["kind"], "missing_half");
+    }
+}
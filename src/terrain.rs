@@ -0,0 +1,228 @@
+//! Terrain generation from heightmaps.
+//!
+//! Every heightmap cell becomes a column of blocks standing on y = 0: a surface block on top, a
+//! few subsurface blocks below it and fill down to the bottom. Columns below the water level are
+//! topped up with water, and slope rules swap materials on steep ground, e.g. bare stone cliffs.
+
+use crate::{BlockState, Region, UniversalSchematic};
+use crate::render::Image;
+
+/// Pixels less opaque than this become empty columns.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Heights in blocks on a `width` × `length` grid, row by row along z. Cells with a negative
+/// height are left empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub width: u32,
+    pub length: u32,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: u32, length: u32, heights: Vec<f32>) -> Result<Self, String> {
+        let cells = (width as usize).checked_mul(length as usize)
+            .ok_or_else(|| format!("A {}x{} heightmap is too large", width, length))?;
+        if heights.len() != cells {
+            return Err(format!(
+                "Expected {} heights for a {}x{} heightmap, got {}",
+                cells, width, length, heights.len()
+            ));
+        }
+        Ok(Heightmap { width, length, heights })
+    }
+
+    /// Rows of heights, one row per z.
+    pub fn from_rows(rows: &[Vec<f32>]) -> Result<Self, String> {
+        let width = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != width) {
+            return Err("All heightmap rows must have the same length".to_string());
+        }
+        Heightmap::new(width as u32, rows.len() as u32, rows.concat())
+    }
+
+    /// Brightness scaled so white is `max_height` and black is 0. Transparent pixels are empty.
+    pub fn from_image(image: &Image, max_height: f32) -> Self {
+        let heights = image.pixels.chunks_exact(4)
+            .map(|p| {
+                if p[3] < ALPHA_THRESHOLD {
+                    return -1.0;
+                }
+                let luminance = 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+                luminance / 255.0 * max_height
+            })
+            .collect();
+        Heightmap { width: image.width, length: image.height, heights }
+    }
+
+    pub fn from_png(data: &[u8], max_height: f32) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_image(&Image::from_png(data)?, max_height))
+    }
+
+    /// Height of the top block of the column, or `None` for an empty cell.
+    pub fn column_height(&self, x: u32, z: u32) -> Option<i32> {
+        let height = self.heights[z as usize * self.width as usize + x as usize];
+        if height < 0.0 || height.is_nan() {
+            None
+        } else {
+            Some(height.round() as i32)
+        }
+    }
+
+    /// The largest height difference between a column and its four neighbours.
+    pub fn slope(&self, x: u32, z: u32) -> i32 {
+        let Some(height) = self.column_height(x, z) else { return 0 };
+        let neighbours = [
+            (x.checked_sub(1), Some(z)),
+            (Some(x + 1).filter(|&nx| nx < self.width), Some(z)),
+            (Some(x), z.checked_sub(1)),
+            (Some(x), Some(z + 1).filter(|&nz| nz < self.length)),
+        ];
+        neighbours.iter()
+            .filter_map(|&(nx, nz)| self.column_height(nx?, nz?))
+            .map(|other| (other - height).abs())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Replaces the surface, and optionally the subsurface, where the ground is at least this steep.
+#[derive(Debug, Clone)]
+pub struct SlopeRule {
+    pub min_slope: i32,
+    pub surface: BlockState,
+    pub subsurface: Option<BlockState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TerrainOptions {
+    pub surface: BlockState,
+    pub subsurface: BlockState,
+    /// How many subsurface blocks go below the surface block.
+    pub subsurface_depth: i32,
+    pub fill: BlockState,
+    /// Columns whose top is below this height get water up to it.
+    pub water_level: Option<i32>,
+    pub water: BlockState,
+    /// Surface used instead of `surface` under water.
+    pub underwater_surface: BlockState,
+    /// The steepest matching rule wins.
+    pub slope_rules: Vec<SlopeRule>,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> Self {
+        let block = |name: &str| BlockState::new(format!("minecraft:{}", name));
+        TerrainOptions {
+            surface: block("grass_block"),
+            subsurface: block("dirt"),
+            subsurface_depth: 3,
+            fill: block("stone"),
+            water_level: None,
+            water: block("water"),
+            underwater_surface: block("sand"),
+            slope_rules: vec![SlopeRule { min_slope: 3, surface: block("stone"), subsurface: Some(block("stone")) }],
+        }
+    }
+}
+
+/// Builds terrain from `heightmap`, with the heightmap's (0, 0) at x = 0, z = 0.
+pub fn generate_terrain(heightmap: &Heightmap, options: &TerrainOptions) -> UniversalSchematic {
+    let mut schematic = UniversalSchematic::new("Terrain".to_string());
+    let highest = (0..heightmap.length)
+        .flat_map(|z| (0..heightmap.width).map(move |x| (x, z)))
+        .filter_map(|(x, z)| heightmap.column_height(x, z))
+        .max();
+    let Some(highest) = highest else { return schematic };
+    let top = highest.max(options.water_level.unwrap_or(0)).max(0);
+
+    let mut slope_rules: Vec<&SlopeRule> = options.slope_rules.iter().collect();
+    slope_rules.sort_by_key(|rule| -rule.min_slope);
+
+    let mut region = Region::new(
+        schematic.default_region_name.clone(),
+        (0, 0, 0),
+        (heightmap.width as i32, top + 1, heightmap.length as i32),
+    );
+    for z in 0..heightmap.length {
+        for x in 0..heightmap.width {
+            let Some(height) = heightmap.column_height(x, z) else { continue };
+            let underwater = options.water_level.is_some_and(|level| height < level);
+            let slope = heightmap.slope(x, z);
+            let rule = slope_rules.iter().find(|rule| slope >= rule.min_slope);
+
+            let surface = match rule {
+                _ if underwater => &options.underwater_surface,
+                Some(rule) => &rule.surface,
+                None => &options.surface,
+            };
+            let subsurface = rule.and_then(|rule| rule.subsurface.as_ref()).unwrap_or(&options.subsurface);
+
+            let (x, z) = (x as i32, z as i32);
+            for y in 0..height {
+                let block = if y >= height - options.subsurface_depth { subsurface } else { &options.fill };
+                region.set_block(x, y, z, block.clone());
+            }
+            region.set_block(x, height, z, surface.clone());
+            if let Some(level) = options.water_level {
+                for y in height + 1..=level {
+                    region.set_block(x, y, z, options.water.clone());
+                }
+            }
+        }
+    }
+    schematic.add_region(region);
+    schematic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_at(schematic: &UniversalSchematic, x: i32, y: i32, z: i32) -> String {
+        schematic.get_block(x, y, z).map(|b| b.name.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_layers_and_water() {
+        let heightmap = Heightmap::from_rows(&[vec![5.0, 6.0], vec![1.0, -1.0]]).unwrap();
+        let options = TerrainOptions { water_level: Some(3), slope_rules: Vec::new(), ..TerrainOptions::default() };
+        let terrain = generate_terrain(&heightmap, &options);
+
+        assert_eq!(name_at(&terrain, 0, 5, 0), "minecraft:grass_block");
+        assert_eq!(name_at(&terrain, 0, 4, 0), "minecraft:dirt");
+        assert_eq!(name_at(&terrain, 0, 2, 0), "minecraft:dirt");
+        assert_eq!(name_at(&terrain, 0, 1, 0), "minecraft:stone");
+        assert_eq!(name_at(&terrain, 0, 6, 0), "minecraft:air");
+
+        // Below the water level: sand on top, water above it
+        assert_eq!(name_at(&terrain, 0, 1, 1), "minecraft:sand");
+        assert_eq!(name_at(&terrain, 0, 2, 1), "minecraft:water");
+        assert_eq!(name_at(&terrain, 0, 3, 1), "minecraft:water");
+        assert_eq!(name_at(&terrain, 0, 4, 1), "minecraft:air");
+
+        // Negative heights stay empty
+        assert_eq!(name_at(&terrain, 1, 0, 1), "minecraft:air");
+        assert_eq!(terrain.get_dimensions(), (2, 7, 2));
+    }
+
+    #[test]
+    fn test_slope_rules_and_image() {
+        let mut image = Image::new(3, 1, [0, 0, 0, 255]);
+        image.pixels[4..8].copy_from_slice(&[255, 255, 255, 255]);
+        let heightmap = Heightmap::from_image(&image, 10.0);
+        assert_eq!(heightmap.column_height(1, 0), Some(10));
+        assert_eq!(heightmap.slope(0, 0), 10);
+
+        let terrain = generate_terrain(&heightmap, &TerrainOptions::default());
+        assert_eq!(name_at(&terrain, 1, 10, 0), "minecraft:stone");
+        assert_eq!(name_at(&terrain, 1, 9, 0), "minecraft:stone");
+
+        let flat = Heightmap::new(2, 1, vec![2.0, 2.0]).unwrap();
+        let terrain = generate_terrain(&flat, &TerrainOptions::default());
+        assert_eq!(name_at(&terrain, 0, 2, 0), "minecraft:grass_block");
+
+        assert!(Heightmap::new(2, 2, vec![1.0]).is_err());
+        assert!(Heightmap::new(70_000, 70_000, vec![1.0]).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Builders shared by the unit tests.
+
+use crate::block_entity::BlockEntity;
+use crate::entity::{Entity, NbtValue as EntityNbtValue};
+use crate::region::{PendingTick, Region};
+use crate::utils::NbtValue;
+use crate::{BlockState, UniversalSchematic};
+
+/// A `minecraft:` block with the given properties.
+pub fn block(name: &str, properties: &[(&str, &str)]) -> BlockState {
+    properties.iter().fold(BlockState::new(format!("minecraft:{}", name)), |b, (k, v)| {
+        b.with_property(k.to_string(), v.to_string())
+    })
+}
+
+/// A schematic using every field the formats keep: metadata, an origin, two regions, a block
+/// entity, an entity with NBT, a pending tick and extra region NBT.
+pub fn sample_schematic() -> UniversalSchematic {
+    let mut schematic = UniversalSchematic::new("Sample".to_string());
+    schematic.metadata.author = Some("Tester".to_string());
+    schematic.metadata.mc_version = Some(3953);
+    schematic.metadata.preview_image = Some(vec![1, 2, 3]);
+    schematic.metadata.extra_nbt.insert("Tool".to_string(), NbtValue::String("x".to_string()));
+    schematic.origin = (1, -2, 3);
+    for x in 0..20 {
+        schematic.set_block(x, 0, 0, block("stone", &[]));
+    }
+    schematic.set_block(5, 3, 2, block("chest", &[("facing", "west")]));
+    let mut chest = BlockEntity::new("minecraft:chest".to_string(), (5, 3, 2));
+    chest.nbt.insert("Lock".to_string(), NbtValue::String("key".to_string()));
+    schematic.get_default_region_mut().add_block_entity(chest);
+    let mut entity = Entity::new("minecraft:armor_stand".to_string(), (0.1, 1.0 / 3.0, 2.5));
+    entity.nbt.insert("Invisible".to_string(), EntityNbtValue::Byte(1));
+    schematic.add_entity(entity);
+    let mut second = Region::new("Second".to_string(), (-5, -5, -5), (2, 2, 2));
+    second.set_block(-5, -5, -5, block("water", &[]));
+    second.pending_fluid_ticks.push(PendingTick {
+        position: (-5, -5, -5), target: "minecraft:water".to_string(), priority: 0, sub_tick: 3, time: 5,
+    });
+    second.extra_nbt.insert("Note".to_string(), NbtValue::Int(4));
+    schematic.add_region(second);
+    schematic
+}
+
+/// Gzip data that inflates to `len` zero bytes, written as a hand-made deflate stream of
+/// back-references since compressing that much would be slow in debug builds. The trailer's
+/// checksum is left empty, as readers with a size limit stop before reaching it.
+pub fn gzip_bomb(len: u64) -> Vec<u8> {
+    gzip_bomb_after(&[], len)
+}
+
+/// Like [`gzip_bomb`], but the zero bytes follow `prefix`, e.g. the NBT header of a huge array.
+pub fn gzip_bomb_after(prefix: &[u8], len: u64) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    let (mut acc, mut bits) = (0u64, 0u32);
+    // Huffman codes are packed most significant bit first, everything else least significant first
+    let mut put = |out: &mut Vec<u8>, code: u64, len: u32| {
+        for i in (0..len).rev() {
+            acc |= ((code >> i) & 1) << bits;
+            bits += 1;
+        }
+        while bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    };
+    // A final block with fixed Huffman codes: the bits 1, then 1, 0
+    put(&mut out, 0b110, 3);
+    for &byte in prefix {
+        match byte {
+            0..=143 => put(&mut out, 0x30 + u64::from(byte), 8),
+            _ => put(&mut out, 0x190 + u64::from(byte - 144), 9),
+        }
+    }
+    put(&mut out, 0x30, 8); // literal 0
+    let rest = len.saturating_sub(1);
+    for _ in 0..rest / 258 {
+        put(&mut out, 0b1100_0101, 8); // length 258
+        put(&mut out, 0, 5); // distance 1
+    }
+    for _ in 0..rest % 258 {
+        put(&mut out, 0x30, 8);
+    }
+    put(&mut out, 0, 7); // end of block
+    put(&mut out, 0, 7); // pad to a byte
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(&((prefix.len() as u64 + len) as u32).to_le_bytes());
+    out
+}
@@ -0,0 +1,454 @@
+//! Large block lettering.
+//!
+//! Text is laid out with a bitmap font, either the bundled 5×8 font or a BDF font, and every
+//! set pixel becomes a block (or a `scale` × `scale` square of blocks). The result can stand as
+//! a wall or lie on the floor, facing any horizontal direction.
+
+use std::collections::HashMap;
+use rand::{Rng, SeedableRng};
+use crate::{BlockState, UniversalSchematic};
+
+/// The bundled font for ASCII 0x20..=0x7E. Each glyph is five columns from left to right; bit 0
+/// is the top row and bit 7 the descender row below the baseline.
+const BUNDLED_FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x08, 0x07, 0x03, 0x00], [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00], [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46], [0x21, 0x41, 0x49, 0x4D, 0x33], [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x31], [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x46, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00], [0x00, 0x08, 0x14, 0x22, 0x41], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x59, 0x09, 0x06], [0x3E, 0x41, 0x5D, 0x59, 0x4E],
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x73], [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32], [0x03, 0x01, 0x7F, 0x01, 0x03], [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x59, 0x49, 0x4D, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x41, 0x7F], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x03, 0x07, 0x08, 0x00], [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7F, 0x28, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x28], [0x38, 0x44, 0x44, 0x28, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x00, 0x08, 0x7E, 0x09, 0x02], [0x18, 0xA4, 0xA4, 0x9C, 0x78],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x40, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x78, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xFC, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xFC], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3F, 0x44, 0x24], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44], [0x4C, 0x90, 0x90, 0x90, 0x7C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyph {
+    /// Horizontal distance to the next glyph's origin.
+    pub advance: i32,
+    /// Offset of the bitmap's left edge from the origin.
+    pub x_offset: i32,
+    /// Offset of the bitmap's bottom edge above the baseline; negative for descenders.
+    pub y_offset: i32,
+    pub width: usize,
+    pub height: usize,
+    /// Row-major pixels, top row first.
+    pub pixels: Vec<bool>,
+}
+
+impl Glyph {
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    pub glyphs: HashMap<char, Glyph>,
+    /// Rows above the baseline.
+    pub ascent: i32,
+    /// Rows below the baseline.
+    pub descent: i32,
+}
+
+impl Default for BitmapFont {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
+
+impl BitmapFont {
+    /// The bundled 5×8 ASCII font.
+    pub fn bundled() -> Self {
+        let glyphs = BUNDLED_FONT.iter().enumerate()
+            .map(|(i, columns)| {
+                let pixels = (0..8)
+                    .flat_map(|row| columns.iter().map(move |column| column & (1 << row) != 0))
+                    .collect();
+                let glyph = Glyph { advance: 6, x_offset: 0, y_offset: -1, width: 5, height: 8, pixels };
+                ((0x20 + i as u8) as char, glyph)
+            })
+            .collect();
+        BitmapFont { glyphs, ascent: 7, descent: 1 }
+    }
+
+    /// Parses a font in the Glyph Bitmap Distribution Format.
+    pub fn from_bdf(source: &str) -> Result<Self, String> {
+        let mut font = BitmapFont { glyphs: HashMap::new(), ascent: 0, descent: 0 };
+        let mut bounding_box = None;
+        let mut lines = source.lines().map(str::trim);
+
+        while let Some(line) = lines.next() {
+            let mut parts = line.split_whitespace();
+            let numbers = |parts: std::str::SplitWhitespace| -> Result<Vec<i32>, String> {
+                parts.map(|p| p.parse::<i32>().map_err(|e| format!("Invalid BDF line '{}': {}", line, e))).collect()
+            };
+            match parts.next() {
+                Some("FONTBOUNDINGBOX") => {
+                    let n = numbers(parts)?;
+                    if n.len() < 4 {
+                        return Err(format!("Invalid BDF line '{}'", line));
+                    }
+                    bounding_box = Some((n[0], n[1], n[2], n[3]));
+                }
+                Some("FONT_ASCENT") => font.ascent = numbers(parts)?.first().copied().unwrap_or(0),
+                Some("FONT_DESCENT") => font.descent = numbers(parts)?.first().copied().unwrap_or(0),
+                Some("STARTCHAR") => {
+                    let (encoding, glyph) = parse_bdf_char(&mut lines, bounding_box)?;
+                    if let Some(c) = encoding.and_then(char::from_u32) {
+                        font.glyphs.insert(c, glyph);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Fonts without FONT_ASCENT/FONT_DESCENT fall back to the bounding box
+        if font.ascent == 0 && font.descent == 0 {
+            let (_, height, _, y_offset) = bounding_box.ok_or("BDF font has no FONTBOUNDINGBOX")?;
+            font.ascent = height + y_offset;
+            font.descent = -y_offset;
+        }
+        if font.glyphs.is_empty() {
+            return Err("BDF font has no glyphs".to_string());
+        }
+        Ok(font)
+    }
+
+    fn line_height(&self) -> i32 {
+        self.ascent + self.descent
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Lays out `text` into a bitmap, one line per `\n`, with `line_spacing` empty rows between
+    /// lines. Returns the width, the height and the row-major pixels.
+    pub fn rasterize(&self, text: &str, line_spacing: i32) -> (usize, usize, Vec<bool>) {
+        let lines: Vec<&str> = text.lines().collect();
+        let line_width = |line: &str| -> i32 {
+            line.chars().filter_map(|c| self.glyph(c)).map(|g| g.advance).sum()
+        };
+        let width = lines.iter().map(|line| line_width(line)).max().unwrap_or(0).max(0) as usize;
+        let pitch = self.line_height() + line_spacing;
+        let height = (lines.len() as i32 * pitch - line_spacing).max(0) as usize;
+
+        let mut pixels = vec![false; width * height];
+        for (row, line) in lines.iter().enumerate() {
+            let baseline = row as i32 * pitch + self.ascent;
+            let mut pen = 0;
+            for glyph in line.chars().filter_map(|c| self.glyph(c)) {
+                let top = baseline - glyph.y_offset - glyph.height as i32;
+                for gy in 0..glyph.height {
+                    for gx in 0..glyph.width {
+                        let (x, y) = (pen + glyph.x_offset + gx as i32, top + gy as i32);
+                        if glyph.get(gx, gy) && x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                            pixels[y as usize * width + x as usize] = true;
+                        }
+                    }
+                }
+                pen += glyph.advance;
+            }
+        }
+        (width, height, pixels)
+    }
+}
+
+fn parse_bdf_char<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    bounding_box: Option<(i32, i32, i32, i32)>,
+) -> Result<(Option<u32>, Glyph), String> {
+    let (mut width, mut height, mut x_offset, mut y_offset) = bounding_box.unwrap_or((0, 0, 0, 0));
+    let mut encoding = None;
+    let mut advance = None;
+    let mut rows: Vec<&str> = Vec::new();
+    let mut in_bitmap = false;
+
+    for line in lines.by_ref() {
+        if line == "ENDCHAR" {
+            break;
+        }
+        if in_bitmap {
+            rows.push(line);
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next();
+        let n: Vec<i32> = parts.filter_map(|p| p.parse().ok()).collect();
+        match keyword {
+            Some("ENCODING") => encoding = n.first().and_then(|&e| u32::try_from(e).ok()),
+            Some("DWIDTH") => advance = n.first().copied(),
+            Some("BBX") if n.len() >= 4 => (width, height, x_offset, y_offset) = (n[0], n[1], n[2], n[3]),
+            Some("BITMAP") => in_bitmap = true,
+            _ => {}
+        }
+    }
+
+    let (width, height) = (width.max(0) as usize, height.max(0) as usize);
+    let mut pixels = vec![false; width * height];
+    for (y, row) in rows.iter().take(height).enumerate() {
+        let bits = u128::from_str_radix(row, 16).map_err(|e| format!("Invalid BDF bitmap row '{}': {}", row, e))?;
+        let row_bits = row.len() * 4;
+        for x in 0..width.min(row_bits) {
+            pixels[y * width + x] = bits & (1 << (row_bits - 1 - x)) != 0;
+        }
+    }
+    let glyph = Glyph {
+        advance: advance.unwrap_or(width as i32 + x_offset),
+        x_offset,
+        y_offset,
+        width,
+        height,
+        pixels,
+    };
+    Ok((encoding, glyph))
+}
+
+/// How blocks are picked for the pixels of the text or its background. `u` runs along the text
+/// and `v` down from its top, both in blocks.
+#[derive(Debug, Clone)]
+pub enum BlockPattern {
+    Solid(BlockState),
+    Checkerboard(BlockState, BlockState),
+    /// One block per row, repeating.
+    HorizontalStripes(Vec<BlockState>),
+    /// One block per column, repeating.
+    VerticalStripes(Vec<BlockState>),
+    /// A seeded random pick, weighted.
+    Random { blocks: Vec<(BlockState, u32)>, seed: u64 },
+}
+
+impl BlockPattern {
+    fn pick(&self, u: usize, v: usize, rng: &mut rand::rngs::StdRng) -> Option<BlockState> {
+        match self {
+            BlockPattern::Solid(block) => Some(block.clone()),
+            BlockPattern::Checkerboard(a, b) => Some(if (u + v).is_multiple_of(2) { a.clone() } else { b.clone() }),
+            BlockPattern::HorizontalStripes(blocks) => blocks.get(v % blocks.len().max(1)).cloned(),
+            BlockPattern::VerticalStripes(blocks) => blocks.get(u % blocks.len().max(1)).cloned(),
+            BlockPattern::Random { blocks, .. } => {
+                let total: u32 = blocks.iter().map(|(_, weight)| weight).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut roll = rng.gen_range(0..total);
+                blocks.iter()
+                    .find(|(_, weight)| {
+                        if roll < *weight {
+                            return true;
+                        }
+                        roll -= weight;
+                        false
+                    })
+                    .map(|(block, _)| block.clone())
+            }
+        }
+    }
+
+    fn seed(&self) -> u64 {
+        match self {
+            BlockPattern::Random { seed, .. } => *seed,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOrientation {
+    /// Upright, readable from the side it faces. Depth goes back away from the reader.
+    Wall,
+    /// Lying flat, readable from above. Depth goes down.
+    Floor,
+}
+
+/// For walls, the direction the readable side faces. For floors, the direction the top of the
+/// text points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    North,
+    South,
+    East,
+    West,
+}
+
+/// Parses `"north"`, `"south"`, `"east"` or `"west"` for wall text, optionally prefixed with
+/// `"floor_"` for floor text.
+pub fn parse_placement(placement: &str) -> Result<(TextOrientation, Facing), String> {
+    let (orientation, facing) = match placement.strip_prefix("floor_") {
+        Some(facing) => (TextOrientation::Floor, facing),
+        None => (TextOrientation::Wall, placement),
+    };
+    let facing = match facing {
+        "north" => Facing::North,
+        "south" => Facing::South,
+        "east" => Facing::East,
+        "west" => Facing::West,
+        other => return Err(format!("Unknown text placement '{}'", other)),
+    };
+    Ok((orientation, facing))
+}
+
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    pub orientation: TextOrientation,
+    pub facing: Facing,
+    /// Blocks per font pixel along each side.
+    pub scale: u32,
+    /// Thickness of the letters in blocks.
+    pub depth: u32,
+    pub line_spacing: i32,
+    pub foreground: BlockPattern,
+    /// Fills the back layer around the letters when set.
+    pub background: Option<BlockPattern>,
+    /// Extra background around the text, in blocks.
+    pub padding: u32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            orientation: TextOrientation::Wall,
+            facing: Facing::South,
+            scale: 1,
+            depth: 1,
+            line_spacing: 1,
+            foreground: BlockPattern::Solid(BlockState::new("minecraft:black_concrete".to_string())),
+            background: None,
+            padding: 0,
+        }
+    }
+}
+
+/// Writes `text` in blocks. The result starts at the origin and extends along positive axes.
+pub fn generate_text(text: &str, font: &BitmapFont, options: &TextOptions) -> UniversalSchematic {
+    let mut schematic = UniversalSchematic::new(text.lines().next().unwrap_or("Text").to_string());
+    let (glyph_width, glyph_height, pixels) = font.rasterize(text, options.line_spacing);
+    let scale = options.scale.max(1) as usize;
+    let padding = options.padding as usize;
+    let depth = options.depth.max(1) as i32;
+    let width = glyph_width * scale + 2 * padding;
+    let height = glyph_height * scale + 2 * padding;
+
+    let mut foreground_rng = rand::rngs::StdRng::seed_from_u64(options.foreground.seed());
+    let mut background_rng = rand::rngs::StdRng::seed_from_u64(options.background.as_ref().map_or(0, |b| b.seed()));
+
+    // (u, v) is the position on the text, d the layer counted from the front
+    let place = |u: usize, v: usize, d: i32| -> (i32, i32, i32) {
+        let (u, v) = (u as i32, v as i32);
+        let (w, h) = (width as i32, height as i32);
+        let back = depth - 1 - d;
+        match (options.orientation, options.facing) {
+            (TextOrientation::Wall, Facing::South) => (u, h - 1 - v, back),
+            (TextOrientation::Wall, Facing::North) => (w - 1 - u, h - 1 - v, d),
+            (TextOrientation::Wall, Facing::East) => (back, h - 1 - v, w - 1 - u),
+            (TextOrientation::Wall, Facing::West) => (d, h - 1 - v, u),
+            (TextOrientation::Floor, Facing::North) => (u, back, v),
+            (TextOrientation::Floor, Facing::South) => (w - 1 - u, back, h - 1 - v),
+            (TextOrientation::Floor, Facing::East) => (h - 1 - v, back, u),
+            (TextOrientation::Floor, Facing::West) => (v, back, w - 1 - u),
+        }
+    };
+
+    for v in 0..height {
+        for u in 0..width {
+            let is_text = u >= padding && v >= padding && {
+                let (gx, gy) = ((u - padding) / scale, (v - padding) / scale);
+                gx < glyph_width && gy < glyph_height && pixels[gy * glyph_width + gx]
+            };
+            if is_text {
+                for d in 0..depth {
+                    if let Some(block) = options.foreground.pick(u, v, &mut foreground_rng) {
+                        let (x, y, z) = place(u, v, d);
+                        schematic.set_block(x, y, z, block);
+                    }
+                }
+            } else if let Some(background) = &options.background {
+                if let Some(block) = background.pick(u, v, &mut background_rng) {
+                    let (x, y, z) = place(u, v, depth - 1);
+                    schematic.set_block(x, y, z, block);
+                }
+            }
+        }
+    }
+    schematic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_block(schematic: &UniversalSchematic, x: i32, y: i32, z: i32, name: &str) -> bool {
+        schematic.get_block(x, y, z).is_some_and(|b| b.name == name)
+    }
+
+    #[test]
+    fn test_bundled_font_layout() {
+        let font = BitmapFont::bundled();
+        let (width, height, pixels) = font.rasterize("I\nI", 1);
+        assert_eq!((width, height), (6, 17));
+        // The stem of the I is the middle column, from the top row to the baseline
+        let stem: Vec<bool> = (0..8).map(|y| pixels[y * width + 2]).collect();
+        assert_eq!(stem, vec![true, true, true, true, true, true, true, false]);
+        assert!(pixels[9 * width + 2]);
+    }
+
+    #[test]
+    fn test_wall_and_floor_placement() {
+        let black = "minecraft:black_concrete";
+        let options = TextOptions { scale: 2, depth: 2, ..TextOptions::default() };
+        let wall = generate_text("L", &BitmapFont::bundled(), &options);
+        // L's stem is column 0; scaled by 2 it covers x 0..2, from the top down to the baseline
+        assert_eq!(wall.get_dimensions(), (10, 14, 2));
+        assert!(is_block(&wall, 0, 15, 0, black) && is_block(&wall, 1, 15, 1, black));
+        assert!(is_block(&wall, 1, 2, 0, black) && is_block(&wall, 1, 2, 1, black));
+        assert!(is_block(&wall, 9, 2, 1, black));
+
+        let floor = generate_text("L", &BitmapFont::bundled(), &TextOptions {
+            orientation: TextOrientation::Floor,
+            facing: Facing::North,
+            background: Some(BlockPattern::Solid(BlockState::new("minecraft:white_concrete".to_string()))),
+            padding: 1,
+            ..TextOptions::default()
+        });
+        assert_eq!(floor.get_dimensions(), (8, 1, 10));
+        // Top-left corner of the L sits one block in from the padding
+        assert!(is_block(&floor, 1, 0, 1, black));
+        assert!(is_block(&floor, 0, 0, 0, "minecraft:white_concrete"));
+        assert!(is_block(&floor, 5, 0, 1, "minecraft:white_concrete"));
+    }
+
+    #[test]
+    fn test_bdf_font() {
+        let source = "STARTFONT 2.1\nFONTBOUNDINGBOX 3 3 0 0\nSTARTPROPERTIES 2\nFONT_ASCENT 3\nFONT_DESCENT 0\nENDPROPERTIES\nCHARS 1\nSTARTCHAR plus\nENCODING 43\nDWIDTH 4 0\nBBX 3 3 0 0\nBITMAP\n40\nE0\n40\nENDCHAR\nENDFONT\n";
+        let font = BitmapFont::from_bdf(source).unwrap();
+        let (width, height, pixels) = font.rasterize("++", 0);
+        assert_eq!((width, height), (8, 3));
+        let row = |y: usize| pixels[y * width..(y + 1) * width].to_vec();
+        assert_eq!(row(1), vec![true, true, true, false, true, true, true, false]);
+        assert_eq!(row(0), vec![false, true, false, false, false, true, false, false]);
+        assert!(BitmapFont::from_bdf("STARTFONT 2.1\nENDFONT\n").is_err());
+    }
+}
@@ -2,6 +2,7 @@ use crate::utils::{NbtMap, parse_custom_name, parse_items_array};
 use std::collections::HashMap;
 use quartz_nbt::{NbtCompound, NbtTag};
 use rand::SeedableRng;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use crate::{BlockState};
 use crate::block_entity::BlockEntity;
@@ -16,10 +17,16 @@ use crate::utils::NbtValue;
 #[derive(Serialize, Deserialize, Clone)]
 pub struct UniversalSchematic {
     pub metadata: Metadata,
-    pub regions: HashMap<String, Region>,
+    /// Regions in the order they were added, which is also the order writers emit them in.
+    pub regions: IndexMap<String, Region>,
     pub default_region_name: String,
+    /// Paste anchor in schematic coordinates: pasting at a position puts this point there.
+    /// Read from and written to the WorldEdit offset of `.schem` files.
+    #[serde(default)]
+    pub origin: (i32, i32, i32),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ChunkLoadingStrategy {
     Default,
     DistanceToCamera(f32, f32, f32), // Camera position
@@ -37,8 +44,9 @@ impl UniversalSchematic {
                 name: Some(name),
                 ..Metadata::default()
             },
-            regions: HashMap::new(),
+            regions: IndexMap::new(),
             default_region_name: "Main".to_string(),
+            origin: (0, 0, 0),
         }
     }
 
@@ -181,15 +189,23 @@ impl UniversalSchematic {
             .map_err(|e| format!("Failed to serialize 'regions' in UniversalSchematic: {}", e))?;
 
 
+        let default_region_json = serde_json::to_string(&self.default_region_name)
+            .map_err(|e| format!("Failed to serialize 'default_region_name' in UniversalSchematic: {}", e))?;
+
         // Combine everything into a single JSON object manually
         let combined_json = format!(
-            "{{\"metadata\":{},\"regions\":{}}}",
-            metadata_json, regions_json
+            "{{\"metadata\":{},\"regions\":{},\"default_region_name\":{},\"origin\":[{},{},{}]}}",
+            metadata_json, regions_json, default_region_json, self.origin.0, self.origin.1, self.origin.2
         );
 
         Ok(combined_json)
     }
 
+    /// Reads JSON from `get_json_string` or in the schema described in [`crate::formats::json`].
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        crate::formats::json::from_json(json)
+    }
+
     pub(crate) fn total_blocks(&self) -> i32 {
         self.regions.values().map(|r| r.count_blocks() as i32).sum()
     }
@@ -226,7 +242,7 @@ impl UniversalSchematic {
     }
 
     pub fn remove_region(&mut self, name: &str) -> Option<Region> {
-        self.regions.remove(name)
+        self.regions.shift_remove(name)
     }
 
     pub fn get_region(&self, name: &str) -> Option<&Region> {
@@ -306,6 +322,7 @@ impl UniversalSchematic {
         root.insert("Regions", NbtTag::Compound(regions_tag));
 
         root.insert("DefaultRegion", NbtTag::String(self.default_region_name.clone()));
+        root.insert("Origin", NbtTag::IntArray(vec![self.origin.0, self.origin.1, self.origin.2]));
 
         root
     }
@@ -316,7 +333,7 @@ impl UniversalSchematic {
 
         let regions_tag = nbt.get::<_, &NbtCompound>("Regions")
             .map_err(|e| format!("Failed to get Regions: {}", e))?;
-        let mut regions = HashMap::new();
+        let mut regions = IndexMap::new();
         for (region_name, region_tag) in regions_tag.inner() {
             if let NbtTag::Compound(region_compound) = region_tag {
                 regions.insert(region_name.to_string(), Region::from_nbt(&region_compound.clone())?);
@@ -327,10 +344,16 @@ impl UniversalSchematic {
             .map_err(|e| format!("Failed to get DefaultRegion: {}", e))?
             .to_string();
 
+        let origin = match nbt.get::<_, &[i32]>("Origin") {
+            Ok(&[x, y, z]) => (x, y, z),
+            _ => (0, 0, 0),
+        };
+
         Ok(UniversalSchematic {
             metadata,
             regions,
             default_region_name,
+            origin,
         })
     }
 
@@ -453,6 +476,23 @@ impl UniversalSchematic {
         Ok(())
     }
 
+    /// Pastes all of `from_schematic` so that its origin lands on `position`.
+    pub fn paste(
+        &mut self,
+        from_schematic: &UniversalSchematic,
+        position: (i32, i32, i32),
+        excluded_blocks: &[BlockState],
+    ) -> Result<(), String> {
+        let bounds = from_schematic.get_bounding_box();
+        let origin = from_schematic.origin;
+        let target_position = (
+            position.0 + bounds.min.0 - origin.0,
+            position.1 + bounds.min.1 - origin.1,
+            position.2 + bounds.min.2 - origin.2
+        );
+        self.copy_region(from_schematic, &bounds, target_position, excluded_blocks)
+    }
+
     pub fn split_into_chunks(&self, chunk_width: i32, chunk_height: i32, chunk_length: i32) -> Vec<Chunk> {
         use std::collections::HashMap;
         let mut chunk_map: HashMap<(i32, i32, i32), Vec<BlockPosition>> = HashMap::new();
@@ -483,14 +523,17 @@ impl UniversalSchematic {
             }
         }
 
-        chunk_map.into_iter()
+        let mut chunks: Vec<Chunk> = chunk_map.into_iter()
             .map(|((chunk_x, chunk_y, chunk_z), positions)| Chunk {
                 chunk_x,
                 chunk_y,
                 chunk_z,
                 positions,
             })
-            .collect()
+            .collect();
+        // Sorted so the strategies below give the same order every time
+        chunks.sort_by_key(|chunk| (chunk.chunk_y, chunk.chunk_z, chunk.chunk_x));
+        chunks
     }
 
 
@@ -1228,6 +1271,22 @@ mod tests {
         assert_eq!(original_palette, deserialized_palette);
     }
 
+    #[test]
+    fn test_nbt_round_trip_keeps_offset_regions() {
+        let schematic = crate::test_fixtures::sample_schematic();
+        let loaded = UniversalSchematic::from_nbt(schematic.to_nbt()).unwrap();
+        assert_eq!(loaded.origin, schematic.origin);
+        assert_eq!(loaded.regions.len(), schematic.regions.len());
+        for (name, region) in &schematic.regions {
+            let other = &loaded.regions[name];
+            assert_eq!((other.position, other.size), (region.position, region.size));
+            assert_eq!(other.palette, region.palette);
+            assert_eq!(other.blocks, region.blocks);
+            assert!(region.block_entities.keys().all(|position| other.block_entities.contains_key(position)));
+        }
+        assert_eq!(loaded.get_block(-5, -5, -5), schematic.get_block(-5, -5, -5));
+    }
+
 
     #[test]
     fn test_multiple_region_merging() {
@@ -1318,4 +1377,22 @@ mod tests {
             assert_eq!(total_items as u32, expected_items);
         }
     }
+
+    #[test]
+    fn test_paste_aligns_origin() {
+        let mut tree = UniversalSchematic::new("Tree".to_string());
+        tree.set_block(0, 0, 0, BlockState::new("minecraft:oak_log".to_string()));
+        tree.set_block(0, 1, 0, BlockState::new("minecraft:oak_leaves".to_string()));
+        tree.set_block(2, 0, 2, BlockState::new("minecraft:stone".to_string()));
+        tree.origin = (0, -1, 0);
+
+        let mut world = UniversalSchematic::new("World".to_string());
+        world.paste(&tree, (10, 5, 10), &[]).unwrap();
+        assert_eq!(world.get_block(10, 6, 10).unwrap().name, "minecraft:oak_log");
+        assert_eq!(world.get_block(10, 7, 10).unwrap().name, "minecraft:oak_leaves");
+        assert_eq!(world.get_block(12, 6, 12).unwrap().name, "minecraft:stone");
+
+        let restored = UniversalSchematic::from_nbt(tree.to_nbt()).unwrap();
+        assert_eq!(restored.origin, (0, -1, 0));
+    }
 }
@@ -0,0 +1,258 @@
+//! Little-endian NBT as used by Bedrock Edition (`.mcstructure`, level.dat, ...).
+//!
+//! quartz_nbt only speaks the big-endian Java flavour, so this reads and writes the same
+//! `NbtCompound` tree with the byte order flipped.
+
+use quartz_nbt::{NbtCompound, NbtList, NbtTag};
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// Nesting deeper than this is rejected rather than risking a stack overflow.
+const MAX_DEPTH: usize = 512;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.pos < len {
+            return Err("Unexpected end of NBT data".to_string());
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Err(format!("Negative NBT length {}", len));
+        }
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.i16()? as u16 as usize;
+        let bytes = self.take(len)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn payload(&mut self, tag_type: u8, depth: usize) -> Result<NbtTag, String> {
+        if depth > MAX_DEPTH {
+            return Err("NBT nesting is too deep".to_string());
+        }
+        Ok(match tag_type {
+            TAG_BYTE => NbtTag::Byte(self.u8()? as i8),
+            TAG_SHORT => NbtTag::Short(self.i16()?),
+            TAG_INT => NbtTag::Int(self.i32()?),
+            TAG_LONG => NbtTag::Long(self.i64()?),
+            TAG_FLOAT => NbtTag::Float(f32::from_bits(self.i32()? as u32)),
+            TAG_DOUBLE => NbtTag::Double(f64::from_bits(self.i64()? as u64)),
+            TAG_BYTE_ARRAY => {
+                let len = self.length()?;
+                NbtTag::ByteArray(self.take(len)?.iter().map(|&b| b as i8).collect())
+            }
+            TAG_STRING => NbtTag::String(self.string()?),
+            TAG_LIST => {
+                let element_type = self.u8()?;
+                let len = self.length()?;
+                // Every element takes at least one byte, which bounds the allocation
+                let mut list = NbtList::with_capacity(len.min(self.data.len() - self.pos));
+                for _ in 0..len {
+                    list.push(self.payload(element_type, depth + 1)?);
+                }
+                NbtTag::List(list)
+            }
+            TAG_COMPOUND => NbtTag::Compound(self.compound(depth + 1)?),
+            TAG_INT_ARRAY => {
+                let len = self.length()?;
+                let bytes = self.take(len.checked_mul(4).ok_or("NBT array too long")?)?;
+                NbtTag::IntArray(bytes.chunks_exact(4).map(|c| i32::from_le_bytes(c.try_into().unwrap())).collect())
+            }
+            TAG_LONG_ARRAY => {
+                let len = self.length()?;
+                let bytes = self.take(len.checked_mul(8).ok_or("NBT array too long")?)?;
+                NbtTag::LongArray(bytes.chunks_exact(8).map(|c| i64::from_le_bytes(c.try_into().unwrap())).collect())
+            }
+            other => return Err(format!("Unknown NBT tag type {}", other)),
+        })
+    }
+
+    fn compound(&mut self, depth: usize) -> Result<NbtCompound, String> {
+        let mut compound = NbtCompound::new();
+        loop {
+            let tag_type = self.u8()?;
+            if tag_type == TAG_END {
+                return Ok(compound);
+            }
+            let name = self.string()?;
+            let value = self.payload(tag_type, depth)?;
+            compound.insert(name, value);
+        }
+    }
+}
+
+/// Reads an uncompressed little-endian NBT document, returning the root compound and its name.
+pub fn read_le_nbt(data: &[u8]) -> Result<(NbtCompound, String), String> {
+    let mut reader = Reader { data, pos: 0 };
+    if reader.u8()? != TAG_COMPOUND {
+        return Err("Root NBT tag is not a compound".to_string());
+    }
+    let name = reader.string()?;
+    let root = reader.compound(0)?;
+    Ok((root, name))
+}
+
+/// Writes `root` as an uncompressed little-endian NBT document.
+pub fn write_le_nbt(root: &NbtCompound, root_name: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.push(TAG_COMPOUND);
+    write_string(&mut out, root_name);
+    write_compound(&mut out, root);
+    out
+}
+
+fn tag_type(tag: &NbtTag) -> u8 {
+    match tag {
+        NbtTag::Byte(_) => TAG_BYTE,
+        NbtTag::Short(_) => TAG_SHORT,
+        NbtTag::Int(_) => TAG_INT,
+        NbtTag::Long(_) => TAG_LONG,
+        NbtTag::Float(_) => TAG_FLOAT,
+        NbtTag::Double(_) => TAG_DOUBLE,
+        NbtTag::ByteArray(_) => TAG_BYTE_ARRAY,
+        NbtTag::String(_) => TAG_STRING,
+        NbtTag::List(_) => TAG_LIST,
+        NbtTag::Compound(_) => TAG_COMPOUND,
+        NbtTag::IntArray(_) => TAG_INT_ARRAY,
+        NbtTag::LongArray(_) => TAG_LONG_ARRAY,
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    let bytes = value.as_bytes();
+    out.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn write_compound(out: &mut Vec<u8>, compound: &NbtCompound) {
+    // Sorted so identical compounds always produce identical bytes
+    let mut entries: Vec<(&String, &NbtTag)> = compound.inner().iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, tag) in entries {
+        out.push(tag_type(tag));
+        write_string(out, name);
+        write_payload(out, tag);
+    }
+    out.push(TAG_END);
+}
+
+fn write_payload(out: &mut Vec<u8>, tag: &NbtTag) {
+    match tag {
+        NbtTag::Byte(v) => out.push(*v as u8),
+        NbtTag::Short(v) => out.extend_from_slice(&v.to_le_bytes()),
+        NbtTag::Int(v) => out.extend_from_slice(&v.to_le_bytes()),
+        NbtTag::Long(v) => out.extend_from_slice(&v.to_le_bytes()),
+        NbtTag::Float(v) => out.extend_from_slice(&v.to_le_bytes()),
+        NbtTag::Double(v) => out.extend_from_slice(&v.to_le_bytes()),
+        NbtTag::ByteArray(v) => {
+            out.extend_from_slice(&(v.len() as i32).to_le_bytes());
+            out.extend(v.iter().map(|&b| b as u8));
+        }
+        NbtTag::String(v) => write_string(out, v),
+        NbtTag::List(list) => {
+            let element_type = list.iter().next().map(tag_type).unwrap_or(TAG_END);
+            out.push(element_type);
+            out.extend_from_slice(&(list.len() as i32).to_le_bytes());
+            for element in list.iter() {
+                write_payload(out, element);
+            }
+        }
+        NbtTag::Compound(compound) => write_compound(out, compound),
+        NbtTag::IntArray(v) => {
+            out.extend_from_slice(&(v.len() as i32).to_le_bytes());
+            for value in v {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        NbtTag::LongArray(v) => {
+            out.extend_from_slice(&(v.len() as i32).to_le_bytes());
+            for value in v {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_le_nbt_roundtrip() {
+        let mut inner = NbtCompound::new();
+        inner.insert("name", "minecraft:stone");
+        inner.insert("version", NbtTag::Int(18090528));
+
+        let mut root = NbtCompound::new();
+        root.insert("format_version", NbtTag::Int(1));
+        root.insert("size", NbtTag::List(NbtList::from(vec![NbtTag::Int(1), NbtTag::Int(2), NbtTag::Int(3)])));
+        root.insert("origin", NbtTag::LongArray(vec![-1, 2]));
+        root.insert("pos", NbtTag::Float(1.5));
+        root.insert("block", NbtTag::Compound(inner));
+        root.insert("empty", NbtTag::List(NbtList::new()));
+
+        let bytes = write_le_nbt(&root, "");
+        assert_eq!(bytes[0], TAG_COMPOUND);
+        let (read, name) = read_le_nbt(&bytes).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(read, root);
+    }
+
+    #[test]
+    fn test_le_nbt_byte_order() {
+        let mut root = NbtCompound::new();
+        root.insert("a", NbtTag::Int(1));
+        let bytes = write_le_nbt(&root, "");
+        // compound, empty name, int tag, name "a", value 1 little-endian, end
+        assert_eq!(bytes, vec![10, 0, 0, 3, 1, 0, b'a', 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_le_nbt_rejects_truncated_data() {
+        let mut root = NbtCompound::new();
+        root.insert("a", NbtTag::Long(1));
+        let bytes = write_le_nbt(&root, "");
+        assert!(read_le_nbt(&bytes[..bytes.len() - 3]).is_err());
+    }
+}
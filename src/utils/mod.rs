@@ -1,5 +1,7 @@
 mod nbt;
 mod block_string;
+mod le_nbt;
 
 pub use nbt::{NbtValue, NbtMap};
-pub use block_string::{parse_items_array, parse_custom_name};
\ No newline at end of file
+pub use block_string::{parse_block_string, parse_items_array, parse_custom_name};
+pub use le_nbt::{read_le_nbt, write_le_nbt};
@@ -1,6 +1,6 @@
 use quartz_nbt::{self, NbtCompound, NbtTag};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NbtValue {
@@ -19,7 +19,9 @@ pub enum NbtValue {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct NbtMap(HashMap<String, NbtValue>);
+/// Compound contents in insertion order, so tags read from a file are written back in the same
+/// order and identical maps always serialize to identical bytes.
+pub struct NbtMap(IndexMap<String, NbtValue>);
 
 impl Default for NbtMap {
     fn default() -> Self {
@@ -29,7 +31,7 @@ impl Default for NbtMap {
 
 impl NbtMap {
     pub fn new() -> Self {
-        NbtMap(HashMap::new())
+        NbtMap(IndexMap::new())
     }
 
     pub fn insert(&mut self, key: String, value: NbtValue) -> Option<NbtValue> {
@@ -45,14 +47,14 @@ impl NbtMap {
     }
 
     pub fn remove(&mut self, key: &str) -> Option<NbtValue> {
-        self.0.remove(key)
+        self.0.shift_remove(key)
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<String, NbtValue> {
+    pub fn iter(&self) -> indexmap::map::Iter<'_, String, NbtValue> {
         self.0.iter()
     }
 
-    pub fn iter_mut(&mut self) -> std::collections::hash_map::IterMut<String, NbtValue> {
+    pub fn iter_mut(&mut self) -> indexmap::map::IterMut<'_, String, NbtValue> {
         self.0.iter_mut()
     }
 
@@ -73,11 +75,53 @@ impl NbtMap {
         compound
     }
 
+    /// Serializes the map as SNBT (the text form used by commands), with keys sorted
+    /// so the output is stable.
+    pub fn to_snbt(&self) -> String {
+        let mut keys: Vec<&String> = self.0.keys().collect();
+        keys.sort();
+        let entries: Vec<String> = keys.iter()
+            .map(|key| format!("{}:{}", snbt_key(key), self.0[*key].to_snbt()))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn snbt_key(key: &str) -> String {
+    let is_plain = !key.is_empty() && key.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'));
+    if is_plain {
+        key.to_string()
+    } else {
+        snbt_string(key)
+    }
+}
+
+fn snbt_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
 impl IntoIterator for NbtMap {
     type Item = (String, NbtValue);
-    type IntoIter = std::collections::hash_map::IntoIter<String, NbtValue>;
+    type IntoIter = indexmap::map::IntoIter<String, NbtValue>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.into_iter()
@@ -86,7 +130,7 @@ impl IntoIterator for NbtMap {
 
 impl<'a> IntoIterator for &'a NbtMap {
     type Item = (&'a String, &'a NbtValue);
-    type IntoIter = std::collections::hash_map::Iter<'a, String, NbtValue>;
+    type IntoIter = indexmap::map::Iter<'a, String, NbtValue>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.iter()
@@ -95,7 +139,7 @@ impl<'a> IntoIterator for &'a NbtMap {
 
 impl<'a> IntoIterator for &'a mut NbtMap {
     type Item = (&'a String, &'a mut NbtValue);
-    type IntoIter = std::collections::hash_map::IterMut<'a, String, NbtValue>;
+    type IntoIter = indexmap::map::IterMut<'a, String, NbtValue>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.iter_mut()
@@ -141,6 +185,27 @@ impl NbtValue {
     }
 
 
+    pub fn to_snbt(&self) -> String {
+        fn join<T>(values: &[T], f: impl Fn(&T) -> String) -> String {
+            values.iter().map(f).collect::<Vec<_>>().join(",")
+        }
+
+        match self {
+            NbtValue::Byte(v) => format!("{}b", v),
+            NbtValue::Short(v) => format!("{}s", v),
+            NbtValue::Int(v) => v.to_string(),
+            NbtValue::Long(v) => format!("{}L", v),
+            NbtValue::Float(v) => format!("{:?}f", v),
+            NbtValue::Double(v) => format!("{:?}d", v),
+            NbtValue::ByteArray(v) => format!("[B;{}]", join(v, |b| format!("{}b", b))),
+            NbtValue::String(v) => snbt_string(v),
+            NbtValue::List(v) => format!("[{}]", join(v, |x| x.to_snbt())),
+            NbtValue::Compound(v) => v.to_snbt(),
+            NbtValue::IntArray(v) => format!("[I;{}]", join(v, |i| i.to_string())),
+            NbtValue::LongArray(v) => format!("[L;{}]", join(v, |l| format!("{}L", l))),
+        }
+    }
+
     pub fn as_string(&self) -> Option<&String> {
         if let NbtValue::String(s) = self {
             Some(s)
@@ -6,12 +6,23 @@ use web_sys::console;
 use crate::{
     UniversalSchematic,
     BlockState,
-    formats::{litematic, schematic},
+    formats::{litematic, schematic, mcstructure, vox, native, json, peek, compression::NbtCompression, limits::LoadLimits},
     print_utils::{format_schematic as print_schematic, format_json_schematic as print_json_schematic},
     block_position::BlockPosition,
 };
 use std::collections::HashMap;
 use crate::bounding_box::BoundingBox;
+use crate::block_colors::BlockColorPalette;
+use crate::render::{self, Image, RenderOptions};
+use crate::map_art;
+use crate::terrain;
+use crate::text;
+use crate::redstone::{Netlist, RedstoneSimulator};
+use crate::lighting;
+use crate::survival;
+use crate::build_order;
+use crate::fingerprint;
+use crate::datafixer;
 use crate::universal_schematic::ChunkLoadingStrategy;
 
 #[wasm_bindgen(start)]
@@ -38,40 +49,364 @@ impl SchematicWrapper {
     
 
 
-    pub fn from_data(&mut self, data: &[u8]) -> Result<(), JsValue> {
-        if litematic::is_litematic(data) {
+    /// Loads any supported format. With `untrusted`, litematic, Sponge, native and JSON loads
+    /// are capped by `LoadLimits::untrusted()`, for data from uploads; mcstructure and vox sizes
+    /// are already bounded by the data they hold.
+    pub fn from_data(&mut self, data: &[u8], untrusted: Option<bool>) -> Result<(), JsValue> {
+        let limits = LoadLimits::untrusted_if(untrusted.unwrap_or(false));
+        if native::is_native(data) {
+            console::log_1(&"Parsing native data".into());
+            self.from_native(data, untrusted)
+        } else if litematic::is_litematic_with_limits(data, &limits) {
             console::log_1(&"Parsing litematic data".into());
-            self.from_litematic(data)
-        } else if schematic::is_schematic(data) {
+            self.from_litematic(data, untrusted)
+        } else if schematic::is_schematic_with_limits(data, &limits) {
             console::log_1(&"Parsing schematic data".into());
-            self.from_schematic(data)
+            self.from_schematic(data, untrusted)
+        } else if mcstructure::is_mcstructure(data) {
+            console::log_1(&"Parsing mcstructure data".into());
+            self.from_mcstructure(data)
+        } else if vox::is_vox(data) {
+            console::log_1(&"Parsing vox data".into());
+            self.from_vox(data, None)
         } else {
             Err(JsValue::from_str("Unknown or unsupported schematic format"))
         }
     }
 
-    pub fn from_litematic(&mut self, data: &[u8]) -> Result<(), JsValue> {
-        self.0 = litematic::from_litematic(data)
+    pub fn from_litematic(&mut self, data: &[u8], untrusted: Option<bool>) -> Result<(), JsValue> {
+        self.0 = litematic::from_litematic_with_limits(data, &LoadLimits::untrusted_if(untrusted.unwrap_or(false)))
             .map_err(|e| JsValue::from_str(&format!("Litematic parsing error: {}", e)))?;
         Ok(())
     }
 
-    pub fn to_litematic(&self) -> Result<Vec<u8>, JsValue> {
-        litematic::to_litematic(&self.0)
+    /// Writes a litematic, embedding an isometric preview of `preview_size` pixels if given.
+    /// `version` forces format 6 or 7 instead of picking it from the data version.
+    /// `compression` is "gzip" (default), "zlib" or "none", with `level` from 0 to 9, or "zstd"
+    /// (level 0 to 22) in builds with the `zstd` feature. A fixed `timestamp` in milliseconds
+    /// replaces `Date.now()`, making the output byte-stable.
+    pub fn to_litematic(&self, preview_size: Option<u32>, version: Option<i32>, compression: Option<String>, level: Option<u32>, timestamp: Option<f64>) -> Result<Vec<u8>, JsValue> {
+        let compression = NbtCompression::parse(compression.as_deref().unwrap_or("gzip"), level)
+            .map_err(|e| JsValue::from_str(&e))?;
+        let timestamp = timestamp.map(|t| t as i64);
+        let options = litematic::LitematicWriteOptions { preview_size, version, compression, timestamp };
+        litematic::to_litematic_with_options(&self.0, &options)
             .map_err(|e| JsValue::from_str(&format!("Litematic conversion error: {}", e)))
     }
 
-    pub fn from_schematic(&mut self, data: &[u8]) -> Result<(), JsValue> {
-        self.0 = schematic::from_schematic(data)
+    pub fn from_json(&mut self, data: &str, untrusted: Option<bool>) -> Result<(), JsValue> {
+        self.0 = json::from_json_with_limits(data, &LoadLimits::untrusted_if(untrusted.unwrap_or(false)))
+            .map_err(|e| JsValue::from_str(&format!("JSON parsing error: {}", e)))?;
+        Ok(())
+    }
+
+    /// JSON in the schema documented in `formats::json`; `compact` uses palette indices.
+    pub fn to_json(&self, compact: bool) -> Result<String, JsValue> {
+        json::to_json(&self.0, compact)
+            .map_err(|e| JsValue::from_str(&format!("JSON conversion error: {}", e)))
+    }
+
+    pub fn from_native(&mut self, data: &[u8], untrusted: Option<bool>) -> Result<(), JsValue> {
+        self.0 = native::from_native_with_limits(data, &LoadLimits::untrusted_if(untrusted.unwrap_or(false)))
+            .map_err(|e| JsValue::from_str(&format!("Native parsing error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Writes the lossless cache format. `compression` of "none" gives the fastest loads.
+    pub fn to_native(&self, compression: Option<String>, level: Option<u32>) -> Result<Vec<u8>, JsValue> {
+        let compression = NbtCompression::parse(compression.as_deref().unwrap_or("gzip"), level.or(Some(1)))
+            .map_err(|e| JsValue::from_str(&e))?;
+        let options = native::NativeWriteOptions { compression };
+        native::to_native_with_options(&self.0, &options)
+            .map_err(|e| JsValue::from_str(&format!("Native conversion error: {}", e)))
+    }
+
+    pub fn from_schematic(&mut self, data: &[u8], untrusted: Option<bool>) -> Result<(), JsValue> {
+        self.0 = schematic::from_schematic_with_limits(data, &LoadLimits::untrusted_if(untrusted.unwrap_or(false)))
             .map_err(|e| JsValue::from_str(&format!("Schematic parsing error: {}", e)))?;
         Ok(())
     }
 
-    pub fn to_schematic(&self) -> Result<Vec<u8>, JsValue> {
-        schematic::to_schematic(&self.0)
+    /// Writes a Sponge schematic of `version` 2 or 3 (default). `compression` is "gzip"
+    /// (default), "zlib" or "none", with `level` from 0 to 9, or "zstd" (level 0 to 22) in builds
+    /// with the `zstd` feature.
+    pub fn to_schematic(&self, version: Option<i32>, compression: Option<String>, level: Option<u32>) -> Result<Vec<u8>, JsValue> {
+        let compression = NbtCompression::parse(compression.as_deref().unwrap_or("gzip"), level)
+            .map_err(|e| JsValue::from_str(&e))?;
+        let options = schematic::SchematicWriteOptions { version: version.unwrap_or(3), compression };
+        schematic::to_schematic_with_options(&self.0, &options)
             .map_err(|e| JsValue::from_str(&format!("Schematic conversion error: {}", e)))
     }
 
+    pub fn from_mcstructure(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        self.0 = mcstructure::from_mcstructure(data)
+            .map_err(|e| JsValue::from_str(&format!("Mcstructure parsing error: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn to_mcstructure(&self) -> Result<Vec<u8>, JsValue> {
+        mcstructure::to_mcstructure(&self.0)
+            .map_err(|e| JsValue::from_str(&format!("Mcstructure conversion error: {}", e)))
+    }
+
+    /// Imports a MagicaVoxel file, optionally matching colors only against `block_set`.
+    pub fn from_vox(&mut self, data: &[u8], block_set: Option<Vec<String>>) -> Result<(), JsValue> {
+        let mut options = vox::VoxImportOptions::default();
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = BlockColorPalette::from_blocks(&blocks);
+        }
+        self.0 = vox::from_vox_with_options(data, &options)
+            .map_err(|e| JsValue::from_str(&format!("Vox parsing error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Builds block-per-pixel art from a PNG, as a wall or (with `floor`) lying flat.
+    pub fn from_pixel_art(&mut self, data: &[u8], block_set: Option<Vec<String>>, floor: bool, dithering: bool, survival_only: bool) -> Result<(), JsValue> {
+        let image = Image::from_png(data)
+            .map_err(|e| JsValue::from_str(&format!("PNG parsing error: {}", e)))?;
+        let mut options = map_art::PixelArtOptions { dithering, ..map_art::PixelArtOptions::default() };
+        if floor {
+            options.orientation = map_art::ArtOrientation::Floor;
+        }
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = BlockColorPalette::from_blocks(&blocks);
+        }
+        if survival_only {
+            options.palette.retain(|b| map_art::is_survival_obtainable(&b.name));
+        }
+        self.0 = map_art::generate_pixel_art(&image, &options)
+            .map_err(|e| JsValue::from_str(&format!("Pixel art error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Builds map art from a PNG using the vanilla map colors.
+    pub fn from_map_art(&mut self, data: &[u8], block_set: Option<Vec<String>>, staircasing: bool, dithering: bool, survival_only: bool) -> Result<(), JsValue> {
+        let image = Image::from_png(data)
+            .map_err(|e| JsValue::from_str(&format!("PNG parsing error: {}", e)))?;
+        let mut options = map_art::MapArtOptions { staircasing, dithering, ..map_art::MapArtOptions::default() };
+        if let Some(block_set) = block_set {
+            let blocks: Vec<BlockState> = block_set.into_iter().map(BlockState::new).collect();
+            options.palette = map_art::MapArtPalette::from_blocks(&blocks);
+        }
+        if survival_only {
+            options.palette.retain(|b| map_art::is_survival_obtainable(&b.name));
+        }
+        self.0 = map_art::generate_map_art(&image, &options)
+            .map_err(|e| JsValue::from_str(&format!("Map art error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Builds terrain from a grayscale heightmap PNG; white is `max_height`.
+    pub fn from_heightmap(&mut self, data: &[u8], max_height: f32, water_level: Option<i32>, surface: Option<String>, subsurface: Option<String>, fill: Option<String>) -> Result<(), JsValue> {
+        let heightmap = terrain::Heightmap::from_png(data, max_height)
+            .map_err(|e| JsValue::from_str(&format!("PNG parsing error: {}", e)))?;
+        let options = terrain_options(water_level, surface, subsurface, fill)?;
+        self.0 = terrain::generate_terrain(&heightmap, &options);
+        Ok(())
+    }
+
+    /// Builds terrain from rows of `width` heights, one row per z. Negative heights are left
+    /// empty.
+    pub fn from_height_array(&mut self, heights: Vec<f32>, width: u32, water_level: Option<i32>, surface: Option<String>, subsurface: Option<String>, fill: Option<String>) -> Result<(), JsValue> {
+        let length = (heights.len() as u32).checked_div(width).unwrap_or(0);
+        let heightmap = terrain::Heightmap::new(width, length, heights)
+            .map_err(|e| JsValue::from_str(&format!("Heightmap error: {}", e)))?;
+        let options = terrain_options(water_level, surface, subsurface, fill)?;
+        self.0 = terrain::generate_terrain(&heightmap, &options);
+        Ok(())
+    }
+
+    /// Writes `text` in blocks. `options` may set `foreground` and `background` block strings,
+    /// `scale`, `depth`, `padding`, `placement` ("south", "floor_north", ...) and `bdfFont`
+    /// with the source of a BDF font.
+    pub fn from_text(&mut self, text: &str, options: JsValue) -> Result<(), JsValue> {
+        let get = |key: &str| -> Option<JsValue> {
+            Reflect::get(&options, &JsValue::from_str(key)).ok().filter(|v| !v.is_undefined() && !v.is_null())
+        };
+        let get_string = |key: &str| get(key).and_then(|v| v.as_string());
+        let get_number = |key: &str| get(key).and_then(|v| v.as_f64()).map(|n| n as u32);
+        let parse = |block: String| {
+            UniversalSchematic::parse_block_string(&block)
+                .map(|(state, _)| state)
+                .map_err(|e| JsValue::from_str(&format!("Invalid block '{}': {}", block, e)))
+        };
+
+        let mut text_options = text::TextOptions::default();
+        if options.is_object() {
+            if let Some(foreground) = get_string("foreground") {
+                text_options.foreground = text::BlockPattern::Solid(parse(foreground)?);
+            }
+            if let Some(background) = get_string("background") {
+                text_options.background = Some(text::BlockPattern::Solid(parse(background)?));
+            }
+            text_options.scale = get_number("scale").unwrap_or(text_options.scale);
+            text_options.depth = get_number("depth").unwrap_or(text_options.depth);
+            text_options.padding = get_number("padding").unwrap_or(text_options.padding);
+            if let Some(placement) = get_string("placement") {
+                (text_options.orientation, text_options.facing) = text::parse_placement(&placement)
+                    .map_err(|e| JsValue::from_str(&e))?;
+            }
+        }
+        let font = match get_string("bdfFont").filter(|_| options.is_object()) {
+            Some(source) => text::BitmapFont::from_bdf(&source).map_err(|e| JsValue::from_str(&format!("BDF parsing error: {}", e)))?,
+            None => text::BitmapFont::bundled(),
+        };
+        self.0 = text::generate_text(text, &font, &text_options);
+        Ok(())
+    }
+
+    pub fn to_vox(&self) -> Result<Vec<u8>, JsValue> {
+        vox::to_vox(&self.0)
+            .map_err(|e| JsValue::from_str(&format!("Vox conversion error: {}", e)))
+    }
+
+    pub fn render_isometric_png(&self, resolution: Option<u32>) -> Result<Vec<u8>, JsValue> {
+        let options = RenderOptions { resolution: resolution.unwrap_or(512), ..RenderOptions::default() };
+        render::to_isometric_png(&self.0, &options)
+            .map_err(|e| JsValue::from_str(&format!("Render error: {}", e)))
+    }
+
+    pub fn render_top_down_png(&self, resolution: Option<u32>) -> Result<Vec<u8>, JsValue> {
+        let options = RenderOptions { resolution: resolution.unwrap_or(512), ..RenderOptions::default() };
+        render::to_top_down_png(&self.0, &options)
+            .map_err(|e| JsValue::from_str(&format!("Render error: {}", e)))
+    }
+
+    pub fn netlist_json(&self) -> Result<String, JsValue> {
+        Netlist::extract(&self.0).to_json()
+            .map_err(|e| JsValue::from_str(&format!("Netlist error: {}", e)))
+    }
+
+    pub fn netlist_dot(&self) -> String {
+        Netlist::extract(&self.0).to_dot()
+    }
+
+    /// Returns `{ min, size, blockLight, skyLight }` with the light arrays x first, then z, then y.
+    pub fn compute_light(&self, open_sky: Option<bool>) -> Result<JsValue, JsValue> {
+        let grid = lighting::compute_light(&self.0, open_sky.unwrap_or(true)).map_err(|e| JsValue::from_str(&e))?;
+        let result = Object::new();
+        let triple = |v: (i32, i32, i32)| Array::of3(&v.0.into(), &v.1.into(), &v.2.into());
+        Reflect::set(&result, &"min".into(), &triple(grid.min)).unwrap();
+        Reflect::set(&result, &"size".into(), &triple(grid.size)).unwrap();
+        Reflect::set(&result, &"blockLight".into(), &js_sys::Uint8Array::from(&grid.block_light[..])).unwrap();
+        Reflect::set(&result, &"skyLight".into(), &js_sys::Uint8Array::from(&grid.sky_light[..])).unwrap();
+        Ok(result.into())
+    }
+
+    pub fn find_dark_spots(&self, include_sky_light: Option<bool>, open_sky: Option<bool>) -> Result<Array, JsValue> {
+        let grid = lighting::compute_light(&self.0, open_sky.unwrap_or(true)).map_err(|e| JsValue::from_str(&e))?;
+        Ok(lighting::find_dark_spots(&self.0, &grid, include_sky_light.unwrap_or(false))
+            .into_iter()
+            .map(|(x, y, z)| JsValue::from(Array::of3(&x.into(), &y.into(), &z.into())))
+            .collect())
+    }
+
+    /// Returns `{ position, block, kind, reason }` for every block that would break, fall or
+    /// can't be obtained in survival.
+    pub fn validate_survival(&self) -> Array {
+        let report = survival::validate_survival(&self.0);
+        let issues = Array::new();
+        for issue in &report.issues {
+            let obj = Object::new();
+            let (x, y, z) = issue.position;
+            Reflect::set(&obj, &"position".into(), &Array::of3(&x.into(), &y.into(), &z.into())).unwrap();
+            Reflect::set(&obj, &"block".into(), &JsValue::from_str(&issue.block)).unwrap();
+            Reflect::set(&obj, &"kind".into(), &JsValue::from_str(issue.kind.name())).unwrap();
+            Reflect::set(&obj, &"reason".into(), &JsValue::from_str(&issue.reason)).unwrap();
+            issues.push(&obj);
+        }
+        issues
+    }
+
+    /// Upgrades or downgrades to the `target` data version and returns the fixes that changed
+    /// something.
+    pub fn convert_data_version(&mut self, target: i32) -> Result<Array, JsValue> {
+        let report = datafixer::convert(&mut self.0, target)
+            .map_err(|e| JsValue::from_str(&format!("Conversion error: {}", e)))?;
+        Ok(report.applied.iter().map(|fix| JsValue::from_str(fix)).collect())
+    }
+
+    /// Exports a copy for an older data version, replacing blocks it doesn't have. `format` is
+    /// "schematic" (Sponge v2), "schematic_v3" or "litematic"; `rules_json` is a list of
+    /// extra substitution rules that take precedence over the built-in ones. Returns
+    /// `{ data, substitutions, unhandled }`, where `unhandled` lists too-new blocks no rule
+    /// replaced.
+    pub fn export_for_version(&self, target: i32, format: Option<String>, rules_json: Option<String>) -> Result<Object, JsValue> {
+        let mut rules = datafixer::SubstitutionRules::vanilla();
+        if let Some(json) = rules_json {
+            let overrides = datafixer::SubstitutionRules::from_json(&json)
+                .map_err(|e| JsValue::from_str(&format!("Conversion error: {}", e)))?;
+            rules = rules.with_overrides(overrides.rules);
+        }
+        let mut downgraded = self.0.clone();
+        let report = datafixer::downgrade(&mut downgraded, target, &rules)
+            .map_err(|e| JsValue::from_str(&format!("Conversion error: {}", e)))?;
+        let bytes = match format.as_deref().unwrap_or("schematic") {
+            "schematic" => schematic::to_schematic_v2(&downgraded),
+            "schematic_v3" => schematic::to_schematic_v3(&downgraded),
+            "litematic" => litematic::to_litematic(&downgraded),
+            other => return Err(JsValue::from_str(&format!("Unknown format: {}", other))),
+        }.map_err(|e| JsValue::from_str(&format!("Export error: {}", e)))?;
+
+        let substitutions = Array::new();
+        for substitution in &report.substitutions {
+            let obj = Object::new();
+            let (x, y, z) = substitution.position;
+            Reflect::set(&obj, &"position".into(), &Array::of3(&x.into(), &y.into(), &z.into())).unwrap();
+            Reflect::set(&obj, &"from".into(), &JsValue::from_str(&substitution.from)).unwrap();
+            Reflect::set(&obj, &"to".into(), &JsValue::from_str(&substitution.to)).unwrap();
+            substitutions.push(&obj);
+        }
+        let result = Object::new();
+        Reflect::set(&result, &"data".into(), &js_sys::Uint8Array::from(bytes.as_slice())).unwrap();
+        Reflect::set(&result, &"substitutions".into(), &substitutions).unwrap();
+        let unhandled: Array = report.unhandled.iter().map(|name| JsValue::from_str(name)).collect();
+        Reflect::set(&result, &"unhandled".into(), &unhandled).unwrap();
+        Ok(result)
+    }
+
+    /// Placement order for printers and bots as JSON, with chunks visited in `strategy` order.
+    pub fn build_order_json(&self, strategy: Option<String>, chunk_size: Option<i32>, redstone_last: Option<bool>) -> Result<String, JsValue> {
+        let strategy = match strategy.as_deref().unwrap_or("bottom_up") {
+            "default" => ChunkLoadingStrategy::Default,
+            "top_down" => ChunkLoadingStrategy::TopDown,
+            "bottom_up" => ChunkLoadingStrategy::BottomUp,
+            "center_outward" => ChunkLoadingStrategy::CenterOutward,
+            "random" => ChunkLoadingStrategy::Random,
+            other => return Err(JsValue::from_str(&format!("Unknown strategy '{}'", other))),
+        };
+        let options = build_order::BuildPlanOptions {
+            chunk_size: chunk_size.unwrap_or(16),
+            strategy,
+            redstone_last: redstone_last.unwrap_or(true),
+        };
+        build_order::plan_build(&self.0, &options).to_json()
+            .map_err(|e| JsValue::from_str(&format!("Build order error: {}", e)))
+    }
+
+    /// Hex hash of the blocks (and block entities) that ignores metadata, region layout and
+    /// position, and with `rotation_invariant` also rotation around the Y axis.
+    pub fn content_hash(&self, rotation_invariant: Option<bool>, include_block_entities: Option<bool>) -> String {
+        let options = fingerprint::FingerprintOptions {
+            rotation_invariant: rotation_invariant.unwrap_or(false),
+            include_block_entities: include_block_entities.unwrap_or(true),
+        };
+        fingerprint::content_hash(&self.0, &options)
+    }
+
+    /// Scores from 0 to 1 for how alike this schematic and `other` are: `palette`, `voxels`
+    /// and their mean `score`.
+    pub fn similarity(&self, other: &SchematicWrapper, rotation_invariant: Option<bool>) -> JsValue {
+        let result = fingerprint::similarity(&self.0, &other.0, rotation_invariant.unwrap_or(false));
+        let obj = Object::new();
+        Reflect::set(&obj, &"palette".into(), &result.palette.into()).unwrap();
+        Reflect::set(&obj, &"voxels".into(), &result.voxels.into()).unwrap();
+        Reflect::set(&obj, &"score".into(), &result.score.into()).unwrap();
+        obj.into()
+    }
+
     pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_name: &str) {
         self.0.set_block(x, y, z, BlockState::new(block_name.to_string()));
     }
@@ -256,6 +591,27 @@ impl SchematicWrapper {
         self.0.total_blocks()
     }
 
+    /// Paste anchor in schematic coordinates.
+    pub fn get_origin(&self) -> Vec<i32> {
+        let (x, y, z) = self.0.origin;
+        vec![x, y, z]
+    }
+
+    pub fn set_origin(&mut self, x: i32, y: i32, z: i32) {
+        self.0.origin = (x, y, z);
+    }
+
+    /// Pastes `from_schematic` so that its origin lands on the given position.
+    pub fn paste(&mut self, from_schematic: &SchematicWrapper, x: i32, y: i32, z: i32, excluded_blocks: Option<Vec<String>>) -> Result<(), JsValue> {
+        let excluded = excluded_blocks.unwrap_or_default()
+            .iter()
+            .map(|s| UniversalSchematic::parse_block_string(s).map(|(bs, _)| bs))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| JsValue::from_str(&format!("Invalid block state: {}", e)))?;
+        self.0.paste(&from_schematic.0, (x, y, z), &excluded)
+            .map_err(|e| JsValue::from_str(&format!("Failed to paste: {}", e)))
+    }
+
     pub fn get_volume(&self) -> i32 {
         self.0.total_volume()
     }
@@ -420,8 +776,80 @@ impl BlockStateWrapper {
     }
 }
 
+#[wasm_bindgen]
+pub struct RedstoneSimulatorWrapper(RedstoneSimulator);
+
+#[wasm_bindgen]
+impl RedstoneSimulatorWrapper {
+    #[wasm_bindgen(constructor)]
+    pub fn new(schematic: &SchematicWrapper) -> Self {
+        RedstoneSimulatorWrapper(RedstoneSimulator::new(&schematic.0))
+    }
+
+    pub fn step(&mut self, ticks: u32) {
+        self.0.step(ticks);
+    }
+
+    pub fn run_until_stable(&mut self, max_ticks: u32) -> bool {
+        self.0.run_until_stable(max_ticks)
+    }
+
+    pub fn toggle_lever(&mut self, x: i32, y: i32, z: i32) -> Result<bool, JsValue> {
+        self.0.toggle_lever(x, y, z).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn set_lever(&mut self, x: i32, y: i32, z: i32, on: bool) -> Result<(), JsValue> {
+        self.0.set_lever(x, y, z, on).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn press_button(&mut self, x: i32, y: i32, z: i32) -> Result<(), JsValue> {
+        self.0.press_button(x, y, z).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<BlockStateWrapper> {
+        self.0.get_block(x, y, z).cloned().map(BlockStateWrapper)
+    }
+
+    pub fn get_signal(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.0.get_signal(x, y, z)
+    }
+
+    pub fn is_on(&self, x: i32, y: i32, z: i32) -> bool {
+        self.0.is_on(x, y, z)
+    }
+
+    pub fn current_tick(&self) -> f64 {
+        self.0.current_tick() as f64
+    }
+
+    pub fn apply_to(&self, schematic: &mut SchematicWrapper) {
+        self.0.apply_to(&mut schematic.0);
+    }
+}
+
 
 // Standalone functions
+/// Reads format, version, metadata and dimensions of litematic or `.schem` bytes without
+/// decoding the blocks.
+#[wasm_bindgen]
+pub fn peek_info(data: &[u8]) -> Result<JsValue, JsValue> {
+    let info = peek::peek_info(data)
+        .map_err(|e| JsValue::from_str(&format!("Peek error: {}", e)))?;
+    let optional = |value: Option<String>| value.map_or(JsValue::NULL, |value| JsValue::from_str(&value));
+    let obj = Object::new();
+    Reflect::set(&obj, &"format".into(), &info.format.into())?;
+    Reflect::set(&obj, &"version".into(), &info.version.into())?;
+    Reflect::set(&obj, &"dataVersion".into(), &info.data_version.map_or(JsValue::NULL, JsValue::from))?;
+    Reflect::set(&obj, &"name".into(), &optional(info.name))?;
+    Reflect::set(&obj, &"author".into(), &optional(info.author))?;
+    Reflect::set(&obj, &"description".into(), &optional(info.description))?;
+    Reflect::set(&obj, &"dimensions".into(), &Array::of3(&info.width.into(), &info.height.into(), &info.length.into()))?;
+    Reflect::set(&obj, &"regionCount".into(), &(info.region_count as u32).into())?;
+    Reflect::set(&obj, &"blockCount".into(), &info.block_count.map_or(JsValue::NULL, |count| JsValue::from(count as f64)))?;
+    Reflect::set(&obj, &"previewImage".into(), &info.preview_image.map_or(JsValue::NULL, |pixels| js_sys::Int32Array::from(&pixels[..]).into()))?;
+    Ok(obj.into())
+}
+
 #[wasm_bindgen]
 pub fn debug_schematic(schematic: &SchematicWrapper) -> String {
     format!("{}\n{}", schematic.debug_info(), print_schematic(&schematic.0))
@@ -430,4 +858,22 @@ pub fn debug_schematic(schematic: &SchematicWrapper) -> String {
 #[wasm_bindgen]
 pub fn debug_json_schematic(schematic: &SchematicWrapper) -> String {
     format!("{}\n{}", schematic.debug_info(), print_json_schematic(&schematic.0))
-}
\ No newline at end of file
+}
+fn terrain_options(water_level: Option<i32>, surface: Option<String>, subsurface: Option<String>, fill: Option<String>) -> Result<terrain::TerrainOptions, JsValue> {
+    let parse = |block: String| {
+        UniversalSchematic::parse_block_string(&block)
+            .map(|(state, _)| state)
+            .map_err(|e| JsValue::from_str(&format!("Invalid block '{}': {}", block, e)))
+    };
+    let mut options = terrain::TerrainOptions { water_level, ..terrain::TerrainOptions::default() };
+    if let Some(surface) = surface {
+        options.surface = parse(surface)?;
+    }
+    if let Some(subsurface) = subsurface {
+        options.subsurface = parse(subsurface)?;
+    }
+    if let Some(fill) = fill {
+        options.fill = parse(fill)?;
+    }
+    Ok(options)
+}
@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::Path;
-use nucleation::{BlockState, litematic, schematic, UniversalSchematic};
+use nucleation::{BlockState, litematic, native, peek, schematic, UniversalSchematic};
+use nucleation::limits::LoadLimits;
 
 
 
@@ -199,6 +200,23 @@ fn test_litematic_to_schem_conversion() {
     // fs::remove_file("tests/output/litematic-rose-farm.schem").expect("Failed to remove converted schem file");
     println!("Successfully converted litematic-rose-farm.litematic to .schem format and verified the contents.");
 }
+
+#[test]
+fn test_writers_are_byte_stable() {
+    // Each load builds fresh hash maps with their own iteration order
+    let data = fs::read("tests/samples/trencher.litematic").expect("Failed to read trencher.litematic");
+    let first = litematic::from_litematic(&data).unwrap();
+    let second = litematic::from_litematic(&data).unwrap();
+
+    let options = litematic::LitematicWriteOptions { timestamp: Some(0), ..Default::default() };
+    assert_eq!(
+        litematic::to_litematic_with_options(&first, &options).unwrap(),
+        litematic::to_litematic_with_options(&second, &options).unwrap(),
+    );
+    assert_eq!(schematic::to_schematic_v2(&first).unwrap(), schematic::to_schematic_v2(&second).unwrap());
+    assert_eq!(schematic::to_schematic_v3(&first).unwrap(), schematic::to_schematic_v3(&second).unwrap());
+    assert_eq!(native::to_native(&first).unwrap(), native::to_native(&second).unwrap());
+}
     
 
 
@@ -230,4 +248,34 @@ impl<'a> Iterator for TestFiles<'a> {
 fn list_test_file(extension: &str) -> TestFiles {
     const DIR_PATH: &str = "./tests/samples";
     TestFiles { extension, reader: fs::read_dir(DIR_PATH).unwrap() }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_peek_info_on_samples() {
+    for name in ["trencher.litematic", "sample.litematic", "sample.schem", "large_schematic.schem"] {
+        let data = fs::read(format!("tests/samples/{}", name)).expect("Failed to read sample");
+        let info = peek::peek_info(&data).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        let loaded = if name.ends_with(".litematic") {
+            litematic::from_litematic(&data).unwrap()
+        } else {
+            schematic::from_schematic(&data).unwrap()
+        };
+        let size = loaded.get_bounding_box().get_dimensions();
+        assert_eq!((info.width as i32, info.height as i32, info.length as i32), size, "{}", name);
+        assert_eq!(info.region_count, loaded.regions.len(), "{}", name);
+    }
+}
+
+#[test]
+fn test_samples_load_within_untrusted_limits() {
+    let limits = LoadLimits::untrusted();
+    for name in ["trencher.litematic", "all_items.litematic", "sample.schem", "large_schematic.schem"] {
+        let data = fs::read(format!("tests/samples/{}", name)).expect("Failed to read sample");
+        let result = if name.ends_with(".litematic") {
+            litematic::from_litematic_with_limits(&data, &limits)
+        } else {
+            schematic::from_schematic_with_limits(&data, &limits)
+        };
+        assert!(result.is_ok(), "{}: {:?}", name, result.err());
+    }
+}